@@ -0,0 +1,76 @@
+#include <iostream>
+#include <utility>
+
+#include "./generated.h"
+
+using rust::crate::Label;
+using rust::crate::Shape;
+
+// Called by `visit` on a `rust::Ref`, with the fields by reference.
+struct Printer {
+  void operator()(Shape::Case<Shape::Tag::Circle>, rust::Ref<double_t> r) const {
+    std::cout << "Circle of radius " << *r << std::endl;
+  }
+  void operator()(Shape::Case<Shape::Tag::Rect>, rust::Ref<double_t> w,
+                  rust::Ref<double_t> h) const {
+    std::cout << "Rect of " << *w << " x " << *h << std::endl;
+  }
+  void operator()(Shape::Case<Shape::Tag::Labeled>, rust::Ref<Label> label) const {
+    std::cout << "Labeled with" << std::endl;
+    label.print();
+  }
+  void operator()(Shape::Case<Shape::Tag::Empty>) const {
+    std::cout << "Empty" << std::endl;
+  }
+};
+
+int main() {
+  Shape circle = Shape::Circle(2.0);
+  Shape rect = Shape::Rect(3.0, 4.0);
+  Shape labeled = Shape::Labeled(Label::make(1));
+  Shape empty = Shape::Empty();
+
+  for (rust::Ref<Shape> shape : {rust::Ref<Shape>(circle), rust::Ref<Shape>(rect),
+                                 rust::Ref<Shape>(labeled), rust::Ref<Shape>(empty)}) {
+    shape.visit(Printer{});
+  }
+
+  // `match` on a const value passes the fields as `rust::Ref`.
+  const Shape &const_rect = rect;
+  double_t perimeter = const_rect.match(
+      [](rust::Ref<double_t> r) { return 6 * *r; },
+      [](rust::Ref<double_t> w, rust::Ref<double_t> h) { return 2 * (*w + *h); },
+      [](rust::Ref<Label>) { return 0.0; }, []() { return 0.0; });
+  std::cout << "Perimeter of the rect: " << perimeter << std::endl;
+
+  // `match` on a mutable value, or on a `rust::RefMut`, passes them as `rust::RefMut`.
+  rect.match([](rust::RefMut<double_t>) {},
+             [](rust::RefMut<double_t> w, rust::RefMut<double_t>) { *w = 5.0; },
+             [](rust::RefMut<Label>) {}, []() {});
+  std::cout << "Area of the rect: " << rect.area() << std::endl;
+  rust::RefMut<Shape>(circle).visit([](auto tag, auto... fields) {
+    std::cout << "Visiting variant " << static_cast<int>(decltype(tag)::value) << " with "
+              << sizeof...(fields) << " fields" << std::endl;
+  });
+
+  // On an rvalue, `match` and `visit` move the fields out of the enum.
+  std::move(labeled).match(
+      [](double_t) { std::cout << "Not a circle" << std::endl; },
+      [](double_t, double_t) { std::cout << "Not a rect" << std::endl; },
+      [](Label label) {
+        std::cout << "Took" << std::endl;
+        label.print();
+      },
+      []() { std::cout << "Not empty" << std::endl; });
+  std::cout << "After the owned match" << std::endl;
+
+  Shape::Variant variant = std::move(rect).into_variant();
+  const auto &fields = std::get<1>(variant);
+  std::cout << "Rect as a variant: " << std::get<0>(fields) << " x " << std::get<1>(fields)
+            << std::endl;
+
+  Shape::Labeled(Label::make(2)).visit([](auto, auto... fields) {
+    std::cout << "Moved " << sizeof...(fields) << " field out" << std::endl;
+  });
+  std::cout << "Done" << std::endl;
+}
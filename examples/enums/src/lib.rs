@@ -0,0 +1,37 @@
+#[rustfmt::skip]
+mod generated;
+
+struct Label(u32);
+
+impl Label {
+    fn make(id: u32) -> Label {
+        Label(id)
+    }
+
+    fn print(&self) {
+        println!("Label {}", self.0);
+    }
+}
+
+impl Drop for Label {
+    fn drop(&mut self) {
+        println!("Label {} dropped", self.0);
+    }
+}
+
+enum Shape {
+    Circle(f64),
+    Rect { w: f64, h: f64 },
+    Labeled(Label),
+    Empty,
+}
+
+impl Shape {
+    fn area(&self) -> f64 {
+        match self {
+            Shape::Circle(r) => 3.0 * r * r,
+            Shape::Rect { w, h } => w * h,
+            Shape::Labeled(_) | Shape::Empty => 0.0,
+        }
+    }
+}
@@ -0,0 +1,29 @@
+#include "aggregation.zng.h"
+extern "C" {
+    void _zngur_crate_StatsAccumulator_extern_method_create_s12(
+        uint8_t* o
+    ) {
+        ::rust::aggregation::StatsAccumulator oo = ::rust::Impl< ::rust::aggregation::StatsAccumulator, ::rust::Inherent >::create(
+        );
+        ::rust::__zngur_internal_move_to_rust(o, oo);
+    }
+    
+    void _zngur_crate_StatsAccumulator_extern_method_add_packet_s12(
+        uint8_t* i0, uint8_t* i1, uint8_t* o
+    ) {
+        ::rust::Unit oo = ::rust::Impl< ::rust::aggregation::StatsAccumulator, ::rust::Inherent >::add_packet(
+            ::rust::__zngur_internal_move_from_rust< ::rust::RefMut< ::rust::aggregation::StatsAccumulator > >(i0), ::rust::__zngur_internal_move_from_rust< ::rust::packet::Packet >(i1)
+        );
+        ::rust::__zngur_internal_move_to_rust(o, oo);
+    }
+    
+    void _zngur_crate_StatsAccumulator_extern_method_print_report_s12(
+        uint8_t* i0, uint8_t* o
+    ) {
+        ::rust::Unit oo = ::rust::Impl< ::rust::aggregation::StatsAccumulator, ::rust::Inherent >::print_report(
+            ::rust::__zngur_internal_move_from_rust< ::rust::Ref< ::rust::aggregation::StatsAccumulator > >(i0)
+        );
+        ::rust::__zngur_internal_move_to_rust(o, oo);
+    }
+    
+} // extern "C"
@@ -0,0 +1,30 @@
+#include <iostream>
+
+#include "./generated.h"
+
+using rust::crate::ParseError;
+using rust::crate::Parser;
+
+int main() {
+  // `#result_as_exception` returns the `Ok` value directly
+  std::cout << "42 = " << Parser::parse_number('4', '2') << std::endl;
+
+  // and throws the `Err` value as `rust::Error<E>`
+  try {
+    Parser::parse_number('4', 'x');
+    std::cout << "Unreachable" << std::endl;
+  } catch (const rust::Error<ParseError> &e) {
+    std::cout << "Caught: " << e.what() << std::endl;
+    e.error().print();
+  }
+
+  // `#result_as_expected` returns `std::expected` in C++23
+  rust::Expected<uint32_t, ParseError> ok = Parser::checked_div(42, 6);
+  if (ok) {
+    std::cout << "42 / 6 = " << *ok << std::endl;
+  }
+  rust::Expected<uint32_t, ParseError> err = Parser::checked_div(42, 0);
+  if (!err) {
+    err.error().print();
+  }
+}
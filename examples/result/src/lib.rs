@@ -0,0 +1,33 @@
+#[rustfmt::skip]
+mod generated;
+
+#[derive(Clone, Copy)]
+enum ParseError {
+    NotADigit(u8),
+    DivisionByZero,
+}
+
+impl ParseError {
+    fn print(&self) {
+        match self {
+            ParseError::NotADigit(c) => println!("Error: {:?} is not a digit", *c as char),
+            ParseError::DivisionByZero => println!("Error: division by zero"),
+        }
+    }
+}
+
+struct Parser;
+
+impl Parser {
+    fn parse_digit(c: u8) -> Result<u32, ParseError> {
+        (c as char).to_digit(10).ok_or(ParseError::NotADigit(c))
+    }
+
+    fn parse_number(a: u8, b: u8) -> Result<u32, ParseError> {
+        Ok(Self::parse_digit(a)? * 10 + Self::parse_digit(b)?)
+    }
+
+    fn checked_div(a: u32, b: u32) -> Result<u32, ParseError> {
+        a.checked_div(b).ok_or(ParseError::DivisionByZero)
+    }
+}
@@ -0,0 +1,27 @@
+#include "generated.h"
+extern "C" {
+    void _zngur_crate_MyCppWrapper_extern_method_new_s12(
+        uint8_t* i0, uint8_t* i1, uint8_t* o
+    ) {
+        ::rust::crate::MyCppWrapper oo = ::rust::Impl< ::rust::crate::MyCppWrapper, ::rust::Inherent >::new_(
+            ::rust::__zngur_internal_move_from_rust< ::int32_t >(i0), ::rust::__zngur_internal_move_from_rust< ::int32_t >(i1)
+        );
+        ::rust::__zngur_internal_move_to_rust(o, oo);
+    }
+    
+    void _zngur_crate_MyCppWrapper_extern_method_print_s12(
+        uint8_t* i0, uint8_t* o
+    ) {
+        ::rust::Unit oo = ::rust::Impl< ::rust::crate::MyCppWrapper, ::rust::Inherent >::print(
+            ::rust::__zngur_internal_move_from_rust< ::rust::Ref< ::rust::crate::MyCppWrapper > >(i0)
+        );
+        ::rust::__zngur_internal_move_to_rust(o, oo);
+    }
+    
+    void _zngur__zngur_crate_MyCppWrapper_destructor_(uint8_t* i0, uint8_t* o) {
+        (void)o;
+        auto* data = reinterpret_cast< ::CppType* >(i0);
+        data->~CppType();
+    }
+    
+} // extern "C"
@@ -0,0 +1,438 @@
+#pragma once
+#include <cstddef>
+#include <cstdint>
+#include <cstring>
+#include <csignal>
+#include <array>
+#include <iostream>
+#include <functional>
+#include <math.h>
+#include <type_traits>
+#include <tuple>
+#if __cplusplus >= 201703L
+#include <variant>
+#endif
+#include <zngur.h>
+#include <cpp_type.h>
+extern "C" {
+    void _zngur_crate_MyCppWrapper_drop_in_place_s12e25(uint8_t*);
+    
+} // extern "C"
+
+namespace rust {
+namespace crate {
+struct MyCppWrapper;
+}
+}
+
+namespace rust {
+} // namespace rust
+
+namespace rust {
+namespace crate {
+struct MyCppWrapper;
+}
+}
+
+namespace rust {
+}
+
+namespace rust {
+template<typename Offset, typename... Offsets>
+struct FieldOwned< ::rust::crate::MyCppWrapper, Offset, Offsets...> {
+}; // struct FieldOwned< ::rust::crate::MyCppWrapper, Offset, Offsets... >
+
+template<typename Offset, typename... Offsets>
+struct FieldRef< ::rust::crate::MyCppWrapper, Offset, Offsets...> {
+}; // struct FieldRef< ::rust::crate::MyCppWrapper, Offset, Offsets... >
+
+template<typename Offset, typename... Offsets>
+struct FieldRefMut< ::rust::crate::MyCppWrapper, Offset, Offsets...> {
+}; // struct FieldRefMut< ::rust::crate::MyCppWrapper, Offset, Offsets... >
+
+} // namespace rust
+
+namespace rust {
+template<>
+struct __zngur_internal< ::rust::crate::MyCppWrapper > {
+    static inline uint8_t* data_ptr(const ::rust::crate::MyCppWrapper& t) noexcept ;
+    static inline void check_init(const ::rust::crate::MyCppWrapper& t) noexcept ;
+    static inline void assume_init(::rust::crate::MyCppWrapper& t) noexcept ;
+    static inline void assume_deinit(::rust::crate::MyCppWrapper& t) noexcept ;
+    static inline size_t size_of() noexcept ;
+    static inline size_t align_of() noexcept ;
+};
+}
+
+namespace rust {
+namespace crate {
+struct MyCppWrapper {
+  public:
+    
+    union alignas(4) {
+        alignas(4) mutable ::std::array< ::uint8_t, 8> __zngur_data;
+        
+    };
+    
+    bool drop_flag;
+    
+  public:
+    
+    MyCppWrapper() : drop_flag(false) {  }
+    ~MyCppWrapper() {
+        if (drop_flag) {
+            _zngur_crate_MyCppWrapper_drop_in_place_s12e25(::rust::__zngur_internal_data_ptr(*this));
+        }
+        
+    }
+    MyCppWrapper(const MyCppWrapper& other) = delete;
+    MyCppWrapper& operator=(const MyCppWrapper& other) = delete;
+    MyCppWrapper(MyCppWrapper&& other) : drop_flag(false) {
+        *this = ::std::move(other);
+    }
+    MyCppWrapper& operator=(MyCppWrapper&& other) {
+        if (this != &other) {
+            if (drop_flag) {
+                _zngur_crate_MyCppWrapper_drop_in_place_s12e25(::rust::__zngur_internal_data_ptr(*this));
+            }
+            this->drop_flag = other.drop_flag;
+            this->__zngur_data = other.__zngur_data;
+            other.drop_flag = false;
+        }
+        return *this;
+    }
+    
+    static_assert(sizeof(::CppType) == 8, "Size mismatch");
+    static_assert(alignof(::CppType) == 4, "Alignment mismatch");
+    static_assert(::rust::is_trivially_relocatable< ::CppType >::value, "Type must be trivially relocatable");
+    
+    inline ::CppType& cpp() & {
+        return *reinterpret_cast< ::CppType* >(__zngur_data.data());
+    }
+    inline const ::CppType& cpp() const & {
+        return *reinterpret_cast< const ::CppType* >(__zngur_data.data());
+    }
+    inline ::CppType&& cpp() && {
+        return ::std::move(*reinterpret_cast< ::CppType* >(__zngur_data.data()));
+    }
+    
+    template<typename... Args>
+    static inline MyCppWrapper build(Args&&... args) {
+        MyCppWrapper w;
+        new (&w.cpp()) ::CppType(::std::forward<Args>(args)...);
+        ::rust::__zngur_internal< MyCppWrapper >::assume_init(w);
+        return w;
+    }
+    
+}; // struct MyCppWrapper
+
+} // namespace rust
+} // namespace crate
+
+namespace rust {
+inline size_t __zngur_internal< ::rust::crate::MyCppWrapper >::size_of() noexcept {
+    return 8;
+}
+inline size_t __zngur_internal< ::rust::crate::MyCppWrapper >::align_of() noexcept {
+    return 4;
+}
+
+inline void __zngur_internal< ::rust::crate::MyCppWrapper >::check_init(const ::rust::crate::MyCppWrapper& t) noexcept {
+    if (!t.drop_flag) {
+        ::std::cerr << "Use of uninitialized or moved Zngur Rust object with type ::rust::crate::MyCppWrapper" << ::std::endl;
+        while (true) raise(SIGSEGV);
+    }
+}
+
+inline void __zngur_internal< ::rust::crate::MyCppWrapper >::assume_init(::rust::crate::MyCppWrapper& t) noexcept {
+    t.drop_flag = true;
+}
+
+inline void __zngur_internal< ::rust::crate::MyCppWrapper >::assume_deinit(::rust::crate::MyCppWrapper& t) noexcept {
+    ::rust::__zngur_internal_check_init< ::rust::crate::MyCppWrapper >(t);
+    t.drop_flag = false;
+}
+
+inline uint8_t* __zngur_internal< ::rust::crate::MyCppWrapper >::data_ptr(::rust::crate::MyCppWrapper const & t) noexcept {
+    return const_cast<uint8_t*>(t.__zngur_data.data());
+    
+}
+
+} // namespace rust
+
+namespace rust {
+template<>
+struct RefMut< ::rust::crate::MyCppWrapper > {
+  public:
+    RefMut() {
+        __zngur_data =  0 ;
+    }
+    
+    union {
+        size_t __zngur_data;
+        
+    };
+    
+    RefMut(const ::rust::crate::MyCppWrapper& t) {
+        ::rust::__zngur_internal_check_init< ::rust::crate::MyCppWrapper >(t);
+        __zngur_data = reinterpret_cast<size_t>(__zngur_internal_data_ptr(t));
+    }
+    
+    template <
+        typename Offset,
+        typename... Offsets,
+        typename ::std::enable_if<
+        zngur_detail::all_static_offset<Offset, Offsets...>::value,
+        bool>::type = true>
+    RefMut(const FieldOwned< ::rust::crate::MyCppWrapper, Offset, Offsets... >& f) {
+        constexpr bool heap_allocated = __zngur_internal_calc_field<Offset, Offsets...>::heap_allocated();
+        constexpr size_t offset = __zngur_internal_calc_field<Offset, Offsets...>::offset();
+        if (heap_allocated) {
+            __zngur_data = *reinterpret_cast<const size_t*>(&f) + offset;
+            } else {
+            
+            __zngur_data = reinterpret_cast<size_t>(&f) + offset;
+        }
+    }
+    
+    template <
+        typename Offset,
+        typename... Offsets,
+        typename ::std::enable_if<
+        !zngur_detail::all_static_offset<Offset, Offsets...>::value,
+        bool>::type = true>
+    RefMut(const FieldOwned< ::rust::crate::MyCppWrapper, Offset, Offsets... >& f) {
+        constexpr bool heap_allocated = __zngur_internal_calc_field<Offset, Offsets...>::heap_allocated();
+        size_t offset = __zngur_internal_calc_field<Offset, Offsets...>::offset();
+        if (heap_allocated) {
+            __zngur_data = *reinterpret_cast<const size_t*>(&f) + offset;
+            } else {
+            
+            __zngur_data = reinterpret_cast<size_t>(&f) + offset;
+        }
+    }
+    
+    template <
+        typename Offset,
+        typename... Offsets,
+        typename ::std::enable_if<
+        zngur_detail::all_static_offset<Offset, Offsets...>::value,
+        bool>::type = true>
+    RefMut(const FieldRefMut< ::rust::crate::MyCppWrapper, Offset, Offsets...>& f) {
+        constexpr size_t offset = __zngur_internal_calc_field<Offset, Offsets...>::offset();
+        __zngur_data = *reinterpret_cast<const size_t*>(&f) + offset;
+    }
+    
+    template <
+        typename Offset,
+        typename... Offsets,
+        typename ::std::enable_if<
+        !zngur_detail::all_static_offset<Offset, Offsets...>::value,
+        bool>::type = true>
+    RefMut(const FieldRefMut< ::rust::crate::MyCppWrapper, Offset, Offsets...>& f) {
+        size_t offset = __zngur_internal_calc_field<Offset, Offsets...>::offset();
+        __zngur_data = *reinterpret_cast<const size_t*>(&f) + offset;
+    }
+    
+    inline ::CppType& cpp() {
+        return *reinterpret_cast< ::CppType* >(__zngur_data);
+    }
+    
+}; // struct RefMut< ::rust::crate::MyCppWrapper >
+
+template<>
+struct __zngur_internal< RefMut < ::rust::crate::MyCppWrapper > > {
+    static inline uint8_t* data_ptr(const RefMut< ::rust::crate::MyCppWrapper >& t) noexcept {
+        return const_cast<uint8_t*>(reinterpret_cast<const uint8_t*>(&t.__zngur_data));
+    }
+    static inline void assume_init(RefMut< ::rust::crate::MyCppWrapper >&) noexcept {}
+    static inline void check_init(const RefMut< ::rust::crate::MyCppWrapper >&) noexcept {}
+    static inline void assume_deinit(RefMut< ::rust::crate::MyCppWrapper >&) noexcept {}
+    static inline size_t size_of() noexcept {
+        return sizeof(uintptr_t);
+    }
+    static inline size_t align_of() noexcept {
+        return alignof(uintptr_t);
+    }
+};
+
+} // namespace rust
+
+namespace rust {
+template<>
+struct Ref< ::rust::crate::MyCppWrapper > {
+  public:
+    Ref() {
+        __zngur_data =  0 ;
+    }
+    
+    union {
+        size_t __zngur_data;
+        
+    };
+    
+    Ref(const ::rust::crate::MyCppWrapper& t) {
+        ::rust::__zngur_internal_check_init< ::rust::crate::MyCppWrapper >(t);
+        __zngur_data = reinterpret_cast<size_t>(__zngur_internal_data_ptr(t));
+    }
+    
+    Ref(RefMut< ::rust::crate::MyCppWrapper > rm) {
+        __zngur_data = rm.__zngur_data;
+    }
+    
+    template <
+        typename Offset,
+        typename... Offsets,
+        typename ::std::enable_if<
+        zngur_detail::all_static_offset<Offset, Offsets...>::value,
+        bool>::type = true>
+    Ref(const FieldOwned< ::rust::crate::MyCppWrapper, Offset, Offsets... >& f) {
+        constexpr bool heap_allocated = __zngur_internal_calc_field<Offset, Offsets...>::heap_allocated();
+        constexpr size_t offset = __zngur_internal_calc_field<Offset, Offsets...>::offset();
+        if (heap_allocated) {
+            __zngur_data = *reinterpret_cast<const size_t*>(&f) + offset;
+            } else {
+            __zngur_data = reinterpret_cast<size_t>(&f) + offset;
+        }
+    }
+    
+    template <
+        typename Offset,
+        typename... Offsets,
+        typename ::std::enable_if<
+        !zngur_detail::all_static_offset<Offset, Offsets...>::value,
+        bool>::type = true>
+    Ref(const FieldOwned< ::rust::crate::MyCppWrapper, Offset, Offsets... >& f) {
+        constexpr bool heap_allocated = __zngur_internal_calc_field<Offset, Offsets...>::heap_allocated();
+        size_t offset = __zngur_internal_calc_field<Offset, Offsets...>::offset();
+        if (heap_allocated) {
+            __zngur_data = *reinterpret_cast<const size_t*>(&f) + offset;
+            } else {
+            __zngur_data = reinterpret_cast<size_t>(&f) + offset;
+        }
+    }
+    
+    template <
+        typename Offset,
+        typename... Offsets,
+        typename ::std::enable_if<
+        zngur_detail::all_static_offset<Offset, Offsets...>::value,
+        bool>::type = true>
+    Ref(const FieldRef< ::rust::crate::MyCppWrapper, Offset, Offsets...>& f) {
+        constexpr size_t offset = __zngur_internal_calc_field<Offset, Offsets...>::offset();
+        __zngur_data = *reinterpret_cast<const size_t*>(&f) + offset;
+    }
+    
+    template <
+        typename Offset,
+        typename... Offsets,
+        typename ::std::enable_if<
+        !zngur_detail::all_static_offset<Offset, Offsets...>::value,
+        bool>::type = true>
+    Ref(const FieldRef< ::rust::crate::MyCppWrapper, Offset, Offsets...>& f) {
+        size_t offset = __zngur_internal_calc_field<Offset, Offsets...>::offset();
+        __zngur_data = *reinterpret_cast<const size_t*>(&f) + offset;
+    }
+    
+    template <
+        typename Offset,
+        typename... Offsets,
+        typename ::std::enable_if<
+        zngur_detail::all_static_offset<Offset, Offsets...>::value,
+        bool>::type = true>
+    Ref(const FieldRefMut< ::rust::crate::MyCppWrapper, Offset, Offsets...>& f) {
+        constexpr size_t offset = __zngur_internal_calc_field<Offset, Offsets...>::offset();
+        __zngur_data = *reinterpret_cast<const size_t*>(&f) + offset;
+    }
+    
+    template <
+        typename Offset,
+        typename... Offsets,
+        typename ::std::enable_if<
+        !zngur_detail::all_static_offset<Offset, Offsets...>::value,
+        bool>::type = true>
+    Ref(const FieldRefMut< ::rust::crate::MyCppWrapper, Offset, Offsets...>& f) {
+        size_t offset = __zngur_internal_calc_field<Offset, Offsets...>::offset();
+        __zngur_data = *reinterpret_cast<const size_t*>(&f) + offset;
+    }
+    
+    inline const ::CppType& cpp() const {
+        return *reinterpret_cast< const ::CppType* >(__zngur_data);
+    }
+    inline ::CppType& cpp() {
+        return *reinterpret_cast< ::CppType* >(__zngur_data);
+    }
+    
+};
+
+template<>
+struct __zngur_internal< Ref < ::rust::crate::MyCppWrapper > > {
+    static inline uint8_t* data_ptr(const Ref < ::rust::crate::MyCppWrapper >& t) noexcept {
+        return const_cast<uint8_t*>(reinterpret_cast<const uint8_t*>(&t.__zngur_data));
+    }
+    static inline void assume_init(Ref < ::rust::crate::MyCppWrapper >&) noexcept {}
+    static inline void check_init(const Ref < ::rust::crate::MyCppWrapper >&) noexcept {}
+    static inline void assume_deinit(Ref < ::rust::crate::MyCppWrapper >&) noexcept {}
+    static inline size_t size_of() noexcept {
+        return sizeof(uintptr_t);
+    }
+    static inline size_t align_of() noexcept {
+        return alignof(uintptr_t);
+    }
+};
+
+template<>
+struct __zngur_internal< Raw < ::rust::crate::MyCppWrapper > > {
+    static inline uint8_t* data_ptr(const Raw < ::rust::crate::MyCppWrapper >& t) noexcept {
+        return const_cast<uint8_t*>(reinterpret_cast<const uint8_t*>(&t.__zngur_data));
+    }
+    static inline void assume_init(Raw < ::rust::crate::MyCppWrapper >&) noexcept {}
+    static inline void check_init(const Raw < ::rust::crate::MyCppWrapper >&) noexcept {}
+    static inline void assume_deinit(Raw < ::rust::crate::MyCppWrapper >&) noexcept {}
+    static inline size_t size_of() noexcept {
+        return sizeof(uintptr_t);
+    }
+    static inline size_t align_of() noexcept {
+        return alignof(uintptr_t);
+    }
+};
+
+template<>
+struct __zngur_internal< RawMut < ::rust::crate::MyCppWrapper > > {
+    static inline uint8_t* data_ptr(const RawMut < ::rust::crate::MyCppWrapper >& t) noexcept {
+        return const_cast<uint8_t*>(reinterpret_cast<const uint8_t*>(&t.__zngur_data));
+    }
+    static inline void assume_init(RawMut < ::rust::crate::MyCppWrapper >&) noexcept {}
+    static inline void check_init(const RawMut < ::rust::crate::MyCppWrapper >&) noexcept {}
+    static inline void assume_deinit(RawMut < ::rust::crate::MyCppWrapper >&) noexcept {}
+    static inline size_t size_of() noexcept {
+        return sizeof(uintptr_t);
+    }
+    static inline size_t align_of() noexcept {
+        return alignof(uintptr_t);
+    }
+};
+
+} // namespace rust
+
+namespace rust {
+} // namespace rust
+
+namespace rust {
+namespace exported_functions {
+} // namespace exported_functions
+
+template<>
+class Impl< ::rust::crate::MyCppWrapper, ::rust::Inherent > {
+  public:
+    
+    static ::rust::crate::MyCppWrapper new_(
+        ::int32_t, ::int32_t
+    );
+    
+    static ::rust::Unit print(
+        ::rust::Ref< ::rust::crate::MyCppWrapper >
+    );
+};
+
+} // namespace rust
@@ -0,0 +1,271 @@
+
+macro_rules! __zngur_str_as_array {
+    ($s:expr) => {{
+        const VAL: &str = $s;
+        // SAFETY: `VAL` has at least size `N` because it's const len is right there.
+        const ARR: [u8; VAL.len()] = unsafe { *(VAL.as_bytes() as *const [u8]).cast() };
+        ARR
+    }};
+}
+
+pub const fn __zngur_usize_num_digits(val: usize) -> usize {
+    // docs currently say 64bit only but that's a bug
+    if val == 0 { 1 } else { val.ilog10() as usize + 1 }
+}
+
+pub const fn __zngur_usize_digit(val: usize, digit: usize) -> u8 {
+    let mut temp = val;
+    let mut i = 0;
+    while i < digit {
+        temp /= 10;
+        i += 1;
+    }
+    if temp == 0 && val > 0 {
+        ::core::panic!("no such digit!")
+    } else {
+        (temp % 10) as u8
+    }
+}
+
+pub const fn __zngur_digit_to_ascii(digit: u8) -> u8 {
+    ::core::assert!(digit <= 9);
+    digit + b'0'
+}
+
+pub const fn __zngur_usize_to_digit_array<const N: usize>(val: usize) -> [u8; N] {
+    let mut arr: [u8; N] = [0; N];
+    let mut i = 0;
+    while i < N {
+        arr[N - 1 - i] = __zngur_digit_to_ascii(__zngur_usize_digit(val, i));
+        i += 1;
+    }
+    arr
+}
+
+macro_rules! __zngur_usize_to_str {
+    ($x:expr) => {{
+        const VAL: usize = $x;
+        const ARR: [u8; __zngur_usize_num_digits(VAL)] = __zngur_usize_to_digit_array(VAL);
+        // SAFETY: `ARR` is an ascii byte array which is utf8 compliant
+        const STR: &str = unsafe { str::from_utf8_unchecked(&ARR) };
+        STR
+    }};
+}
+
+pub const fn __zngur_const_str_array_concat<const T: usize, const N: usize, const M: usize>(
+    x: [u8; N],
+    y: [u8; M],
+) -> [u8; T] {
+    ::core::assert!(N + M == T);
+    let mut arr: [u8; T] = [0; T];
+    let mut i = 0;
+    while i < N {
+        arr[i] = x[i];
+        i += 1;
+    }
+    while i - N < M {
+        arr[i] = y[i - N];
+        i += 1;
+    }
+    arr
+}
+
+macro_rules! __zngur_const_str_concat {
+
+    ( $x:expr, $y:expr $(,)? ) => {{
+        const X: &str = $x;
+        const Y: &str = $y;
+        const LEN: usize = X.len() + Y.len();
+        const ARR: [u8; LEN] = __zngur_const_str_array_concat::<LEN, {X.len()}, {Y.len()}>(
+            __zngur_str_as_array!(X),
+            __zngur_str_as_array!(Y),
+        );
+        // SAFETY: `ARR` is an concatenated utf8 byte array built from validated `const str&`
+        const STR: &str =  unsafe { str::from_utf8_unchecked(&ARR) };
+        STR
+    }};
+    ( $x:expr, $y:expr, $($rest:expr),+ $(,)? ) => {
+        __zngur_const_str_concat!($x, __zngur_const_str_concat!( $y, $($rest),+ ))
+    };
+
+}
+
+macro_rules! __zngur_assert_is_copy {
+    ($x:ty $(,)?) => {
+        const _: () = {
+            const fn static_assert_is_copy<T: Copy>() {}
+            static_assert_is_copy::<$x>();
+        };
+    };
+}
+
+macro_rules! __zngur_assert_size {
+    ($x:ty, $size:expr $(,)?) => {
+        const _: () = ::core::assert!(
+            $size == ::core::mem::size_of::<$x>(),
+            "{}",
+            __zngur_const_str_concat!(
+                "zngur declared size of ",
+                stringify!($x),
+                " is incorrect: expected ",
+                __zngur_usize_to_str!($size),
+                " , real size is ",
+                __zngur_usize_to_str!(::core::mem::size_of::<$x>()),
+            )
+        );
+    };
+}
+
+macro_rules! __zngur_assert_align {
+    ($x:ty, $align:expr $(,)?) => {
+        const _: () = ::core::assert!(
+            $align == ::core::mem::align_of::<$x>(),
+            "{}",
+            __zngur_const_str_concat!(
+                "zngur declared align of ",
+                stringify!($x),
+                " is incorrect: expected ",
+                __zngur_usize_to_str!($align),
+                " , real align is ",
+                __zngur_usize_to_str!(::core::mem::align_of::<$x>()),
+            )
+        );
+    };
+}
+
+macro_rules! __zngur_assert_size_conservative {
+    ($x:ty, $size:expr $(,)?) => {
+        const _: () = ::core::assert!(
+            $size >= ::core::mem::size_of::<$x>(),
+            "{}",
+            __zngur_const_str_concat!(
+                "zngur declared conservative size of ",
+                stringify!($x),
+                " is incorrect: expected size less than or equal to ",
+                __zngur_usize_to_str!($size),
+                " , real size is ",
+                __zngur_usize_to_str!(::core::mem::size_of::<$x>()),
+            )
+        );
+    };
+}
+
+macro_rules! __zngur_assert_align_conservative {
+    ($x:ty, $align:expr $(,)?) => {
+        const _: () = ::core::assert!(
+            $align >= ::core::mem::align_of::<$x>(),
+            "{}",
+            __zngur_const_str_concat!(
+                "zngur declared conservative align of ",
+                stringify!($x),
+                " is incorrect: expected align less than or equal to ",
+                __zngur_usize_to_str!($align),
+                " , real align is ",
+                __zngur_usize_to_str!(::core::mem::align_of::<$x>()),
+            )
+        );
+    };
+}
+
+macro_rules! __zngur_assert_has_field {
+    ($x:ty, $y:ty, $($field:tt)+ $(,)?) => {
+        const _: () = {
+            #[allow(dead_code)]
+            fn check_field(value: $x) -> $y {
+                value.$($field)+
+            }
+        };
+    };
+}
+
+macro_rules! __zngur_assert_field_offset {
+    ($x:ty, $offset:expr, $($field:tt)+ $(,)?) => {
+        const _: () = ::core::assert!(
+            $offset == ::core::mem::offset_of!($x, $($field)+),
+            "{}",
+            __zngur_const_str_concat!(
+                "zngur declared offset of field ",
+                stringify!($($field)+),
+                " in ",
+                stringify!($x),
+                " is incorrect: expected offset of ",
+                __zngur_usize_to_str!($offset),
+                " , real offset is ",
+                __zngur_usize_to_str!(::core::mem::offset_of!($x, $($field)+)),
+            )
+        );
+    };
+}
+__zngur_assert_size!(crate::MyCppWrapper, 8);
+__zngur_assert_align!(crate::MyCppWrapper, 4);
+
+unsafe extern "C" { fn _zngur__zngur_crate_MyCppWrapper_destructor_(i0: *mut u8, o: *mut u8); }
+
+#[allow(non_snake_case)]
+pub fn _zngur_crate_MyCppWrapper_destructor(i0: &mut crate::MyCppWrapper, ) -> () { unsafe {
+let mut i0 = ::core::mem::MaybeUninit::new(i0);
+let mut r = ::core::mem::MaybeUninit::uninit();
+_zngur__zngur_crate_MyCppWrapper_destructor_(i0.as_mut_ptr() as *mut u8, r.as_mut_ptr() as *mut u8);
+r.assume_init()
+} }
+
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "C" fn _zngur_crate_MyCppWrapper_drop_in_place_s12e25(v: *mut u8) { unsafe {
+    ::std::ptr::drop_in_place(v as *mut crate::MyCppWrapper);
+} }
+
+pub mod cpp {
+
+    #[repr(C)]
+    #[repr(align(4))]
+    pub struct MyCppWrapper {
+      pub(crate) buffer: core::mem::MaybeUninit<[u8; 8]>,
+      _no_auto_traits: core::marker::PhantomData<*mut ()>,
+      _pinned: core::marker::PhantomPinned,
+    }
+
+    unsafe impl ::zngur_lib::ZngCppObject for MyCppWrapper {}
+    unsafe impl ::zngur_lib::ZngCppStackObject for MyCppWrapper {}
+
+    unsafe impl ::zngur_lib::ZngCppDestruct for MyCppWrapper {
+        unsafe fn destruct(&mut self) {
+            unsafe extern "C" {
+                fn _zngur__zngur_crate_MyCppWrapper_destructor_(i0: *mut u8, o: *mut u8);
+            }
+            let mut dummy = ();
+            unsafe {
+                _zngur__zngur_crate_MyCppWrapper_destructor_(self.buffer.assume_init_mut().as_mut_ptr() as *mut _, &mut dummy as *mut () as *mut u8);
+            }
+        }
+    } 
+
+    impl Drop for MyCppWrapper {
+        fn drop(&mut self) {
+            use ::zngur_lib::ZngCppDestruct;
+            unsafe {
+                self.destruct();
+            }
+        }
+    }
+
+}
+unsafe extern "C" {
+    fn _zngur_crate_MyCppWrapper_extern_method_new_s12(i0: *mut u8, i1: *mut u8, o: *mut u8);
+
+    fn _zngur_crate_MyCppWrapper_extern_method_print_s12(i0: *mut u8, o: *mut u8);
+}impl crate::MyCppWrapper {pub fn new(i0: i32, i1: i32, ) -> crate::MyCppWrapper { unsafe {
+let mut i0 = ::core::mem::MaybeUninit::new(i0);
+let mut i1 = ::core::mem::MaybeUninit::new(i1);
+let mut r = ::core::mem::MaybeUninit::uninit();
+_zngur_crate_MyCppWrapper_extern_method_new_s12(i0.as_mut_ptr() as *mut u8, i1.as_mut_ptr() as *mut u8, r.as_mut_ptr() as *mut u8);
+r.assume_init()
+} }
+pub fn print(&self, ) -> () { unsafe {
+let i0 = self;
+let mut i0 = ::core::mem::MaybeUninit::new(i0);
+let mut r = ::core::mem::MaybeUninit::uninit();
+_zngur_crate_MyCppWrapper_extern_method_print_s12(i0.as_mut_ptr() as *mut u8, r.as_mut_ptr() as *mut u8);
+r.assume_init()
+} }
+}
\ No newline at end of file
@@ -15,11 +15,7 @@ fn main() {
     let out_dir = build::out_dir();
 
     // Force rerun if generated files don't exist
-    let generated_files = [
-        out_dir.join("generated.cpp"),
-        out_dir.join("generated.h"),
-        out_dir.join("generated.rs"),
-    ];
+    let generated_files = [out_dir.join("generated.cpp"), out_dir.join("generated.h")];
     for file in &generated_files {
         if !file.exists() {
             println!("cargo:rerun-if-changed=nonexistent_trigger_file");
@@ -30,7 +26,6 @@ fn main() {
     Zngur::from_zng_file(crate_dir.join("main.zng"))
         .with_cpp_file(out_dir.join("generated.cpp"))
         .with_h_file(out_dir.join("generated.h"))
-        .with_rs_file(out_dir.join("generated.rs"))
         .with_crate_name("crate")
         .with_zng_header_in_place()
         .generate();
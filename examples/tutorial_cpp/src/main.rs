@@ -1,5 +1,5 @@
 mod generated {
-    include!(concat!(env!("OUT_DIR"), "/generated.rs"));
+    zngur::include_zng!("main.zng", crate_name = "crate");
 }
 
 pub use generated::cpp::Inventory;
@@ -0,0 +1,1152 @@
+//! Generating a zng spec for a Rust crate from its rustdoc JSON output.
+//!
+//! The JSON output is generated with
+//! `cargo +nightly rustdoc -- -Z unstable-options --output-format json`. The public structs, enums
+//! and free functions of the crate are declared in the spec with their methods and trait impls,
+//! together with the other types that their signatures use. The items and signatures which Zngur
+//! can't bridge are left out, and reported as [`Unsupported`].
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::Display,
+};
+
+use zngur_def::{
+    LayoutPolicy, Mutability, PrimitiveRustType, RustPathAndGenerics, RustTrait, RustType,
+    ZngurConstructor, ZngurFn, ZngurMethod, ZngurMethodDetails, ZngurMethodReceiver, ZngurSpec,
+    ZngurTrait, ZngurType, ZngurVariant, ZngurWellknownTrait,
+};
+
+use crate::rustdoc::{
+    Crate, GenericArg, GenericArgs, GenericBound, Id, Item, ItemEnum, ItemKind, StructKind, Term,
+    Type, VariantKind, Visibility,
+};
+
+mod rustdoc;
+#[cfg(test)]
+mod tests;
+mod update;
+mod verify;
+
+pub use update::{AddedMethod, AutoZngUpdate};
+pub use verify::verify;
+
+/// The auto traits, which can be added to a `dyn Trait` as a marker bound.
+const AUTO_TRAITS: &[&str] = &["Send", "Sync", "Unpin", "UnwindSafe", "RefUnwindSafe"];
+
+#[must_use]
+/// Builder for generating a spec from rustdoc JSON.
+///
+/// ```ignore
+/// let output = AutoZng::new()
+///     .with_filter("crate::shapes")
+///     .generate(&std::fs::read_to_string("target/doc/shapes.json")?)?;
+/// print!("{}", output.spec);
+/// ```
+pub struct AutoZng {
+    filters: Vec<Vec<String>>,
+    layout: LayoutPolicy,
+}
+
+impl Default for AutoZng {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AutoZng {
+    pub fn new() -> Self {
+        AutoZng {
+            filters: vec![],
+            layout: LayoutPolicy::Auto,
+        }
+    }
+
+    /// Only declare the items under `path`, like `crate::shapes` or `crate::shapes::Point`.
+    ///
+    /// May be repeated. By default, every public item of the crate is declared. The types which
+    /// are used by the declared items are declared anyway, but without their methods.
+    pub fn with_filter(mut self, path: &str) -> Self {
+        self.filters
+            .push(path.split("::").map(|s| s.to_owned()).collect());
+        self
+    }
+
+    /// The layout policy of the sized types. Default is `#layout(auto)`, which needs a layout
+    /// probe when generating the code.
+    pub fn with_layout_policy(mut self, layout: LayoutPolicy) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Generates the spec from the text of the rustdoc JSON output.
+    pub fn generate(&self, rustdoc_json: &str) -> Result<AutoZngOutput, String> {
+        let krate = read_crate(rustdoc_json)?;
+        let mut generator = Generator::new(&krate, self);
+        generator.run();
+        Ok(AutoZngOutput {
+            spec: generator.spec,
+            unsupported: generator.unsupported,
+        })
+    }
+
+    fn is_included(&self, path: &[String]) -> bool {
+        self.filters.is_empty() || self.filters.iter().any(|f| path.starts_with(f))
+    }
+}
+
+/// The declaration of a method, without its doc comment and attributes.
+fn signature(method: &ZngurMethodDetails) -> String {
+    let method = ZngurMethodDetails {
+        data: ZngurMethod {
+            doc: None,
+            cpp_name: None,
+            result_mode: None,
+            ..method.data.clone()
+        },
+        use_path: method.use_path.clone(),
+        deref: method.deref.clone(),
+    };
+    method.to_zng_string().trim_end().to_owned()
+}
+
+/// Reads the rustdoc JSON output, explaining the errors of the other format versions.
+fn read_crate(rustdoc_json: &str) -> Result<Crate, String> {
+    serde_json::from_str(rustdoc_json).map_err(|e| {
+        let version =
+            serde_json::from_str::<rustdoc::FormatVersion>(rustdoc_json).map(|v| v.format_version);
+        match version {
+            Ok(version) if version != rustdoc::FORMAT_VERSION => format!(
+                "Can't read rustdoc JSON of format version {version}, expected version {}: {e}",
+                rustdoc::FORMAT_VERSION
+            ),
+            _ => format!("Can't read rustdoc JSON: {e}"),
+        }
+    })
+}
+
+/// The result of [`AutoZng::generate`].
+pub struct AutoZngOutput {
+    pub spec: ZngurSpec,
+    /// The items which are left out of the spec.
+    pub unsupported: Vec<Unsupported>,
+}
+
+/// An item or a signature which can't be declared in the spec.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unsupported {
+    /// The path of the item, like `crate::shapes::Point::area`.
+    pub item: String,
+    pub reason: String,
+}
+
+impl Display for Unsupported {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}`: {}", self.item, self.reason)
+    }
+}
+
+/// How `Self`, the generic parameters and the associated types are resolved while converting the
+/// types of a signature.
+#[derive(Default)]
+struct Scope<'a> {
+    self_ty: Option<RustType>,
+    generics: HashMap<String, RustType>,
+    assoc_types: HashMap<String, &'a Type>,
+}
+
+struct Generator<'a> {
+    krate: &'a Crate,
+    options: &'a AutoZng,
+    /// The public paths of the local items, where they are first found from the crate root.
+    local_paths: HashMap<Id, Vec<String>>,
+    /// The local structs, enums and traits, by their path.
+    local_items: HashMap<Vec<String>, Id>,
+    /// The types which need a declaration, in the order they are found.
+    queue: VecDeque<RustType>,
+    seen: HashSet<RustType>,
+    /// The generic types which have at least one declared instance.
+    instantiated: HashSet<Vec<String>>,
+    /// The local types which are declared with their methods.
+    with_methods: HashSet<RustType>,
+    spec: ZngurSpec,
+    unsupported: Vec<Unsupported>,
+}
+
+impl<'a> Generator<'a> {
+    fn new(krate: &'a Crate, options: &'a AutoZng) -> Self {
+        Generator {
+            krate,
+            options,
+            local_paths: HashMap::new(),
+            local_items: HashMap::new(),
+            queue: VecDeque::new(),
+            seen: HashSet::new(),
+            instantiated: HashSet::new(),
+            with_methods: HashSet::new(),
+            spec: ZngurSpec::default(),
+            unsupported: vec![],
+        }
+    }
+
+    fn run(&mut self) {
+        let mut items = vec![];
+        self.walk_module(
+            self.krate.root,
+            vec!["crate".to_owned()],
+            &mut items,
+            &mut HashSet::new(),
+        );
+        for (id, path) in &items {
+            self.local_items.entry(path.clone()).or_insert(*id);
+        }
+        let mut generic_types = vec![];
+        for (id, path) in items {
+            if !self.options.is_included(&path) {
+                continue;
+            }
+            let item = &self.krate.index[&id];
+            match &item.inner {
+                ItemEnum::Struct(rustdoc::Struct { generics, .. })
+                | ItemEnum::Enum(rustdoc::Enum { generics, .. }) => {
+                    if generics.type_params().next().is_some() {
+                        generic_types.push(path);
+                        continue;
+                    }
+                    self.declare(RustType::Adt(RustPathAndGenerics {
+                        path,
+                        generics: vec![],
+                        named_generics: vec![],
+                    }));
+                }
+                ItemEnum::Function(_) => {
+                    if let Err(reason) = self.free_fn(item, &path) {
+                        self.report(path.join("::"), reason);
+                    }
+                }
+                _ => {}
+            }
+        }
+        while let Some(ty) = self.queue.pop_front() {
+            let zngur_type = self.type_item(ty);
+            self.spec.types.push(zngur_type);
+        }
+        for path in generic_types {
+            if !self.instantiated.contains(&path) {
+                self.report(
+                    path.join("::"),
+                    "Generic types are only declared for the instances used in other items"
+                        .to_owned(),
+                );
+            }
+        }
+    }
+
+    fn report(&mut self, item: String, reason: String) {
+        self.unsupported.push(Unsupported { item, reason });
+    }
+
+    /// Finds the public items of a module and the modules inside it.
+    fn walk_module(
+        &mut self,
+        module: Id,
+        path: Vec<String>,
+        items: &mut Vec<(Id, Vec<String>)>,
+        visited: &mut HashSet<Id>,
+    ) {
+        if !visited.insert(module) {
+            return;
+        }
+        let Some(Item {
+            inner: ItemEnum::Module(module),
+            ..
+        }) = self.krate.index.get(&module)
+        else {
+            return;
+        };
+        for id in &module.items {
+            let Some(item) = self.krate.index.get(id) else {
+                continue;
+            };
+            if !matches!(item.visibility, Visibility::Public) {
+                continue;
+            }
+            let (id, name) = match &item.inner {
+                ItemEnum::Use(rustdoc::Use {
+                    id: Some(target),
+                    is_glob: true,
+                    ..
+                }) => {
+                    self.walk_module(*target, path.clone(), items, visited);
+                    continue;
+                }
+                ItemEnum::Use(rustdoc::Use {
+                    name,
+                    id: Some(target),
+                    ..
+                }) => (*target, name),
+                _ => match &item.name {
+                    Some(name) => (*id, name),
+                    None => continue,
+                },
+            };
+            let Some(target) = self.krate.index.get(&id) else {
+                continue;
+            };
+            let mut item_path = path.clone();
+            item_path.push(name.clone());
+            match &target.inner {
+                ItemEnum::Module(_) => self.walk_module(id, item_path, items, visited),
+                ItemEnum::Struct(_)
+                | ItemEnum::Enum(_)
+                | ItemEnum::Function(_)
+                | ItemEnum::Trait(_)
+                | ItemEnum::TypeAlias(_)
+                    if target.crate_id == 0 && !self.local_paths.contains_key(&id) =>
+                {
+                    self.local_paths.insert(id, item_path.clone());
+                    items.push((id, item_path));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// The path of an item in the spec.
+    fn item_path(&self, id: Id, written: &str) -> Result<Vec<String>, String> {
+        if let Some(path) = self.local_paths.get(&id) {
+            return Ok(path.clone());
+        }
+        let Some(summary) = self.krate.paths.get(&id) else {
+            return Err(format!("`{written}` is not in the rustdoc output"));
+        };
+        let mut path = summary.path.clone();
+        if summary.crate_id == 0 {
+            path[0] = "crate".to_owned();
+            return Ok(path);
+        }
+        let krate = self
+            .krate
+            .external_crates
+            .get(&summary.crate_id)
+            .map(|c| c.name.as_str());
+        // The items of the standard library are defined in private modules, but they are all
+        // exported from a module directly under the crate root.
+        if matches!(krate, Some("std" | "core" | "alloc")) && path.len() > 3 {
+            path.drain(2..path.len() - 1);
+        }
+        if matches!(krate, Some("core" | "alloc")) {
+            path[0] = "std".to_owned();
+        }
+        Ok(path)
+    }
+
+    fn ty(&self, ty: &Type, scope: &Scope<'_>) -> Result<RustType, String> {
+        Ok(match ty {
+            Type::ResolvedPath(path) => return self.resolved_path(path, scope),
+            Type::DynTrait(dyn_trait) => {
+                let (tr, markers) =
+                    self.bounds(dyn_trait.traits.iter().map(|poly| &poly.trait_), scope)?;
+                RustType::Dyn(tr, markers)
+            }
+            Type::ImplTrait(bounds) => {
+                let traits = bounds.iter().filter_map(|bound| match bound {
+                    GenericBound::TraitBound { trait_ } => Some(trait_),
+                    _ => None,
+                });
+                let (tr, markers) = self.bounds(traits, scope)?;
+                RustType::Impl(tr, markers)
+            }
+            Type::Generic(name) if name == "Self" => scope
+                .self_ty
+                .clone()
+                .ok_or("`Self` is only supported in the receiver of trait methods")?,
+            Type::Generic(name) => scope
+                .generics
+                .get(name)
+                .cloned()
+                .ok_or_else(|| format!("Generic parameter `{name}` needs explicit instances"))?,
+            Type::Primitive(name) => RustType::Primitive(match name.as_str() {
+                "bool" => PrimitiveRustType::Bool,
+                "char" => PrimitiveRustType::Char,
+                "str" => PrimitiveRustType::Str,
+                "usize" => PrimitiveRustType::Usize,
+                "u8" | "u16" | "u32" | "u64" | "u128" => {
+                    PrimitiveRustType::Uint(name[1..].parse().unwrap())
+                }
+                "i8" | "i16" | "i32" | "i64" | "i128" => {
+                    PrimitiveRustType::Int(name[1..].parse().unwrap())
+                }
+                "f32" | "f64" => PrimitiveRustType::Float(name[1..].parse().unwrap()),
+                _ => return Err(format!("Primitive type `{name}` is not supported")),
+            }),
+            Type::FunctionPointer(fn_ptr) => {
+                let (inputs, output) = self.signature(&fn_ptr.sig, scope)?;
+                RustType::FnPtr {
+                    is_unsafe: fn_ptr.header.is_unsafe,
+                    abi: fn_ptr.header.abi.name().map(|abi| abi.to_owned()),
+                    inputs: inputs.into_iter().map(|(_, ty)| ty).collect(),
+                    output: Box::new(output),
+                }
+            }
+            Type::Tuple(fields) => RustType::Tuple(
+                fields
+                    .iter()
+                    .map(|field| self.ty(field, scope))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Type::Slice(inner) => RustType::Slice(Box::new(self.ty(inner, scope)?)),
+            Type::Array { ty, len } => {
+                let len = len
+                    .parse()
+                    .map_err(|_| format!("Array length `{len}` is not a number"))?;
+                RustType::Array(Box::new(self.ty(ty, scope)?), len)
+            }
+            Type::RawPointer { is_mutable, ty } => {
+                RustType::Raw(mutability(*is_mutable), Box::new(self.ty(ty, scope)?))
+            }
+            Type::BorrowedRef { is_mutable, ty } => {
+                RustType::Ref(mutability(*is_mutable), Box::new(self.ty(ty, scope)?))
+            }
+            Type::QualifiedPath {
+                name, self_type, ..
+            } => match (&**self_type, scope.assoc_types.get(name)) {
+                (Type::Generic(self_name), Some(assoc)) if self_name == "Self" => {
+                    self.ty(assoc, scope)?
+                }
+                _ => {
+                    return Err(
+                        "Associated types are only supported as `Self::Name` in trait impls"
+                            .to_owned(),
+                    );
+                }
+            },
+            Type::Pat {} | Type::Infer => {
+                return Err("`_` and pattern types are not supported".to_owned());
+            }
+        })
+    }
+
+    fn resolved_path(&self, path: &rustdoc::Path, scope: &Scope<'_>) -> Result<RustType, String> {
+        // Local type aliases are replaced with the aliased type.
+        if let Some(Item {
+            inner: ItemEnum::TypeAlias(alias),
+            ..
+        }) = self.krate.index.get(&path.id)
+        {
+            let mut alias_scope = Scope::default();
+            let args = self.generic_args(path, scope)?.generics;
+            for (param, arg) in alias.generics.type_params().zip(args) {
+                alias_scope.generics.insert(param.to_owned(), arg);
+            }
+            return self.ty(&alias.ty, &alias_scope);
+        }
+        let segments = self.item_path(path.id, &path.path)?;
+        let mut generics = self.generic_args(path, scope)?;
+        if self.krate.paths.get(&path.id).map(|s| &s.kind) == Some(&ItemKind::TypeAlias) {
+            // The common aliases of the standard library, which aren't in the rustdoc output.
+            let error = match segments.join("::").as_str() {
+                "std::fmt::Result" => {
+                    generics.generics = vec![RustType::UNIT];
+                    ["std", "fmt", "Error"]
+                }
+                "std::io::Result" => ["std", "io", "Error"],
+                _ => {
+                    return Err(format!(
+                        "Type alias `{}` is not supported, use the aliased type instead",
+                        path.path
+                    ));
+                }
+            };
+            generics.generics.push(RustType::Adt(RustPathAndGenerics {
+                path: error.map(|s| s.to_owned()).to_vec(),
+                generics: vec![],
+                named_generics: vec![],
+            }));
+            generics.path = ["std", "result", "Result"].map(|s| s.to_owned()).to_vec();
+            return Ok(RustType::Adt(generics));
+        }
+        generics.path = segments;
+        if generics.path == ["std", "boxed", "Box"] {
+            let Some(inner) = generics.generics.into_iter().next() else {
+                return Err("`Box` without a type argument".to_owned());
+            };
+            return Ok(RustType::Boxed(Box::new(inner)));
+        }
+        Ok(RustType::Adt(generics))
+    }
+
+    /// The generic arguments of a path, with an empty path.
+    fn generic_args(
+        &self,
+        path: &rustdoc::Path,
+        scope: &Scope<'_>,
+    ) -> Result<RustPathAndGenerics, String> {
+        let mut result = RustPathAndGenerics {
+            path: vec![],
+            generics: vec![],
+            named_generics: vec![],
+        };
+        match path.args.as_deref() {
+            None => {}
+            Some(GenericArgs::AngleBracketed { args, constraints }) => {
+                for arg in args {
+                    match arg {
+                        GenericArg::Lifetime(_) => {}
+                        GenericArg::Type(ty) => result.generics.push(self.ty(ty, scope)?),
+                        GenericArg::Const {} | GenericArg::Infer => {
+                            return Err(format!(
+                                "Const generic arguments of `{}` are not supported",
+                                path.path
+                            ));
+                        }
+                    }
+                }
+                for constraint in constraints {
+                    let rustdoc::AssocItemConstraintKind::Equality(Term::Type(ty)) =
+                        &constraint.binding
+                    else {
+                        return Err(format!(
+                            "Associated item constraints of `{}` are not supported",
+                            path.path
+                        ));
+                    };
+                    result
+                        .named_generics
+                        .push((constraint.name.clone(), self.ty(ty, scope)?));
+                }
+            }
+            Some(GenericArgs::Parenthesized { .. } | GenericArgs::ReturnTypeNotation) => {
+                return Err(format!(
+                    "Parenthesized arguments of `{}` are only supported for `Fn` traits",
+                    path.path
+                ));
+            }
+        }
+        Ok(result)
+    }
+
+    /// The trait and the marker bounds of a `dyn` or `impl` type.
+    fn bounds<'b>(
+        &self,
+        traits: impl Iterator<Item = &'b rustdoc::Path>,
+        scope: &Scope<'_>,
+    ) -> Result<(RustTrait, Vec<String>), String> {
+        let mut main = None;
+        let mut markers = vec![];
+        for path in traits {
+            let name = path.path.rsplit("::").next().unwrap_or_default();
+            if main.is_some() && AUTO_TRAITS.contains(&name) {
+                markers.push(name.to_owned());
+                continue;
+            }
+            if main.is_some() {
+                return Err("Only one trait besides the auto traits is supported".to_owned());
+            }
+            main = Some(self.rust_trait(path, scope)?);
+        }
+        Ok((
+            main.ok_or("A `dyn` or `impl` type without a trait")?,
+            markers,
+        ))
+    }
+
+    fn rust_trait(&self, path: &rustdoc::Path, scope: &Scope<'_>) -> Result<RustTrait, String> {
+        if let Some(GenericArgs::Parenthesized { inputs, output }) = path.args.as_deref() {
+            let name = path.path.rsplit("::").next().unwrap_or_default();
+            if !matches!(name, "Fn" | "FnMut" | "FnOnce") {
+                return Err(format!(
+                    "Parenthesized arguments of `{name}` are not supported"
+                ));
+            }
+            return Ok(RustTrait::Fn {
+                name: name.to_owned(),
+                inputs: inputs
+                    .iter()
+                    .map(|input| self.ty(input, scope))
+                    .collect::<Result<_, _>>()?,
+                output: Box::new(match output {
+                    Some(output) => self.ty(output, scope)?,
+                    None => RustType::UNIT,
+                }),
+            });
+        }
+        let mut tr = self.generic_args(path, scope)?;
+        tr.path = self.item_path(path.id, &path.path)?;
+        Ok(RustTrait::Normal(tr))
+    }
+
+    /// The named inputs and the output of a function signature.
+    fn signature(
+        &self,
+        sig: &rustdoc::FunctionSignature,
+        scope: &Scope<'_>,
+    ) -> Result<(Vec<(String, RustType)>, RustType), String> {
+        if sig.is_c_variadic {
+            return Err("Variadic functions are not supported".to_owned());
+        }
+        let inputs = sig
+            .inputs
+            .iter()
+            .map(|(name, ty)| Ok((name.clone(), self.ty(ty, scope)?)))
+            .collect::<Result<_, String>>()?;
+        let output = match &sig.output {
+            Some(output) => self.ty(output, scope)?,
+            None => RustType::UNIT,
+        };
+        Ok((inputs, output))
+    }
+
+    /// Checks that a function can be called from C++, without explicit generic arguments.
+    fn check_function(function: &rustdoc::Function) -> Result<(), String> {
+        if function.generics.type_params().next().is_some() {
+            return Err("Generic functions need explicit instances".to_owned());
+        }
+        if function.header.is_unsafe {
+            return Err("Unsafe functions are not supported".to_owned());
+        }
+        if function.header.is_async {
+            return Err("Async functions are not supported".to_owned());
+        }
+        if function.sig.is_c_variadic {
+            return Err("Variadic functions are not supported".to_owned());
+        }
+        Ok(())
+    }
+
+    fn free_fn(&mut self, item: &Item, path: &[String]) -> Result<(), String> {
+        let ItemEnum::Function(function) = &item.inner else {
+            return Ok(());
+        };
+        Self::check_function(function)?;
+        let (inputs, output) = self.signature(&function.sig, &Scope::default())?;
+        let inputs = inputs.into_iter().map(|(_, ty)| ty).collect::<Vec<_>>();
+        for ty in inputs.iter().chain([&output]) {
+            self.use_type(ty);
+        }
+        self.spec.funcs.push(ZngurFn {
+            path: RustPathAndGenerics {
+                path: path.to_vec(),
+                generics: vec![],
+                named_generics: vec![],
+            },
+            inputs,
+            output,
+            doc: item.docs.clone(),
+            cpp_name: None,
+            result_mode: None,
+        });
+        Ok(())
+    }
+
+    /// A method of a type or a trait. `Self` in the receiver is always allowed.
+    fn method(&self, item: &Item, scope: &Scope<'_>) -> Result<Option<ZngurMethod>, String> {
+        let ItemEnum::Function(function) = &item.inner else {
+            return Ok(None);
+        };
+        Self::check_function(function)?;
+        let mut inputs = &function.sig.inputs[..];
+        let receiver = match inputs.first() {
+            Some((name, ty)) if name == "self" => {
+                inputs = &inputs[1..];
+                match ty {
+                    Type::Generic(s) if s == "Self" => ZngurMethodReceiver::Move,
+                    Type::BorrowedRef { is_mutable, ty } if matches!(&**ty, Type::Generic(s) if s == "Self") => {
+                        ZngurMethodReceiver::Ref(mutability(*is_mutable))
+                    }
+                    _ => {
+                        return Err(
+                            "Only `self`, `&self` and `&mut self` receivers are supported"
+                                .to_owned(),
+                        );
+                    }
+                }
+            }
+            _ => ZngurMethodReceiver::Static,
+        };
+        let inputs = inputs
+            .iter()
+            .map(|(_, ty)| self.ty(ty, scope))
+            .collect::<Result<Vec<_>, _>>()?;
+        let output = match &function.sig.output {
+            Some(output) => self.ty(output, scope)?,
+            None => RustType::UNIT,
+        };
+        Ok(Some(ZngurMethod {
+            name: item.name.clone().unwrap_or_default(),
+            generics: vec![],
+            receiver,
+            inputs,
+            output,
+            is_safe: true,
+            doc: item.docs.clone(),
+            cpp_name: None,
+            result_mode: None,
+        }))
+    }
+
+    /// Queues the types in `ty` which need a declaration, like the validation of the spec does.
+    fn use_type(&mut self, ty: &RustType) {
+        match ty {
+            RustType::Primitive(
+                PrimitiveRustType::Uint(_)
+                | PrimitiveRustType::Int(_)
+                | PrimitiveRustType::Float(_)
+                | PrimitiveRustType::Usize,
+            ) => {}
+            RustType::Tuple(fields) if fields.is_empty() => {}
+            // Returning `impl Trait` returns a box of the trait object.
+            RustType::Impl(tr, markers) => self.use_type(&RustType::Boxed(Box::new(
+                RustType::Dyn(tr.clone(), markers.clone()),
+            ))),
+            RustType::Ref(_, inner) | RustType::Raw(_, inner) => self.use_type(inner),
+            RustType::FnPtr { inputs, output, .. } => {
+                for ty in inputs.iter().chain([&**output]) {
+                    self.use_type(ty);
+                }
+            }
+            _ => self.declare(ty.clone()),
+        }
+    }
+
+    fn declare(&mut self, ty: RustType) {
+        if self.seen.insert(ty.clone()) {
+            self.queue.push_back(ty);
+        }
+    }
+
+    fn type_item(&mut self, ty: RustType) -> ZngurType {
+        let mut zngur_type = ZngurType {
+            ty: ty.clone(),
+            layout: self.options.layout,
+            wellknown_traits: vec![],
+            methods: vec![],
+            constructors: vec![],
+            fields: vec![],
+            variants: vec![],
+            cpp_value: None,
+            cpp_ref: None,
+            cpp_stack_owned: None,
+            doc: None,
+            cpp_name: None,
+        };
+        match &ty {
+            RustType::Primitive(PrimitiveRustType::Bool) => {
+                zngur_type.layout = LayoutPolicy::StackAllocated { size: 1, align: 1 };
+                zngur_type.wellknown_traits = vec![ZngurWellknownTrait::Copy];
+            }
+            RustType::Primitive(PrimitiveRustType::Char) => {
+                zngur_type.layout = LayoutPolicy::StackAllocated { size: 4, align: 4 };
+                zngur_type.wellknown_traits = vec![ZngurWellknownTrait::Copy];
+            }
+            RustType::Primitive(PrimitiveRustType::Str) | RustType::Slice(_) => {
+                zngur_type.layout = LayoutPolicy::OnlyByRef;
+                zngur_type.wellknown_traits = vec![ZngurWellknownTrait::Unsized];
+            }
+            RustType::Dyn(tr, _) => {
+                zngur_type.layout = LayoutPolicy::OnlyByRef;
+                zngur_type.wellknown_traits = vec![ZngurWellknownTrait::Unsized];
+                self.declare_trait(tr);
+            }
+            RustType::Boxed(inner) => {
+                if let RustType::Dyn(tr, _) = &**inner {
+                    self.declare_trait(tr);
+                }
+            }
+            RustType::Adt(pg) => match self.local_items.get(&pg.path) {
+                Some(id) => self.local_type(&mut zngur_type, *id, pg),
+                None => self.std_enum(&mut zngur_type, pg),
+            },
+            _ => {}
+        }
+        if !zngur_type
+            .wellknown_traits
+            .contains(&ZngurWellknownTrait::Copy)
+            && !zngur_type
+                .wellknown_traits
+                .contains(&ZngurWellknownTrait::Unsized)
+        {
+            zngur_type.wellknown_traits.push(ZngurWellknownTrait::Drop);
+        }
+        zngur_type
+    }
+
+    /// `Option` and `Result` are declared as enums.
+    fn std_enum(&mut self, zngur_type: &mut ZngurType, pg: &RustPathAndGenerics) {
+        let variants: &[(&str, Option<usize>)] =
+            match (pg.path.join("::").as_str(), pg.generics.len()) {
+                ("std::option::Option", 1) => &[("None", None), ("Some", Some(0))],
+                ("std::result::Result", 2) => &[("Ok", Some(0)), ("Err", Some(1))],
+                _ => return,
+            };
+        for (name, field) in variants {
+            let fields = field
+                .map(|i| vec![("0".to_owned(), pg.generics[i].clone())])
+                .unwrap_or_default();
+            for (_, ty) in &fields {
+                self.use_type(ty);
+            }
+            zngur_type.variants.push(ZngurVariant {
+                name: (*name).to_owned(),
+                fields,
+                doc: None,
+            });
+        }
+    }
+
+    fn local_type(&mut self, zngur_type: &mut ZngurType, id: Id, pg: &RustPathAndGenerics) {
+        let item = &self.krate.index[&id];
+        let (generics, impls) = match &item.inner {
+            ItemEnum::Struct(s) => (&s.generics, &s.impls),
+            ItemEnum::Enum(e) => (&e.generics, &e.impls),
+            _ => return,
+        };
+        zngur_type.doc = item.docs.clone();
+        let mut scope = Scope {
+            self_ty: Some(zngur_type.ty.clone()),
+            ..Scope::default()
+        };
+        for (param, arg) in generics.type_params().zip(&pg.generics) {
+            scope.generics.insert(param.to_owned(), arg.clone());
+        }
+        if !pg.generics.is_empty() {
+            self.instantiated.insert(pg.path.clone());
+        }
+        // The types which aren't included are declared without their methods, since they are
+        // only needed for the signatures of the included items.
+        let is_included = self.options.is_included(&pg.path);
+        if is_included {
+            self.with_methods.insert(zngur_type.ty.clone());
+        }
+        let item_name = zngur_type.ty.to_string();
+        match &item.inner {
+            ItemEnum::Struct(s) if is_included && zngur_type.layout != LayoutPolicy::OnlyByRef => {
+                let fields = match &s.kind {
+                    StructKind::Plain {
+                        fields,
+                        has_stripped_fields: false,
+                    } => Some(self.fields(fields.iter().map(Some), false, &scope)),
+                    StructKind::Tuple(fields) => {
+                        Some(self.fields(fields.iter().map(Option::as_ref), true, &scope))
+                    }
+                    _ => None,
+                };
+                match fields {
+                    Some(Ok(Some(inputs))) => {
+                        for (_, ty) in &inputs {
+                            self.use_type(ty);
+                        }
+                        zngur_type.constructors.push(ZngurConstructor {
+                            name: None,
+                            inputs,
+                            doc: None,
+                            cpp_name: None,
+                        });
+                    }
+                    Some(Err(reason)) => {
+                        self.report(item_name.clone(), format!("No constructor: {reason}"))
+                    }
+                    _ => {}
+                }
+            }
+            ItemEnum::Enum(e) if !e.has_stripped_variants => {
+                match self.variants(&e.variants, &scope) {
+                    Ok(Some(variants)) => {
+                        for variant in &variants {
+                            for (_, ty) in &variant.fields {
+                                self.use_type(ty);
+                            }
+                        }
+                        zngur_type.variants = variants;
+                    }
+                    Ok(None) => {}
+                    Err(reason) => self.report(item_name.clone(), format!("No variants: {reason}")),
+                }
+            }
+            _ => {}
+        }
+        for impl_id in impls {
+            self.impl_block(zngur_type, *impl_id, &scope, is_included);
+        }
+    }
+
+    /// The fields of a struct or a variant, or `None` if some of them aren't visible.
+    fn fields<'b>(
+        &self,
+        fields: impl Iterator<Item = Option<&'b Id>>,
+        is_tuple: bool,
+        scope: &Scope<'_>,
+    ) -> Result<Option<Vec<(String, RustType)>>, String> {
+        let mut result = vec![];
+        for (i, field) in fields.enumerate() {
+            let Some(Item {
+                name,
+                visibility,
+                inner: ItemEnum::StructField(ty),
+                ..
+            }) = field.and_then(|field| self.krate.index.get(field))
+            else {
+                return Ok(None);
+            };
+            if !matches!(visibility, Visibility::Public | Visibility::Default) {
+                return Ok(None);
+            }
+            let name = match (is_tuple, name) {
+                (false, Some(name)) => name.clone(),
+                _ => i.to_string(),
+            };
+            result.push((name, self.ty(ty, scope)?));
+        }
+        Ok(Some(result))
+    }
+
+    fn variants(&self, ids: &[Id], scope: &Scope<'_>) -> Result<Option<Vec<ZngurVariant>>, String> {
+        let mut variants = vec![];
+        for id in ids {
+            let item = &self.krate.index[id];
+            let ItemEnum::Variant(variant) = &item.inner else {
+                return Ok(None);
+            };
+            let fields = match &variant.kind {
+                VariantKind::Plain => Some(vec![]),
+                VariantKind::Tuple(fields) => {
+                    self.fields(fields.iter().map(Option::as_ref), true, scope)?
+                }
+                VariantKind::Struct {
+                    fields,
+                    has_stripped_fields: false,
+                } => self.fields(fields.iter().map(Some), false, scope)?,
+                VariantKind::Struct { .. } => None,
+            };
+            let Some(fields) = fields else {
+                return Ok(None);
+            };
+            variants.push(ZngurVariant {
+                name: item.name.clone().unwrap_or_default(),
+                fields,
+                doc: item.docs.clone(),
+            });
+        }
+        Ok(Some(variants))
+    }
+
+    /// Adds the wellknown traits and the methods of an impl block of the type, if it applies to
+    /// the type. The bounds of generic impls are not checked.
+    fn impl_block(
+        &mut self,
+        zngur_type: &mut ZngurType,
+        impl_id: Id,
+        type_scope: &Scope<'_>,
+        with_methods: bool,
+    ) {
+        let Some(Item {
+            inner: ItemEnum::Impl(imp),
+            ..
+        }) = self.krate.index.get(&impl_id)
+        else {
+            return;
+        };
+        if imp.is_synthetic || imp.is_negative || imp.blanket_impl.is_some() {
+            return;
+        }
+        let params = imp.generics.type_params().collect::<Vec<_>>();
+        let mut generics = HashMap::new();
+        if !self.unify(&imp.for_, &zngur_type.ty, &params, &mut generics) {
+            return;
+        }
+        let mut scope = Scope {
+            self_ty: type_scope.self_ty.clone(),
+            generics,
+            assoc_types: HashMap::new(),
+        };
+        let use_path = match &imp.trait_ {
+            Some(tr) => {
+                let Ok(path) = self.item_path(tr.id, &tr.path) else {
+                    return;
+                };
+                let wellknown = match path.join("::").as_str() {
+                    "std::fmt::Debug" => Some(ZngurWellknownTrait::Debug),
+                    "std::marker::Copy" => Some(ZngurWellknownTrait::Copy),
+                    _ => None,
+                };
+                if let Some(wellknown) = wellknown {
+                    zngur_type.wellknown_traits.push(wellknown);
+                    return;
+                }
+                Some(path)
+            }
+            None => None,
+        };
+        if !with_methods {
+            return;
+        }
+        let items = imp
+            .items
+            .iter()
+            .filter_map(|id| self.krate.index.get(id))
+            .collect::<Vec<_>>();
+        for item in &items {
+            if let (ItemEnum::AssocType { ty: Some(ty) }, Some(name)) = (&item.inner, &item.name) {
+                scope.assoc_types.insert(name.clone(), ty);
+            }
+        }
+        for item in items {
+            // The items of trait impls are as visible as the trait.
+            if use_path.is_none() && !matches!(item.visibility, Visibility::Public) {
+                continue;
+            }
+            let name = format!(
+                "{}::{}",
+                zngur_type.ty,
+                item.name.as_deref().unwrap_or_default()
+            );
+            if params
+                .iter()
+                .any(|param| !scope.generics.contains_key(*param))
+            {
+                self.report(name, "Generic impls need explicit instances".to_owned());
+                continue;
+            }
+            let method = match self.method(item, &scope) {
+                Ok(Some(method)) => method,
+                Ok(None) => continue,
+                Err(reason) => {
+                    self.report(name, reason);
+                    continue;
+                }
+            };
+            if zngur_type
+                .methods
+                .iter()
+                .any(|m| m.data.name == method.name)
+            {
+                self.report(
+                    name,
+                    "Another method with the same name is already declared".to_owned(),
+                );
+                continue;
+            }
+            for ty in method.inputs.iter().chain([&method.output]) {
+                self.use_type(ty);
+            }
+            zngur_type.methods.push(ZngurMethodDetails {
+                data: method,
+                use_path: use_path.clone(),
+                deref: None,
+            });
+        }
+    }
+
+    /// Declares a trait of the crate, so C++ can implement it for `Box<dyn Trait>`.
+    fn declare_trait(&mut self, tr: &RustTrait) {
+        let RustTrait::Normal(pg) = tr else {
+            return;
+        };
+        if self.spec.traits.contains_key(tr) {
+            return;
+        }
+        let Some(Item {
+            inner: ItemEnum::Trait(trait_item),
+            ..
+        }) = self
+            .local_items
+            .get(&pg.path)
+            .and_then(|id| self.krate.index.get(id))
+        else {
+            return;
+        };
+        let mut scope = Scope::default();
+        for (param, arg) in trait_item.generics.type_params().zip(&pg.generics) {
+            scope.generics.insert(param.to_owned(), arg.clone());
+        }
+        let mut methods = vec![];
+        for item in trait_item
+            .items
+            .iter()
+            .filter_map(|id| self.krate.index.get(id))
+        {
+            let name = format!("{tr}::{}", item.name.as_deref().unwrap_or_default());
+            match self.method(item, &scope) {
+                Ok(Some(method)) => methods.push(method),
+                Ok(None) => {}
+                Err(reason) => self.report(name, reason),
+            }
+        }
+        for method in &methods {
+            for ty in method.inputs.iter().chain([&method.output]) {
+                self.use_type(ty);
+            }
+        }
+        self.spec.traits.insert(
+            tr.clone(),
+            ZngurTrait {
+                tr: tr.clone(),
+                methods,
+            },
+        );
+    }
+
+    /// Matches the type of an impl block with a type, and finds the generic parameters of the
+    /// impl block.
+    fn unify(
+        &self,
+        pattern: &Type,
+        ty: &RustType,
+        params: &[&str],
+        generics: &mut HashMap<String, RustType>,
+    ) -> bool {
+        match (pattern, ty) {
+            (Type::Generic(name), _) if params.contains(&name.as_str()) => {
+                match generics.get(name) {
+                    Some(bound) => bound == ty,
+                    None => {
+                        generics.insert(name.clone(), ty.clone());
+                        true
+                    }
+                }
+            }
+            (Type::ResolvedPath(path), RustType::Adt(pg)) => {
+                if self.item_path(path.id, &path.path).as_ref() != Ok(&pg.path) {
+                    return false;
+                }
+                let args = match path.args.as_deref() {
+                    None => vec![],
+                    Some(GenericArgs::AngleBracketed { args, .. }) => args
+                        .iter()
+                        .filter_map(|arg| match arg {
+                            GenericArg::Type(ty) => Some(ty),
+                            _ => None,
+                        })
+                        .collect(),
+                    Some(_) => return false,
+                };
+                args.len() == pg.generics.len()
+                    && args
+                        .iter()
+                        .zip(&pg.generics)
+                        .all(|(arg, ty)| self.unify(arg, ty, params, generics))
+            }
+            (Type::BorrowedRef { is_mutable, ty: p }, RustType::Ref(m, ty))
+            | (Type::RawPointer { is_mutable, ty: p }, RustType::Raw(m, ty)) => {
+                mutability(*is_mutable) == *m && self.unify(p, ty, params, generics)
+            }
+            (Type::Slice(p), RustType::Slice(ty)) => self.unify(p, ty, params, generics),
+            (Type::Tuple(ps), RustType::Tuple(tys)) => {
+                ps.len() == tys.len()
+                    && ps
+                        .iter()
+                        .zip(tys)
+                        .all(|(p, ty)| self.unify(p, ty, params, generics))
+            }
+            _ => self.ty(pattern, &Scope::default()).as_ref() == Ok(ty),
+        }
+    }
+}
+
+fn mutability(is_mutable: bool) -> Mutability {
+    if is_mutable {
+        Mutability::Mut
+    } else {
+        Mutability::Not
+    }
+}
@@ -0,0 +1,374 @@
+//! The parts of the rustdoc JSON output which autozng reads.
+//!
+//! Unknown fields are ignored, so this follows the format closely enough to read a range of
+//! format versions around [`FORMAT_VERSION`].
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, de::IgnoredAny};
+
+/// The format version of the rustdoc JSON output which this model is written for.
+pub(crate) const FORMAT_VERSION: u32 = 57;
+
+pub(crate) type Id = u32;
+
+/// The version of the output, which is read first to explain the errors of other versions.
+#[derive(Debug, Deserialize)]
+pub(crate) struct FormatVersion {
+    pub format_version: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Crate {
+    pub root: Id,
+    pub index: HashMap<Id, Item>,
+    pub paths: HashMap<Id, ItemSummary>,
+    pub external_crates: HashMap<u32, ExternalCrate>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ExternalCrate {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ItemSummary {
+    pub crate_id: u32,
+    pub path: Vec<String>,
+    pub kind: ItemKind,
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ItemKind {
+    TypeAlias,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Item {
+    pub crate_id: u32,
+    pub name: Option<String>,
+    pub visibility: Visibility,
+    pub docs: Option<String>,
+    pub inner: ItemEnum,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Visibility {
+    Public,
+    Default,
+    Crate,
+    Restricted {},
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ItemEnum {
+    Module(Module),
+    Use(Use),
+    Struct(Struct),
+    StructField(Type),
+    Enum(Enum),
+    Variant(Variant),
+    Function(Function),
+    Trait(Trait),
+    Impl(Impl),
+    AssocType {
+        #[serde(rename = "type")]
+        ty: Option<Type>,
+    },
+    ExternCrate(IgnoredAny),
+    Union(IgnoredAny),
+    TraitAlias(IgnoredAny),
+    TypeAlias(TypeAlias),
+    Constant(IgnoredAny),
+    Static(IgnoredAny),
+    ExternType,
+    Macro(IgnoredAny),
+    ProcMacro(IgnoredAny),
+    Primitive(IgnoredAny),
+    AssocConst(IgnoredAny),
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Module {
+    pub items: Vec<Id>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Use {
+    pub name: String,
+    pub id: Option<Id>,
+    pub is_glob: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Struct {
+    pub kind: StructKind,
+    pub generics: Generics,
+    pub impls: Vec<Id>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum StructKind {
+    Unit,
+    /// The fields which are hidden from the documentation are `None`.
+    Tuple(Vec<Option<Id>>),
+    Plain {
+        fields: Vec<Id>,
+        has_stripped_fields: bool,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Enum {
+    pub generics: Generics,
+    pub has_stripped_variants: bool,
+    pub variants: Vec<Id>,
+    pub impls: Vec<Id>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Variant {
+    pub kind: VariantKind,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum VariantKind {
+    Plain,
+    Tuple(Vec<Option<Id>>),
+    Struct {
+        fields: Vec<Id>,
+        has_stripped_fields: bool,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct TypeAlias {
+    #[serde(rename = "type")]
+    pub ty: Type,
+    pub generics: Generics,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Function {
+    pub sig: FunctionSignature,
+    pub generics: Generics,
+    pub header: FunctionHeader,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct FunctionSignature {
+    pub inputs: Vec<(String, Type)>,
+    pub output: Option<Type>,
+    pub is_c_variadic: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct FunctionHeader {
+    pub is_unsafe: bool,
+    pub is_async: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Trait {
+    pub items: Vec<Id>,
+    pub generics: Generics,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Impl {
+    pub generics: Generics,
+    #[serde(rename = "trait")]
+    pub trait_: Option<Path>,
+    #[serde(rename = "for")]
+    pub for_: Type,
+    pub items: Vec<Id>,
+    pub is_negative: bool,
+    pub is_synthetic: bool,
+    pub blanket_impl: Option<Type>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Generics {
+    pub params: Vec<GenericParamDef>,
+}
+
+impl Generics {
+    /// The names of the type and const parameters. Lifetimes don't matter for C++.
+    pub fn type_params(&self) -> impl Iterator<Item = &str> {
+        self.params
+            .iter()
+            .filter(|param| !matches!(param.kind, GenericParamDefKind::Lifetime {}))
+            .map(|param| param.name.as_str())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct GenericParamDef {
+    pub name: String,
+    pub kind: GenericParamDefKind,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum GenericParamDefKind {
+    Lifetime {},
+    Type {},
+    Const {},
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Path {
+    pub path: String,
+    pub id: Id,
+    pub args: Option<Box<GenericArgs>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum GenericArgs {
+    AngleBracketed {
+        args: Vec<GenericArg>,
+        constraints: Vec<AssocItemConstraint>,
+    },
+    Parenthesized {
+        inputs: Vec<Type>,
+        output: Option<Type>,
+    },
+    ReturnTypeNotation,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum GenericArg {
+    Lifetime(IgnoredAny),
+    Type(Type),
+    Const {},
+    Infer,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct AssocItemConstraint {
+    pub name: String,
+    pub binding: AssocItemConstraintKind,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum AssocItemConstraintKind {
+    Equality(Term),
+    Constraint(IgnoredAny),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Term {
+    Type(Type),
+    Constant {},
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum GenericBound {
+    TraitBound {
+        #[serde(rename = "trait")]
+        trait_: Path,
+    },
+    Outlives(IgnoredAny),
+    Use(IgnoredAny),
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct DynTrait {
+    pub traits: Vec<PolyTrait>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct PolyTrait {
+    #[serde(rename = "trait")]
+    pub trait_: Path,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct FunctionPointer {
+    pub sig: FunctionSignature,
+    pub header: FunctionPointerHeader,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct FunctionPointerHeader {
+    pub is_unsafe: bool,
+    pub abi: Abi,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) enum Abi {
+    Rust,
+    C {},
+    Cdecl {},
+    Stdcall {},
+    Fastcall {},
+    Aapcs {},
+    Win64 {},
+    SysV64 {},
+    System {},
+    Other(String),
+}
+
+impl Abi {
+    /// The name of the ABI in an `extern` function pointer, or `None` for the Rust ABI.
+    pub fn name(&self) -> Option<&str> {
+        Some(match self {
+            Abi::Rust => return None,
+            Abi::C {} => "C",
+            Abi::Cdecl {} => "cdecl",
+            Abi::Stdcall {} => "stdcall",
+            Abi::Fastcall {} => "fastcall",
+            Abi::Aapcs {} => "aapcs",
+            Abi::Win64 {} => "win64",
+            Abi::SysV64 {} => "sysv64",
+            Abi::System {} => "system",
+            Abi::Other(name) => name.trim_matches('"'),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Type {
+    ResolvedPath(Path),
+    DynTrait(DynTrait),
+    Generic(String),
+    Primitive(String),
+    FunctionPointer(Box<FunctionPointer>),
+    Tuple(Vec<Type>),
+    Slice(Box<Type>),
+    Array {
+        #[serde(rename = "type")]
+        ty: Box<Type>,
+        len: String,
+    },
+    Pat {},
+    ImplTrait(Vec<GenericBound>),
+    Infer,
+    RawPointer {
+        is_mutable: bool,
+        #[serde(rename = "type")]
+        ty: Box<Type>,
+    },
+    BorrowedRef {
+        is_mutable: bool,
+        #[serde(rename = "type")]
+        ty: Box<Type>,
+    },
+    QualifiedPath {
+        name: String,
+        self_type: Box<Type>,
+    },
+}
@@ -0,0 +1,653 @@
+use expect_test::{Expect, expect};
+use serde_json::{Value, json};
+use zngur_def::LayoutPolicy;
+use zngur_parser::{ParsedZngFile, cfg::NullCfg};
+
+use crate::{AutoZng, verify};
+
+/// A rustdoc JSON output with the given local items, and the paths of the external items that
+/// they use.
+fn rustdoc(items: Vec<Value>, paths: Value) -> String {
+    let mut index = serde_json::Map::new();
+    for item in items {
+        index.insert(item["id"].to_string(), item);
+    }
+    json!({
+        "root": 0,
+        "format_version": 57,
+        "index": index,
+        "paths": paths,
+        "external_crates": {
+            "1": { "name": "core" },
+            "2": { "name": "alloc" },
+        },
+    })
+    .to_string()
+}
+
+fn item(id: u32, name: Option<&str>, visibility: &str, inner: Value) -> Value {
+    json!({
+        "id": id,
+        "crate_id": 0,
+        "name": name,
+        "visibility": visibility,
+        "docs": null,
+        "inner": inner,
+    })
+}
+
+fn module(id: u32, name: &str, items: &[u32]) -> Value {
+    item(
+        id,
+        Some(name),
+        "public",
+        json!({ "module": { "items": items } }),
+    )
+}
+
+fn function(id: u32, name: &str, visibility: &str, inputs: Value, output: Value) -> Value {
+    item(
+        id,
+        Some(name),
+        visibility,
+        json!({ "function": {
+            "sig": { "inputs": inputs, "output": output, "is_c_variadic": false },
+            "generics": { "params": [] },
+            "header": { "is_unsafe": false, "is_async": false },
+        }}),
+    )
+}
+
+fn inherent_impl(id: u32, generics: &[&str], for_: Value, items: &[u32]) -> Value {
+    let params = generics
+        .iter()
+        .map(|name| json!({ "name": name, "kind": { "type": {} } }))
+        .collect::<Vec<_>>();
+    item(
+        id,
+        None,
+        "default",
+        json!({ "impl": {
+            "generics": { "params": params },
+            "trait": null,
+            "for": for_,
+            "items": items,
+            "is_negative": false,
+            "is_synthetic": false,
+            "blanket_impl": null,
+        }}),
+    )
+}
+
+fn resolved(path: &str, id: u32, args: &[Value]) -> Value {
+    let args = if args.is_empty() {
+        Value::Null
+    } else {
+        let args = args
+            .iter()
+            .map(|ty| json!({ "type": ty }))
+            .collect::<Vec<_>>();
+        json!({ "angle_bracketed": { "args": args, "constraints": [] } })
+    };
+    json!({ "resolved_path": { "path": path, "id": id, "args": args } })
+}
+
+fn self_ref() -> Value {
+    json!(["self", { "borrowed_ref": { "is_mutable": false, "type": { "generic": "Self" } } }])
+}
+
+fn check_autozng(autozng: AutoZng, json: &str, result: Expect) {
+    let output = autozng.generate(json).unwrap();
+    let zng = output.spec.to_zng_string();
+    // The generated spec should be complete, so the parser accepts it.
+    ParsedZngFile::parse_str(&zng, NullCfg).validate().unwrap();
+    let mut text = zng;
+    for unsupported in &output.unsupported {
+        text += &format!("// skipped {unsupported}\n");
+    }
+    result.assert_eq(&text);
+}
+
+#[test]
+fn structs_enums_and_generic_instances() {
+    let point = resolved("Point", 2, &[]);
+    let wrapper_t = resolved("Wrapper", 10, &[json!({ "generic": "T" })]);
+    let json = rustdoc(
+        vec![
+            module(0, "fixture", &[1, 20]),
+            module(1, "shapes", &[2, 10, 30]),
+            item(
+                2,
+                Some("Point"),
+                "public",
+                json!({ "struct": {
+                    "kind": { "plain": { "fields": [3, 4], "has_stripped_fields": false } },
+                    "generics": { "params": [] },
+                    "impls": [5, 7],
+                }}),
+            ),
+            item(
+                3,
+                Some("x"),
+                "public",
+                json!({ "struct_field": { "primitive": "f64" } }),
+            ),
+            item(
+                4,
+                Some("y"),
+                "public",
+                json!({ "struct_field": { "primitive": "f64" } }),
+            ),
+            inherent_impl(5, &[], point.clone(), &[6, 8]),
+            function(
+                6,
+                "new",
+                "public",
+                json!([["x", { "primitive": "f64" }], ["y", { "primitive": "f64" }]]),
+                json!({ "generic": "Self" }),
+            ),
+            function(8, "private", "default", json!([self_ref()]), Value::Null),
+            item(
+                7,
+                None,
+                "default",
+                json!({ "impl": {
+                    "generics": { "params": [] },
+                    "trait": { "path": "Copy", "id": 100, "args": null },
+                    "for": point,
+                    "items": [],
+                    "is_negative": false,
+                    "is_synthetic": false,
+                    "blanket_impl": null,
+                }}),
+            ),
+            item(
+                10,
+                Some("Wrapper"),
+                "public",
+                json!({ "struct": {
+                    "kind": { "tuple": [11] },
+                    "generics": { "params": [{ "name": "T", "kind": { "type": {} } }] },
+                    "impls": [12],
+                }}),
+            ),
+            item(
+                11,
+                Some("0"),
+                "public",
+                json!({ "struct_field": { "generic": "T" } }),
+            ),
+            inherent_impl(12, &["T"], wrapper_t, &[13]),
+            function(
+                13,
+                "get",
+                "public",
+                json!([self_ref()]),
+                resolved(
+                    "Option",
+                    101,
+                    &[
+                        json!({ "borrowed_ref": { "is_mutable": false, "type": { "generic": "T" } } }),
+                    ],
+                ),
+            ),
+            item(
+                30,
+                Some("Shape"),
+                "public",
+                json!({ "enum": {
+                    "generics": { "params": [] },
+                    "has_stripped_variants": false,
+                    "variants": [31, 32],
+                    "impls": [],
+                }}),
+            ),
+            item(
+                31,
+                Some("Circle"),
+                "default",
+                json!({ "variant": { "kind": { "tuple": [33] } } }),
+            ),
+            item(
+                33,
+                Some("0"),
+                "default",
+                json!({ "struct_field": { "primitive": "f64" } }),
+            ),
+            item(
+                32,
+                Some("Empty"),
+                "default",
+                json!({ "variant": { "kind": "plain" } }),
+            ),
+            function(
+                20,
+                "wrap",
+                "public",
+                json!([["p", { "borrowed_ref": { "is_mutable": true, "type": resolved("shapes::Point", 2, &[]) } }]]),
+                resolved("Wrapper", 10, &[json!({ "primitive": "bool" })]),
+            ),
+        ],
+        json!({
+            "100": { "crate_id": 1, "path": ["core", "marker", "Copy"], "kind": "trait" },
+            "101": { "crate_id": 1, "path": ["core", "option", "Option"], "kind": "enum" },
+        }),
+    );
+    check_autozng(
+        AutoZng::new(),
+        &json,
+        expect![[r#"
+            type crate::shapes::Point {
+                #layout(auto);
+                wellknown_traits(Copy);
+                constructor { x: f64, y: f64 };
+                fn new(f64, f64) -> crate::shapes::Point;
+            }
+
+            enum crate::shapes::Shape {
+                #layout(auto);
+                Circle(f64);
+                Empty;
+            }
+
+            type crate::shapes::Wrapper::<bool> {
+                #layout(auto);
+                constructor(bool);
+                fn get(&self) -> ::std::option::Option::<&bool>;
+            }
+
+            type bool {
+                #layout(size = 1, align = 1);
+                wellknown_traits(Copy);
+            }
+
+            enum ::std::option::Option::<&bool> {
+                #layout(auto);
+                None;
+                Some(&bool);
+            }
+
+            mod crate {
+                fn wrap(&mut crate::shapes::Point) -> crate::shapes::Wrapper::<bool>;
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn unsupported_items_are_reported() {
+    let point = resolved("Point", 2, &[]);
+    let json = rustdoc(
+        vec![
+            module(0, "fixture", &[2, 20, 21]),
+            item(
+                2,
+                Some("Point"),
+                "public",
+                json!({ "struct": {
+                    "kind": { "plain": { "fields": [], "has_stripped_fields": true } },
+                    "generics": { "params": [] },
+                    "impls": [5],
+                }}),
+            ),
+            inherent_impl(5, &[], point, &[6, 7]),
+            function(
+                6,
+                "boxed",
+                "public",
+                json!([[
+                    "self",
+                    resolved("Box", 102, &[json!({ "generic": "Self" })])
+                ]]),
+                Value::Null,
+            ),
+            function(
+                7,
+                "len",
+                "public",
+                json!([self_ref()]),
+                json!({ "primitive": "usize" }),
+            ),
+            function(
+                20,
+                "offset",
+                "public",
+                json!([]),
+                json!({ "primitive": "isize" }),
+            ),
+            function(
+                21,
+                "lines",
+                "public",
+                json!([]),
+                json!({ "qualified_path": {
+                    "name": "Item",
+                    "self_type": resolved("Lines", 103, &[]),
+                    "trait": { "path": "Iterator", "id": 104, "args": null },
+                }}),
+            ),
+        ],
+        json!({
+            "102": { "crate_id": 2, "path": ["alloc", "boxed", "Box"], "kind": "struct" },
+            "103": { "crate_id": 1, "path": ["core", "str", "iter", "Lines"], "kind": "struct" },
+            "104": { "crate_id": 1, "path": ["core", "iter", "traits", "iterator", "Iterator"], "kind": "trait" },
+        }),
+    );
+    check_autozng(
+        AutoZng::new().with_layout_policy(LayoutPolicy::HeapAllocated),
+        &json,
+        expect![[r#"
+            type crate::Point {
+                #heap_allocated;
+                fn len(&self) -> usize;
+            }
+            // skipped `crate::offset`: Primitive type `isize` is not supported
+            // skipped `crate::lines`: Associated types are only supported as `Self::Name` in trait impls
+            // skipped `crate::Point::boxed`: Only `self`, `&self` and `&mut self` receivers are supported
+        "#]],
+    );
+}
+
+#[test]
+fn filtered_out_types_are_declared_without_methods() {
+    let json = rustdoc(
+        vec![
+            module(0, "fixture", &[1, 2]),
+            module(1, "a", &[10]),
+            module(2, "b", &[20]),
+            function(
+                10,
+                "make",
+                "public",
+                json!([]),
+                resolved("crate::b::B", 20, &[]),
+            ),
+            item(
+                20,
+                Some("B"),
+                "public",
+                json!({ "struct": {
+                    "kind": "unit",
+                    "generics": { "params": [] },
+                    "impls": [21],
+                }}),
+            ),
+            inherent_impl(21, &[], resolved("B", 20, &[]), &[22]),
+            function(22, "hidden", "public", json!([self_ref()]), Value::Null),
+        ],
+        json!({}),
+    );
+    check_autozng(
+        AutoZng::new().with_filter("crate::a"),
+        &json,
+        expect![[r#"
+            type crate::b::B {
+                #layout(auto);
+            }
+
+            mod crate::a {
+                fn make() -> crate::b::B;
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn other_format_versions_are_reported() {
+    let error = AutoZng::new()
+        .generate(r#"{ "format_version": 12, "index": {} }"#)
+        .err()
+        .unwrap();
+    expect!["Can't read rustdoc JSON of format version 12, expected version 57: missing field `root` at line 1 column 37"]
+        .assert_eq(&error);
+}
+
+#[test]
+fn update_adds_new_methods_and_reports_changed_ones() {
+    let point = resolved("Point", 2, &[]);
+    let f64_ty = json!({ "primitive": "f64" });
+    let json = rustdoc(
+        vec![
+            module(0, "fixture", &[2, 10]),
+            item(
+                2,
+                Some("Point"),
+                "public",
+                json!({ "struct": {
+                    "kind": { "plain": { "fields": [], "has_stripped_fields": true } },
+                    "generics": { "params": [] },
+                    "impls": [3],
+                }}),
+            ),
+            inherent_impl(3, &[], point, &[4, 5, 6]),
+            function(
+                4,
+                "new",
+                "public",
+                json!([["x", f64_ty], ["y", f64_ty]]),
+                json!({ "generic": "Self" }),
+            ),
+            function(
+                5,
+                "area",
+                "public",
+                json!([self_ref()]),
+                json!({ "primitive": "f32" }),
+            ),
+            function(6, "norm", "public", json!([self_ref()]), f64_ty.clone()),
+            item(
+                10,
+                Some("Wrapper"),
+                "public",
+                json!({ "struct": {
+                    "kind": { "plain": { "fields": [], "has_stripped_fields": true } },
+                    "generics": { "params": [{ "name": "T", "kind": { "type": {} } }] },
+                    "impls": [11],
+                }}),
+            ),
+            inherent_impl(
+                11,
+                &["T"],
+                resolved("Wrapper", 10, &[json!({ "generic": "T" })]),
+                &[12],
+            ),
+            function(
+                12,
+                "get",
+                "public",
+                json!([self_ref()]),
+                json!({ "generic": "T" }),
+            ),
+        ],
+        json!({}),
+    );
+    let zng = ParsedZngFile::parse_str(
+        r#"
+type crate::Point {
+    #layout(size = 16, align = 8);
+    wellknown_traits(Copy);
+
+    #cpp_name "make"
+    fn new(f64, f64) -> crate::Point;
+    fn area(&self) -> f64;
+    fn gone(&self);
+}
+
+type<T> crate::Wrapper<T> for T in [i32] {
+    #heap_allocated;
+}
+"#,
+        NullCfg,
+    );
+    let update = AutoZng::new().update(&json, &zng).unwrap();
+    let mut text = String::new();
+    for (_, file) in &update.files {
+        text += file;
+    }
+    for added in &update.added {
+        text += &format!("// added {}::{}\n", added.ty, added.name);
+    }
+    text += &update.diagnostics.to_string();
+    expect![[r#"
+
+        type crate::Point {
+            #layout(size = 16, align = 8);
+            wellknown_traits(Copy);
+
+            #cpp_name "make"
+            fn new(f64, f64) -> crate::Point;
+            fn area(&self) -> f64;
+            fn gone(&self);
+            fn norm(&self) -> f64;
+        }
+
+        type<T> crate::Wrapper<T> for T in [i32] {
+            #heap_allocated;
+        }
+        // added crate::Point::norm
+        Error: The signature of `crate::Point::area` is changed
+           ╭─[test.zng:8:5]
+           │
+         8 │     fn area(&self) -> f64;
+           │     ───────────┬──────────  
+           │                ╰──────────── It is now `fn area(&self) -> f32;`
+        ───╯
+        Error: `crate::Point::gone` is not a public method of the crate
+           ╭─[test.zng:9:5]
+           │
+         9 │     fn gone(&self);
+           │     ───────┬───────  
+           │            ╰───────── Remove this method
+        ───╯
+        Warning: The new methods of `crate::Wrapper::<i32>` are not added to the type template
+            ╭─[test.zng:12:37]
+            │
+         12 │ type<T> crate::Wrapper<T> for T in [i32] {
+            │                                     ─┬─  
+            │                                      ╰─── Add `fn get(&self) -> i32;`
+        ────╯
+    "#]]
+    .assert_eq(&text);
+}
+
+#[test]
+fn verify_reports_mismatches_with_suggestions() {
+    let f64_ty = json!({ "primitive": "f64" });
+    let json = rustdoc(
+        vec![
+            module(0, "fixture", &[1]),
+            module(1, "shapes", &[2, 20, 30]),
+            item(
+                2,
+                Some("Point"),
+                "public",
+                json!({ "struct": {
+                    "kind": { "plain": { "fields": [3, 4], "has_stripped_fields": false } },
+                    "generics": { "params": [] },
+                    "impls": [5],
+                }}),
+            ),
+            item(3, Some("x"), "public", json!({ "struct_field": f64_ty })),
+            item(4, Some("y"), "public", json!({ "struct_field": f64_ty })),
+            inherent_impl(5, &[], resolved("Point", 2, &[]), &[6]),
+            function(
+                6,
+                "area",
+                "public",
+                json!([self_ref()]),
+                json!({ "primitive": "f32" }),
+            ),
+            function(20, "wrap", "public", json!([["x", f64_ty]]), Value::Null),
+            item(
+                30,
+                Some("Shape"),
+                "public",
+                json!({ "enum": {
+                    "generics": { "params": [] },
+                    "has_stripped_variants": false,
+                    "variants": [31],
+                    "impls": [],
+                }}),
+            ),
+            item(
+                31,
+                Some("Circle"),
+                "default",
+                json!({ "variant": { "kind": { "tuple": [32] } } }),
+            ),
+            item(32, Some("0"), "default", json!({ "struct_field": f64_ty })),
+        ],
+        json!({}),
+    );
+    let zng = ParsedZngFile::parse_str(
+        r#"
+type crate::shapes::Pointt {
+    #heap_allocated;
+}
+
+type crate::shapes::Point {
+    #heap_allocated;
+    constructor { x: f64, y: f32 };
+    fn area(&self) -> f64;
+    fn are(&self) -> f32;
+}
+
+enum crate::shapes::Shape {
+    #heap_allocated;
+    Circel(f64);
+}
+
+mod crate {
+    fn wrap(f64);
+}
+"#,
+        NullCfg,
+    );
+    let diagnostics = verify(&json, &zng).unwrap();
+    expect![[r#"
+        Error: `crate::shapes::Pointt` is not a public type of the crate
+           ╭─[test.zng:2:6]
+           │
+         2 │ type crate::shapes::Pointt {
+           │      ──────────┬──────────  
+           │                ╰──────────── Did you mean `crate::shapes::Point`?
+        ───╯
+        Error: Mismatched signature of `crate::shapes::Point::area`
+           ╭─[test.zng:9:5]
+           │
+         9 │     fn area(&self) -> f64;
+           │     ───────────┬──────────  
+           │                ╰──────────── The crate returns `f32`, not `f64`
+           │                │            
+           │                ╰──────────── Use `fn area(&self) -> f32;`
+        ───╯
+        Error: `crate::shapes::Point` has no public method `are`
+            ╭─[test.zng:10:5]
+            │
+         10 │     fn are(&self) -> f32;
+            │     ──────────┬──────────  
+            │               ╰──────────── Did you mean `fn area(&self) -> f32;`?
+        ────╯
+        Error: Mismatched fields in the constructor of `crate::shapes::Point`
+           ╭─[test.zng:8:5]
+           │
+         8 │     constructor { x: f64, y: f32 };
+           │     ───────────────┬───────────────  
+           │                    ╰───────────────── The crate declares `constructor { x: f64, y: f64 }`
+        ───╯
+        Error: `crate::shapes::Shape` has no variant `Circel`
+            ╭─[test.zng:15:5]
+            │
+         15 │     Circel(f64);
+            │     ──────┬─────  
+            │           ╰─────── Did you mean `Circle`?
+        ────╯
+        Error: `crate::wrap` is not a public function of the crate
+            ╭─[test.zng:19:5]
+            │
+         19 │     fn wrap(f64);
+            │     ──────┬─────  
+            │           ╰─────── Did you mean `crate::shapes::wrap`?
+        ────╯
+    "#]].assert_eq(&diagnostics.to_string());
+}
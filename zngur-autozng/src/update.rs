@@ -0,0 +1,207 @@
+//! Updating an existing spec from the rustdoc JSON output, keeping the edits made to it.
+
+use std::{collections::HashMap, path::PathBuf};
+
+use itertools::Itertools;
+use zngur_def::{RustType, ZngurMethod};
+use zngur_parser::{
+    ParseResult, TypeDeclaration,
+    diagnostics::{Diagnostic, Diagnostics, Severity},
+};
+
+use crate::{AutoZng, Generator, Unsupported, read_crate, signature};
+
+/// A method added by [`AutoZng::update`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddedMethod {
+    pub ty: RustType,
+    pub name: String,
+    /// The file of the `type` item which the method is added to.
+    pub path: PathBuf,
+}
+
+/// The result of [`AutoZng::update`].
+pub struct AutoZngUpdate {
+    pub added: Vec<AddedMethod>,
+    /// The new text of the changed files.
+    pub files: Vec<(PathBuf, String)>,
+    /// The declared methods which are removed from the crate or whose signature is changed.
+    pub diagnostics: Diagnostics,
+    /// The items which can't be declared in the spec.
+    pub unsupported: Vec<Unsupported>,
+}
+
+impl AutoZng {
+    /// Compares a parsed spec with the rustdoc JSON output of the crate, and adds the new public
+    /// methods of its types to the files of the spec.
+    ///
+    /// Only the local types which are included by the filters are compared. The new methods are
+    /// added after the last item of the `type` items, and the rest of the files is kept as it is,
+    /// so the layouts, `#cpp_name`s and left out methods of the spec stay as they are. The
+    /// declared methods which are not in the crate anymore, or whose signature is changed, are
+    /// reported as errors.
+    ///
+    /// Only the active `#if` and `#match` branches of the spec are compared, so a method which is
+    /// declared in another branch is added again.
+    pub fn update(&self, rustdoc_json: &str, zng: &ParseResult) -> Result<AutoZngUpdate, String> {
+        let krate = read_crate(rustdoc_json)?;
+        let mut generator = Generator::new(&krate, self);
+        // The declared instances of generic types are compared, even if no item uses them.
+        for ty in &zng.spec.types {
+            generator.declare(ty.ty.clone());
+        }
+        generator.run();
+        let mut declarations: HashMap<&RustType, Vec<&TypeDeclaration>> = HashMap::new();
+        for declaration in zng.type_declarations() {
+            declarations
+                .entry(&declaration.ty)
+                .or_default()
+                .push(declaration);
+        }
+        let mut diagnostics = vec![];
+        let mut added = vec![];
+        let mut edits: HashMap<&PathBuf, Vec<(usize, String)>> = HashMap::new();
+        for generated in &generator.spec.types {
+            let (Some(declarations), Some(declared)) = (
+                declarations.get(&generated.ty),
+                zng.spec.types.iter().find(|ty| ty.ty == generated.ty),
+            ) else {
+                continue;
+            };
+            if !generator.with_methods.contains(&generated.ty) {
+                continue;
+            }
+            for method in &declared.methods {
+                // Methods of the deref target are checked with the target.
+                if method.deref.is_some() {
+                    continue;
+                }
+                let Some((path, span)) = declarations.iter().find_map(|declaration| {
+                    let (_, span) = declaration
+                        .methods
+                        .iter()
+                        .find(|(name, _)| *name == method.data.name)?;
+                    Some((&declaration.path, span.clone()))
+                }) else {
+                    continue;
+                };
+                let name = format!("{}::{}", generated.ty, method.data.name);
+                match generated
+                    .methods
+                    .iter()
+                    .find(|m| m.data.name == method.data.name)
+                {
+                    Some(new) if same_signature(&new.data, &method.data) => {}
+                    Some(new) => diagnostics.push(
+                        Diagnostic::error(
+                            path,
+                            span.clone(),
+                            format!("The signature of `{name}` is changed"),
+                        )
+                        .with_label(
+                            path,
+                            span,
+                            format!("It is now `{}`", signature(new)),
+                        ),
+                    ),
+                    // Blanket impls are not in the rustdoc output of the type, so the methods of
+                    // traits can't be checked when they are missing.
+                    None if method.use_path.is_some() => {}
+                    None if generator.unsupported.iter().any(|u| u.item == name) => {}
+                    None => diagnostics.push(
+                        Diagnostic::error(
+                            path,
+                            span.clone(),
+                            format!("`{name}` is not a public method of the crate"),
+                        )
+                        .with_label(path, span, "Remove this method"),
+                    ),
+                }
+            }
+            let new_methods = generated
+                .methods
+                .iter()
+                .filter(|m| !declared.methods.iter().any(|d| d.data.name == m.data.name))
+                .collect::<Vec<_>>();
+            if new_methods.is_empty() {
+                continue;
+            }
+            let Some((declaration, body_end)) = declarations
+                .iter()
+                .find_map(|declaration| Some((declaration, declaration.body_end?)))
+            else {
+                let declaration = declarations[0];
+                diagnostics.push(
+                    Diagnostic::new(
+                        Severity::Warning,
+                        &declaration.path,
+                        declaration.span.clone(),
+                        format!(
+                            "The new methods of `{}` are not added to the type template",
+                            generated.ty
+                        ),
+                    )
+                    .with_label(
+                        &declaration.path,
+                        declaration.span.clone(),
+                        format!(
+                            "Add {}",
+                            new_methods
+                                .iter()
+                                .map(|m| format!("`{}`", signature(m)))
+                                .join(", ")
+                        ),
+                    ),
+                );
+                continue;
+            };
+            let text = zng.source(&declaration.path).unwrap_or_default();
+            let indent = indentation(text, body_end);
+            let mut insert = String::new();
+            for method in new_methods {
+                for line in method.to_zng_string().lines() {
+                    insert += &format!("\n{indent}{line}");
+                }
+                added.push(AddedMethod {
+                    ty: generated.ty.clone(),
+                    name: method.data.name.clone(),
+                    path: declaration.path.clone(),
+                });
+            }
+            edits
+                .entry(&declaration.path)
+                .or_default()
+                .push((body_end, insert));
+        }
+        let mut files = vec![];
+        for (path, edits) in edits.into_iter().sorted_by_key(|(path, _)| *path) {
+            let mut text = zng.source(path).unwrap_or_default().to_owned();
+            // Editing from the end keeps the earlier offsets valid.
+            for (offset, insert) in edits.into_iter().sorted_by_key(|(offset, _)| *offset).rev() {
+                text.insert_str(offset, &insert);
+            }
+            files.push((path.clone(), text));
+        }
+        Ok(AutoZngUpdate {
+            added,
+            files,
+            diagnostics: zng.diagnostics(diagnostics),
+            unsupported: generator.unsupported,
+        })
+    }
+}
+
+/// Whether the methods are called the same way. The docs and the C++ names are not compared.
+fn same_signature(a: &ZngurMethod, b: &ZngurMethod) -> bool {
+    a.generics == b.generics
+        && a.receiver == b.receiver
+        && a.inputs == b.inputs
+        && a.output == b.output
+}
+
+/// The indentation of the line containing `offset`.
+fn indentation(text: &str, offset: usize) -> &str {
+    let start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line = &text[start..offset];
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
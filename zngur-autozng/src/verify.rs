@@ -0,0 +1,394 @@
+//! Checking the signatures of a spec against the rustdoc JSON output of the crate.
+
+use std::ops::Range;
+
+use itertools::Itertools;
+use zngur_def::{
+    Mutability, RustType, ZngurMethod, ZngurMethodDetails, ZngurMethodReceiver, ZngurType,
+};
+use zngur_parser::{
+    ParseResult, TypeDeclaration,
+    diagnostics::{Diagnostic, Diagnostics},
+};
+
+use crate::{AutoZng, Generator, read_crate, rustdoc::ItemEnum, signature};
+
+/// Checks the local types, methods, constructors and free functions of a spec against the
+/// rustdoc JSON output of the crate, and reports the mismatches at their declarations.
+///
+/// A typo in a signature otherwise only shows up as a type error in the generated Rust code.
+/// The items which are not in the crate get a suggestion for a similar path or name, and the
+/// mismatched signatures get the signature of the crate. Only the items under `crate` are
+/// checked, and the items whose signature autozng can't convert are skipped.
+pub fn verify(rustdoc_json: &str, zng: &ParseResult) -> Result<Diagnostics, String> {
+    let krate = read_crate(rustdoc_json)?;
+    let options = AutoZng::new();
+    let mut generator = Generator::new(&krate, &options);
+    for ty in &zng.spec.types {
+        generator.declare(ty.ty.clone());
+    }
+    generator.run();
+    let mut verifier = Verifier {
+        generator: &generator,
+        diagnostics: vec![],
+    };
+    for declaration in zng.type_declarations() {
+        if let Some(declared) = zng.spec.types.iter().find(|ty| ty.ty == declaration.ty) {
+            verifier.type_declaration(declaration, declared);
+        }
+    }
+    for declaration in zng.fn_declarations() {
+        if declaration.rust_path.first().map(String::as_str) != Some("crate") {
+            continue;
+        }
+        let Some(declared) = zng
+            .spec
+            .funcs
+            .iter()
+            .find(|f| f.path.path == declaration.rust_path)
+        else {
+            continue;
+        };
+        let name = declaration.rust_path.join("::");
+        let as_method = |inputs: &[RustType], output: &RustType| ZngurMethod {
+            name: declaration.rust_path.last().cloned().unwrap_or_default(),
+            generics: vec![],
+            receiver: ZngurMethodReceiver::Static,
+            inputs: inputs.to_vec(),
+            output: output.clone(),
+            is_safe: true,
+            doc: None,
+            cpp_name: None,
+            result_mode: None,
+        };
+        let span = (&declaration.path, declaration.span.clone());
+        match generator
+            .spec
+            .funcs
+            .iter()
+            .find(|f| f.path.path == declaration.rust_path)
+        {
+            Some(real) => verifier.signature(
+                &name,
+                &as_method(&declared.inputs, &declared.output),
+                &as_method(&real.inputs, &real.output),
+                span,
+            ),
+            None if verifier.is_unsupported(&name) => {}
+            None => {
+                let functions = generator.spec.funcs.iter().map(|f| &f.path.path);
+                let label = match suggest_path(&declaration.rust_path, functions) {
+                    Some(path) => format!("Did you mean `{}`?", path.join("::")),
+                    None => "The crate has no public function with this path".to_owned(),
+                };
+                verifier.error(
+                    span,
+                    format!("`{name}` is not a public function of the crate"),
+                    [label],
+                );
+            }
+        }
+    }
+    Ok(zng.diagnostics(verifier.diagnostics))
+}
+
+struct Verifier<'a> {
+    generator: &'a Generator<'a>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+type FileSpan<'b> = (&'b std::path::PathBuf, Range<usize>);
+
+impl Verifier<'_> {
+    fn error(
+        &mut self,
+        (path, span): FileSpan<'_>,
+        message: String,
+        labels: impl IntoIterator<Item = String>,
+    ) {
+        let mut diagnostic = Diagnostic::error(path, span.clone(), message);
+        for label in labels {
+            diagnostic = diagnostic.with_label(path, span.clone(), label);
+        }
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn is_unsupported(&self, item: &str) -> bool {
+        self.generator.unsupported.iter().any(|u| u.item == item)
+    }
+
+    fn type_declaration(&mut self, declaration: &TypeDeclaration, declared: &ZngurType) {
+        let RustType::Adt(pg) = &declaration.ty else {
+            return;
+        };
+        if pg.path.first().map(String::as_str) != Some("crate") {
+            return;
+        }
+        let types = self
+            .generator
+            .local_items
+            .iter()
+            .filter(|(_, id)| {
+                matches!(
+                    self.generator.krate.index[id].inner,
+                    ItemEnum::Struct(_) | ItemEnum::Enum(_)
+                )
+            })
+            .map(|(path, _)| path);
+        if !self.generator.local_items.contains_key(&pg.path) {
+            let label = match suggest_path(&pg.path, types) {
+                Some(path) => format!("Did you mean `{}`?", path.join("::")),
+                None => "The crate has no public struct or enum with this path".to_owned(),
+            };
+            self.error(
+                (&declaration.path, declaration.span.clone()),
+                format!("`{}` is not a public type of the crate", pg.path.join("::")),
+                [label],
+            );
+            return;
+        }
+        let Some(real) = self
+            .generator
+            .spec
+            .types
+            .iter()
+            .find(|ty| ty.ty == declaration.ty)
+        else {
+            return;
+        };
+        for (name, span) in &declaration.methods {
+            let Some(method) = declared.methods.iter().find(|m| m.data.name == *name) else {
+                continue;
+            };
+            // Methods of the deref target are checked with the target.
+            if method.deref.is_some() {
+                continue;
+            }
+            let item = format!("{}::{name}", declaration.ty);
+            let span = (&declaration.path, span.clone());
+            match real.methods.iter().find(|m| m.data.name == *name) {
+                Some(real_method) => self.signature(&item, &method.data, &real_method.data, span),
+                // Blanket impls are not in the rustdoc output of the type, so the methods of
+                // traits can't be checked when they are missing.
+                None if method.use_path.is_some() || self.is_unsupported(&item) => {}
+                None => {
+                    let label =
+                        match closest(name, real.methods.iter().map(|m| m.data.name.as_str())) {
+                            Some(similar) => {
+                                let similar = real
+                                    .methods
+                                    .iter()
+                                    .find(|m| m.data.name == similar)
+                                    .unwrap();
+                                format!("Did you mean `{}`?", signature(similar))
+                            }
+                            None => "The crate has no public method with this name".to_owned(),
+                        };
+                    self.error(
+                        span,
+                        format!("`{}` has no public method `{name}`", declaration.ty),
+                        [label],
+                    );
+                }
+            }
+        }
+        for (name, span) in &declaration.constructors {
+            let span = (&declaration.path, span.clone());
+            let ty = &declaration.ty;
+            let declared_fields = declared
+                .constructors
+                .iter()
+                .find(|c| c.name == *name)
+                .map(|c| (&c.inputs, "constructor "))
+                .or_else(|| {
+                    let name = name.as_ref()?;
+                    let variant = declared.variants.iter().find(|v| v.name == *name)?;
+                    Some((&variant.fields, ""))
+                });
+            let Some((declared_fields, keyword)) = declared_fields else {
+                continue;
+            };
+            let real_fields = match name {
+                None => real
+                    .constructors
+                    .iter()
+                    .find(|c| c.name.is_none())
+                    .map(|c| &c.inputs),
+                Some(name) => real
+                    .variants
+                    .iter()
+                    .find(|v| v.name == *name)
+                    .map(|v| &v.fields),
+            };
+            match real_fields {
+                Some(real_fields) if real_fields == declared_fields => {}
+                Some(real_fields) => self.error(
+                    span,
+                    match name {
+                        None => format!("Mismatched fields in the constructor of `{ty}`"),
+                        Some(name) => format!("Mismatched fields of `{ty}::{name}`"),
+                    },
+                    [format!(
+                        "The crate declares `{}{}`",
+                        match name {
+                            None => "constructor".to_owned(),
+                            Some(name) => format!("{keyword}{name}"),
+                        },
+                        fields(real_fields)
+                    )],
+                ),
+                // The fields which autozng can't convert are already reported.
+                None if self.is_unsupported(&ty.to_string()) => {}
+                None => {
+                    let label = match name {
+                        None => "Only the structs whose fields are all public can be constructed"
+                            .to_owned(),
+                        Some(name) => {
+                            match closest(name, real.variants.iter().map(|v| v.name.as_str())) {
+                                Some(similar) => format!("Did you mean `{similar}`?"),
+                                None => "The crate has no variant with this name".to_owned(),
+                            }
+                        }
+                    };
+                    self.error(
+                        span,
+                        match name {
+                            None => format!("`{ty}` has no public constructor"),
+                            Some(name) => format!("`{ty}` has no variant `{name}`"),
+                        },
+                        [label],
+                    );
+                }
+            }
+        }
+    }
+
+    /// Reports the differences of a declared signature and the signature in the crate.
+    fn signature(
+        &mut self,
+        item: &str,
+        declared: &ZngurMethod,
+        real: &ZngurMethod,
+        span: FileSpan<'_>,
+    ) {
+        let mut labels = vec![];
+        if declared.receiver != real.receiver {
+            labels.push(format!(
+                "The crate has {}, not {}",
+                receiver(real.receiver),
+                receiver(declared.receiver)
+            ));
+        }
+        if declared.inputs.len() != real.inputs.len() {
+            labels.push(format!(
+                "The crate takes {} arguments, not {}",
+                real.inputs.len(),
+                declared.inputs.len()
+            ));
+        } else {
+            for (i, (declared, real)) in declared.inputs.iter().zip(&real.inputs).enumerate() {
+                if declared != real {
+                    labels.push(format!(
+                        "Argument {} is `{real}` in the crate, not `{declared}`",
+                        i + 1
+                    ));
+                }
+            }
+        }
+        if declared.output != real.output {
+            labels.push(format!(
+                "The crate returns `{}`, not `{}`",
+                real.output, declared.output
+            ));
+        }
+        if labels.is_empty() {
+            return;
+        }
+        let details = ZngurMethodDetails {
+            data: real.clone(),
+            use_path: None,
+            deref: None,
+        };
+        labels.push(format!("Use `{}`", signature(&details)));
+        self.error(span, format!("Mismatched signature of `{item}`"), labels);
+    }
+}
+
+fn receiver(receiver: ZngurMethodReceiver) -> &'static str {
+    match receiver {
+        ZngurMethodReceiver::Static => "no receiver",
+        ZngurMethodReceiver::Ref(Mutability::Not) => "a `&self` receiver",
+        ZngurMethodReceiver::Ref(Mutability::Mut) => "a `&mut self` receiver",
+        ZngurMethodReceiver::Move => "a `self` receiver",
+    }
+}
+
+/// The fields of a constructor or a variant, as they are written after its name.
+fn fields(fields: &[(String, RustType)]) -> String {
+    if fields.is_empty() {
+        String::new()
+    } else if fields
+        .iter()
+        .enumerate()
+        .all(|(i, (name, _))| *name == i.to_string())
+    {
+        format!("({})", fields.iter().map(|(_, ty)| ty).join(", "))
+    } else {
+        format!(
+            " {{ {} }}",
+            fields
+                .iter()
+                .map(|(name, ty)| format!("{name}: {ty}"))
+                .join(", ")
+        )
+    }
+}
+
+/// A path ending in the same name, like the path of an item which is moved to another module,
+/// or else a path whose name is similar.
+fn suggest_path<'b>(
+    path: &[String],
+    candidates: impl Iterator<Item = &'b Vec<String>>,
+) -> Option<&'b Vec<String>> {
+    let candidates = candidates.sorted().collect::<Vec<_>>();
+    let name = path.last()?;
+    if let Some(same_name) = candidates.iter().find(|c| c.last() == Some(name)) {
+        return Some(same_name);
+    }
+    let similar = closest(
+        name,
+        candidates
+            .iter()
+            .filter_map(|c| c.last())
+            .map(String::as_str),
+    )?;
+    candidates
+        .into_iter()
+        .find(|c| c.last().map(String::as_str) == Some(similar))
+}
+
+/// The candidate with the smallest edit distance to `name`, if it is close enough to be a typo.
+fn closest<'b>(name: &str, candidates: impl Iterator<Item = &'b str>) -> Option<&'b str> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    candidates
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// The Levenshtein distance of two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous + usize::from(ca != *cb);
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(previous + 1);
+        }
+    }
+    row[b.len()]
+}
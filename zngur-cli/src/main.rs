@@ -1,7 +1,10 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{collections::HashMap, path::PathBuf, process::exit};
 
-use clap::Parser;
-use zngur::{Zngur, ZngurHdr};
+use clap::{Args, Parser, ValueEnum};
+use zngur::{LayoutProbe, Zngur, ZngurHdr};
+use zngur_autozng::AutoZng;
+use zngur_def::LayoutPolicy;
+use zngur_parser::formatter::format_zng;
 
 use crate::cfg_extractor::{CfgFromRustc, cfg_from_rustc};
 
@@ -40,10 +43,163 @@ impl<'a> From<&'a str> for CfgKey {
     }
 }
 
+/// A path for a crate or package, of the form name=path.
+#[derive(Clone)]
+struct NamedPath {
+    name: String,
+    path: PathBuf,
+}
+
+impl std::str::FromStr for NamedPath {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, path) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected name=path, found `{s}`"))?;
+        Ok(NamedPath {
+            name: name.to_owned(),
+            path: path.into(),
+        })
+    }
+}
+
+/// The options which affect how the zng file is parsed into a spec.
+#[derive(Args)]
+struct SpecOptions {
+    /// A rust config value of the form key(=value1(,value2 ...)) to use when
+    /// generating the zngur spec.
+    /// i.e.  -C target_os=linux -C target_feature=sse,sse2 -C debug_assertions
+    ///
+    /// see https://doc.rust-lang.org/reference/conditional-compilation.html
+    /// for possible values
+    ///
+    /// combined with any values loaded from rustc (if enabled)
+    ///
+    /// Default is an empty configuration
+    #[arg(long = "cfg", short = 'C')]
+    rust_cfg: Vec<CfgKey>,
+
+    /// A feature name to enable when generating the zngur spec
+    ///
+    /// combined with any values loaded from rustc (if enabled)
+    ///
+    /// Default is no features
+    #[arg(long = "feature", short = 'F')]
+    rust_features: Vec<String>,
+
+    #[command(flatten)]
+    load_rustc_cfg: CfgFromRustc,
+
+    /// A directory to search for merged files whose path doesn't start with `.` or `..`,
+    /// like `merge "std_types.zng";`
+    ///
+    /// May be repeated, the directories are searched in order.
+    #[arg(long = "include-dir", short = 'I')]
+    include_dirs: Vec<PathBuf>,
+
+    /// The directory of a package, of the form name=path, for merged files like
+    /// `merge "@mycrate/foo.zng";`
+    ///
+    /// Packages without a directory are looked up with `cargo metadata`.
+    #[arg(long = "package-dir")]
+    package_dirs: Vec<NamedPath>,
+
+    /// The root file of a crate, like `src/lib.rs`, whose items marked with `#[zngur::export]`
+    /// are merged into the spec
+    #[arg(long = "exports")]
+    exports: Option<PathBuf>,
+}
+
+impl SpecOptions {
+    fn apply(self, mut zng: Zngur) -> Zngur {
+        for dir in self.include_dirs {
+            zng = zng.with_include_dir(dir);
+        }
+        for NamedPath { name, path } in self.package_dirs {
+            zng = zng.with_package_dir(&name, path);
+        }
+        if let Some(crate_root) = self.exports {
+            zng = zng.with_exports_from(crate_root);
+        }
+
+        let mut cfg: HashMap<String, Vec<String>> = HashMap::new();
+        if self.load_rustc_cfg.load_cfg_from_rustc {
+            cfg.extend(cfg_from_rustc(self.load_rustc_cfg, &self.rust_features));
+        }
+        if !self.rust_cfg.is_empty() {
+            cfg.extend(self.rust_cfg.into_iter().map(CfgKey::into_tuple));
+        }
+        if !self.rust_features.is_empty() {
+            cfg.insert("feature".to_owned(), self.rust_features);
+        }
+        if !cfg.is_empty() {
+            zng = zng.with_rust_in_memory_cfg(cfg);
+        }
+        zng
+    }
+}
+
+// The options of the probe build, which computes the layout of types. This is not a doc
+// comment, since clap would use it as the help of the commands which flatten these options.
+#[derive(Args)]
+struct ProbeOptions {
+    /// The crate which the `crate::` paths refer to, of the form name=path/to/libname.rlib
+    #[arg(long = "probe-crate")]
+    krate: Option<NamedPath>,
+
+    /// Another crate whose types are used in the zng file, of the form
+    /// name=path/to/libname.rlib
+    #[arg(long = "probe-extern")]
+    externs: Vec<NamedPath>,
+
+    /// A directory to search for the dependencies of the crates, like target/debug/deps
+    #[arg(long = "probe-library-dir")]
+    library_dirs: Vec<PathBuf>,
+
+    /// The target to compile the probe for. It must be runnable on this machine.
+    #[arg(long = "probe-target")]
+    target: Option<String>,
+
+    /// The rustc which compiles the probe
+    ///
+    /// Default is the rustc in PATH
+    #[arg(long = "probe-rustc")]
+    probe_rustc: Option<PathBuf>,
+}
+
+impl ProbeOptions {
+    fn into_probe(self) -> LayoutProbe {
+        let mut probe = LayoutProbe::new();
+        if let Some(NamedPath { name, path }) = self.krate {
+            probe = probe.with_crate(&name, path);
+        }
+        for NamedPath { name, path } in self.externs {
+            probe = probe.with_extern(&name, path);
+        }
+        for dir in self.library_dirs {
+            probe = probe.with_library_dir(dir);
+        }
+        if let Some(target) = self.target {
+            probe = probe.with_target(&target);
+        }
+        if let Some(rustc) = self.probe_rustc {
+            probe = probe.with_rustc(rustc);
+        }
+        probe
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DumpFormat {
+    Json,
+}
+
 #[derive(Parser)]
 #[command(version)]
 enum Command {
     #[command(alias = "g")]
+    /// Generates the Rust and C++ glue code of a zng file.
     Generate {
         /// Path to the zng file
         path: PathBuf,
@@ -80,29 +236,8 @@ enum Command {
         #[arg(long)]
         mangling_base: Option<String>,
 
-        /// A rust config value of the form key(=value1(,value2 ...)) to use when
-        /// generating the zngur spec.
-        /// i.e.  -C target_os=linux -C target_feature=sse,sse2 -C debug_assertions
-        ///
-        /// see https://doc.rust-lang.org/reference/conditional-compilation.html
-        /// for possible values
-        ///
-        /// combined with any values loaded from rustc (if enabled)
-        ///
-        /// Default is an empty configuration
-        #[arg(long = "cfg", short = 'C')]
-        rust_cfg: Vec<CfgKey>,
-
-        /// A feature name to enable when generating the zngur spec
-        ///
-        /// combined with any values loaded from rustc (if enabled)
-        ///
-        /// Default is no features
-        #[arg(long = "feature", short = 'F')]
-        rust_features: Vec<String>,
-
         #[command(flatten)]
-        load_rustc_cfg: CfgFromRustc,
+        spec_options: SpecOptions,
 
         /// When set, the generator will embed the common Zngur types into the generated header.
         ///
@@ -124,6 +259,10 @@ enum Command {
         /// If not provided, it tries to read `CARGO_PKG_NAME` and defaults to `crate` if unset.
         #[arg(long)]
         crate_name: Option<String>,
+
+        // The probe is only built if there are `#layout(auto)` types.
+        #[command(flatten)]
+        probe_options: ProbeOptions,
     },
     #[command(alias = "h")]
     /// Generates the zngur.h file that contains shared interop definitions used by all generated zngur bridges.
@@ -145,6 +284,316 @@ enum Command {
         #[arg(long)]
         cpp_namespace: Option<String>,
     },
+    /// Prints the spec of a zng file, after processing the merges, aliases and cfg conditions.
+    ///
+    /// This is useful for tools which need the fully resolved spec.
+    Dump {
+        /// Path to the zng file
+        path: PathBuf,
+
+        /// The output format
+        #[arg(long, value_enum, default_value = "json")]
+        format: DumpFormat,
+
+        #[command(flatten)]
+        spec_options: SpecOptions,
+    },
+    /// Computes the layout of the types of a zng file with a probe build, and prints them.
+    ///
+    /// Types which are only used by reference or are heap allocated are skipped.
+    LayoutProbe {
+        /// Path to the zng file
+        path: PathBuf,
+
+        #[command(flatten)]
+        spec_options: SpecOptions,
+
+        #[command(flatten)]
+        probe_options: ProbeOptions,
+    },
+    /// Probes the layout of the types of a zng file, and rewrites the `#layout(...)` and
+    /// `#layout_conservative(...)` directives which are out of date.
+    ///
+    /// Only the directives in the active `#if` and `#match` branches are checked, so run this
+    /// once with the cfg of each target, like `--load-cfg-from-rustc --target ...`.
+    FixLayouts {
+        /// Path to the zng file
+        path: PathBuf,
+
+        #[command(flatten)]
+        spec_options: SpecOptions,
+
+        #[command(flatten)]
+        probe_options: ProbeOptions,
+    },
+    /// Generates a zng file for a crate from its rustdoc JSON output, and prints it.
+    ///
+    /// The JSON output is generated with
+    /// `cargo +nightly rustdoc -- -Z unstable-options --output-format json`. The items which
+    /// can't be declared are reported as warnings.
+    Autozng {
+        /// Path to the rustdoc JSON output, like target/doc/my_crate.json
+        path: PathBuf,
+
+        /// Only declare the items under this path, like `crate::shapes`
+        ///
+        /// May be repeated. Default is every public item of the crate.
+        #[arg(long = "filter", short = 'f')]
+        filters: Vec<String>,
+
+        /// Declare the sized types with `#heap_allocated` instead of `#layout(auto)`
+        #[arg(long)]
+        heap_allocated: bool,
+
+        /// Path of the generated zng file
+        ///
+        /// Default is printing to the standard output.
+        #[arg(long, short = 'o')]
+        output: Option<PathBuf>,
+
+        /// Path of an existing zng file to update, instead of generating a new one
+        ///
+        /// The new public methods of its types are added to it, and the declared methods which
+        /// are removed from the crate or have a different signature are reported as errors. The
+        /// rest of the file, like the layouts and `#cpp_name`s, is kept as it is.
+        #[arg(long, conflicts_with = "output")]
+        update: Option<PathBuf>,
+
+        #[command(flatten)]
+        spec_options: SpecOptions,
+    },
+    /// Checks the signatures of a zng file against the rustdoc JSON output of the crate.
+    ///
+    /// The local types, methods, constructors and free functions which are not in the crate, or
+    /// whose signature is different, are reported at their declaration, with a suggestion for the
+    /// correct path or signature.
+    Verify {
+        /// Path to the zng file
+        path: PathBuf,
+
+        /// Path to the rustdoc JSON output, like target/doc/my_crate.json
+        #[arg(long)]
+        rustdoc: PathBuf,
+
+        #[command(flatten)]
+        spec_options: SpecOptions,
+    },
+    /// Formats zng files in the canonical style.
+    Fmt {
+        /// Paths to the zng files
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
+        /// Don't write the files, but exit with an error if any of them is not formatted.
+        ///
+        /// This is useful for checking the formatting in CI.
+        #[arg(long)]
+        check: bool,
+    },
+}
+
+fn dump(path: PathBuf, format: DumpFormat, spec_options: SpecOptions) {
+    let spec = spec_options
+        .apply(Zngur::from_zng_file(path))
+        .try_parse_spec()
+        .unwrap_or_else(|diagnostics| {
+            diagnostics.eprint();
+            exit(101);
+        });
+    match format {
+        DumpFormat::Json => println!("{}", serde_json::to_string_pretty(&spec).unwrap()),
+    }
+}
+
+fn layout_probe(path: PathBuf, spec_options: SpecOptions, probe_options: ProbeOptions) {
+    let spec = spec_options
+        .apply(Zngur::from_zng_file(path))
+        .try_parse_spec()
+        .unwrap_or_else(|diagnostics| {
+            diagnostics.eprint();
+            exit(101);
+        });
+    let types = spec
+        .types
+        .into_iter()
+        .filter(|ty| {
+            !matches!(
+                ty.layout,
+                LayoutPolicy::HeapAllocated | LayoutPolicy::OnlyByRef
+            )
+        })
+        .map(|ty| ty.ty)
+        .collect::<Vec<_>>();
+    let layouts = probe_options
+        .into_probe()
+        .probe(&types)
+        .unwrap_or_else(|e| {
+            eprintln!("{e}");
+            exit(101);
+        });
+    for (ty, (size, align)) in types.iter().zip(layouts) {
+        println!("{ty}: #layout(size = {size}, align = {align})");
+    }
+}
+
+fn fix_layouts(path: PathBuf, spec_options: SpecOptions, probe_options: ProbeOptions) {
+    let fixes = spec_options
+        .apply(Zngur::from_zng_file(path))
+        .with_layout_probe(probe_options.into_probe())
+        .try_fix_layouts()
+        .unwrap_or_else(|diagnostics| {
+            diagnostics.eprint();
+            exit(101);
+        });
+    for fix in fixes {
+        println!(
+            "{}: {}: size = {}, align = {} -> size = {}, align = {}",
+            fix.path.display(),
+            fix.ty,
+            fix.old.0,
+            fix.old.1,
+            fix.new.0,
+            fix.new.1
+        );
+    }
+}
+
+fn autozng(
+    path: PathBuf,
+    filters: Vec<String>,
+    heap_allocated: bool,
+    output: Option<PathBuf>,
+    update: Option<PathBuf>,
+    spec_options: SpecOptions,
+) {
+    let mut autozng = AutoZng::new();
+    for filter in filters {
+        autozng = autozng.with_filter(&filter);
+    }
+    if heap_allocated {
+        autozng = autozng.with_layout_policy(LayoutPolicy::HeapAllocated);
+    }
+    let json = std::fs::read_to_string(&path).unwrap_or_else(|e| {
+        eprintln!("Can't read `{}`: {e}", path.display());
+        exit(101);
+    });
+    if let Some(zng_path) = update {
+        let parse_result = spec_options
+            .apply(Zngur::from_zng_file(zng_path))
+            .try_parse()
+            .unwrap_or_else(|diagnostics| {
+                diagnostics.eprint();
+                exit(101);
+            });
+        let result = autozng.update(&json, &parse_result).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            exit(101);
+        });
+        for unsupported in &result.unsupported {
+            eprintln!("warning: skipped {unsupported}");
+        }
+        // A file which can't be written doesn't stop the others, which are independent.
+        let mut failed = false;
+        for (path, text) in &result.files {
+            if let Err(e) = std::fs::write(path, text) {
+                eprintln!("Can't write `{}`: {e}", path.display());
+                failed = true;
+            }
+        }
+        if failed {
+            exit(101);
+        }
+        for added in &result.added {
+            println!(
+                "{}: {}: added fn {}",
+                added.path.display(),
+                added.ty,
+                added.name
+            );
+        }
+        result.diagnostics.eprint();
+        if result.diagnostics.has_errors() {
+            exit(101);
+        }
+        return;
+    }
+    let result = autozng.generate(&json).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        exit(101);
+    });
+    for unsupported in &result.unsupported {
+        eprintln!("warning: skipped {unsupported}");
+    }
+    match output {
+        Some(output) => {
+            if let Err(e) = std::fs::write(&output, result.spec.to_zng_string()) {
+                eprintln!("Can't write `{}`: {e}", output.display());
+                exit(101);
+            }
+        }
+        None => print!("{}", result.spec),
+    }
+}
+
+fn verify(path: PathBuf, rustdoc: PathBuf, spec_options: SpecOptions) {
+    let json = std::fs::read_to_string(&rustdoc).unwrap_or_else(|e| {
+        eprintln!("Can't read `{}`: {e}", rustdoc.display());
+        exit(101);
+    });
+    let parse_result = spec_options
+        .apply(Zngur::from_zng_file(path))
+        .try_parse()
+        .unwrap_or_else(|diagnostics| {
+            diagnostics.eprint();
+            exit(101);
+        });
+    let diagnostics = zngur_autozng::verify(&json, &parse_result).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        exit(101);
+    });
+    diagnostics.eprint();
+    if diagnostics.has_errors() {
+        exit(101);
+    }
+}
+
+fn fmt(paths: Vec<PathBuf>, check: bool) {
+    let mut failed = false;
+    for path in paths {
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) => {
+                eprintln!("Can't read `{}`: {e}", path.display());
+                failed = true;
+                continue;
+            }
+        };
+        match format_zng(&text) {
+            Ok(formatted) if formatted == text => {}
+            Ok(_) if check => {
+                eprintln!("{} is not formatted", path.display());
+                failed = true;
+            }
+            Ok(formatted) => {
+                if let Err(e) = std::fs::write(&path, formatted) {
+                    eprintln!("Can't write `{}`: {e}", path.display());
+                    failed = true;
+                }
+            }
+            Err(errors) => {
+                for error in errors {
+                    let before = &text[..error.span.start];
+                    let line = before.matches('\n').count() + 1;
+                    let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+                    eprintln!("{}:{line}:{column}: {}", path.display(), error.message);
+                }
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        exit(1);
+    }
 }
 
 fn main() {
@@ -157,12 +606,11 @@ fn main() {
             rs_file,
             depfile,
             mangling_base,
-            rust_cfg,
-            rust_features,
-            load_rustc_cfg,
+            spec_options,
             zng_header_in_place,
             cpp_namespace,
             crate_name,
+            probe_options,
         } => {
             let pp = path.parent().unwrap();
             let cpp_file = cpp_file.unwrap_or_else(|| pp.join("generated.cpp"));
@@ -179,26 +627,14 @@ fn main() {
             if let Some(crate_name) = crate_name {
                 zng = zng.with_crate_name(&crate_name);
             }
-
-            let mut cfg: HashMap<String, Vec<String>> = HashMap::new();
-            if load_rustc_cfg.load_cfg_from_rustc {
-                cfg.extend(cfg_from_rustc(load_rustc_cfg, &rust_features));
-            }
-            if !rust_cfg.is_empty() {
-                cfg.extend(rust_cfg.into_iter().map(CfgKey::into_tuple));
-            }
-            if !rust_features.is_empty() {
-                cfg.insert("feature".to_owned(), rust_features);
-            }
-            if !cfg.is_empty() {
-                zng = zng.with_rust_in_memory_cfg(cfg);
-            }
+            zng = spec_options.apply(zng);
             if let Some(depfile) = depfile {
                 zng = zng.with_depfile(depfile);
             }
             if let Some(mangling_base) = mangling_base {
                 zng = zng.with_mangling_base(&mangling_base);
             }
+            zng = zng.with_layout_probe(probe_options.into_probe());
             zng.generate();
         }
         Command::MakeZngHeader {
@@ -214,5 +650,34 @@ fn main() {
             }
             hdr.generate();
         }
+        Command::Dump {
+            path,
+            format,
+            spec_options,
+        } => dump(path, format, spec_options),
+        Command::LayoutProbe {
+            path,
+            spec_options,
+            probe_options,
+        } => layout_probe(path, spec_options, probe_options),
+        Command::FixLayouts {
+            path,
+            spec_options,
+            probe_options,
+        } => fix_layouts(path, spec_options, probe_options),
+        Command::Autozng {
+            path,
+            filters,
+            heap_allocated,
+            output,
+            update,
+            spec_options,
+        } => autozng(path, filters, heap_allocated, output, update, spec_options),
+        Command::Verify {
+            path,
+            rustdoc,
+            spec_options,
+        } => verify(path, rustdoc, spec_options),
+        Command::Fmt { paths, check } => fmt(paths, check),
     }
 }
@@ -4,15 +4,18 @@ use indexmap::IndexMap;
 use itertools::Itertools;
 
 mod merge;
+mod printer;
 pub use merge::{Merge, MergeFailure, MergeResult};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Mutability {
     Mut,
     Not,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ZngurMethodReceiver {
     Static,
     Ref(Mutability),
@@ -20,6 +23,7 @@ pub enum ZngurMethodReceiver {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ZngurMethod {
     pub name: String,
     pub generics: Vec<RustType>,
@@ -27,16 +31,40 @@ pub struct ZngurMethod {
     pub inputs: Vec<RustType>,
     pub output: RustType,
     pub is_safe: bool,
+    /// The text of the `///` doc comment lines, joined by newlines.
+    pub doc: Option<String>,
+    /// The name of the method in C++, set with `#cpp_name`. The Rust name is used if it is None.
+    pub cpp_name: Option<String>,
+    /// How the returned `Result` is unwrapped in C++, set with `#result_as_exception` or
+    /// `#result_as_expected`.
+    pub result_mode: Option<ZngurResultMode>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ZngurFn {
     pub path: RustPathAndGenerics,
     pub inputs: Vec<RustType>,
     pub output: RustType,
+    pub doc: Option<String>,
+    pub cpp_name: Option<String>,
+    pub result_mode: Option<ZngurResultMode>,
+}
+
+/// The C++ signature of a function returning a `Result`, which is a plain `Result` type by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ZngurResultMode {
+    /// `#result_as_exception`, returning the `Ok` value and throwing a `rust::Error<E>` for the
+    /// `Err` value.
+    Exception,
+    /// `#result_as_expected`, returning a `rust::Expected<T, E>`, which is `std::expected` in
+    /// C++23 and `tl::expected` before.
+    Expected,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ZngurExternCppFn {
     pub name: String,
     pub inputs: Vec<RustType>,
@@ -45,6 +73,7 @@ pub struct ZngurExternCppFn {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ZngurExternCppImpl {
     pub tr: Option<RustTrait>,
     pub ty: RustType,
@@ -52,26 +81,46 @@ pub struct ZngurExternCppImpl {
 }
 
 #[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ZngurConstructor {
     pub name: Option<String>,
     pub inputs: Vec<(String, RustType)>,
+    pub doc: Option<String>,
+    pub cpp_name: Option<String>,
+}
+
+/// A variant of an enum type, declared in an `enum` item.
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ZngurVariant {
+    pub name: String,
+    /// The payload of the variant. The fields of tuple variants are named by their index.
+    pub fields: Vec<(String, RustType)>,
+    pub doc: Option<String>,
 }
 
 #[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ZngurField {
     pub name: String,
     pub ty: RustType,
     pub offset: Option<usize>,
+    pub doc: Option<String>,
+    pub cpp_name: Option<String>,
 }
 
 #[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ZngurFieldData {
     pub name: String,
     pub ty: RustType,
     pub offset: ZngurFieldDataOffset,
+    pub doc: Option<String>,
+    pub cpp_name: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ZngurFieldDataOffset {
     Offset(usize),
     Auto(String),
@@ -98,6 +147,7 @@ impl ZngurFieldDataOffset {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ZngurWellknownTrait {
     Debug,
     Drop,
@@ -106,6 +156,7 @@ pub enum ZngurWellknownTrait {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ZngurWellknownTraitData {
     Debug {
         pretty_print: String,
@@ -119,11 +170,21 @@ pub enum ZngurWellknownTraitData {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LayoutPolicy {
-    StackAllocated { size: usize, align: usize },
-    Conservative { size: usize, align: usize },
+    StackAllocated {
+        size: usize,
+        align: usize,
+    },
+    Conservative {
+        size: usize,
+        align: usize,
+    },
     HeapAllocated,
     OnlyByRef,
+    /// `#layout(auto)`, the size and align are computed by a probe build before generating the
+    /// code, and then used like [`LayoutPolicy::StackAllocated`].
+    Auto,
 }
 
 impl LayoutPolicy {
@@ -131,6 +192,7 @@ impl LayoutPolicy {
 }
 
 #[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ZngurMethodDetails {
     pub data: ZngurMethod,
     pub use_path: Option<Vec<String>>,
@@ -138,12 +200,15 @@ pub struct ZngurMethodDetails {
 }
 
 #[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CppValue(pub String, pub String);
 
 #[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CppRef(pub String);
 
 #[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CppStackOwned {
     pub cpp_type: String,
     pub size: usize,
@@ -157,6 +222,7 @@ impl Display for CppRef {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ZngurType {
     pub ty: RustType,
     pub layout: LayoutPolicy,
@@ -164,37 +230,59 @@ pub struct ZngurType {
     pub methods: Vec<ZngurMethodDetails>,
     pub constructors: Vec<ZngurConstructor>,
     pub fields: Vec<ZngurField>,
+    /// The variants of the type, if it is declared with an `enum` item. Their order defines the
+    /// tags.
+    pub variants: Vec<ZngurVariant>,
     pub cpp_value: Option<CppValue>,
     pub cpp_ref: Option<CppRef>,
     pub cpp_stack_owned: Option<CppStackOwned>,
+    pub doc: Option<String>,
+    /// An additional name of the type in C++, set with `#cpp_name`.
+    pub cpp_name: Option<String>,
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ZngurTrait {
     pub tr: RustTrait,
     pub methods: Vec<ZngurMethod>,
 }
 
+/// An `impl Trait for Type;` item, which exposes every method of a declared trait on a type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ZngurTraitImpl {
+    pub ty: RustType,
+    pub tr: RustTrait,
+}
+
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AdditionalIncludes(pub String);
 
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConvertPanicToException(pub bool);
 
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Import(pub std::path::PathBuf);
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ModuleImport {
     pub path: std::path::PathBuf,
 }
 
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ZngurSpec {
     pub imports: Vec<Import>,
     pub imported_modules: Vec<ModuleImport>,
     pub types: Vec<ZngurType>,
+    #[cfg_attr(feature = "serde", serde(with = "indexmap::map::serde_seq"))]
     pub traits: IndexMap<RustTrait, ZngurTrait>,
+    pub trait_impls: Vec<ZngurTraitImpl>,
     pub funcs: Vec<ZngurFn>,
     pub extern_cpp_funcs: Vec<ZngurExternCppFn>,
     pub extern_cpp_impls: Vec<ZngurExternCppImpl>,
@@ -207,6 +295,7 @@ pub struct ZngurSpec {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RustTrait {
     Normal(RustPathAndGenerics),
     Fn {
@@ -227,6 +316,7 @@ impl RustTrait {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PrimitiveRustType {
     Uint(u32),
     Int(u32),
@@ -238,6 +328,7 @@ pub enum PrimitiveRustType {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RustPathAndGenerics {
     pub path: Vec<String>,
     pub generics: Vec<RustType>,
@@ -245,12 +336,21 @@ pub struct RustPathAndGenerics {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RustType {
     Primitive(PrimitiveRustType),
     Ref(Mutability, Box<RustType>),
     Raw(Mutability, Box<RustType>),
     Boxed(Box<RustType>),
     Slice(Box<RustType>),
+    Array(Box<RustType>, usize),
+    FnPtr {
+        is_unsafe: bool,
+        /// The ABI of an `extern` function pointer, like `"C"`. `None` is the Rust ABI.
+        abi: Option<String>,
+        inputs: Vec<RustType>,
+        output: Box<RustType>,
+    },
     Dyn(RustTrait, Vec<String>),
     Impl(RustTrait, Vec<String>),
     Tuple(Vec<RustType>),
@@ -259,6 +359,23 @@ pub enum RustType {
 
 impl RustType {
     pub const UNIT: Self = RustType::Tuple(Vec::new());
+
+    /// The `T` and `E` of a `::std::result::Result<T, E>` or `::core::result::Result<T, E>`.
+    pub fn as_result(&self) -> Option<(&RustType, &RustType)> {
+        let RustType::Adt(pg) = self else {
+            return None;
+        };
+        match (pg.path.as_slice(), pg.generics.as_slice()) {
+            ([krate, result, name], [ok, err])
+                if (krate == "std" || krate == "core")
+                    && result == "result"
+                    && name == "Result" =>
+            {
+                Some((ok, err))
+            }
+            _ => None,
+        }
+    }
 }
 
 impl Display for RustPathAndGenerics {
@@ -342,6 +459,25 @@ impl Display for RustType {
                 Ok(())
             }
             RustType::Slice(s) => write!(f, "[{s}]"),
+            RustType::Array(s, n) => write!(f, "[{s}; {n}]"),
+            RustType::FnPtr {
+                is_unsafe,
+                abi,
+                inputs,
+                output,
+            } => {
+                if *is_unsafe {
+                    write!(f, "unsafe ")?;
+                }
+                if let Some(abi) = abi {
+                    write!(f, "extern \"{abi}\" ")?;
+                }
+                write!(f, "fn({})", inputs.iter().join(", "))?;
+                if **output != RustType::UNIT {
+                    write!(f, " -> {output}")?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -1,7 +1,8 @@
 use crate::{
     AdditionalIncludes, ConvertPanicToException, CppRef, CppStackOwned, CppValue, LayoutPolicy,
     ZngurConstructor, ZngurExternCppFn, ZngurExternCppImpl, ZngurField, ZngurFn,
-    ZngurMethodDetails, ZngurSpec, ZngurTrait, ZngurType,
+    ZngurMethodDetails, ZngurResultMode, ZngurSpec, ZngurTrait, ZngurTraitImpl, ZngurType,
+    ZngurVariant,
 };
 
 /// Trait for types with a partial union operation.
@@ -69,6 +70,47 @@ fn merge_by_identity<T: Merge>(
     Ok(())
 }
 
+/// Merges the doc comments of two declarations of the same item, so only one of them needs
+/// to be documented. If both are documented, the doc comment of the first one is kept.
+fn merge_doc(doc: Option<String>, into: &mut Option<String>) {
+    if into.is_none() {
+        *into = doc;
+    }
+}
+
+/// Merges the `#cpp_name` of two declarations of the same item, so only one of them needs to
+/// rename it.
+fn merge_cpp_name(cpp_name: Option<String>, into: &mut Option<String>) -> MergeResult {
+    match (cpp_name, into.as_ref()) {
+        (Some(cpp_name), Some(existing)) if cpp_name != *existing => {
+            Err(MergeFailure::Conflict("C++ name mismatch".to_string()))
+        }
+        (Some(cpp_name), _) => {
+            *into = Some(cpp_name);
+            Ok(())
+        }
+        (None, _) => Ok(()),
+    }
+}
+
+/// Merges the `#result_as_exception` or `#result_as_expected` of two declarations of the same
+/// function, like [`merge_cpp_name`].
+fn merge_result_mode(
+    result_mode: Option<ZngurResultMode>,
+    into: &mut Option<ZngurResultMode>,
+) -> MergeResult {
+    match (result_mode, *into) {
+        (Some(result_mode), Some(existing)) if result_mode != existing => {
+            Err(MergeFailure::Conflict("Result mode mismatch".to_string()))
+        }
+        (Some(result_mode), _) => {
+            *into = Some(result_mode);
+            Ok(())
+        }
+        (None, _) => Ok(()),
+    }
+}
+
 impl<T: Merge> Merge for Option<T> {
     /// Writes the partial union of `self` and `into` to the latter.
     ///
@@ -144,6 +186,8 @@ impl Merge for ZngurType {
             ));
         }
 
+        merge_doc(self.doc, &mut into.doc);
+        merge_cpp_name(self.cpp_name, &mut into.cpp_name)?;
         self.cpp_value.merge(&mut into.cpp_value)?;
         self.cpp_ref.merge(&mut into.cpp_ref)?;
         self.cpp_stack_owned.merge(&mut into.cpp_stack_owned)?;
@@ -156,6 +200,7 @@ impl Merge for ZngurType {
             a.name == b.name
         })?;
         merge_by_identity(self.fields, &mut into.fields, |a, b| a.name == b.name)?;
+        merge_by_identity(self.variants, &mut into.variants, |a, b| a.name == b.name)?;
 
         Ok(())
     }
@@ -229,28 +274,69 @@ impl Merge<ZngurSpec> for ZngurType {
 }
 
 impl Merge for ZngurMethodDetails {
-    fn merge(self, into: &mut Self) -> MergeResult {
+    fn merge(mut self, into: &mut Self) -> MergeResult {
+        let doc = self.data.doc.take();
+        let mut into_doc = into.data.doc.take();
+        let cpp_name = self.data.cpp_name.take();
+        let mut into_cpp_name = into.data.cpp_name.take();
+        let result_mode = self.data.result_mode.take();
+        let mut into_result_mode = into.data.result_mode.take();
         if self != *into {
             return Err(MergeFailure::Conflict("Method mismatch".to_string()));
         }
+        merge_doc(doc, &mut into_doc);
+        merge_cpp_name(cpp_name, &mut into_cpp_name)?;
+        merge_result_mode(result_mode, &mut into_result_mode)?;
+        into.data.doc = into_doc;
+        into.data.cpp_name = into_cpp_name;
+        into.data.result_mode = into_result_mode;
         Ok(())
     }
 }
 
 impl Merge for ZngurConstructor {
-    fn merge(self, into: &mut Self) -> MergeResult {
+    fn merge(mut self, into: &mut Self) -> MergeResult {
+        let doc = self.doc.take();
+        let mut into_doc = into.doc.take();
+        let cpp_name = self.cpp_name.take();
+        let mut into_cpp_name = into.cpp_name.take();
         if self != *into {
             return Err(MergeFailure::Conflict("Constructor mismatch".to_string()));
         }
+        merge_doc(doc, &mut into_doc);
+        merge_cpp_name(cpp_name, &mut into_cpp_name)?;
+        into.doc = into_doc;
+        into.cpp_name = into_cpp_name;
+        Ok(())
+    }
+}
+
+impl Merge for ZngurVariant {
+    fn merge(mut self, into: &mut Self) -> MergeResult {
+        let doc = self.doc.take();
+        let mut into_doc = into.doc.take();
+        if self != *into {
+            return Err(MergeFailure::Conflict("Variant mismatch".to_string()));
+        }
+        merge_doc(doc, &mut into_doc);
+        into.doc = into_doc;
         Ok(())
     }
 }
 
 impl Merge for ZngurField {
-    fn merge(self, into: &mut Self) -> MergeResult {
+    fn merge(mut self, into: &mut Self) -> MergeResult {
+        let doc = self.doc.take();
+        let mut into_doc = into.doc.take();
+        let cpp_name = self.cpp_name.take();
+        let mut into_cpp_name = into.cpp_name.take();
         if self != *into {
             return Err(MergeFailure::Conflict("Field mismatch".to_string()));
         }
+        merge_doc(doc, &mut into_doc);
+        merge_cpp_name(cpp_name, &mut into_cpp_name)?;
+        into.doc = into_doc;
+        into.cpp_name = into_cpp_name;
         Ok(())
     }
 }
@@ -265,8 +351,21 @@ impl Merge<ZngurSpec> for ZngurTrait {
 impl Merge<ZngurSpec> for ZngurFn {
     /// Merges a function into a specification's function list.
     fn merge(self, into: &mut ZngurSpec) -> MergeResult {
-        push_unique(self, &mut into.funcs);
-        Ok(())
+        match into
+            .funcs
+            .iter_mut()
+            .find(|f| f.path == self.path && f.inputs == self.inputs && f.output == self.output)
+        {
+            Some(existing) => {
+                merge_doc(self.doc, &mut existing.doc);
+                merge_cpp_name(self.cpp_name, &mut existing.cpp_name)?;
+                merge_result_mode(self.result_mode, &mut existing.result_mode)
+            }
+            None => {
+                into.funcs.push(self);
+                Ok(())
+            }
+        }
     }
 }
 
@@ -286,6 +385,14 @@ impl Merge<ZngurSpec> for ZngurExternCppImpl {
     }
 }
 
+impl Merge<ZngurSpec> for ZngurTraitImpl {
+    /// Merges a trait impl into a specification's trait impl list.
+    fn merge(self, into: &mut ZngurSpec) -> MergeResult {
+        push_unique(self, &mut into.trait_impls);
+        Ok(())
+    }
+}
+
 impl Merge<ZngurSpec> for AdditionalIncludes {
     /// Merges #include directives into a specification's additional includes string.
     fn merge(self, into: &mut ZngurSpec) -> MergeResult {
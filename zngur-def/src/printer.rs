@@ -0,0 +1,419 @@
+//! Printing a [`ZngurSpec`] back to `.zng` source.
+//!
+//! The printed file parses to the same spec, except for the fields which have no `.zng` syntax,
+//! like `mangling_base`. Declarations are grouped by kind instead of keeping their original order,
+//! and `Drop` is left out of the well-known traits since the parser adds it implicitly.
+
+use std::fmt::{Display, Formatter};
+
+use itertools::Itertools;
+
+use crate::{
+    LayoutPolicy, Mutability, RustPathAndGenerics, RustType, ZngurConstructor, ZngurExternCppFn,
+    ZngurExternCppImpl, ZngurField, ZngurFn, ZngurMethod, ZngurMethodDetails, ZngurMethodReceiver,
+    ZngurResultMode, ZngurSpec, ZngurTrait, ZngurType, ZngurVariant, ZngurWellknownTrait,
+};
+
+const INDENT: &str = "    ";
+
+impl ZngurSpec {
+    /// Prints the spec as `.zng` source, which can be parsed back to the same spec.
+    pub fn to_zng_string(&self) -> String {
+        let mut printer = Printer::default();
+        printer.spec(self);
+        printer.out
+    }
+}
+
+impl ZngurMethodDetails {
+    /// Prints the method as an item of a `type` item, with its doc comment and attributes.
+    pub fn to_zng_string(&self) -> String {
+        let mut printer = Printer::default();
+        printer.method_details(self);
+        printer.out
+    }
+}
+
+impl Display for ZngurSpec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_zng_string())
+    }
+}
+
+#[derive(Default)]
+struct Printer {
+    out: String,
+    depth: usize,
+}
+
+impl Printer {
+    fn line(&mut self, line: &str) {
+        for _ in 0..self.depth {
+            self.out += INDENT;
+        }
+        self.out += line;
+        self.out += "\n";
+    }
+
+    fn open(&mut self, header: &str) {
+        self.line(&format!("{header} {{"));
+        self.depth += 1;
+    }
+
+    fn close(&mut self) {
+        self.depth -= 1;
+        self.line("}");
+    }
+
+    /// Separate top level items with a blank line.
+    fn separate(&mut self) {
+        if !self.out.is_empty() && !self.out.ends_with("{\n") {
+            self.out += "\n";
+        }
+    }
+
+    fn doc(&mut self, doc: &Option<String>) {
+        if let Some(doc) = doc {
+            for line in doc.split('\n') {
+                match line {
+                    "" => self.line("///"),
+                    line => self.line(&format!("/// {line}")),
+                }
+            }
+        }
+    }
+
+    fn cpp_name(&mut self, cpp_name: &Option<String>) {
+        if let Some(cpp_name) = cpp_name {
+            self.line(&format!("#cpp_name \"{cpp_name}\""));
+        }
+    }
+
+    fn result_mode(&mut self, result_mode: Option<ZngurResultMode>) {
+        match result_mode {
+            Some(ZngurResultMode::Exception) => self.line("#result_as_exception"),
+            Some(ZngurResultMode::Expected) => self.line("#result_as_expected"),
+            None => {}
+        }
+    }
+
+    fn spec(&mut self, spec: &ZngurSpec) {
+        for import in &spec.imports {
+            self.line(&format!("merge \"{}\";", import.0.display()));
+        }
+        for module in &spec.imported_modules {
+            self.line(&format!("import \"{}\";", module.path.display()));
+        }
+        if !spec.additional_includes.0.is_empty() {
+            self.separate();
+            self.line(&format!(
+                "#cpp_additional_includes \"{}\"",
+                spec.additional_includes.0
+            ));
+        }
+        if spec.convert_panic_to_exception.0 {
+            self.separate();
+            self.line("#convert_panic_to_exception");
+        }
+        for tr in spec.traits.values() {
+            self.separate();
+            self.trait_item(tr);
+        }
+        for ty in &spec.types {
+            self.separate();
+            self.type_item(ty);
+        }
+        if !spec.trait_impls.is_empty() {
+            self.separate();
+            for trait_impl in &spec.trait_impls {
+                self.line(&format!("impl {} for {};", trait_impl.tr, trait_impl.ty));
+            }
+        }
+        // Functions are declared by their name in a `mod` of their parent path.
+        for (module, funcs) in &spec
+            .funcs
+            .iter()
+            .group_by(|func| &func.path.path[..func.path.path.len() - 1])
+        {
+            self.separate();
+            if module.is_empty() {
+                funcs.for_each(|func| self.fn_item(func));
+                continue;
+            }
+            self.open(&format!("mod {}", path(module)));
+            funcs.for_each(|func| self.fn_item(func));
+            self.close();
+        }
+        if !spec.extern_cpp_funcs.is_empty() || !spec.extern_cpp_impls.is_empty() {
+            self.separate();
+            self.open("extern \"C++\"");
+            for func in &spec.extern_cpp_funcs {
+                self.extern_cpp_fn(func);
+            }
+            for extern_impl in &spec.extern_cpp_impls {
+                self.extern_cpp_impl(extern_impl);
+            }
+            self.close();
+        }
+    }
+
+    fn trait_item(&mut self, tr: &ZngurTrait) {
+        self.open(&format!("trait {}", tr.tr));
+        for method in &tr.methods {
+            self.method("", method, "");
+        }
+        self.close();
+    }
+
+    fn type_item(&mut self, ty: &ZngurType) {
+        self.doc(&ty.doc);
+        self.cpp_name(&ty.cpp_name);
+        let keyword = if ty.variants.is_empty() {
+            "type"
+        } else {
+            "enum"
+        };
+        self.open(&format!("{keyword} {}", ty.ty));
+
+        let traits = ty
+            .wellknown_traits
+            .iter()
+            .filter_map(|tr| match tr {
+                ZngurWellknownTrait::Debug => Some("Debug"),
+                ZngurWellknownTrait::Copy => Some("Copy"),
+                ZngurWellknownTrait::Unsized => Some("?Sized"),
+                ZngurWellknownTrait::Drop => None,
+            })
+            .collect::<Vec<_>>();
+        // Unsized types, `#cpp_ref` and `#cpp_stack_owned` imply their layout policy.
+        let implied_layout =
+            traits.contains(&"?Sized") || ty.cpp_ref.is_some() || ty.cpp_stack_owned.is_some();
+        if !implied_layout {
+            self.line(&format!("{};", Layout(&ty.layout)));
+        }
+        if !traits.is_empty() {
+            self.line(&format!("wellknown_traits({});", traits.join(", ")));
+        }
+        if let Some(cpp_value) = &ty.cpp_value {
+            self.line(&format!(
+                "#cpp_value \"{}\" \"{}\";",
+                cpp_value.0, cpp_value.1
+            ));
+        }
+        if let Some(cpp_ref) = &ty.cpp_ref {
+            self.line(&format!("#cpp_ref \"{cpp_ref}\";"));
+        }
+        if let Some(stack_owned) = &ty.cpp_stack_owned {
+            self.line(&format!(
+                "#cpp_stack_owned \"{}\"(size = {}, align = {});",
+                stack_owned.cpp_type, stack_owned.size, stack_owned.align
+            ));
+        }
+        for variant in &ty.variants {
+            self.variant(variant);
+        }
+        for constructor in &ty.constructors {
+            self.constructor(constructor);
+        }
+        for field in &ty.fields {
+            self.field(field);
+        }
+        for method in &ty.methods {
+            self.method_details(method);
+        }
+        self.close();
+    }
+
+    fn variant(&mut self, variant: &ZngurVariant) {
+        self.doc(&variant.doc);
+        self.line(&format!(
+            "{}{};",
+            variant.name,
+            ConstructorArgs(&variant.fields)
+        ));
+    }
+
+    fn constructor(&mut self, constructor: &ZngurConstructor) {
+        self.doc(&constructor.doc);
+        self.cpp_name(&constructor.cpp_name);
+        let name = constructor
+            .name
+            .as_ref()
+            .map(|name| format!(" {name}"))
+            .unwrap_or_default();
+        self.line(&format!(
+            "constructor{name}{};",
+            ConstructorArgs(&constructor.inputs)
+        ));
+    }
+
+    fn field(&mut self, field: &ZngurField) {
+        self.doc(&field.doc);
+        self.cpp_name(&field.cpp_name);
+        let offset = field
+            .offset
+            .map_or("auto".to_owned(), |offset| offset.to_string());
+        self.line(&format!(
+            "field {} (offset = {offset}, type = {});",
+            field.name, field.ty
+        ));
+    }
+
+    fn method_details(&mut self, method: &ZngurMethodDetails) {
+        let mut suffix = String::new();
+        if let Some(use_path) = &method.use_path {
+            suffix += &format!(" use {}", path(use_path));
+        }
+        if let Some((deref, _)) = &method.deref {
+            // The mutability of the deref is the mutability of the receiver.
+            suffix += &format!(" deref {deref}");
+        }
+        self.method("", &method.data, &suffix);
+    }
+
+    fn fn_item(&mut self, func: &ZngurFn) {
+        self.doc(&func.doc);
+        self.cpp_name(&func.cpp_name);
+        self.result_mode(func.result_mode);
+        self.signature(
+            "",
+            func.path.path.last().unwrap(),
+            &func.path.generics,
+            None,
+            &func.inputs,
+            &func.output,
+            "",
+        );
+    }
+
+    fn extern_cpp_fn(&mut self, func: &ZngurExternCppFn) {
+        self.signature(
+            safety(func.is_safe),
+            &func.name,
+            &[],
+            None,
+            &func.inputs,
+            &func.output,
+            "",
+        );
+    }
+
+    fn extern_cpp_impl(&mut self, extern_impl: &ZngurExternCppImpl) {
+        match &extern_impl.tr {
+            Some(tr) => self.open(&format!("impl {tr} for {}", extern_impl.ty)),
+            None => self.open(&format!("impl {}", extern_impl.ty)),
+        }
+        for method in &extern_impl.methods {
+            self.method(safety(method.is_safe), method, "");
+        }
+        self.close();
+    }
+
+    fn method(&mut self, prefix: &str, method: &ZngurMethod, suffix: &str) {
+        self.doc(&method.doc);
+        self.cpp_name(&method.cpp_name);
+        self.result_mode(method.result_mode);
+        self.signature(
+            prefix,
+            &method.name,
+            &method.generics,
+            Some(method.receiver),
+            &method.inputs,
+            &method.output,
+            suffix,
+        );
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn signature(
+        &mut self,
+        prefix: &str,
+        name: &str,
+        generics: &[RustType],
+        receiver: Option<ZngurMethodReceiver>,
+        inputs: &[RustType],
+        output: &RustType,
+        suffix: &str,
+    ) {
+        let mut line = format!("{prefix}fn {name}");
+        if !generics.is_empty() {
+            line += &format!("<{}>", generics.iter().join(", "));
+        }
+        let receiver = match receiver {
+            None | Some(ZngurMethodReceiver::Static) => None,
+            Some(ZngurMethodReceiver::Ref(Mutability::Not)) => Some("&self".to_owned()),
+            Some(ZngurMethodReceiver::Ref(Mutability::Mut)) => Some("&mut self".to_owned()),
+            Some(ZngurMethodReceiver::Move) => Some("self".to_owned()),
+        };
+        let inputs = receiver
+            .into_iter()
+            .chain(inputs.iter().map(|ty| ty.to_string()))
+            .join(", ");
+        line += &format!("({inputs})");
+        if *output != RustType::UNIT {
+            line += &format!(" -> {output}");
+        }
+        self.line(&format!("{line}{suffix};"));
+    }
+}
+
+fn safety(is_safe: bool) -> &'static str {
+    if is_safe { "safe " } else { "unsafe " }
+}
+
+/// A resolved path, printed as an absolute path unless it starts with `crate`.
+fn path(segments: &[String]) -> String {
+    RustPathAndGenerics {
+        path: segments.to_vec(),
+        generics: vec![],
+        named_generics: vec![],
+    }
+    .to_string()
+}
+
+struct Layout<'a>(&'a LayoutPolicy);
+
+impl Display for Layout<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            LayoutPolicy::StackAllocated { size, align } => {
+                write!(f, "#layout(size = {size}, align = {align})")
+            }
+            LayoutPolicy::Conservative { size, align } => {
+                write!(f, "#layout_conservative(size = {size}, align = {align})")
+            }
+            LayoutPolicy::HeapAllocated => write!(f, "#heap_allocated"),
+            LayoutPolicy::OnlyByRef => write!(f, "#only_by_ref"),
+            LayoutPolicy::Auto => write!(f, "#layout(auto)"),
+        }
+    }
+}
+
+/// The arguments of a constructor or an enum variant. Arguments named by their index are printed
+/// as a tuple.
+struct ConstructorArgs<'a>(&'a [(String, RustType)]);
+
+impl Display for ConstructorArgs<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        let is_tuple = self
+            .0
+            .iter()
+            .enumerate()
+            .all(|(i, (name, _))| *name == i.to_string());
+        if is_tuple {
+            write!(f, "({})", self.0.iter().map(|(_, ty)| ty).format(", "))
+        } else {
+            write!(
+                f,
+                " {{ {} }}",
+                self.0
+                    .iter()
+                    .map(|(name, ty)| format!("{name}: {ty}"))
+                    .format(", ")
+            )
+        }
+    }
+}
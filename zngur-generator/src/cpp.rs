@@ -5,7 +5,10 @@ use std::{
 
 use indexmap::IndexMap;
 use itertools::Itertools;
-use zngur_def::{CppRef, CppStackOwned, CppValue, RustTrait, ZngurFieldData, ZngurMethodReceiver};
+use zngur_def::{
+    CppRef, CppStackOwned, CppValue, RustTrait, ZngurFieldData, ZngurMethodReceiver,
+    ZngurResultMode,
+};
 
 use crate::{
     ZngurWellknownTraitData,
@@ -45,14 +48,20 @@ impl CppPath {
         }
     }
 
+    /// Array lengths and `void` are written without the leading `::`.
+    fn is_bare(&self) -> bool {
+        self.0.len() == 1 && (self.0[0] == "void" || self.0[0].bytes().all(|b| b.is_ascii_digit()))
+    }
+
     fn need_header(&self) -> bool {
-        if self.0.len() == 1 && self.0[0].ends_with("_t") {
+        if (self.0.len() == 1 && self.0[0].ends_with("_t")) || self.is_bare() {
             return false;
         }
         // Top level namespace and type...
         if self.0.len() == 2 {
             let ty = &self.0[1];
-            return ty != "Unit" && ty != "Ref" && ty != "RefMut";
+            return !["Unit", "Ref", "RefMut", "Array", "FnPtr", "RustFnPtr"]
+                .contains(&ty.as_str());
         }
         true
     }
@@ -86,6 +95,9 @@ impl From<&str> for CppPath {
 
 impl Display for CppPath {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_bare() {
+            return write!(f, "{}", self.0[0]);
+        }
         write!(f, "::{}", self.0.iter().join("::"))
     }
 }
@@ -267,10 +279,33 @@ impl CppTraitMethod {
 pub struct CppFnSig {
     pub rust_link_name: String,
     pub inputs: Vec<CppType>,
+    /// The return type of the C++ function, which is not the Rust one if it has a result mode.
     pub output: CppType,
+    pub doc: Option<String>,
+    pub result: Option<CppResult>,
+}
+
+/// The `Result` of a function with a result mode, which Rust writes to separate `Ok` and `Err`
+/// outputs.
+#[derive(Debug)]
+pub struct CppResult {
+    pub mode: ZngurResultMode,
+    pub ok: CppType,
+    pub err: CppType,
 }
 
 impl CppFnSig {
+    /// Functions throwing a `rust::Error` for the `Err` value can't be `noexcept`.
+    pub fn noexcept(&self) -> &'static str {
+        match &self.result {
+            Some(CppResult {
+                mode: ZngurResultMode::Exception,
+                ..
+            }) => "",
+            _ => "noexcept",
+        }
+    }
+
     pub fn render_inputs(&self, namespace: &str) -> String {
         self.inputs
             .iter()
@@ -481,9 +516,36 @@ impl CppLayoutPolicy {
     }
 }
 
+/// The variants of an enum type, declared with an `enum` item.
+#[derive(Debug)]
+pub struct CppEnum {
+    /// The `enum class` of the variant names.
+    pub tag: CppType,
+    pub variants: Vec<CppEnumVariant>,
+}
+
+#[derive(Debug)]
+pub struct CppEnumVariant {
+    pub name: String,
+    pub fields: Vec<CppEnumField>,
+    /// The function moving the fields out of a value of this variant.
+    pub take_fields: String,
+}
+
+#[derive(Debug)]
+pub struct CppEnumField {
+    pub ty: CppType,
+    /// The name of the method returning a `Ref` to the field. The `RefMut` one has a `_mut` suffix.
+    pub getter: String,
+}
+
 #[derive(Debug)]
 pub struct CppTypeDefinition {
     pub ty: CppType,
+    pub doc: Option<String>,
+    /// The alias of the type set with `#cpp_name`.
+    pub cpp_name: Option<String>,
+    pub enum_def: Option<CppEnum>,
     pub layout: CppLayoutPolicy,
     pub methods: Vec<CppMethod>,
     pub constructors: Vec<CppFnSig>,
@@ -515,6 +577,22 @@ impl CppTypeDefinition {
             .any(|t| matches!(t, ZngurWellknownTraitData::Unsized))
     }
 
+    /// The `std::array` that a `rust::Array` of C++ builtin types converts to and from.
+    pub fn std_array(&self, namespace: &str) -> Option<String> {
+        if self.ty.path.to_string() != format!("::{namespace}::Array")
+            || !self.layout.is_stack_allocated()
+        {
+            return None;
+        }
+        let [elem, len] = &self.ty.generic_args[..] else {
+            return None;
+        };
+        if elem.path.0.len() != 1 || elem.path.is_bare() {
+            return None;
+        }
+        Some(format!("::std::array< {elem}, {len} >"))
+    }
+
     pub fn has_copy(&self) -> bool {
         self.wellknown_traits
             .iter()
@@ -616,6 +694,9 @@ impl Default for CppTypeDefinition {
     fn default() -> Self {
         Self {
             ty: CppType::from("fill::me::you::forgot::it"),
+            doc: None,
+            cpp_name: None,
+            enum_def: None,
             layout: CppLayoutPolicy::OnlyByRef,
             methods: vec![],
             constructors: vec![],
@@ -839,6 +920,13 @@ pub fn normalize_whitespace(cpp: &str) -> String {
     let mut last_extra_indent: usize = 0;
     let mut indents: Vec<usize> = Vec::new();
     for line in lines {
+        if line.trim_start().starts_with("///") {
+            // doc comments are kept, at the indent of the line they document
+            out.extend(iter::once(" ").cycle().take(state.indent));
+            out.push(line.trim());
+            out.push("\n");
+            continue;
+        }
         let trimmed = trim_end_c_comments(line).trim_end();
         let ty = line_type(trimmed);
         let indent = line.count_start_matches(char::is_whitespace);
@@ -1,3 +1,6 @@
+use cpp::CppEnum;
+use cpp::CppEnumField;
+use cpp::CppEnumVariant;
 use cpp::CppExportedFnDefinition;
 use cpp::CppExportedImplDefinition;
 use cpp::CppFile;
@@ -16,10 +19,14 @@ use rust::IntoCpp;
 pub mod cpp;
 mod rust;
 mod template;
+#[cfg(test)]
+mod tests;
 
 use askama::Template;
 pub use rust::RustFile;
-pub use zngur_parser::{ParseResult, ParsedZngFile, cfg};
+#[cfg(feature = "export")]
+pub use zngur_parser::export;
+pub use zngur_parser::{ParseResult, ParsedZngFile, cfg, diagnostics, import, layout_fix};
 
 pub use zngur_def::*;
 
@@ -33,7 +40,8 @@ impl ZngurGenerator {
     }
 
     pub fn render(self, zng_header_in_place: bool) -> (String, String, Option<String>) {
-        let zng = self.0;
+        let mut zng = self.0;
+        expand_trait_impls(&mut zng);
         let mut cpp_file = CppFile::default();
         cpp_file.header_file_name = zng.cpp_include_header_name.clone();
         cpp_file.additional_includes = zng.additional_includes.0;
@@ -96,6 +104,9 @@ impl ZngurGenerator {
                 }
                 LayoutPolicy::HeapAllocated => (),
                 LayoutPolicy::OnlyByRef => (),
+                LayoutPolicy::Auto => {
+                    panic!("The `#layout(auto)` of `{ty}` should be probed before rendering")
+                }
             }
             if is_copy {
                 rust_file.add_static_is_copy_assert(&ty);
@@ -177,13 +188,90 @@ impl ZngurGenerator {
             let mut constructors = vec![];
             let mut fields = vec![];
             let mut wellknown_traits = vec![];
-            for constructor in ty_def.constructors {
+            let mut enum_def = None;
+            if !ty_def.variants.is_empty() {
+                let rust_link_name = rust_file.add_enum_tag(ty, &ty_def.variants);
+                let tag = CppType::from(format!("{default_ns}::{rust_link_name}Tag").as_str());
+                cpp_methods.push(CppMethod {
+                    name: "tag".to_owned(),
+                    kind: ZngurMethodReceiver::Ref(Mutability::Not),
+                    sig: CppFnSig {
+                        rust_link_name,
+                        inputs: vec![ty.into_cpp(default_ns, &sanitized_crate_name).into_ref()],
+                        output: tag.clone(),
+                        doc: None,
+                        result: None,
+                    },
+                });
+                let mut variants = vec![];
+                for variant in &ty_def.variants {
+                    // The payload of single field tuple variants, like `Some`, is not numbered.
+                    let is_newtype = variant.fields.len() == 1 && variant.fields[0].0 == "0";
+                    let mut variant_fields = vec![];
+                    for (field, field_ty) in &variant.fields {
+                        let mut getter = format!("as_{}", variant.name);
+                        if !is_newtype {
+                            getter += &format!("_{field}");
+                        }
+                        for mutability in [Mutability::Not, Mutability::Mut] {
+                            let rust_link_name = rust_file.add_variant_field_getter(
+                                ty,
+                                &variant.name,
+                                field,
+                                field_ty,
+                                mutability,
+                            );
+                            let name = match mutability {
+                                Mutability::Mut => format!("{getter}_mut"),
+                                Mutability::Not => getter.clone(),
+                            };
+                            let self_ty = RustType::Ref(mutability, Box::new(ty.clone()));
+                            let output = RustType::Ref(mutability, Box::new(field_ty.clone()));
+                            cpp_methods.push(CppMethod {
+                                name,
+                                kind: ZngurMethodReceiver::Ref(mutability),
+                                sig: CppFnSig {
+                                    rust_link_name,
+                                    inputs: vec![
+                                        self_ty.into_cpp(default_ns, &sanitized_crate_name),
+                                    ],
+                                    output: output.into_cpp(default_ns, &sanitized_crate_name),
+                                    doc: None,
+                                    result: None,
+                                },
+                            });
+                        }
+                        variant_fields.push(CppEnumField {
+                            ty: field_ty.into_cpp(default_ns, &sanitized_crate_name),
+                            getter,
+                        });
+                    }
+                    variants.push(CppEnumVariant {
+                        name: cpp_handle_keyword(&variant.name).to_owned(),
+                        fields: variant_fields,
+                        take_fields: rust_file.add_variant_take_fields(ty, variant),
+                    });
+                }
+                enum_def = Some(CppEnum { tag, variants });
+            }
+            let variant_constructors =
+                ty_def.variants.into_iter().map(|variant| ZngurConstructor {
+                    name: Some(variant.name),
+                    inputs: variant.fields,
+                    doc: variant.doc,
+                    cpp_name: None,
+                });
+            for constructor in ty_def.constructors.into_iter().chain(variant_constructors) {
                 match constructor.name {
                     Some(name) => {
+                        rust_file.add_doc(&constructor.doc);
                         let rust_link_names = rust_file
                             .add_constructor(&format!("{}::{}", ty, name), &constructor.inputs);
+                        let cpp_name = constructor
+                            .cpp_name
+                            .unwrap_or_else(|| cpp_handle_keyword(&name).to_owned());
                         cpp_methods.push(CppMethod {
-                            name: cpp_handle_keyword(&name).to_owned(),
+                            name: cpp_name.clone(),
                             kind: ZngurMethodReceiver::Static,
                             sig: CppFnSig {
                                 rust_link_name: rust_link_names.constructor,
@@ -193,10 +281,12 @@ impl ZngurGenerator {
                                     .map(|x| x.1.into_cpp(default_ns, &sanitized_crate_name))
                                     .collect(),
                                 output: ty.into_cpp(default_ns, &sanitized_crate_name),
+                                doc: constructor.doc.clone(),
+                                result: None,
                             },
                         });
                         cpp_methods.push(CppMethod {
-                            name: format!("matches_{}", name),
+                            name: format!("matches_{}", cpp_name),
                             kind: ZngurMethodReceiver::Ref(Mutability::Not),
                             sig: CppFnSig {
                                 rust_link_name: rust_link_names.match_check,
@@ -204,10 +294,13 @@ impl ZngurGenerator {
                                     ty.into_cpp(default_ns, &sanitized_crate_name).into_ref(),
                                 ],
                                 output: CppType::from("uint8_t"),
+                                doc: None,
+                                result: None,
                             },
                         });
                     }
                     None => {
+                        rust_file.add_doc(&constructor.doc);
                         let rust_link_name = rust_file
                             .add_constructor(&format!("{}", ty), &constructor.inputs)
                             .constructor;
@@ -219,6 +312,8 @@ impl ZngurGenerator {
                                 .map(|x| x.1.into_cpp(default_ns, &sanitized_crate_name))
                                 .collect(),
                             output: ty.into_cpp(default_ns, &sanitized_crate_name),
+                            doc: constructor.doc,
+                            result: None,
                         });
                     }
                 }
@@ -228,6 +323,8 @@ impl ZngurGenerator {
                 let field = ZngurFieldData {
                     name: field.name,
                     ty: field.ty,
+                    doc: field.doc,
+                    cpp_name: field.cpp_name,
                     offset: match field.offset {
                         Some(offset) => ZngurFieldDataOffset::Offset(offset),
                         None => ZngurFieldDataOffset::Auto(
@@ -247,6 +344,8 @@ impl ZngurGenerator {
                             .map(|x| x.into_cpp(default_ns, &sanitized_crate_name))
                             .collect(),
                         output: ty.into_cpp(default_ns, &sanitized_crate_name),
+                        doc: None,
+                        result: None,
                     });
                 }
             }
@@ -265,7 +364,8 @@ impl ZngurGenerator {
                 } = method_details;
                 let rusty_inputs = real_inputs_of_method(&method, &ty);
 
-                let sig = rust_file.add_function(
+                rust_file.add_doc(&method.doc);
+                let mut sig = rust_file.add_function(
                     &format!(
                         "<{}>::{}::<{}>",
                         deref.as_ref().map(|x| &x.0).unwrap_or(&ty),
@@ -276,17 +376,24 @@ impl ZngurGenerator {
                     &method.output,
                     use_path,
                     deref.map(|x| x.1),
+                    method.result_mode,
                     default_ns,
                     &sanitized_crate_name,
                 );
+                sig.doc = method.doc;
                 cpp_methods.push(CppMethod {
-                    name: cpp_handle_keyword(&method.name).to_owned(),
+                    name: method
+                        .cpp_name
+                        .unwrap_or_else(|| cpp_handle_keyword(&method.name).to_owned()),
                     kind: method.receiver,
                     sig,
                 });
             }
             cpp_file.type_defs.push(CppTypeDefinition {
                 ty: ty.into_cpp(default_ns, &sanitized_crate_name),
+                doc: ty_def.doc,
+                cpp_name: ty_def.cpp_name,
+                enum_def,
                 layout: rust_file.add_layout_policy_shim(&ty, ty_def.layout),
                 constructors,
                 fields,
@@ -317,6 +424,8 @@ impl ZngurGenerator {
                                             .map(|x| x.into_cpp(default_ns, &sanitized_crate_name))
                                             .collect(),
                                         output: output.into_cpp(default_ns, &sanitized_crate_name),
+                                        doc: None,
+                                        result: None,
                                     },
                                 });
                             }
@@ -345,19 +454,24 @@ pub mod cpp {{
             ));
         }
         for func in zng.funcs {
-            let sig = rust_file.add_function(
+            rust_file.add_doc(&func.doc);
+            let mut sig = rust_file.add_function(
                 &func.path.to_string(),
                 &func.inputs,
                 &func.output,
                 None,
                 None,
+                func.result_mode,
                 default_ns,
                 &sanitized_crate_name,
             );
-            cpp_file.fn_defs.push(CppFnDefinition {
-                name: CppPath::from_rust_path(&func.path.path, default_ns, &sanitized_crate_name),
-                sig,
-            });
+            sig.doc = func.doc;
+            let mut name =
+                CppPath::from_rust_path(&func.path.path, default_ns, &sanitized_crate_name);
+            if let Some(cpp_name) = func.cpp_name {
+                *name.0.last_mut().unwrap() = cpp_name;
+            }
+            cpp_file.fn_defs.push(CppFnDefinition { name, sig });
         }
         for func in zng.extern_cpp_funcs {
             let rust_link_name = rust_file.add_extern_cpp_function(
@@ -376,6 +490,8 @@ pub mod cpp {{
                         .map(|x| x.into_cpp(default_ns, &sanitized_crate_name))
                         .collect(),
                     output: func.output.into_cpp(default_ns, &sanitized_crate_name),
+                    doc: None,
+                    result: None,
                 },
             });
         }
@@ -406,6 +522,8 @@ pub mod cpp {{
                                 rust_link_name: link_name.clone(),
                                 inputs,
                                 output: method.output.into_cpp(default_ns, &sanitized_crate_name),
+                                doc: None,
+                                result: None,
                             },
                         )
                     })
@@ -433,6 +551,31 @@ impl ZngHeaderGenerator {
     }
 }
 
+/// Adds the methods of the traits in `impl Trait for Type;` items to their types, calling them
+/// through the trait.
+fn expand_trait_impls(zng: &mut ZngurSpec) {
+    for imp in std::mem::take(&mut zng.trait_impls) {
+        // Impls of undeclared traits or types, and of Fn traits, are reported by the parser.
+        let (Some(tr), RustTrait::Normal(pg)) = (zng.traits.get(&imp.tr), &imp.tr) else {
+            continue;
+        };
+        let Some(ty_def) = zng.types.iter_mut().find(|ty_def| ty_def.ty == imp.ty) else {
+            continue;
+        };
+        for method in &tr.methods {
+            // Methods declared on the type itself take precedence.
+            if ty_def.methods.iter().any(|m| m.data.name == method.name) {
+                continue;
+            }
+            ty_def.methods.push(ZngurMethodDetails {
+                data: method.clone(),
+                use_path: Some(pg.path.clone()),
+                deref: None,
+            });
+        }
+    }
+}
+
 fn real_inputs_of_method(method: &ZngurMethod, ty: &RustType) -> Vec<RustType> {
     let receiver_type = match method.receiver {
         ZngurMethodReceiver::Static => None,
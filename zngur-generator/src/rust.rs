@@ -5,7 +5,9 @@ use sha2::{Digest, Sha256};
 
 use crate::{
     ZngurTrait, ZngurWellknownTrait, ZngurWellknownTraitData,
-    cpp::{CppFnSig, CppLayoutPolicy, CppPath, CppTraitDefinition, CppTraitMethod, CppType},
+    cpp::{
+        CppFnSig, CppLayoutPolicy, CppPath, CppResult, CppTraitDefinition, CppTraitMethod, CppType,
+    },
 };
 
 use zngur_def::*;
@@ -108,6 +110,35 @@ impl IntoCpp for RustType {
                 path: CppPath::from(&*format!("{namespace}::Slice")),
                 generic_args: vec![s.into_cpp(namespace, crate_name)],
             },
+            RustType::Array(s, n) => CppType {
+                path: CppPath::from(&*format!("{namespace}::Array")),
+                generic_args: vec![
+                    s.into_cpp(namespace, crate_name),
+                    CppType::from(&*n.to_string()),
+                ],
+            },
+            RustType::FnPtr {
+                abi,
+                inputs,
+                output,
+                ..
+            } => {
+                let (name, output) = match abi.as_deref() {
+                    Some("C" | "C-unwind") if **output == RustType::UNIT => {
+                        ("FnPtr", CppType::from("void"))
+                    }
+                    Some("C" | "C-unwind") => ("FnPtr", output.into_cpp(namespace, crate_name)),
+                    // C++ can't call functions with other ABIs, so they are opaque.
+                    _ => ("RustFnPtr", output.into_cpp(namespace, crate_name)),
+                };
+                CppType {
+                    path: CppPath::from(&*format!("{namespace}::{name}")),
+                    generic_args: [output]
+                        .into_iter()
+                        .chain(inputs.iter().map(|x| x.into_cpp(namespace, crate_name)))
+                        .collect(),
+                }
+            }
             RustType::Raw(m, t) => CppType {
                 path: match m {
                     Mutability::Mut => CppPath::from(&*format!("{namespace}::RawMut")),
@@ -414,6 +445,8 @@ fn mangle_name(name: &str, mangling_base: &str) -> String {
         (2, ")", 'q'),
         (2, "@", 'z'),
         (2, "-", 'h'),
+        (2, ";", 'o'),
+        (2, "\"", 'u'),
     ];
     while let Some((pos, which)) = bads.iter().filter_map(|x| Some((name.find(x.1)?, x))).min() {
         name.replace_range(pos..pos + which.1.len(), "_");
@@ -770,12 +803,120 @@ pub extern "C" fn {match_check}(i: *mut u8, o: *mut u8) {{ unsafe {{
         }
     }
 
+    /// Adds the function returning the index of the variant of an enum value, in the order of
+    /// `variants`.
+    pub fn add_enum_tag(&mut self, ty: &RustType, variants: &[ZngurVariant]) -> String {
+        let mangled_name = self.mangle_name(&format!("{ty}_tag"));
+        wln!(
+            self,
+            r#"
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "C" fn {mangled_name}(i0: *mut u8, o: *mut u8) {{ unsafe {{
+    ::std::ptr::write(o as *mut u32, match ::std::ptr::read(i0 as *mut &{ty}) {{"#
+        );
+        for (index, variant) in variants.iter().enumerate() {
+            wln!(self, "        {ty}::{} {{ .. }} => {index},", variant.name);
+        }
+        wln!(self, "    }});\n}} }}");
+        mangled_name
+    }
+
+    /// Adds the function returning a reference to a field of a variant, which panics if the
+    /// value is another variant.
+    pub fn add_variant_field_getter(
+        &mut self,
+        ty: &RustType,
+        variant: &str,
+        field: &str,
+        field_ty: &RustType,
+        mutability: Mutability,
+    ) -> String {
+        let mut_str = match mutability {
+            Mutability::Mut => "mut ",
+            Mutability::Not => "",
+        };
+        let mangled_name = self.mangle_name(&format!(
+            "{ty}_{variant}_{field}_getter{}",
+            if mut_str.is_empty() { "" } else { "_mut" }
+        ));
+        wln!(
+            self,
+            r#"
+#[allow(non_snake_case, unreachable_patterns)]
+#[unsafe(no_mangle)]
+pub extern "C" fn {mangled_name}(i0: *mut u8, o: *mut u8) {{ unsafe {{"#
+        );
+        self.wrap_in_catch_unwind(|this| {
+            wln!(
+                this,
+                r#"    match ::std::ptr::read(i0 as *mut &{mut_str}{ty}) {{
+        {ty}::{variant} {{ {field}: f, .. }} => ::std::ptr::write(o as *mut &{mut_str}{field_ty}, f),
+        _ => panic!("the value is not a `{variant}`"),
+    }}"#
+            );
+        });
+        wln!(self, "}} }}");
+        mangled_name
+    }
+
+    /// Adds the function moving the fields of a variant out of the value. The value is
+    /// left uninitialized afterwards, so the caller must not drop it again.
+    pub fn add_variant_take_fields(&mut self, ty: &RustType, variant: &ZngurVariant) -> String {
+        let mangled_name = self.mangle_name(&format!("{ty}_{}_take_fields", variant.name));
+        w!(
+            self,
+            r#"
+#[allow(non_snake_case, unreachable_patterns)]
+#[unsafe(no_mangle)]
+pub extern "C" fn {mangled_name}(i0: *mut u8"#
+        );
+        for n in 0..variant.fields.len() {
+            w!(self, ", o{n}: *mut u8");
+        }
+        wln!(self, ") {{ unsafe {{");
+        w!(
+            self,
+            "    match &*(i0 as *mut {ty}) {{\n        {ty}::{} {{ ",
+            variant.name
+        );
+        for (n, (field, _)) in variant.fields.iter().enumerate() {
+            w!(self, "{field}: a{n}, ");
+        }
+        wln!(self, ".. }} => {{");
+        for (n, (_, field_ty)) in variant.fields.iter().enumerate() {
+            wln!(
+                self,
+                "            ::std::ptr::write(o{n} as *mut {field_ty}, ::std::ptr::read(a{n}));"
+            );
+        }
+        wln!(
+            self,
+            r#"        }}
+        _ => unreachable!(),
+    }}
+}} }}"#
+        );
+        mangled_name
+    }
+
+    /// Writes the `///` doc comment of an item as `#[doc]` attributes of the next shim.
+    pub fn add_doc(&mut self, doc: &Option<String>) {
+        if let Some(doc) = doc {
+            for line in doc.lines() {
+                wln!(self, "#[doc = {line:?}]");
+            }
+        }
+    }
+
     pub(crate) fn add_field_assertions(
         &mut self,
         field: &ZngurField,
         owner: &RustType,
     ) -> Option<String> {
-        let ZngurField { name, ty, offset } = field;
+        let ZngurField {
+            name, ty, offset, ..
+        } = field;
         wln!(self, r#"__zngur_assert_has_field!({owner}, {ty}, {name});"#);
         if let Some(offset) = offset {
             wln!(
@@ -917,6 +1058,7 @@ pub extern "C" fn {mangled_name}(d: *mut u8) -> *mut cpp::{type_name} {{
         mangled_name
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn add_function(
         &mut self,
         rust_name: &str,
@@ -924,6 +1066,7 @@ pub extern "C" fn {mangled_name}(d: *mut u8) -> *mut cpp::{type_name} {{
         output: &RustType,
         use_path: Option<Vec<String>>,
         deref: Option<Mutability>,
+        result_mode: Option<ZngurResultMode>,
         namespace: &str,
         crate_name: &str,
     ) -> CppFnSig {
@@ -931,6 +1074,7 @@ pub extern "C" fn {mangled_name}(d: *mut u8) -> *mut cpp::{type_name} {{
         if deref.is_some() {
             mangled_name += "_deref";
         }
+        let result = result_mode.zip(output.as_result());
         w!(
             self,
             r#"
@@ -950,7 +1094,15 @@ pub extern "C" fn {mangled_name}("#
         } else {
             (output.clone(), false)
         };
-        wln!(self, "o: *mut u8) {{ unsafe {{");
+        if result.is_some() {
+            // The `Err` value is written to `e`, and `is_err` is set to 1.
+            wln!(
+                self,
+                "o: *mut u8, e: *mut u8, is_err: *mut u8) {{ unsafe {{"
+            );
+        } else {
+            wln!(self, "o: *mut u8) {{ unsafe {{");
+        }
         self.wrap_in_catch_unwind(|this| {
             if let Some(use_path) = use_path {
                 if use_path.first().is_some_and(|x| x == "crate") {
@@ -960,33 +1112,68 @@ pub extern "C" fn {mangled_name}("#
                 }
             }
 
-            w!(
-                this,
-                "    ::std::ptr::write(o as *mut {modified_output}, {impl_trait} {rust_name}(",
-                impl_trait = if is_impl_trait { "Box::new( " } else { "" },
-            );
+            let mut call = format!("{rust_name}(");
             match deref {
-                Some(Mutability::Mut) => w!(this, "::std::ops::DerefMut::deref_mut"),
-                Some(Mutability::Not) => w!(this, "::std::ops::Deref::deref"),
+                Some(Mutability::Mut) => call += "::std::ops::DerefMut::deref_mut",
+                Some(Mutability::Not) => call += "::std::ops::Deref::deref",
                 None => {}
             }
             for (n, ty) in inputs.iter().enumerate() {
-                w!(this, "(::std::ptr::read(i{n} as *mut {ty})), ");
+                call += &format!("(::std::ptr::read(i{n} as *mut {ty})), ");
             }
-            if is_impl_trait {
-                wln!(this, ")));");
-            } else {
-                wln!(this, "));");
+            call += ")";
+            match result {
+                Some((_, (ok, err))) => wln!(
+                    this,
+                    r#"    match {call} {{
+        ::std::result::Result::Ok(v) => ::std::ptr::write(o as *mut {ok}, v),
+        ::std::result::Result::Err(v) => {{
+            ::std::ptr::write(e as *mut {err}, v);
+            *is_err = 1;
+        }}
+    }}"#
+                ),
+                None if is_impl_trait => wln!(
+                    this,
+                    "    ::std::ptr::write(o as *mut {modified_output}, Box::new(  {call}));"
+                ),
+                None => wln!(
+                    this,
+                    "    ::std::ptr::write(o as *mut {modified_output},  {call});"
+                ),
             }
         });
         wln!(self, " }} }}");
-        CppFnSig {
-            rust_link_name: mangled_name,
-            inputs: inputs
-                .iter()
-                .map(|ty| ty.into_cpp(namespace, crate_name))
-                .collect(),
-            output: modified_output.into_cpp(namespace, crate_name),
+        let inputs = inputs
+            .iter()
+            .map(|ty| ty.into_cpp(namespace, crate_name))
+            .collect();
+        match result {
+            Some((mode, (ok, err))) => {
+                let ok = ok.into_cpp(namespace, crate_name);
+                let err = err.into_cpp(namespace, crate_name);
+                let output = match mode {
+                    ZngurResultMode::Exception => ok.clone(),
+                    ZngurResultMode::Expected => CppType {
+                        path: CppPath::from(&*format!("{namespace}::Expected")),
+                        generic_args: vec![ok.clone(), err.clone()],
+                    },
+                };
+                CppFnSig {
+                    rust_link_name: mangled_name,
+                    inputs,
+                    output,
+                    doc: None,
+                    result: Some(CppResult { mode, ok, err }),
+                }
+            }
+            None => CppFnSig {
+                rust_link_name: mangled_name,
+                inputs,
+                output: modified_output.into_cpp(namespace, crate_name),
+                doc: None,
+                result: None,
+            },
         }
     }
 
@@ -1108,6 +1295,8 @@ pub extern "C" fn {debug_print}(v: *mut u8) {{
                 }
             }
             LayoutPolicy::OnlyByRef => CppLayoutPolicy::OnlyByRef,
+            // Rejected earlier in `ZngurGenerator::render`.
+            LayoutPolicy::Auto => unreachable!(),
         }
     }
 }
@@ -1,6 +1,6 @@
 use crate::cpp::{
-    CppExportedFnDefinition, CppExportedImplDefinition, CppFnDefinition, CppTraitDefinition,
-    CppTypeDefinition,
+    CppExportedFnDefinition, CppExportedImplDefinition, CppFnDefinition, CppFnSig, CppResult,
+    CppTraitDefinition, CppTypeDefinition,
 };
 use askama::Template;
 use indexmap::IndexMap;
@@ -60,8 +60,241 @@ pub(crate) struct CppHeaderTemplate<'a> {
 }
 
 impl<'a> CppHeaderTemplate<'a> {
-    pub fn cpp_handle_field_name(&self, name: &str) -> String {
-        crate::cpp::cpp_handle_field_name(name)
+    pub fn cpp_field_name(&self, field: &ZngurFieldData) -> String {
+        match &field.cpp_name {
+            Some(cpp_name) => cpp_name.clone(),
+            None => crate::cpp::cpp_handle_field_name(&field.name),
+        }
+    }
+
+    /// Renders the `visit` and `match` methods of an enum type. `kind` is the class they
+    /// are rendered in, which is `Ref`, `RefMut` or empty for the owned type.
+    pub fn render_enum_matchers(&self, td: &CppTypeDefinition, kind: &str) -> String {
+        use itertools::Itertools;
+
+        let Some(enum_def) = &td.enum_def else {
+            return String::new();
+        };
+        let ty = &td.ty;
+        let ns = self.namespace;
+        let case = |variant: &str| format!("{ty}::Case< {ty}::Tag::{variant} >()");
+        let mut s = String::new();
+
+        // The qualifier of the methods and the suffix of the getters they pass the fields with.
+        let by_ref: &[(&str, &str)] = match kind {
+            "Ref" => &[("const", "")],
+            "RefMut" => &[("const", "_mut")],
+            _ => &[("const&", ""), ("&", "_mut")],
+        };
+        for (qualifier, suffix) in by_ref {
+            let args = |variant: &crate::cpp::CppEnumVariant| {
+                variant
+                    .fields
+                    .iter()
+                    .map(|field| format!("this->{}{suffix}()", field.getter))
+                    .join(", ")
+            };
+            let first = &enum_def.variants[0];
+            let visit_cases = enum_def
+                .variants
+                .iter()
+                .map(|variant| {
+                    let mut args = args(variant);
+                    if !args.is_empty() {
+                        args = format!(", {args}");
+                    }
+                    format!(
+                        "case {ty}::Tag::{}: return ::std::forward<F>(f)({}{args});",
+                        variant.name,
+                        case(&variant.name),
+                    )
+                })
+                .join("\n        ");
+            let match_cases = enum_def
+                .variants
+                .iter()
+                .enumerate()
+                .map(|(index, variant)| {
+                    format!(
+                        "case {ty}::Tag::{}: return ::std::get<{index}>(fs)({});",
+                        variant.name,
+                        args(variant),
+                    )
+                })
+                .join("\n        ");
+            let first_args = args(first);
+            let first_visit_args = if first_args.is_empty() {
+                String::new()
+            } else {
+                format!(", {first_args}")
+            };
+            s += &format!(
+                r#"
+    template<typename F>
+    auto visit(F&& f) {qualifier} -> decltype(::std::forward<F>(f)({}{first_visit_args})) {{
+      switch (this->tag()) {{
+        {visit_cases}
+      }}
+      ::std::terminate();
+    }}
+
+    template<typename... F>
+    auto match(F&&... f) {qualifier} -> decltype(::std::get<0>(::std::forward_as_tuple(::std::forward<F>(f)...))({first_args})) {{
+      static_assert(sizeof...(F) == {}, "`match` needs a function for each variant of `{ty}`");
+      auto fs = ::std::forward_as_tuple(::std::forward<F>(f)...);
+      switch (this->tag()) {{
+        {match_cases}
+      }}
+      ::std::terminate();
+    }}
+"#,
+                case(&first.name),
+                enum_def.variants.len(),
+            );
+        }
+        if !kind.is_empty() {
+            return s;
+        }
+
+        // The owned value is visited by moving the fields out of it.
+        let owned_args = |variant: &crate::cpp::CppEnumVariant| {
+            (0..variant.fields.len())
+                .map(|n| format!("::std::move(o{n})"))
+                .join(", ")
+        };
+        let take_fields = |variant: &crate::cpp::CppEnumVariant| {
+            let mut s = String::new();
+            for (n, field) in variant.fields.iter().enumerate() {
+                s += &format!("{} o{n}{{}};\n          ", field.ty);
+            }
+            s += &format!(
+                "::{ns}::__zngur_internal_assume_deinit(*this);\n          {}(::{ns}::__zngur_internal_data_ptr(*this)",
+                variant.take_fields
+            );
+            for n in 0..variant.fields.len() {
+                s += &format!(", ::{ns}::__zngur_internal_data_ptr(o{n})");
+            }
+            s += ");\n          ";
+            for n in 0..variant.fields.len() {
+                s += &format!("::{ns}::__zngur_internal_assume_init(o{n});\n          ");
+            }
+            s
+        };
+        let first = &enum_def.variants[0];
+        let first_args = first
+            .fields
+            .iter()
+            .map(|field| format!("::std::declval< {} >()", field.ty))
+            .join(", ");
+        let first_visit_args = if first_args.is_empty() {
+            String::new()
+        } else {
+            format!(", {first_args}")
+        };
+        let visit_cases = enum_def
+            .variants
+            .iter()
+            .map(|variant| {
+                let mut args = owned_args(variant);
+                if !args.is_empty() {
+                    args = format!(", {args}");
+                }
+                format!(
+                    "case {ty}::Tag::{}: {{\n          {}return ::std::forward<F>(f)({}{args});\n        }}",
+                    variant.name,
+                    take_fields(variant),
+                    case(&variant.name),
+                )
+            })
+            .join("\n        ");
+        let match_cases = enum_def
+            .variants
+            .iter()
+            .enumerate()
+            .map(|(index, variant)| {
+                format!(
+                    "case {ty}::Tag::{}: {{\n          {}return ::std::get<{index}>(fs)({});\n        }}",
+                    variant.name,
+                    take_fields(variant),
+                    owned_args(variant),
+                )
+            })
+            .join("\n        ");
+        let variant_tuples = enum_def
+            .variants
+            .iter()
+            .map(|variant| {
+                format!(
+                    "::std::tuple< {} >",
+                    variant.fields.iter().map(|field| &field.ty).join(", ")
+                )
+            })
+            .join(", ");
+        let variant_lambdas = enum_def
+            .variants
+            .iter()
+            .enumerate()
+            .map(|(index, variant)| {
+                format!(
+                    "[]({}) {{ return Variant(::std::in_place_index<{index}>, ::std::make_tuple({})); }}",
+                    variant
+                        .fields
+                        .iter()
+                        .enumerate()
+                        .map(|(n, field)| format!("{} a{n}", field.ty))
+                        .join(", "),
+                    (0..variant.fields.len())
+                        .map(|n| format!("::std::move(a{n})"))
+                        .join(", "),
+                )
+            })
+            .join(",\n        ");
+        s += &format!(
+            r#"
+    template<typename F>
+    auto visit(F&& f) && -> decltype(::std::forward<F>(f)({}{first_visit_args})) {{
+      switch (this->tag()) {{
+        {visit_cases}
+      }}
+      ::std::terminate();
+    }}
+
+    template<typename... F>
+    auto match(F&&... f) && -> decltype(::std::get<0>(::std::forward_as_tuple(::std::forward<F>(f)...))({first_args})) {{
+      static_assert(sizeof...(F) == {}, "`match` needs a function for each variant of `{ty}`");
+      auto fs = ::std::forward_as_tuple(::std::forward<F>(f)...);
+      switch (this->tag()) {{
+        {match_cases}
+      }}
+      ::std::terminate();
+    }}
+
+#if __cplusplus >= 201703L
+    /// The variants as a `std::variant` of the tuples of their fields, in the order of the tags.
+    using Variant = ::std::variant< {variant_tuples} >;
+
+    Variant into_variant() && {{
+      return ::std::move(*this).match(
+        {variant_lambdas}
+      );
+    }}
+#endif
+"#,
+            case(&first.name),
+            enum_def.variants.len(),
+        );
+        s
+    }
+
+    /// Renders a doc comment of the zng file as a Doxygen comment.
+    pub fn doc_comment(&self, doc: &Option<String>) -> String {
+        doc.iter()
+            .flat_map(|doc| doc.lines())
+            // A backslash at the end of a line comment would continue it on the next line, which
+            // is the generated declaration.
+            .map(|line| line.trim_end().trim_end_matches('\\').trim_end())
+            .map(|line| format!("/// {line}\n"))
+            .collect()
     }
 }
 
@@ -87,6 +320,72 @@ impl<'a> CppHeaderTemplate<'a> {
             "".to_owned()
         }
     }
+
+    /// Throws or returns the `Err` value of a function with a result mode, after the panic check.
+    fn result_handler(&self, result: &CppResult) -> String {
+        let err = &result.err;
+        let ns = self.namespace;
+        let on_err = match result.mode {
+            ZngurResultMode::Exception => format!("throw ::{ns}::Error< {err} >(::std::move(e));"),
+            ZngurResultMode::Expected => {
+                format!("return ::{ns}::Unexpected< {err} >(::std::move(e));")
+            }
+        };
+        format!(
+            r#"
+      if (is_err) {{
+        ::{ns}::__zngur_internal_assume_init(e);
+        {on_err}
+      }}
+"#
+        )
+    }
+
+    /// The body of the C++ function which calls the Rust function of `sig`.
+    fn render_call(&self, sig: &CppFnSig) -> String {
+        use itertools::Itertools;
+        let ns = self.namespace;
+        let inputs = &sig.inputs;
+        let mut assume_deinit_str = String::new();
+        for n in 0..inputs.len() {
+            assume_deinit_str.push_str(&format!("::{ns}::__zngur_internal_assume_deinit(i{n}); "));
+        }
+        let mut rust_args = String::new();
+        if !inputs.is_empty() {
+            rust_args = inputs
+                .iter()
+                .enumerate()
+                .map(|(n, _)| format!("::{ns}::__zngur_internal_data_ptr(i{n})"))
+                .join(", ")
+                + ", ";
+        }
+        let (out, err_decl, err_args, result_handler) = match &sig.result {
+            Some(result) => (
+                &result.ok,
+                format!("{} e{{}};\n      uint8_t is_err = 0;", result.err),
+                format!(", ::{ns}::__zngur_internal_data_ptr(e), &is_err"),
+                self.result_handler(result),
+            ),
+            None => (&sig.output, String::new(), String::new(), String::new()),
+        };
+        format!(
+            r#"
+      {out} o{{}};
+      {err_decl}
+      {assume_deinit_str}
+      {rust_link_name} (
+        {rust_args}
+        ::{ns}::__zngur_internal_data_ptr(o){err_args}
+      );
+      {panic_handler}
+      {result_handler}
+      ::{ns}::__zngur_internal_assume_init(o);
+      return o;
+"#,
+            rust_link_name = sig.rust_link_name,
+            panic_handler = self.panic_handler(),
+        )
+    }
     pub fn render_type_methods(&self, td: &crate::cpp::CppTypeDefinition) -> String {
         use itertools::Itertools;
         use zngur_def::{Mutability, ZngurMethodReceiver};
@@ -103,6 +402,7 @@ impl<'a> CppHeaderTemplate<'a> {
             );
             let inputs = &method.sig.inputs;
             let out = &method.sig.output;
+            let noexcept = method.sig.noexcept();
             let splat_inputs = inputs
                 .iter()
                 .enumerate()
@@ -115,46 +415,16 @@ impl<'a> CppHeaderTemplate<'a> {
                 .map(|(n, ty)| format!("{ty} i{n}"))
                 .join(", ");
 
-            let mut assume_deinit_str = String::new();
-            for n in 0..inputs.len() {
-                assume_deinit_str.push_str(&format!(
-                    "::{}::__zngur_internal_assume_deinit(i{n}); ",
-                    self.namespace
-                ));
-            }
-
-            let mut rust_args = String::new();
-            if !inputs.is_empty() {
-                rust_args = inputs
-                    .iter()
-                    .enumerate()
-                    .map(|(n, _)| format!("::{}::__zngur_internal_data_ptr(i{n})", self.namespace))
-                    .join(", ")
-                    + ", ";
-            }
-
             s.push_str(&format!(
                 r#"
-    inline {out} {fn_name} ({splat_inputs}) noexcept {{
-      {out} o{{}};
-      {assume_deinit_str}
-      {rust_link_name} (
-        {rust_args}
-        ::{namespace}::__zngur_internal_data_ptr(o)
-      );
-      {panic_handler}
-      ::{namespace}::__zngur_internal_assume_init(o);
-      return o;
+    inline {out} {fn_name} ({splat_inputs}) {noexcept} {{
+      {call}
     }}
 "#,
                 out = out,
                 fn_name = fn_name,
                 splat_inputs = splat_inputs,
-                assume_deinit_str = assume_deinit_str,
-                rust_link_name = method.sig.rust_link_name,
-                rust_args = rust_args,
-                namespace = self.namespace,
-                panic_handler = self.panic_handler()
+                call = self.render_call(&method.sig),
             ));
 
             if let ZngurMethodReceiver::Ref(m) = method.kind {
@@ -177,7 +447,7 @@ impl<'a> CppHeaderTemplate<'a> {
         template<typename Offset, typename... Offsets>
         inline {out} {namespace}::{field_kind}< {ty}, Offset, Offsets... >::{method_name}(
             {splat_skip_inputs}
-        ) const noexcept {{
+        ) const {noexcept} {{
           return {fn_name}(
             *this
             {move_args}
@@ -200,7 +470,7 @@ impl<'a> CppHeaderTemplate<'a> {
                         r#"
         inline {out} {namespace}::{ref_kind}< {ty} >::{method_name}(
             {splat_skip_inputs}
-        ) const noexcept {{
+        ) const {noexcept} {{
           return {fn_name}(
             *this
             {move_args}
@@ -241,7 +511,7 @@ impl<'a> CppHeaderTemplate<'a> {
                     r#"
       inline {out} {fn_name}(
             {splat_skip_inputs}
-      ) {const_str} noexcept {{
+      ) {const_str} {noexcept} {{
         return {fn_name}(
           {this_arg}
           {move_args}
@@ -399,6 +669,7 @@ impl<'a> CppHeaderTemplate<'a> {
             let out = &fd.sig.output;
             let name = fd.name.name();
             let inputs = &fd.sig.inputs;
+            let doc = self.doc_comment(&fd.sig.doc);
 
             let splat_inputs = inputs
                 .iter()
@@ -406,37 +677,11 @@ impl<'a> CppHeaderTemplate<'a> {
                 .map(|(n, ty)| format!("{ty} i{n}"))
                 .join(", ");
 
-            let mut assume_deinit_str = String::new();
-            for n in 0..inputs.len() {
-                assume_deinit_str.push_str(&format!(
-                    "::{}::__zngur_internal_assume_deinit(i{n}); ",
-                    self.namespace
-                ));
-            }
-
-            let mut rust_args = String::new();
-            if !inputs.is_empty() {
-                rust_args = inputs
-                    .iter()
-                    .enumerate()
-                    .map(|(n, _)| format!("::{}::__zngur_internal_data_ptr(i{n})", self.namespace))
-                    .join(", ")
-                    + ", ";
-            }
-
             s.push_str(&format!(
                 r#"
 {open_ns}
-    inline {out} {name}({splat_inputs}) noexcept {{
-      {out} o{{}};
-      {assume_deinit_str}
-      {rust_link_name} (
-        {rust_args}
-        ::{namespace}::__zngur_internal_data_ptr(o)
-      );
-      {panic_handler}
-      ::{namespace}::__zngur_internal_assume_init(o);
-      return o;
+    {doc}inline {out} {name}({splat_inputs}) {noexcept} {{
+      {call}
     }}
 {close_ns}
 "#,
@@ -444,11 +689,8 @@ impl<'a> CppHeaderTemplate<'a> {
                 out = out,
                 name = name,
                 splat_inputs = splat_inputs,
-                assume_deinit_str = assume_deinit_str,
-                rust_link_name = fd.sig.rust_link_name,
-                rust_args = rust_args,
-                namespace = self.namespace,
-                panic_handler = self.panic_handler(),
+                noexcept = fd.sig.noexcept(),
+                call = self.render_call(&fd.sig),
                 close_ns = close_ns
             ));
         }
@@ -0,0 +1,61 @@
+use zngur_parser::{ParsedZngFile, cfg::NullCfg};
+
+use crate::ZngurGenerator;
+
+/// Generates the Rust and C++ header of a zng file.
+fn generate(zng: &str) -> (String, String) {
+    let parsed = ParsedZngFile::parse_str(zng, NullCfg);
+    let (rust, h, _) =
+        ZngurGenerator::build_from_zng(parsed.spec, "crate".to_owned()).render(false);
+    (rust, h)
+}
+
+/// The names of the functions the generated Rust code exports to C++.
+fn exported_fns(rust: &str) -> Vec<&str> {
+    rust.split("extern \"C\" fn ")
+        .skip(1)
+        .filter_map(|rest| rest.split_once('(').map(|(name, _)| name))
+        .filter(|name| name.starts_with("_zngur_"))
+        .collect()
+}
+
+#[test]
+fn mangle_extern_fn_in_generics() {
+    let (rust, h) = generate(
+        r#"
+type ::std::option::Option<extern "C" fn(i32)> {
+    #layout(size = 8, align = 8);
+    fn is_some(&self) -> bool;
+}
+"#,
+    );
+    let names = exported_fns(&rust);
+    assert!(!names.is_empty());
+    for name in names {
+        assert!(
+            name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+            "invalid identifier `{name}`"
+        );
+        assert!(h.contains(name), "`{name}` is not declared in C++");
+    }
+}
+
+#[test]
+fn doc_comment_ending_in_backslash() {
+    let (rust, h) = generate(
+        r#"
+type crate::Point {
+    #layout(size = 8, align = 4);
+    /// Ends in a backslash \
+    fn norm(&self) -> i32;
+}
+"#,
+    );
+    // A backslash at the end of a C++ line comment would comment out the next line.
+    let doc = h
+        .lines()
+        .find(|line| line.trim_start().starts_with("/// Ends"))
+        .unwrap();
+    assert_eq!(doc.trim(), "/// Ends in a backslash");
+    assert!(rust.contains(r#"#[doc = "Ends in a backslash \\"]"#));
+}
@@ -0,0 +1,159 @@
+use lsp_types::{CompletionItem, CompletionItemKind, InsertTextFormat};
+
+/// The kind of block the cursor is in, which decides what can be written there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Block {
+    TopLevel,
+    Type,
+    Trait,
+    ExternCpp,
+    Other,
+}
+
+const TYPE_DIRECTIVES: &[(&str, &str)] = &[
+    ("layout", "layout(size = ${1:0}, align = ${2:1});"),
+    (
+        "layout_conservative",
+        "layout_conservative(size = ${1:0}, align = ${2:1});",
+    ),
+    ("heap_allocated", "heap_allocated;"),
+    ("only_by_ref", "only_by_ref;"),
+    ("cpp_value", "cpp_value \"${1:field}\" \"${2:Type}\";"),
+    ("cpp_ref", "cpp_ref \"${1:Type}\";"),
+    ("cpp_stack_owned", "cpp_stack_owned \"${1:Type}\";"),
+];
+
+const TOP_LEVEL_DIRECTIVES: &[(&str, &str)] = &[
+    (
+        "cpp_additional_includes",
+        "cpp_additional_includes \"\n$0\n\"",
+    ),
+    ("convert_panic_to_exception", "convert_panic_to_exception"),
+    ("unstable", "unstable(${1:feature})"),
+];
+
+const CONDITIONAL_DIRECTIVES: &[(&str, &str)] = &[
+    ("if", "if cfg!(${1:feature = \"name\"}) {\n\t$0\n}"),
+    ("match", "match cfg!(${1:feature}) {\n\t$0\n}"),
+];
+
+const TYPE_KEYWORDS: &[(&str, &str)] = &[
+    ("wellknown_traits", "wellknown_traits(${1:Debug});"),
+    ("constructor", "constructor { $1 };"),
+    (
+        "field",
+        "field ${1:name} (offset = ${2:0}, type = ${3:i32});",
+    ),
+    ("fn", "fn ${1:name}(${2:&self}) -> ${3:()};"),
+    ("async fn", "async fn ${1:name}(${2:&self}) -> ${3:()};"),
+];
+
+const TOP_LEVEL_KEYWORDS: &[(&str, &str)] = &[
+    ("type", "type ${1:Name} {\n\t$0\n}"),
+    ("trait", "trait ${1:Name} {\n\t$0\n}"),
+    ("fn", "fn ${1:name}(${2}) -> ${3:()};"),
+    ("mod", "mod ${1:name} {\n\t$0\n}"),
+    ("extern \"C++\"", "extern \"C++\" {\n\t$0\n}"),
+    ("merge", "merge \"${1:file.zng}\";"),
+    ("import", "import \"${1:file.zng}\";"),
+    ("use", "use ${1:path} as ${2:Name};"),
+];
+
+const TRAIT_KEYWORDS: &[(&str, &str)] = &[("fn", "fn ${1:name}(${2:&self}) -> ${3:()};")];
+
+const EXTERN_CPP_KEYWORDS: &[(&str, &str)] = &[
+    ("safe fn", "safe fn ${1:name}(${2}) -> ${3:()};"),
+    ("unsafe fn", "unsafe fn ${1:name}(${2}) -> ${3:()};"),
+    ("impl", "impl ${1:Type} {\n\t$0\n}"),
+];
+
+/// Suggests directives and keywords valid at the end of `before_cursor`.
+pub fn completions(before_cursor: &str) -> Vec<CompletionItem> {
+    let block = enclosing_block(before_cursor);
+    let word_start = before_cursor
+        .rfind(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .map_or(0, |i| i + 1);
+    let is_directive = before_cursor[..word_start].ends_with('#');
+    let groups: &[&[(&str, &str)]] = match (is_directive, block) {
+        (true, Block::Type) => &[TYPE_DIRECTIVES, CONDITIONAL_DIRECTIVES],
+        (true, Block::TopLevel) => &[TOP_LEVEL_DIRECTIVES, CONDITIONAL_DIRECTIVES],
+        (true, _) => &[CONDITIONAL_DIRECTIVES],
+        (false, Block::Type) => &[TYPE_KEYWORDS],
+        (false, Block::TopLevel) => &[TOP_LEVEL_KEYWORDS],
+        (false, Block::Trait) => &[TRAIT_KEYWORDS],
+        (false, Block::ExternCpp) => &[EXTERN_CPP_KEYWORDS],
+        (false, Block::Other) => &[],
+    };
+    groups
+        .iter()
+        .flat_map(|group| group.iter())
+        .map(|&(label, snippet)| CompletionItem {
+            label: label.to_owned(),
+            kind: Some(CompletionItemKind::KEYWORD),
+            insert_text: Some(snippet.to_owned()),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Finds the block containing the end of `text` by tracking the braces opened so far.
+///
+/// Conditional blocks (`#if`, `#else`, `#match` and its arms) are transparent, so their
+/// contents are treated like the contents of the block they appear in.
+fn enclosing_block(text: &str) -> Block {
+    let mut stack = vec![];
+    let mut header_start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '/' if chars.peek().is_some_and(|&(_, c)| c == '/') => {
+                for (_, c) in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '"' => {
+                while let Some((_, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '{' => {
+                let parent = stack.last().copied().unwrap_or(Block::TopLevel);
+                stack.push(classify_header(&text[header_start..i], parent));
+                header_start = i + 1;
+            }
+            '}' => {
+                stack.pop();
+                header_start = i + 1;
+            }
+            ';' => header_start = i + 1,
+            _ => {}
+        }
+    }
+    stack.last().copied().unwrap_or(Block::TopLevel)
+}
+
+fn classify_header(header: &str, parent: Block) -> Block {
+    let header = header.trim();
+    let first_word = header
+        .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '#'))
+        .find(|word| !word.is_empty())
+        .unwrap_or("");
+    match first_word {
+        "type" => Block::Type,
+        "mod" => Block::TopLevel,
+        "trait" => Block::Trait,
+        "extern" => Block::ExternCpp,
+        "#if" | "#else" | "#match" => parent,
+        _ if header.contains("=>") => parent,
+        _ => Block::Other,
+    }
+}
@@ -0,0 +1,249 @@
+use std::{error::Error, path::PathBuf};
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, RequestId, Response};
+use lsp_types::{
+    CompletionOptions, CompletionParams, CompletionResponse, DidChangeTextDocumentParams,
+    DidChangeWatchedFilesParams, DidChangeWatchedFilesRegistrationOptions,
+    DidCloseTextDocumentParams, DidOpenTextDocumentParams, FileChangeType, FileSystemWatcher,
+    GlobPattern, GotoDefinitionParams, GotoDefinitionResponse, HoverParams,
+    HoverProviderCapability, InitializeParams, OneOf, PublishDiagnosticsParams, Registration,
+    RegistrationParams, ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+    notification::{
+        DidChangeTextDocument, DidChangeWatchedFiles, DidCloseTextDocument, DidOpenTextDocument,
+        Notification as _, PublishDiagnostics,
+    },
+    request::{Completion, GotoDefinition, HoverRequest, RegisterCapability, Request as _},
+};
+use zngur_parser::import::ImportOptions;
+
+use crate::world::World;
+
+mod completion;
+#[cfg(test)]
+mod tests;
+mod world;
+
+type Result<T> = std::result::Result<T, Box<dyn Error + Sync + Send>>;
+
+fn main() -> Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec!["#".to_owned()]),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let params = connection.initialize(serde_json::to_value(capabilities)?)?;
+    let params: InitializeParams = serde_json::from_value(params)?;
+    let roots = workspace_roots(&params);
+    let import_options = import_options(&params, roots.first());
+    let mut world = World::new(roots, import_options);
+    if supports_file_watching(&params) {
+        watch_zng_files(&connection)?;
+    }
+    main_loop(connection, &mut world)?;
+    io_threads.join()?;
+    Ok(())
+}
+
+fn workspace_roots(params: &InitializeParams) -> Vec<PathBuf> {
+    #[allow(deprecated)]
+    let root_uri = params.root_uri.iter();
+    params
+        .workspace_folders
+        .iter()
+        .flatten()
+        .map(|folder| &folder.uri)
+        .chain(root_uri)
+        .filter_map(|uri| uri.to_file_path().ok())
+        .collect()
+}
+
+/// Reads where the merged files are searched from the `initializationOptions` of the client, like
+/// `{ "includeDirs": ["zng"], "packages": { "mycrate": "../mycrate" } }`. Relative directories are
+/// relative to the first workspace root.
+fn import_options(params: &InitializeParams, root: Option<&PathBuf>) -> ImportOptions {
+    let mut options = ImportOptions::new();
+    let Some(init) = &params.initialization_options else {
+        return options;
+    };
+    let dir = |path: &str| match root {
+        Some(root) => root.join(path),
+        None => PathBuf::from(path),
+    };
+    let include_dirs = init["includeDirs"].as_array().into_iter().flatten();
+    for include_dir in include_dirs.filter_map(|dir| dir.as_str()) {
+        options = options.with_include_dir(dir(include_dir));
+    }
+    for (name, package_dir) in init["packages"].as_object().into_iter().flatten() {
+        if let Some(package_dir) = package_dir.as_str() {
+            options = options.with_package(name, dir(package_dir));
+        }
+    }
+    options
+}
+
+fn supports_file_watching(params: &InitializeParams) -> bool {
+    let workspace = params.capabilities.workspace.as_ref();
+    workspace
+        .and_then(|workspace| workspace.did_change_watched_files)
+        .and_then(|capability| capability.dynamic_registration)
+        .unwrap_or(false)
+}
+
+/// Asks the client to notify us about the `.zng` files changed outside of the editor, so the
+/// documents loaded from the disk are not out of date.
+fn watch_zng_files(connection: &Connection) -> Result<()> {
+    let options = DidChangeWatchedFilesRegistrationOptions {
+        watchers: vec![FileSystemWatcher {
+            glob_pattern: GlobPattern::String("**/*.zng".to_owned()),
+            kind: None,
+        }],
+    };
+    let params = RegistrationParams {
+        registrations: vec![Registration {
+            id: "zng-files".to_owned(),
+            method: DidChangeWatchedFiles::METHOD.to_owned(),
+            register_options: Some(serde_json::to_value(options)?),
+        }],
+    };
+    connection.sender.send(Message::Request(Request::new(
+        RequestId::from("watch-zng-files".to_owned()),
+        RegisterCapability::METHOD.to_owned(),
+        params,
+    )))?;
+    Ok(())
+}
+
+fn main_loop(connection: Connection, world: &mut World) -> Result<()> {
+    for msg in &connection.receiver {
+        match msg {
+            Message::Request(req) => {
+                if connection.handle_shutdown(&req)? {
+                    return Ok(());
+                }
+                connection
+                    .sender
+                    .send(Message::Response(handle_request(world, req)))?;
+            }
+            Message::Notification(not) => {
+                let method = not.method.clone();
+                match handle_notification(world, not) {
+                    Ok(stale) => {
+                        for uri in stale {
+                            publish_diagnostics(&connection, world, uri)?;
+                        }
+                    }
+                    Err(e) => eprintln!("Invalid {method} notification: {e}"),
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+fn handle_request(world: &World, req: Request) -> Response {
+    match req.method.as_str() {
+        GotoDefinition::METHOD => respond(req, |params: GotoDefinitionParams| {
+            let position = params.text_document_position_params;
+            let locations = world.goto_definition(&position.text_document.uri, position.position);
+            (!locations.is_empty()).then_some(GotoDefinitionResponse::Array(locations))
+        }),
+        HoverRequest::METHOD => respond(req, |params: HoverParams| {
+            let position = params.text_document_position_params;
+            world.hover(&position.text_document.uri, position.position)
+        }),
+        Completion::METHOD => respond(req, |params: CompletionParams| {
+            let position = params.text_document_position;
+            world
+                .completions(&position.text_document.uri, position.position)
+                .map(CompletionResponse::Array)
+        }),
+        _ => Response::new_err(
+            req.id,
+            ErrorCode::MethodNotFound as i32,
+            format!("Unsupported request: {}", req.method),
+        ),
+    }
+}
+
+fn respond<P, R>(req: Request, handler: impl FnOnce(P) -> R) -> Response
+where
+    P: serde::de::DeserializeOwned,
+    R: serde::Serialize,
+{
+    match serde_json::from_value::<P>(req.params) {
+        Ok(params) => Response::new_ok(req.id, handler(params)),
+        Err(e) => Response::new_err(req.id, ErrorCode::InvalidParams as i32, e.to_string()),
+    }
+}
+
+/// Applies a notification to the world and returns the documents whose diagnostics are stale.
+fn handle_notification(world: &mut World, not: Notification) -> serde_json::Result<Vec<Url>> {
+    match not.method.as_str() {
+        DidOpenTextDocument::METHOD => {
+            let params: DidOpenTextDocumentParams = serde_json::from_value(not.params)?;
+            let uri = params.text_document.uri;
+            world.open(&uri, params.text_document.text);
+            Ok(world.open_documents_merged_with(&uri))
+        }
+        DidChangeTextDocument::METHOD => {
+            let params: DidChangeTextDocumentParams = serde_json::from_value(not.params)?;
+            let uri = params.text_document.uri;
+            // We only advertise full document sync, so the last change holds the whole text.
+            if let Some(change) = params.content_changes.into_iter().last() {
+                world.open(&uri, change.text);
+            }
+            Ok(world.open_documents_merged_with(&uri))
+        }
+        DidCloseTextDocument::METHOD => {
+            let params: DidCloseTextDocumentParams = serde_json::from_value(not.params)?;
+            let uri = params.text_document.uri;
+            world.close(&uri);
+            // The diagnostics of the closed document are cleared.
+            let mut stale = vec![uri.clone()];
+            stale.extend(world.open_documents_merged_with(&uri));
+            Ok(stale)
+        }
+        DidChangeWatchedFiles::METHOD => {
+            let params: DidChangeWatchedFilesParams = serde_json::from_value(not.params)?;
+            let mut stale = vec![];
+            for change in params.changes {
+                world.file_changed(&change.uri, change.typ != FileChangeType::DELETED);
+                for uri in world.open_documents_merged_with(&change.uri) {
+                    if !stale.contains(&uri) {
+                        stale.push(uri);
+                    }
+                }
+            }
+            Ok(stale)
+        }
+        _ => Ok(vec![]),
+    }
+}
+
+fn publish_diagnostics(connection: &Connection, world: &World, uri: Url) -> Result<()> {
+    // Only the open documents are checked, the diagnostics of the others are cleared.
+    let diagnostics = if world.is_open(&uri) {
+        world.diagnostics(&uri)
+    } else {
+        vec![]
+    };
+    let params = PublishDiagnosticsParams {
+        uri,
+        diagnostics,
+        version: None,
+    };
+    connection
+        .sender
+        .send(Message::Notification(Notification::new(
+            PublishDiagnostics::METHOD.to_owned(),
+            params,
+        )))?;
+    Ok(())
+}
@@ -0,0 +1,249 @@
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use lsp_types::{Position, Url};
+use zngur_parser::import::ImportOptions;
+
+use crate::world::World;
+
+/// The zng files of a workspace in a temporary directory, which is removed on drop.
+struct Workspace(PathBuf);
+
+impl Workspace {
+    fn new(files: &[(&str, &str)]) -> Self {
+        static NEXT_DIR: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "zngur-lsp-{}-{}",
+            std::process::id(),
+            NEXT_DIR.fetch_add(1, Ordering::Relaxed)
+        ));
+        for (path, text) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, text).unwrap();
+        }
+        Self(dir.canonicalize().unwrap())
+    }
+
+    fn world(&self, import_options: ImportOptions) -> World {
+        World::new(vec![self.0.clone()], import_options)
+    }
+
+    fn uri(&self, path: &str) -> Url {
+        Url::from_file_path(self.0.join(path)).unwrap()
+    }
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// The position of the first occurrence of `needle` in `text`.
+fn position_of(text: &str, needle: &str) -> Position {
+    let offset = text.find(needle).unwrap();
+    let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    Position::new(
+        text[..offset].matches('\n').count() as u32,
+        (offset - line_start) as u32,
+    )
+}
+
+fn definition_uris(world: &World, uri: &Url, position: Position) -> Vec<Url> {
+    world
+        .goto_definition(uri, position)
+        .into_iter()
+        .map(|location| location.uri)
+        .collect()
+}
+
+fn diagnostic_messages(world: &World, uri: &Url) -> Vec<String> {
+    world
+        .diagnostics(uri)
+        .into_iter()
+        .map(|diagnostic| diagnostic.message)
+        .collect()
+}
+
+const GLOB_MAIN: &str = r#"merge "./types/*.zng";
+
+type crate::Main {
+    #layout(size = 8, align = 8);
+    fn a(&self) -> crate::A;
+    fn b(&self) -> crate::B;
+}
+"#;
+
+const GLOB_B: &str = r#"type crate::B {
+    #layout(size = 1, align = 1);
+    fn main(&self) -> crate::Main;
+}
+"#;
+
+#[test]
+fn glob_merge() {
+    let workspace = Workspace::new(&[
+        ("main.zng", GLOB_MAIN),
+        (
+            "types/a.zng",
+            "type crate::A {\n    #layout(size = 1, align = 1);\n}\n",
+        ),
+        ("types/b.zng", GLOB_B),
+    ]);
+    let world = workspace.world(ImportOptions::new());
+    let main = workspace.uri("main.zng");
+    assert_eq!(
+        definition_uris(&world, &main, position_of(GLOB_MAIN, "./types")),
+        [workspace.uri("types/a.zng"), workspace.uri("types/b.zng")]
+    );
+    assert_eq!(
+        definition_uris(&world, &main, position_of(GLOB_MAIN, "crate::B;")),
+        [workspace.uri("types/b.zng")]
+    );
+    // The merged file sees the declarations of the file merging it.
+    let b = workspace.uri("types/b.zng");
+    assert_eq!(
+        definition_uris(&world, &b, position_of(GLOB_B, "crate::Main")),
+        [workspace.uri("main.zng")]
+    );
+    assert_eq!(diagnostic_messages(&world, &main), Vec::<String>::new());
+}
+
+const PACKAGE_MAIN: &str = r#"merge "@shared/shared.zng";
+merge "@shared/missing.zng";
+
+type crate::Main {
+    #layout(size = 8, align = 8);
+    fn shared(&self) -> crate::Shared;
+}
+"#;
+
+#[test]
+fn package_merge() {
+    let workspace = Workspace::new(&[
+        ("app/main.zng", PACKAGE_MAIN),
+        (
+            "shared/shared.zng",
+            "type crate::Shared {\n    #layout(size = 1, align = 1);\n}\n",
+        ),
+    ]);
+    let world =
+        workspace.world(ImportOptions::new().with_package("shared", workspace.0.join("shared")));
+    let main = workspace.uri("app/main.zng");
+    assert_eq!(
+        definition_uris(&world, &main, position_of(PACKAGE_MAIN, "@shared/shared")),
+        [workspace.uri("shared/shared.zng")]
+    );
+    assert_eq!(
+        definition_uris(&world, &main, position_of(PACKAGE_MAIN, "crate::Shared;")),
+        [workspace.uri("shared/shared.zng")]
+    );
+    assert_eq!(
+        diagnostic_messages(&world, &main),
+        ["Import path not found: @shared/missing.zng"]
+    );
+}
+
+const SPEC_MAIN: &str = r#"merge "./point.zng";
+
+type crate::Main {
+    #layout(size = 8, align = 8);
+    fn point(&self) -> crate::Point;
+}
+"#;
+
+#[test]
+fn spec_diagnostics_of_merged_file() {
+    let workspace = Workspace::new(&[
+        ("main.zng", SPEC_MAIN),
+        (
+            "point.zng",
+            "type crate::Point {\n    #layout(size = 8, align = 4);\n}\n",
+        ),
+    ]);
+    let mut world = workspace.world(ImportOptions::new());
+    let main = workspace.uri("main.zng");
+    let point = workspace.uri("point.zng");
+    assert_eq!(diagnostic_messages(&world, &point), Vec::<String>::new());
+    // The unsaved text is validated in the spec of the file merging it.
+    world.open(
+        &point,
+        "type crate::Point {\n    #layout(size = 8, align = 4);\n    fn name(&self) -> crate::Name;\n    fn name(&self) -> crate::Name;\n}\n".to_owned(),
+    );
+    assert_eq!(
+        diagnostic_messages(&world, &point),
+        [
+            "Type `crate::Name` is not declared",
+            "Duplicate method `name`",
+        ]
+    );
+    assert_eq!(diagnostic_messages(&world, &main), Vec::<String>::new());
+    // Syntax errors are reported once.
+    world.open(&point, "type crate::Point {\n".to_owned());
+    assert_eq!(diagnostic_messages(&world, &point).len(), 1);
+}
+
+#[test]
+fn closed_documents_are_cached_until_changed() {
+    let workspace = Workspace::new(&[
+        ("main.zng", SPEC_MAIN),
+        (
+            "point.zng",
+            "type crate::Point {\n    #layout(size = 8, align = 4);\n}\n",
+        ),
+    ]);
+    let mut world = workspace.world(ImportOptions::new());
+    let main = workspace.uri("main.zng");
+    let point = workspace.uri("point.zng");
+    world.open(&main, SPEC_MAIN.to_owned());
+    assert_eq!(diagnostic_messages(&world, &main), Vec::<String>::new());
+    std::fs::write(
+        workspace.0.join("point.zng"),
+        "type crate::Moved {\n    #layout(size = 8, align = 4);\n}\n",
+    )
+    .unwrap();
+    assert_eq!(diagnostic_messages(&world, &main), Vec::<String>::new());
+    world.file_changed(&point, true);
+    assert_eq!(
+        diagnostic_messages(&world, &main),
+        ["Type `crate::Point` is not declared"]
+    );
+    // The open documents merging a changed file have stale diagnostics.
+    assert_eq!(world.open_documents_merged_with(&point), [main]);
+}
+
+#[test]
+fn notifications() {
+    let workspace = Workspace::new(&[("main.zng", SPEC_MAIN)]);
+    let mut world = workspace.world(ImportOptions::new());
+    let main = workspace.uri("main.zng");
+    let notification = |method: &str, params: serde_json::Value| {
+        lsp_server::Notification::new(method.to_owned(), params)
+    };
+    let open = notification(
+        "textDocument/didOpen",
+        serde_json::json!({
+            "textDocument": { "uri": main, "languageId": "zng", "version": 1, "text": SPEC_MAIN },
+        }),
+    );
+    assert_eq!(
+        crate::handle_notification(&mut world, open).unwrap(),
+        vec![main.clone()]
+    );
+    assert!(world.is_open(&main));
+    // A malformed notification is an error, which doesn't stop the server.
+    let malformed = notification("textDocument/didOpen", serde_json::json!({}));
+    assert!(crate::handle_notification(&mut world, malformed).is_err());
+    let close = notification(
+        "textDocument/didClose",
+        serde_json::json!({ "textDocument": { "uri": main } }),
+    );
+    assert_eq!(
+        crate::handle_notification(&mut world, close).unwrap(),
+        vec![main.clone()]
+    );
+    assert!(!world.is_open(&main));
+}
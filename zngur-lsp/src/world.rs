@@ -0,0 +1,537 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    ops::Range,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use lsp_types::{
+    CompletionItem, Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, Hover,
+    HoverContents, Location, MarkupContent, MarkupKind, Position, Url,
+};
+use zngur_parser::{
+    ImportResolver, ParsedZngFile,
+    cfg::NullCfg,
+    diagnostics::Severity,
+    import::{ImportOptions, ImportSearch, glob_fs},
+    index::{Definition, FileIndex, FileLink, LinkKind, display_path},
+};
+
+use crate::completion;
+
+/// Directories that never contain `.zng` files worth indexing.
+const SKIPPED_DIRS: &[&str] = &["target", "node_modules", ".git"];
+
+pub struct Document {
+    pub path: PathBuf,
+    pub text: String,
+    pub index: FileIndex,
+    line_starts: Vec<usize>,
+}
+
+impl Document {
+    fn new(path: PathBuf, text: String) -> Self {
+        let index = FileIndex::build(&text);
+        let line_starts = [0]
+            .into_iter()
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            path,
+            text,
+            index,
+            line_starts,
+        }
+    }
+
+    fn load(path: &Path) -> Option<Self> {
+        let text = std::fs::read_to_string(path).ok()?;
+        Some(Self::new(path.to_owned(), text))
+    }
+
+    /// Converts a byte offset into an LSP position, which counts UTF-16 code units.
+    fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.text.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let start = self.line_starts[line];
+        let character = self.text[start..offset].encode_utf16().count();
+        Position::new(line as u32, character as u32)
+    }
+
+    fn offset(&self, position: Position) -> usize {
+        let line = position.line as usize;
+        let Some(&start) = self.line_starts.get(line) else {
+            return self.text.len();
+        };
+        let end = self
+            .line_starts
+            .get(line + 1)
+            .copied()
+            .unwrap_or(self.text.len());
+        let mut character = 0;
+        for (i, c) in self.text[start..end].char_indices() {
+            if character >= position.character as usize {
+                return start + i;
+            }
+            character += c.len_utf16();
+        }
+        end
+    }
+
+    fn range(&self, span: Range<usize>) -> lsp_types::Range {
+        lsp_types::Range::new(self.position(span.start), self.position(span.end))
+    }
+
+    fn location(&self, span: Range<usize>) -> Option<Location> {
+        Some(Location::new(
+            Url::from_file_path(&self.path).ok()?,
+            self.range(span),
+        ))
+    }
+}
+
+/// Reads the merged files like the parser, but from the documents of the world, so the unsaved
+/// text of the open documents is used.
+struct WorldDocuments<'a>(&'a World);
+
+impl ImportResolver for WorldDocuments<'_> {
+    fn resolve_import(&self, cwd: &Path, relpath: &Path) -> Result<String, String> {
+        let path = normalize(&cwd.join(relpath));
+        match self.0.document(&path) {
+            Some(doc) => Ok(doc.text.clone()),
+            None => Err(format!("Couldn't read {}", path.display())),
+        }
+    }
+
+    fn glob_import(&self, cwd: &Path, pattern: &Path) -> Result<Vec<PathBuf>, String> {
+        glob_fs(cwd, pattern)
+    }
+}
+
+pub struct World {
+    open: HashMap<PathBuf, Rc<Document>>,
+    /// The documents loaded from the disk, until they are opened or changed on the disk.
+    closed: RefCell<HashMap<PathBuf, Rc<Document>>>,
+    /// Every `.zng` file found in the workspace, used to find the files merging a document.
+    known_files: HashSet<PathBuf>,
+    /// Finds the files of `merge` paths, with the include directories and packages of the client.
+    imports: Rc<ImportSearch>,
+}
+
+impl World {
+    pub fn new(roots: Vec<PathBuf>, import_options: ImportOptions) -> Self {
+        let mut known_files = HashSet::new();
+        for root in &roots {
+            find_zng_files(root, &mut known_files);
+        }
+        let root_dir = roots
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| PathBuf::from("."));
+        Self {
+            open: HashMap::new(),
+            closed: RefCell::default(),
+            known_files,
+            imports: Rc::new(ImportSearch::new(import_options, root_dir)),
+        }
+    }
+
+    pub fn open(&mut self, uri: &Url, text: String) {
+        let Some(path) = uri_to_path(uri) else {
+            return;
+        };
+        self.known_files.insert(path.clone());
+        self.open
+            .insert(path.clone(), Rc::new(Document::new(path, text)));
+    }
+
+    pub fn close(&mut self, uri: &Url) {
+        if let Some(path) = uri_to_path(uri) {
+            self.open.remove(&path);
+            // The file may have been changed without saving it.
+            self.closed.get_mut().remove(&path);
+        }
+    }
+
+    pub fn is_open(&self, uri: &Url) -> bool {
+        uri_to_path(uri).is_some_and(|path| self.open.contains_key(&path))
+    }
+
+    /// Forgets the text of a file loaded from the disk, after it is changed, created or deleted
+    /// there.
+    pub fn file_changed(&mut self, uri: &Url, exists: bool) {
+        let Some(path) = uri_to_path(uri) else {
+            return;
+        };
+        self.closed.get_mut().remove(&path);
+        if exists {
+            self.known_files.insert(path);
+        } else if !self.open.contains_key(&path) {
+            self.known_files.remove(&path);
+        }
+    }
+
+    /// The open documents whose spec includes the given document, including itself, whose
+    /// diagnostics change with it.
+    pub fn open_documents_merged_with(&self, uri: &Url) -> Vec<Url> {
+        let Some(doc) = self.document_for_uri(uri) else {
+            return vec![];
+        };
+        self.merged_documents(&doc)
+            .into_iter()
+            .filter(|other| self.open.contains_key(&other.path))
+            .filter_map(|other| Url::from_file_path(&other.path).ok())
+            .collect()
+    }
+
+    fn document(&self, path: &Path) -> Option<Rc<Document>> {
+        if let Some(doc) = self.open.get(path) {
+            return Some(doc.clone());
+        }
+        if let Some(doc) = self.closed.borrow().get(path) {
+            return Some(doc.clone());
+        }
+        let doc = Rc::new(Document::load(path)?);
+        self.closed
+            .borrow_mut()
+            .insert(path.to_owned(), doc.clone());
+        Some(doc)
+    }
+
+    fn document_for_uri(&self, uri: &Url) -> Option<Rc<Document>> {
+        self.document(&uri_to_path(uri)?)
+    }
+
+    /// The files targeted by a `merge` or `import` statement of a document. `merge` paths are
+    /// resolved like the parser does, so they can be globs, or be in an include directory or a
+    /// package.
+    fn link_targets(&self, doc: &Document, link: &FileLink) -> Result<Vec<PathBuf>, String> {
+        let dir = doc.path.parent().unwrap_or(Path::new("."));
+        match link.kind {
+            LinkKind::Merge => Ok(self
+                .imports
+                .resolve(&WorldDocuments(self), dir, &link.path)?
+                .into_iter()
+                .map(|file| normalize(&file.path))
+                .collect()),
+            LinkKind::Import => {
+                let path = dir.join(&link.path);
+                if !path.exists() {
+                    return Err(format!("Import path not found: {}", link.path.display()));
+                }
+                Ok(vec![normalize(&path)])
+            }
+        }
+    }
+
+    /// The files targeted by a document's `merge` or `import` statements.
+    fn linked_files(&self, doc: &Document, kind: LinkKind) -> Vec<PathBuf> {
+        doc.index
+            .links
+            .iter()
+            .filter(|link| link.kind == kind)
+            .flat_map(|link| self.link_targets(doc, link).unwrap_or_default())
+            .collect()
+    }
+
+    /// The documents whose declarations are merged with the given one into a single spec.
+    ///
+    /// This follows `merge` statements in both directions, so a merged file can see the
+    /// declarations of the file that merges it.
+    fn merged_documents(&self, doc: &Rc<Document>) -> Vec<Rc<Document>> {
+        let mut merged_by: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+        for path in &self.known_files {
+            if let Some(other) = self.document(path) {
+                for target in self.linked_files(&other, LinkKind::Merge) {
+                    merged_by.entry(target).or_default().push(path.clone());
+                }
+            }
+        }
+        let mut seen = HashSet::from([doc.path.clone()]);
+        let mut result = vec![doc.clone()];
+        let mut queue = vec![doc.clone()];
+        while let Some(current) = queue.pop() {
+            let neighbors = self
+                .linked_files(&current, LinkKind::Merge)
+                .into_iter()
+                .chain(merged_by.get(&current.path).into_iter().flatten().cloned())
+                .collect::<Vec<_>>();
+            for path in neighbors {
+                if seen.insert(path.clone()) {
+                    if let Some(next) = self.document(&path) {
+                        result.push(next.clone());
+                        queue.push(next);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// A document and everything it merges, transitively.
+    fn forward_merges(&self, doc: Rc<Document>) -> Vec<Rc<Document>> {
+        let mut seen = HashSet::from([doc.path.clone()]);
+        let mut result = vec![doc];
+        let mut i = 0;
+        while i < result.len() {
+            for path in self.linked_files(&result[i], LinkKind::Merge) {
+                if seen.insert(path.clone()) {
+                    result.extend(self.document(&path));
+                }
+            }
+            i += 1;
+        }
+        result
+    }
+
+    /// Finds the declarations of a resolved path visible from a document.
+    fn find_definitions(
+        &self,
+        doc: &Rc<Document>,
+        path: &[String],
+    ) -> Vec<(Rc<Document>, Definition)> {
+        let merged = self.merged_documents(doc);
+        let mut result = vec![];
+        for other in &merged {
+            for def in other.index.definitions_of(path) {
+                result.push((other.clone(), def.clone()));
+            }
+        }
+        // Items of an imported module are declared relative to `crate` there, but are
+        // referred to through the crate name of that module here.
+        if path.len() > 1 && path[0] != "crate" {
+            let crate_path = ["crate".to_owned()]
+                .into_iter()
+                .chain(path[1..].iter().cloned())
+                .collect::<Vec<_>>();
+            let imported = merged
+                .iter()
+                .flat_map(|doc| self.linked_files(doc, LinkKind::Import))
+                .collect::<HashSet<_>>();
+            for module in imported.into_iter().filter_map(|path| self.document(&path)) {
+                for other in self.forward_merges(module) {
+                    for def in other.index.definitions_of(&crate_path) {
+                        result.push((other.clone(), def.clone()));
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    pub fn goto_definition(&self, uri: &Url, position: Position) -> Vec<Location> {
+        let Some(doc) = self.document_for_uri(uri) else {
+            return vec![];
+        };
+        let offset = doc.offset(position);
+        if let Some(link) = doc.index.link_at(offset) {
+            return self
+                .link_targets(&doc, link)
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|target| Url::from_file_path(target).ok())
+                .map(|uri| Location::new(uri, lsp_types::Range::default()))
+                .collect();
+        }
+        let Some(reference) = doc.index.reference_at(offset) else {
+            return vec![];
+        };
+        self.find_definitions(&doc, &reference.path)
+            .into_iter()
+            .filter_map(|(other, def)| other.location(def.span))
+            .chain(reference.alias.clone().and_then(|span| doc.location(span)))
+            .collect()
+    }
+
+    pub fn hover(&self, uri: &Url, position: Position) -> Option<Hover> {
+        let doc = self.document_for_uri(uri)?;
+        let offset = doc.offset(position);
+        let (span, sections) = if let Some(link) = doc.index.link_at(offset) {
+            let targets = match self.link_targets(&doc, link) {
+                Ok(targets) => targets
+                    .iter()
+                    .map(|target| format!("`{}`", target.display()))
+                    .collect::<Vec<_>>()
+                    .join("\n\n"),
+                Err(e) => e,
+            };
+            (link.span.clone(), vec![targets])
+        } else if let Some(reference) = doc.index.reference_at(offset) {
+            let mut sections = vec![code_block(&display_path(&reference.path))];
+            if let Some((other, def)) = self.find_definitions(&doc, &reference.path).first() {
+                sections.push(definition_markdown(
+                    def,
+                    (other.path != doc.path).then_some(&other.path),
+                ));
+            }
+            (reference.span.clone(), sections)
+        } else {
+            let def = doc.index.definition_at(offset)?;
+            (def.span.clone(), vec![definition_markdown(def, None)])
+        };
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: sections.join("\n\n---\n\n"),
+            }),
+            range: Some(doc.range(span)),
+        })
+    }
+
+    pub fn completions(&self, uri: &Url, position: Position) -> Option<Vec<CompletionItem>> {
+        let doc = self.document_for_uri(uri)?;
+        let offset = doc.offset(position);
+        Some(completion::completions(&doc.text[..offset]))
+    }
+
+    pub fn diagnostics(&self, uri: &Url) -> Vec<Diagnostic> {
+        let Some(doc) = self.document_for_uri(uri) else {
+            return vec![];
+        };
+        let syntax_errors = doc.index.errors.iter().map(|error| Diagnostic {
+            range: doc.range(error.span.clone()),
+            severity: Some(DiagnosticSeverity::ERROR),
+            source: Some("zngur".to_owned()),
+            message: error.message.clone(),
+            ..Default::default()
+        });
+        let missing_files = doc.index.links.iter().filter_map(|link| {
+            let message = self.link_targets(&doc, link).err()?;
+            Some(Diagnostic {
+                range: doc.range(link.span.clone()),
+                severity: Some(DiagnosticSeverity::ERROR),
+                source: Some("zngur".to_owned()),
+                message,
+                ..Default::default()
+            })
+        });
+        let mut result = syntax_errors.chain(missing_files).collect::<Vec<_>>();
+        for diagnostic in self.spec_diagnostics(&doc) {
+            // The parser reports the syntax errors too, and the merges it can't resolve at the
+            // start of the file instead of at their statement.
+            let duplicate = result.iter().any(|other| {
+                other.message == diagnostic.message
+                    && (other.range == diagnostic.range
+                        || diagnostic.range == lsp_types::Range::default())
+            });
+            if !duplicate {
+                result.push(diagnostic);
+            }
+        }
+        result
+    }
+
+    /// The errors in a document found by parsing and validating the specs it is part of, which
+    /// are the specs of the files which merge it, or of the document itself.
+    fn spec_diagnostics(&self, doc: &Rc<Document>) -> Vec<Diagnostic> {
+        let merged = self.merged_documents(doc);
+        let merged_targets = merged
+            .iter()
+            .flat_map(|other| self.linked_files(other, LinkKind::Merge))
+            .collect::<HashSet<_>>();
+        let mut roots = merged
+            .iter()
+            .filter(|other| !merged_targets.contains(&other.path))
+            .collect::<Vec<_>>();
+        // Files merging each other have no top-level file.
+        if roots.is_empty() {
+            roots.push(doc);
+        }
+        let mut result = vec![];
+        for root in roots {
+            let parsed = ParsedZngFile::try_parse_text_with_resolver(
+                root.path.clone(),
+                &root.text,
+                Box::new(NullCfg),
+                &WorldDocuments(self),
+                self.imports.clone(),
+            );
+            let Err(diagnostics) = parsed.and_then(|parsed| parsed.validate()) else {
+                continue;
+            };
+            for diagnostic in diagnostics.iter() {
+                if normalize(&diagnostic.path) != doc.path {
+                    continue;
+                }
+                let related_information = diagnostic
+                    .labels
+                    .iter()
+                    .filter(|label| {
+                        label.message != diagnostic.message && normalize(&label.path) == doc.path
+                    })
+                    .filter_map(|label| {
+                        Some(DiagnosticRelatedInformation {
+                            location: doc.location(label.span.clone())?,
+                            message: label.message.clone(),
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                let diagnostic = Diagnostic {
+                    range: doc.range(diagnostic.span.clone()),
+                    severity: Some(match diagnostic.severity {
+                        Severity::Error => DiagnosticSeverity::ERROR,
+                        Severity::Warning => DiagnosticSeverity::WARNING,
+                        Severity::Note => DiagnosticSeverity::INFORMATION,
+                    }),
+                    source: Some("zngur".to_owned()),
+                    message: diagnostic.message.clone(),
+                    related_information: (!related_information.is_empty())
+                        .then_some(related_information),
+                    ..Default::default()
+                };
+                if !result.contains(&diagnostic) {
+                    result.push(diagnostic);
+                }
+            }
+        }
+        result
+    }
+}
+
+fn definition_markdown(def: &Definition, declared_in: Option<&PathBuf>) -> String {
+    let mut text = def.label.clone();
+    if let Some(detail) = &def.detail {
+        text = format!("{text}\n{detail}");
+    }
+    let mut result = code_block(&text);
+    if let Some(path) = declared_in {
+        result += &format!("\n\nDeclared in `{}`", path.display());
+    }
+    result
+}
+
+fn code_block(text: &str) -> String {
+    format!("```zng\n{text}\n```")
+}
+
+fn uri_to_path(uri: &Url) -> Option<PathBuf> {
+    uri.to_file_path().ok().map(|path| normalize(&path))
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_owned())
+}
+
+fn find_zng_files(dir: &Path, result: &mut HashSet<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            let skipped = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| SKIPPED_DIRS.contains(&name));
+            if !skipped {
+                find_zng_files(&path, result);
+            }
+        } else if path.extension().is_some_and(|ext| ext == "zng") {
+            result.insert(normalize(&path));
+        }
+    }
+}
@@ -0,0 +1,233 @@
+//! Procedural macros of Zngur. Use them through the `zngur` crate, like `zngur::include_zng!`.
+
+use std::{cell::Cell, path::PathBuf, rc::Rc};
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{
+    Ident, LitStr, Token,
+    parse::{Parse, ParseStream},
+    parse_macro_input,
+};
+use zngur_generator::{
+    LayoutPolicy, ParsedZngFile, ZngurGenerator, cfg::RustCfgProvider, diagnostics::Diagnostics,
+    export::check_export_attribute, import::ImportOptions,
+};
+
+/// The arguments of [`include_zng!`].
+struct IncludeZng {
+    path: LitStr,
+    crate_name: Option<LitStr>,
+    mangling_base: Option<LitStr>,
+    cpp_namespace: Option<LitStr>,
+    exports: Option<LitStr>,
+}
+
+impl Parse for IncludeZng {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut args = IncludeZng {
+            path: input.parse()?,
+            crate_name: None,
+            mangling_base: None,
+            cpp_namespace: None,
+            exports: None,
+        };
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            let key: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            let value: LitStr = input.parse()?;
+            let arg = match key.to_string().as_str() {
+                "crate_name" => &mut args.crate_name,
+                "mangling_base" => &mut args.mangling_base,
+                "cpp_namespace" => &mut args.cpp_namespace,
+                "exports" => &mut args.exports,
+                _ => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        "Unknown argument. Expected `crate_name`, `mangling_base`, `cpp_namespace` or `exports`",
+                    ));
+                }
+            };
+            if arg.replace(value).is_some() {
+                return Err(syn::Error::new(key.span(), "Duplicate argument"));
+            }
+        }
+        Ok(args)
+    }
+}
+
+/// An empty cfg, which records if the zng file asks for a cfg value. Proc macros don't see the
+/// cfg of the crate, so such a zng file would silently take the wrong branches.
+#[derive(Clone, Default)]
+struct UnknownCfg(Rc<Cell<bool>>);
+
+impl RustCfgProvider for UnknownCfg {
+    fn get_cfg(&self, _key: &str) -> Option<Vec<String>> {
+        self.0.set(true);
+        None
+    }
+    fn get_features(&self) -> Vec<String> {
+        self.0.set(true);
+        Vec::new()
+    }
+    fn get_cfg_pairs(&self) -> Vec<(String, Option<String>)> {
+        Vec::new()
+    }
+}
+
+/// Generates the Rust side of a zng file at compile time, in place of
+/// `include!(concat!(env!("OUT_DIR"), "/generated.rs"))`.
+///
+/// The path is relative to the directory of `Cargo.toml`. The errors in the zng file are reported
+/// as compile errors, and the crate is rebuilt when the zng file or its merged files change.
+///
+/// ```ignore
+/// mod generated {
+///     zngur::include_zng!("main.zng");
+/// }
+/// ```
+///
+/// The C++ header and source still need a build script, which should pass the same options to
+/// `Zngur` and can leave out `with_rs_file`. These options are supported, with the same meaning
+/// as the methods of `Zngur`:
+///
+/// ```ignore
+/// zngur::include_zng!("main.zng", crate_name = "crate", cpp_namespace = "my_lib");
+/// ```
+///
+/// `exports = "src/lib.rs"` is the same as `Zngur::with_exports_from`, and merges the items
+/// marked with [`macro@export`] in the crate with this root file into the spec.
+///
+/// The macro doesn't know the cfg of the crate, so zng files which depend on it, like with
+/// `#if cfg!(...)`, and `#layout(auto)` are not supported.
+#[proc_macro]
+pub fn include_zng(input: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(input as IncludeZng);
+    match args.expand() {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+impl IncludeZng {
+    fn expand(&self) -> syn::Result<proc_macro2::TokenStream> {
+        let error = |diagnostics: Diagnostics| {
+            syn::Error::new(self.path.span(), diagnostics.to_string().trim_end())
+        };
+        let crate_dir = std::env::var_os("CARGO_MANIFEST_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_default();
+        let cfg = UnknownCfg::default();
+        let parse_result = ParsedZngFile::try_parse_with_options(
+            crate_dir.join(self.path.value()),
+            Box::new(cfg.clone()),
+            ImportOptions::default(),
+        );
+        // The errors of a spec which depends on the cfg may come from the wrong branches.
+        if cfg.0.get() {
+            return Err(syn::Error::new(
+                self.path.span(),
+                "The zng file depends on the cfg of the crate, which is not supported in `include_zng!`. \
+Generate the Rust code in a build script with `Zngur::with_rust_cargo_cfg` instead",
+            ));
+        }
+        let mut parse_result = parse_result.map_err(error)?;
+        if let Some(exports) = &self.exports {
+            parse_result
+                .merge_exports(crate_dir.join(exports.value()))
+                .map_err(error)?;
+        }
+        parse_result.validate().map_err(error)?;
+        if let Some(ty) = parse_result
+            .spec
+            .types
+            .iter()
+            .find(|ty| ty.layout == LayoutPolicy::Auto)
+        {
+            return Err(error(parse_result.type_error(
+                &ty.ty,
+                "`#layout(auto)` is not supported in `include_zng!`",
+                "Generate the Rust code in a build script with `Zngur::with_layout_probe` to compute the layout of this type",
+            )));
+        }
+
+        let crate_name = self
+            .crate_name
+            .as_ref()
+            .map(LitStr::value)
+            .or_else(|| std::env::var("CARGO_PKG_NAME").ok())
+            .unwrap_or_else(|| "crate".to_owned());
+        // Referencing the zng files makes cargo rebuild the crate when they change.
+        let zng_files = parse_result
+            .processed_files
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>();
+        let mut file = ZngurGenerator::build_from_zng(parse_result.spec, crate_name);
+        if let Some(cpp_namespace) = &self.cpp_namespace {
+            file.0.mangling_base = cpp_namespace.value();
+            file.0.cpp_namespace = Some(cpp_namespace.value());
+        }
+        if let Some(mangling_base) = self
+            .mangling_base
+            .as_ref()
+            .map(LitStr::value)
+            .or_else(|| file.0.cpp_namespace.clone())
+        {
+            file.0.mangling_base = mangling_base;
+        }
+        let (rust, _, _) = file.render(false);
+        let rust: proc_macro2::TokenStream = rust.parse().map_err(|e| {
+            syn::Error::new(
+                self.path.span(),
+                format!("The generated Rust code is not valid: {e}"),
+            )
+        })?;
+        Ok(quote! {
+            #(const _: &[u8] = include_bytes!(#zng_files);)*
+            #rust
+        })
+    }
+}
+
+/// Exports a struct, an enum, an `impl` block or a free function to C++, without declaring it in
+/// the zng file.
+///
+/// The attribute leaves the item as it is. The build script finds the exported items by scanning
+/// the sources of the crate, with `Zngur::with_exports_from`, and merges them into the spec of
+/// the zng file:
+///
+/// ```ignore
+/// #[zngur::export(layout(size = 16, align = 8))]
+/// #[derive(Debug, Clone, Copy)]
+/// pub struct Point {
+///     pub x: f64,
+///     pub y: f64,
+/// }
+///
+/// #[zngur::export]
+/// impl Point {
+///     pub fn norm(&self) -> f64 {
+///         (self.x * self.x + self.y * self.y).sqrt()
+///     }
+/// }
+/// ```
+///
+/// Structs and enums are `#heap_allocated` by default, and take the layout policy as an argument,
+/// like `layout(size = X, align = Y)`, `layout_conservative(size = X, align = Y)` or
+/// `only_by_ref`. The other items take no arguments.
+#[proc_macro_attribute]
+pub fn export(args: TokenStream, item: TokenStream) -> TokenStream {
+    let parsed = parse_macro_input!(item as syn::Item);
+    match check_export_attribute(args.into(), &parsed) {
+        Ok(()) => quote!(#parsed).into(),
+        Err(e) => {
+            let error = e.to_compile_error();
+            quote!(#error #parsed).into()
+        }
+    }
+}
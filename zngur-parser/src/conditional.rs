@@ -348,6 +348,42 @@ impl<Scrutinee: Matchable, Item: BodyItem, Cardinality: ConditionBodyCardinality
     }
 }
 
+impl<Scrutinee: Matchable, Item: BodyItem> Condition<Scrutinee, Item, NItems> {
+    /// Items of every branch, regardless of which branch the active cfg would select
+    pub(crate) fn all_branch_items(&self) -> Vec<&Spanned<Item>> {
+        match self {
+            Self::If(item) => item
+                .arms
+                .iter()
+                .flat_map(|arm| arm.block.iter())
+                .chain(item.fallback.iter().flatten())
+                .collect(),
+            Self::Match(item) => item
+                .arms
+                .iter()
+                .flat_map(|arm| arm.inner.block.iter())
+                .collect(),
+        }
+    }
+
+    /// The blocks of every branch, regardless of which branch the active cfg would select
+    pub(crate) fn all_branch_blocks_mut(&mut self) -> Vec<&mut Vec<Spanned<Item>>> {
+        match self {
+            Self::If(item) => item
+                .arms
+                .iter_mut()
+                .map(|arm| &mut arm.block)
+                .chain(item.fallback.as_mut())
+                .collect(),
+            Self::Match(item) => item
+                .arms
+                .iter_mut()
+                .map(|arm| &mut arm.inner.block)
+                .collect(),
+        }
+    }
+}
+
 /// a trait that helps build combined parsers for ConditionalItem's that accept `#if {} #else {}` or `#match`
 pub trait Conditional<'src, Item: BodyItem, Cardinality: ConditionBodyCardinality<Item>> {
     type Scrutinee: MatchableParse<'src>;
@@ -0,0 +1,179 @@
+//! Structured errors produced while parsing and processing .zng files.
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use ariadne::{Color, Config, Label, Report, ReportKind, sources};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+/// A span in a .zng file annotated with a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiagnosticLabel {
+    pub path: PathBuf,
+    pub span: Range<usize>,
+    pub message: String,
+}
+
+/// A single problem found in a .zng file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// The file the diagnostic is reported in.
+    pub path: PathBuf,
+    /// Byte range of the diagnostic in the file.
+    pub span: Range<usize>,
+    pub labels: Vec<DiagnosticLabel>,
+}
+
+impl Diagnostic {
+    pub fn new(
+        severity: Severity,
+        path: impl Into<PathBuf>,
+        span: Range<usize>,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            severity,
+            message: message.into(),
+            path: path.into(),
+            span,
+            labels: vec![],
+        }
+    }
+
+    pub fn error(path: impl Into<PathBuf>, span: Range<usize>, message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, path, span, message)
+    }
+
+    pub fn with_label(
+        mut self,
+        path: impl Into<PathBuf>,
+        span: Range<usize>,
+        message: impl Into<String>,
+    ) -> Self {
+        self.labels.push(DiagnosticLabel {
+            path: path.into(),
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    fn to_report(&self, config: Config) -> Report<'static, (String, Range<usize>)> {
+        let kind = match self.severity {
+            Severity::Error => ReportKind::Error,
+            Severity::Warning => ReportKind::Warning,
+            Severity::Note => ReportKind::Custom("Note", Color::Green),
+        };
+        Report::build(kind, source_id(&self.path), self.span.start)
+            .with_config(config)
+            .with_message(&self.message)
+            .with_labels(self.labels.iter().enumerate().map(|(i, label)| {
+                Label::new((source_id(&label.path), label.span.clone()))
+                    .with_message(&label.message)
+                    .with_color(if i == 0 { Color::Red } else { Color::Yellow })
+            }))
+            .finish()
+    }
+}
+
+/// The diagnostics of a failed parse, together with the text of the files they point into.
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    diagnostics: Vec<Diagnostic>,
+    sources: HashMap<PathBuf, String>,
+}
+
+impl Diagnostics {
+    pub(crate) fn new(diagnostics: Vec<Diagnostic>, sources: HashMap<PathBuf, String>) -> Self {
+        Self {
+            diagnostics,
+            sources,
+        }
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Diagnostic> {
+        self.diagnostics.iter()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.iter().any(|d| d.severity == Severity::Error)
+    }
+
+    /// The text of a file referenced by the diagnostics.
+    pub fn source(&self, path: &Path) -> Option<&str> {
+        self.sources.get(path).map(String::as_str)
+    }
+
+    /// Renders the diagnostics as ariadne reports.
+    pub fn write(&self, mut w: impl std::io::Write) -> std::io::Result<()> {
+        self.write_with_config(Config::default(), &mut w)
+    }
+
+    /// Renders the diagnostics as ariadne reports to stderr.
+    pub fn eprint(&self) {
+        self.write(std::io::stderr()).unwrap();
+    }
+
+    fn write_with_config(&self, config: Config, mut w: impl std::io::Write) -> std::io::Result<()> {
+        for diagnostic in &self.diagnostics {
+            diagnostic.to_report(config).write(
+                sources(
+                    self.sources
+                        .iter()
+                        .map(|(path, text)| (source_id(path), text.as_str())),
+                ),
+                &mut w,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> IntoIterator for &'a Diagnostics {
+    type Item = &'a Diagnostic;
+    type IntoIter = std::slice::Iter<'a, Diagnostic>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for Diagnostics {
+    type Item = Diagnostic;
+    type IntoIter = std::vec::IntoIter<Diagnostic>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.diagnostics.into_iter()
+    }
+}
+
+impl Display for Diagnostics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut r = Vec::<u8>::new();
+        self.write_with_config(Config::default().with_color(false), &mut r)
+            .map_err(|_| std::fmt::Error)?;
+        f.write_str(&String::from_utf8_lossy(&r))
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+/// Reports identify files by their file name.
+fn source_id(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
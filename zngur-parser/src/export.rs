@@ -0,0 +1,1268 @@
+//! Collecting the items marked with `#[zngur::export]` from the sources of a crate, and merging
+//! them into the spec.
+//!
+//! The attribute itself leaves the items as they are. Build scripts run before their crate is
+//! compiled, so there is no compiled output to collect the signatures from. Instead, the sources
+//! are scanned from the crate root, following the `mod` items like rustc does, and the signatures
+//! are read from the syntax of the items.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use proc_macro2::TokenStream;
+use syn::{
+    Attribute, Expr, ExprLit, Fields, FnArg, GenericParam, Generics, ImplItem, Item, ItemEnum,
+    ItemFn, ItemImpl, ItemMod, ItemStruct, Lit, LitInt, Meta, PathArguments, ReturnType, Signature,
+    Type, TypeParamBound, UseTree, Visibility, parse::Parser, punctuated::Punctuated,
+    spanned::Spanned, token::Plus,
+};
+use zngur_def::{
+    LayoutPolicy, Merge, MergeFailure, Mutability, PrimitiveRustType, RustPathAndGenerics,
+    RustTrait, RustType, ZngurConstructor, ZngurFn, ZngurMethod, ZngurMethodDetails,
+    ZngurMethodReceiver, ZngurType, ZngurVariant, ZngurWellknownTrait,
+};
+
+use crate::{
+    ParseResult,
+    diagnostics::{Diagnostic, Diagnostics},
+    validate::Location,
+};
+
+/// The names which are in scope in every module, and their full paths.
+const PRELUDE: &[(&str, &[&str])] = &[
+    ("String", &["std", "string", "String"]),
+    ("Vec", &["std", "vec", "Vec"]),
+    ("Option", &["std", "option", "Option"]),
+    ("Result", &["std", "result", "Result"]),
+    ("Box", &["std", "boxed", "Box"]),
+    ("Iterator", &["std", "iter", "Iterator"]),
+    ("IntoIterator", &["std", "iter", "IntoIterator"]),
+    ("Clone", &["std", "clone", "Clone"]),
+    ("Default", &["std", "default", "Default"]),
+    ("ToString", &["std", "string", "ToString"]),
+    ("AsRef", &["std", "convert", "AsRef"]),
+    ("AsMut", &["std", "convert", "AsMut"]),
+    ("From", &["std", "convert", "From"]),
+    ("Into", &["std", "convert", "Into"]),
+];
+
+const UNSUPPORTED_ITEM: &str =
+    "`#[zngur::export]` only supports structs, enums, impl blocks and free functions";
+
+impl ParseResult {
+    /// Merges the items marked with `#[zngur::export]` into the spec. `crate_root` is the root
+    /// file of the crate, like `src/lib.rs`, and the files of its modules are found from there.
+    ///
+    /// The scanned files are added to [`Self::processed_files`], since the spec depends on them.
+    pub fn merge_exports(&mut self, crate_root: impl AsRef<Path>) -> Result<(), Diagnostics> {
+        let crate_root = crate_root.as_ref();
+        let mut scanner = Scanner::default();
+        scanner.scan_file(
+            crate_root,
+            vec!["crate".to_owned()],
+            crate_root.parent().unwrap_or(Path::new("")).to_owned(),
+            true,
+            None,
+        );
+        let exports = scanner.exports();
+        let Scanner {
+            files,
+            mut diagnostics,
+            ..
+        } = scanner;
+        for (path, text) in files {
+            self.processed_files.push(path.clone());
+            self.sources.insert(path, text);
+        }
+        if diagnostics.is_empty() {
+            diagnostics = self.merge_exported_items(exports);
+        }
+        if diagnostics.is_empty() {
+            Ok(())
+        } else {
+            Err(Diagnostics::new(diagnostics, self.sources.clone()))
+        }
+    }
+
+    fn merge_exported_items(&mut self, exports: Exports) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+        // The types go first, so the impl blocks can find them.
+        for (ty, location) in exports.types {
+            self.locations.add_type(&ty.ty, location.clone());
+            for constructor in &ty.constructors {
+                for (_, input) in &constructor.inputs {
+                    self.locations.add_use(input.clone(), location.clone());
+                }
+            }
+            for variant in &ty.variants {
+                for (_, field) in &variant.fields {
+                    self.locations.add_use(field.clone(), location.clone());
+                }
+            }
+            let name = ty.ty.clone();
+            if let Err(MergeFailure::Conflict(message)) = ty.merge(&mut self.spec) {
+                diagnostics.push(location.error(
+                    message,
+                    format!("`{name}` is declared differently in the zng file"),
+                ));
+            }
+        }
+        for exported_impl in exports.impls {
+            let ty = exported_impl.ty;
+            let Some(layout) = self
+                .spec
+                .types
+                .iter()
+                .find(|t| t.ty == ty)
+                .map(|t| t.layout)
+            else {
+                diagnostics.push(exported_impl.location.error(
+                    format!("Type `{ty}` is not declared"),
+                    "Export it with `#[zngur::export]`, or declare it in the zng file",
+                ));
+                continue;
+            };
+            for (method, location) in exported_impl.methods {
+                self.locations
+                    .add_method(&ty, &method.data, location.clone());
+                let name = method.data.name.clone();
+                let methods_only = ZngurType {
+                    ty: ty.clone(),
+                    layout,
+                    wellknown_traits: vec![],
+                    methods: vec![method],
+                    constructors: vec![],
+                    fields: vec![],
+                    variants: vec![],
+                    cpp_value: None,
+                    cpp_ref: None,
+                    cpp_stack_owned: None,
+                    doc: None,
+                    cpp_name: None,
+                };
+                if let Err(MergeFailure::Conflict(message)) = methods_only.merge(&mut self.spec) {
+                    diagnostics.push(location.error(
+                        message,
+                        format!("`{name}` is declared differently in the zng file"),
+                    ));
+                }
+            }
+        }
+        for (func, location) in exports.funcs {
+            for ty in func.inputs.iter().chain([&func.output]) {
+                self.locations.add_use(ty.clone(), location.clone());
+            }
+            let name = RustType::Adt(func.path.clone());
+            if let Err(MergeFailure::Conflict(message)) = func.merge(&mut self.spec) {
+                diagnostics.push(location.error(
+                    message,
+                    format!("`{name}` is declared differently in the zng file"),
+                ));
+            }
+        }
+        diagnostics
+    }
+}
+
+/// Checks the arguments of `#[zngur::export]` and the kind of the item it is attached to, for the
+/// attribute macro. The rest of the item is checked when the crate is scanned.
+pub fn check_export_attribute(args: TokenStream, item: &Item) -> syn::Result<()> {
+    let layout = export_layout(args.clone())?;
+    match item {
+        Item::Struct(_) | Item::Enum(_) => Ok(()),
+        Item::Impl(_) | Item::Fn(_) if layout.is_none() => Ok(()),
+        Item::Impl(_) | Item::Fn(_) => Err(syn::Error::new_spanned(
+            args,
+            "Only exported structs and enums have a layout",
+        )),
+        _ => Err(syn::Error::new_spanned(item, UNSUPPORTED_ITEM)),
+    }
+}
+
+/// The layout policy in the arguments of `#[zngur::export(...)]`, if there is one.
+fn export_layout(args: TokenStream) -> syn::Result<Option<LayoutPolicy>> {
+    let mut layout = None;
+    let parser = syn::meta::parser(|meta| {
+        let policy = if meta.path.is_ident("layout") || meta.path.is_ident("layout_conservative") {
+            let (mut size, mut align) = (None, None);
+            meta.parse_nested_meta(|prop| {
+                let value = prop.value()?.parse::<LitInt>()?.base10_parse::<usize>()?;
+                if prop.path.is_ident("size") {
+                    size = Some(value);
+                } else if prop.path.is_ident("align") {
+                    align = Some(value);
+                } else {
+                    return Err(prop.error("Expected `size` or `align`"));
+                }
+                Ok(())
+            })?;
+            let (Some(size), Some(align)) = (size, align) else {
+                return Err(meta.error("Both `size` and `align` are needed"));
+            };
+            if meta.path.is_ident("layout") {
+                LayoutPolicy::StackAllocated { size, align }
+            } else {
+                LayoutPolicy::Conservative { size, align }
+            }
+        } else if meta.path.is_ident("heap_allocate") {
+            LayoutPolicy::HeapAllocated
+        } else if meta.path.is_ident("only_by_ref") {
+            LayoutPolicy::OnlyByRef
+        } else {
+            return Err(meta.error(
+                "Unknown argument. Expected `layout(size = X, align = Y)`, `layout_conservative(size = X, align = Y)`, `heap_allocate` or `only_by_ref`",
+            ));
+        };
+        if layout.replace(policy).is_some() {
+            return Err(meta.error("Duplicate layout policy found"));
+        }
+        Ok(())
+    });
+    parser.parse2(args)?;
+    Ok(layout)
+}
+
+/// An error in an exported item.
+struct ExportError {
+    span: proc_macro2::Span,
+    message: String,
+    label: String,
+}
+
+fn error(
+    span: proc_macro2::Span,
+    message: impl Into<String>,
+    label: impl Into<String>,
+) -> ExportError {
+    ExportError {
+        span,
+        message: message.into(),
+        label: label.into(),
+    }
+}
+
+impl From<syn::Error> for ExportError {
+    fn from(e: syn::Error) -> Self {
+        error(e.span(), e.to_string(), e.to_string())
+    }
+}
+
+/// An `impl` block marked with `#[zngur::export]`.
+struct ExportedImpl {
+    ty: RustType,
+    location: Location,
+    methods: Vec<(ZngurMethodDetails, Location)>,
+}
+
+/// The items marked with `#[zngur::export]`, with the place they are declared in.
+#[derive(Default)]
+struct Exports {
+    types: Vec<(ZngurType, Location)>,
+    impls: Vec<ExportedImpl>,
+    funcs: Vec<(ZngurFn, Location)>,
+}
+
+#[derive(Default)]
+struct Scanner {
+    /// The modules of the crate, and their items.
+    modules: Vec<(Module, Vec<Item>)>,
+    /// The scanned files and their text.
+    files: Vec<(PathBuf, String)>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Scanner {
+    /// Scans a module file. `dir` is where the files of its child modules are.
+    fn scan_file(
+        &mut self,
+        file: &Path,
+        path: Vec<String>,
+        dir: PathBuf,
+        reachable: bool,
+        declaration: Option<Location>,
+    ) {
+        let text = match std::fs::read_to_string(file) {
+            Ok(text) => text,
+            Err(e) => {
+                let message = format!("Can't read `{}`: {e}", file.display());
+                self.diagnostics.push(match declaration {
+                    Some(location) => location.error(message, "The module is declared here"),
+                    None => {
+                        self.files.push((file.to_owned(), String::new()));
+                        Diagnostic::error(file, 0..0, message)
+                    }
+                });
+                return;
+            }
+        };
+        let parsed = syn::parse_file(&text);
+        self.files.push((file.to_owned(), text));
+        match parsed {
+            Ok(parsed) => self.scan_items(parsed.items, path, &dir, reachable, file),
+            Err(e) => self.add_error(file, e.into()),
+        }
+    }
+
+    fn scan_items(
+        &mut self,
+        items: Vec<Item>,
+        path: Vec<String>,
+        dir: &Path,
+        reachable: bool,
+        file: &Path,
+    ) {
+        let mut module = Module {
+            path,
+            file: file.to_owned(),
+            reachable,
+            names: HashMap::new(),
+        };
+        for item in &items {
+            if let Some(name) = item_name(item) {
+                let mut path = module.path.clone();
+                path.push(name.clone());
+                module.names.insert(name, path);
+            }
+        }
+        for item in &items {
+            if let Item::Use(item) = item {
+                module.add_use(&item.tree, item.leading_colon.is_some(), vec![]);
+            }
+        }
+        for item in &items {
+            if let Item::Mod(item) = item {
+                self.scan_mod(item, &module, dir);
+            }
+        }
+        self.modules.push((module, items));
+    }
+
+    fn scan_mod(&mut self, item: &ItemMod, parent: &Module, dir: &Path) {
+        let name = item.ident.to_string();
+        let mut path = parent.path.clone();
+        path.push(name.clone());
+        // The generated code is in a child of the crate root, so it sees the private modules of
+        // the crate root, but not the private modules of other modules.
+        let reachable = parent.is_visible(&item.vis);
+        if let Some((_, items)) = &item.content {
+            self.scan_items(
+                items.clone(),
+                path,
+                &dir.join(&name),
+                reachable,
+                &parent.file,
+            );
+            return;
+        }
+        let mut candidates = vec![];
+        if let Some(file) = path_attribute(&item.attrs) {
+            // Like rustc, a `#[path]` file is treated like a `mod.rs` file.
+            let file = parent.file.parent().unwrap_or(Path::new("")).join(file);
+            let dir = file.parent().unwrap_or(Path::new("")).to_owned();
+            candidates.push((file, dir));
+        } else {
+            candidates.push((dir.join(format!("{name}.rs")), dir.join(&name)));
+            candidates.push((dir.join(&name).join("mod.rs"), dir.join(&name)));
+        }
+        // A missing file is reported by rustc. It might also be the generated module, which
+        // doesn't exist before the first generation.
+        if let Some((file, dir)) = candidates.iter().find(|(file, _)| file.is_file()) {
+            let declaration = parent.location(item.ident.span());
+            self.scan_file(file, path, dir.clone(), reachable, Some(declaration));
+        }
+    }
+
+    /// Converts the exported items, after all the modules are scanned.
+    fn exports(&mut self) -> Exports {
+        // The `use`s of all modules, so paths going through re-exports can be followed to the
+        // item, which is the path Zngur knows the item by.
+        let mut aliases = HashMap::new();
+        for (module, _) in &self.modules {
+            for (name, target) in &module.names {
+                let mut path = module.path.clone();
+                path.push(name.clone());
+                if path != *target {
+                    aliases.insert(path, target.clone());
+                }
+            }
+        }
+        let mut exports = Exports::default();
+        let mut errors = vec![];
+        for (module, items) in &self.modules {
+            let scope = ItemScope {
+                module,
+                aliases: &aliases,
+            };
+            for item in items {
+                let Some(attr) = item_attrs(item)
+                    .iter()
+                    .find(|attr| scope.is_export_attribute(attr))
+                else {
+                    continue;
+                };
+                if let Err(e) = scope.export(item, attr, &mut exports) {
+                    errors.push((module.file.clone(), e));
+                }
+            }
+        }
+        for (file, e) in errors {
+            self.add_error(&file, e);
+        }
+        exports
+    }
+
+    fn add_error(&mut self, file: &Path, e: ExportError) {
+        let span = e.span.byte_range();
+        self.diagnostics
+            .push(Diagnostic::error(file, span.clone(), e.message).with_label(file, span, e.label));
+    }
+}
+
+/// A module of the scanned crate.
+struct Module {
+    /// The path of the module, starting with `crate`.
+    path: Vec<String>,
+    /// The file the module is written in.
+    file: PathBuf,
+    /// Whether the generated code can see the items of this module which aren't private.
+    reachable: bool,
+    /// The items and imports of the module, and their full paths.
+    names: HashMap<String, Vec<String>>,
+}
+
+impl Module {
+    fn is_root(&self) -> bool {
+        self.path.len() == 1
+    }
+
+    /// Whether the generated code can see an item of this module with this visibility.
+    fn is_visible(&self, vis: &Visibility) -> bool {
+        self.reachable && (self.is_root() || !matches!(vis, Visibility::Inherited))
+    }
+
+    fn location(&self, span: proc_macro2::Span) -> Location {
+        Location {
+            path: self.file.clone(),
+            span: span.byte_range().into(),
+        }
+    }
+
+    fn add_use(&mut self, tree: &UseTree, absolute: bool, mut prefix: Vec<String>) {
+        let mut add = |name: String, path: Vec<String>| {
+            let path = self.resolve(absolute, &path, tree.span()).unwrap_or(path);
+            self.names.insert(name, path);
+        };
+        match tree {
+            UseTree::Path(tree) => {
+                prefix.push(tree.ident.to_string());
+                self.add_use(&tree.tree, absolute, prefix);
+            }
+            UseTree::Name(tree) if tree.ident == "self" => {
+                if let Some(name) = prefix.last() {
+                    add(name.clone(), prefix);
+                }
+            }
+            UseTree::Name(tree) => {
+                prefix.push(tree.ident.to_string());
+                add(tree.ident.to_string(), prefix);
+            }
+            UseTree::Rename(tree) => {
+                if tree.ident != "self" {
+                    prefix.push(tree.ident.to_string());
+                }
+                add(tree.rename.to_string(), prefix);
+            }
+            // The items of glob imports are not known without compiling the crate.
+            UseTree::Glob(_) => {}
+            UseTree::Group(group) => {
+                for tree in &group.items {
+                    self.add_use(tree, absolute, prefix.clone());
+                }
+            }
+        }
+    }
+
+    /// The full path of a path written in this module.
+    fn resolve(
+        &self,
+        absolute: bool,
+        segments: &[String],
+        span: proc_macro2::Span,
+    ) -> Result<Vec<String>, ExportError> {
+        if absolute {
+            return Ok(segments.to_vec());
+        }
+        let (first, mut rest) = segments.split_first().expect("paths are not empty");
+        let mut path = match first.as_str() {
+            "crate" => vec!["crate".to_owned()],
+            "self" => self.path.clone(),
+            "super" => {
+                let mut path = self.path.clone();
+                rest = segments;
+                while let Some((_, tail)) = rest.split_first().filter(|(head, _)| *head == "super")
+                {
+                    if path.len() == 1 {
+                        return Err(error(
+                            span,
+                            "There are too many leading `super` keywords",
+                            "This goes above the crate root",
+                        ));
+                    }
+                    path.pop();
+                    rest = tail;
+                }
+                path
+            }
+            "Self" => {
+                return Err(error(
+                    span,
+                    "Paths starting with `Self` are not supported",
+                    "Use the full path of the item",
+                ));
+            }
+            name => match self.names.get(name).cloned().or_else(|| prelude(name)) {
+                Some(path) => path,
+                // A path starting with an unknown name refers to an external crate.
+                None if !rest.is_empty() => {
+                    rest = segments;
+                    vec![]
+                }
+                None => {
+                    return Err(error(
+                        span,
+                        format!("Can't find `{name}`"),
+                        "Only the items and the `use`s of this module are known, use the full path",
+                    ));
+                }
+            },
+        };
+        path.extend(rest.iter().cloned());
+        Ok(path)
+    }
+}
+
+/// A module of the scanned crate, with the `use`s of the other modules.
+struct ItemScope<'a> {
+    module: &'a Module,
+    aliases: &'a HashMap<Vec<String>, Vec<String>>,
+}
+
+impl ItemScope<'_> {
+    /// The full path of a path written in the module, following the re-exports to the item.
+    fn resolve(
+        &self,
+        absolute: bool,
+        segments: &[String],
+        span: proc_macro2::Span,
+    ) -> Result<Vec<String>, ExportError> {
+        let mut path = self.module.resolve(absolute, segments, span)?;
+        // The limit stops the cycles of `use`s, which rustc reports anyway.
+        for _ in 0..32 {
+            let Some(len) = (1..=path.len())
+                .rev()
+                .find(|len| self.aliases.contains_key(&path[..*len]))
+            else {
+                break;
+            };
+            let mut target = self.aliases[&path[..len]].clone();
+            target.extend_from_slice(&path[len..]);
+            path = target;
+        }
+        Ok(path)
+    }
+
+    fn export(
+        &self,
+        item: &Item,
+        attr: &Attribute,
+        exports: &mut Exports,
+    ) -> Result<(), ExportError> {
+        let args = match &attr.meta {
+            Meta::Path(_) => TokenStream::new(),
+            Meta::List(list) => list.tokens.clone(),
+            Meta::NameValue(meta) => {
+                return Err(error(
+                    meta.eq_token.span,
+                    "Expected `#[zngur::export]` or `#[zngur::export(...)]`",
+                    "Remove this",
+                ));
+            }
+        };
+        check_export_attribute(args.clone(), item)?;
+        let layout = export_layout(args)?.unwrap_or(LayoutPolicy::HeapAllocated);
+        match item {
+            Item::Struct(item) => exports.types.push(self.export_struct(item, layout)?),
+            Item::Enum(item) => exports.types.push(self.export_enum(item, layout)?),
+            Item::Impl(item) => exports.impls.push(self.export_impl(item)?),
+            Item::Fn(item) => exports.funcs.push(self.export_fn(item)?),
+            _ => unreachable!("checked by check_export_attribute"),
+        }
+        Ok(())
+    }
+
+    fn is_export_attribute(&self, attr: &Attribute) -> bool {
+        let path = attr.path();
+        let segments = path
+            .segments
+            .iter()
+            .map(|segment| segment.ident.to_string())
+            .collect::<Vec<_>>();
+        self.resolve(path.leading_colon.is_some(), &segments, path.span())
+            .is_ok_and(|path| path == ["zngur", "export"])
+    }
+
+    fn item_type(&self, ident: &syn::Ident) -> RustType {
+        let mut path = self.module.path.clone();
+        path.push(ident.to_string());
+        RustType::Adt(RustPathAndGenerics {
+            path,
+            generics: vec![],
+            named_generics: vec![],
+        })
+    }
+
+    fn check_visibility(
+        &self,
+        vis: &Visibility,
+        span: proc_macro2::Span,
+    ) -> Result<(), ExportError> {
+        if !self.module.reachable {
+            return Err(error(
+                span,
+                format!("Module `{}` is private", self.module.path.join("::")),
+                "Make the module `pub(crate)` to export its items",
+            ));
+        }
+        if !self.module.is_visible(vis) {
+            return Err(error(
+                span,
+                "Exported items must be visible to the generated code",
+                "Make it `pub` or `pub(crate)`",
+            ));
+        }
+        Ok(())
+    }
+
+    fn export_struct(
+        &self,
+        item: &ItemStruct,
+        layout: LayoutPolicy,
+    ) -> Result<(ZngurType, Location), ExportError> {
+        self.check_visibility(&item.vis, item.ident.span())?;
+        check_no_generics(&item.generics)?;
+        let ty = self.item_type(&item.ident);
+        let mut constructors = vec![];
+        // C++ can construct the struct if it can see all of its fields, like Rust.
+        if layout != LayoutPolicy::OnlyByRef
+            && item
+                .fields
+                .iter()
+                .all(|field| self.module.is_visible(&field.vis))
+        {
+            constructors.push(ZngurConstructor {
+                name: None,
+                inputs: self.fields(&item.fields, &ty)?,
+                doc: None,
+                cpp_name: None,
+            });
+        }
+        let zngur_type = ZngurType {
+            layout,
+            wellknown_traits: wellknown_traits(&item.attrs)?,
+            constructors,
+            doc: doc(&item.attrs),
+            ..empty_type(ty)
+        };
+        Ok((zngur_type, self.module.location(item.ident.span())))
+    }
+
+    fn export_enum(
+        &self,
+        item: &ItemEnum,
+        layout: LayoutPolicy,
+    ) -> Result<(ZngurType, Location), ExportError> {
+        self.check_visibility(&item.vis, item.ident.span())?;
+        check_no_generics(&item.generics)?;
+        let ty = self.item_type(&item.ident);
+        let mut variants = vec![];
+        for variant in &item.variants {
+            variants.push(ZngurVariant {
+                name: variant.ident.to_string(),
+                fields: self.fields(&variant.fields, &ty)?,
+                doc: doc(&variant.attrs),
+            });
+        }
+        let zngur_type = ZngurType {
+            layout,
+            wellknown_traits: wellknown_traits(&item.attrs)?,
+            variants,
+            doc: doc(&item.attrs),
+            ..empty_type(ty)
+        };
+        Ok((zngur_type, self.module.location(item.ident.span())))
+    }
+
+    fn export_impl(&self, item: &ItemImpl) -> Result<ExportedImpl, ExportError> {
+        if let Some((_, tr, _)) = &item.trait_ {
+            return Err(error(
+                tr.span(),
+                "Trait impls can't be exported",
+                "Declare the trait and `impl Trait for Type;` in the zng file",
+            ));
+        }
+        check_no_generics(&item.generics)?;
+        let ty = self.rust_type(&item.self_ty, None)?;
+        let mut methods = vec![];
+        for impl_item in &item.items {
+            let ImplItem::Fn(method) = impl_item else {
+                continue;
+            };
+            // Only the public methods are exported, like the public items of a module.
+            if matches!(method.vis, Visibility::Inherited) {
+                continue;
+            }
+            if let Some(unsafety) = method.sig.unsafety {
+                return Err(error(
+                    unsafety.span,
+                    "Unsafe methods can't be exported",
+                    "Make it safe, or move it to another `impl` block",
+                ));
+            }
+            let (receiver, inputs, output) = self.signature(&method.sig, Some(&ty))?;
+            let data = ZngurMethod {
+                name: method.sig.ident.to_string(),
+                generics: vec![],
+                receiver,
+                inputs,
+                output,
+                is_safe: true,
+                doc: doc(&method.attrs),
+                cpp_name: None,
+                result_mode: None,
+            };
+            methods.push((
+                ZngurMethodDetails {
+                    data,
+                    use_path: None,
+                    deref: None,
+                },
+                self.module.location(method.sig.ident.span()),
+            ));
+        }
+        Ok(ExportedImpl {
+            ty,
+            location: self.module.location(item.self_ty.span()),
+            methods,
+        })
+    }
+
+    fn export_fn(&self, item: &ItemFn) -> Result<(ZngurFn, Location), ExportError> {
+        self.check_visibility(&item.vis, item.sig.ident.span())?;
+        if let Some(unsafety) = item.sig.unsafety {
+            return Err(error(
+                unsafety.span,
+                "Unsafe functions can't be exported",
+                "Export a safe function which calls it",
+            ));
+        }
+        let (_, inputs, output) = self.signature(&item.sig, None)?;
+        let mut path = self.module.path.clone();
+        path.push(item.sig.ident.to_string());
+        let func = ZngurFn {
+            path: RustPathAndGenerics {
+                path,
+                generics: vec![],
+                named_generics: vec![],
+            },
+            inputs,
+            output,
+            doc: doc(&item.attrs),
+            cpp_name: None,
+            result_mode: None,
+        };
+        Ok((func, self.module.location(item.sig.ident.span())))
+    }
+
+    fn signature(
+        &self,
+        sig: &Signature,
+        self_ty: Option<&RustType>,
+    ) -> Result<(ZngurMethodReceiver, Vec<RustType>, RustType), ExportError> {
+        if let Some(asyncness) = sig.asyncness {
+            return Err(error(
+                asyncness.span,
+                "Async functions can't be exported",
+                "Export a function which returns a handle to the future instead",
+            ));
+        }
+        if let Some(variadic) = &sig.variadic {
+            return Err(error(
+                variadic.span(),
+                "Variadic functions can't be exported",
+                "Remove this",
+            ));
+        }
+        if sig
+            .generics
+            .params
+            .iter()
+            .any(|param| !matches!(param, GenericParam::Lifetime(_)))
+        {
+            return Err(error(
+                sig.generics.span(),
+                "Generic functions can't be exported",
+                "Declare the instances you need in the zng file",
+            ));
+        }
+        let mut receiver = ZngurMethodReceiver::Static;
+        let mut inputs = vec![];
+        for input in &sig.inputs {
+            match input {
+                FnArg::Receiver(input) => {
+                    if input.colon_token.is_some() {
+                        return Err(error(
+                            input.span(),
+                            "Only `self`, `&self` and `&mut self` receivers are supported",
+                            "Declare the method in the zng file",
+                        ));
+                    }
+                    receiver = match input.reference {
+                        Some(_) => ZngurMethodReceiver::Ref(mutability(input.mutability.is_some())),
+                        None => ZngurMethodReceiver::Move,
+                    };
+                }
+                FnArg::Typed(input) => inputs.push(self.rust_type(&input.ty, self_ty)?),
+            }
+        }
+        let output = self.return_type(&sig.output, self_ty)?;
+        Ok((receiver, inputs, output))
+    }
+
+    fn fields(
+        &self,
+        fields: &Fields,
+        self_ty: &RustType,
+    ) -> Result<Vec<(String, RustType)>, ExportError> {
+        fields
+            .iter()
+            .enumerate()
+            .map(|(i, field)| {
+                let name = match &field.ident {
+                    Some(ident) => ident.to_string(),
+                    None => i.to_string(),
+                };
+                Ok((name, self.rust_type(&field.ty, Some(self_ty))?))
+            })
+            .collect()
+    }
+
+    fn return_type(
+        &self,
+        output: &ReturnType,
+        self_ty: Option<&RustType>,
+    ) -> Result<RustType, ExportError> {
+        match output {
+            ReturnType::Default => Ok(RustType::UNIT),
+            ReturnType::Type(_, ty) => self.rust_type(ty, self_ty),
+        }
+    }
+
+    fn rust_type(&self, ty: &Type, self_ty: Option<&RustType>) -> Result<RustType, ExportError> {
+        let boxed = |ty: &Type| self.rust_type(ty, self_ty).map(Box::new);
+        Ok(match ty {
+            Type::Array(ty) => {
+                let Expr::Lit(ExprLit {
+                    lit: Lit::Int(len), ..
+                }) = &ty.len
+                else {
+                    return Err(error(
+                        ty.len.span(),
+                        "Array lengths must be a number",
+                        "Zngur can't evaluate this expression",
+                    ));
+                };
+                RustType::Array(boxed(&ty.elem)?, len.base10_parse()?)
+            }
+            Type::BareFn(ty) => {
+                if let Some(variadic) = &ty.variadic {
+                    return Err(error(
+                        variadic.span(),
+                        "Variadic function pointers are not supported",
+                        "Remove this",
+                    ));
+                }
+                RustType::FnPtr {
+                    is_unsafe: ty.unsafety.is_some(),
+                    abi: ty.abi.as_ref().map(|abi| {
+                        abi.name
+                            .as_ref()
+                            .map_or_else(|| "C".to_owned(), |name| name.value())
+                    }),
+                    inputs: ty
+                        .inputs
+                        .iter()
+                        .map(|input| self.rust_type(&input.ty, self_ty))
+                        .collect::<Result<_, _>>()?,
+                    output: Box::new(self.return_type(&ty.output, self_ty)?),
+                }
+            }
+            Type::Group(ty) => self.rust_type(&ty.elem, self_ty)?,
+            Type::Paren(ty) => self.rust_type(&ty.elem, self_ty)?,
+            Type::ImplTrait(ty) => {
+                let (tr, bounds) = self.bounds(&ty.bounds, self_ty, ty.span())?;
+                RustType::Impl(tr, bounds)
+            }
+            Type::TraitObject(ty) => {
+                let (tr, bounds) = self.bounds(&ty.bounds, self_ty, ty.span())?;
+                RustType::Dyn(tr, bounds)
+            }
+            Type::Path(ty) => {
+                if let Some(qself) = &ty.qself {
+                    return Err(error(
+                        qself.lt_token.span,
+                        "Qualified paths are not supported",
+                        "Use the type which this path refers to",
+                    ));
+                }
+                self.path_type(&ty.path, self_ty)?
+            }
+            Type::Ptr(ty) => RustType::Raw(mutability(ty.mutability.is_some()), boxed(&ty.elem)?),
+            Type::Reference(ty) => {
+                RustType::Ref(mutability(ty.mutability.is_some()), boxed(&ty.elem)?)
+            }
+            Type::Slice(ty) => RustType::Slice(boxed(&ty.elem)?),
+            Type::Tuple(ty) => RustType::Tuple(
+                ty.elems
+                    .iter()
+                    .map(|ty| self.rust_type(ty, self_ty))
+                    .collect::<Result<_, _>>()?,
+            ),
+            _ => {
+                return Err(error(
+                    ty.span(),
+                    "This type is not supported by Zngur",
+                    "Use another type, or declare the item in the zng file",
+                ));
+            }
+        })
+    }
+
+    fn path_type(
+        &self,
+        path: &syn::Path,
+        self_ty: Option<&RustType>,
+    ) -> Result<RustType, ExportError> {
+        if let Some(ident) = path.get_ident() {
+            let name = ident.to_string();
+            if let Some(primitive) = primitive(&name) {
+                return Ok(RustType::Primitive(primitive));
+            }
+            match name.as_str() {
+                "Self" => {
+                    return self_ty.cloned().ok_or_else(|| {
+                        error(
+                            ident.span(),
+                            "`Self` is only allowed in `impl` blocks",
+                            "Use the name of the type",
+                        )
+                    });
+                }
+                "isize" => {
+                    return Err(error(
+                        ident.span(),
+                        "`isize` is not supported by Zngur",
+                        "Use `usize` or an integer with a fixed size",
+                    ));
+                }
+                _ => {}
+            }
+        }
+        let path = self.path_and_generics(path, self_ty)?;
+        let is_box = matches!(
+            path.path.iter().map(String::as_str).collect::<Vec<_>>()[..],
+            ["std" | "alloc", "boxed", "Box"]
+        );
+        Ok(match &path.generics[..] {
+            [inner] if is_box && path.named_generics.is_empty() => {
+                RustType::Boxed(Box::new(inner.clone()))
+            }
+            _ => RustType::Adt(path),
+        })
+    }
+
+    fn path_and_generics(
+        &self,
+        path: &syn::Path,
+        self_ty: Option<&RustType>,
+    ) -> Result<RustPathAndGenerics, ExportError> {
+        let (last, init) = path
+            .segments
+            .iter()
+            .collect::<Vec<_>>()
+            .split_last()
+            .map(|(last, init)| (*last, init.to_vec()))
+            .expect("paths are not empty");
+        if let Some(segment) = init.iter().find(|segment| !segment.arguments.is_none()) {
+            return Err(error(
+                segment.arguments.span(),
+                "Generic arguments are only supported at the end of the path",
+                "Move them to the end of the path",
+            ));
+        }
+        let segments = path
+            .segments
+            .iter()
+            .map(|segment| segment.ident.to_string())
+            .collect::<Vec<_>>();
+        let mut generics = vec![];
+        let mut named_generics = vec![];
+        match &last.arguments {
+            PathArguments::None => {}
+            PathArguments::AngleBracketed(args) => {
+                for arg in &args.args {
+                    match arg {
+                        syn::GenericArgument::Lifetime(_) => {}
+                        syn::GenericArgument::Type(ty) => {
+                            generics.push(self.rust_type(ty, self_ty)?)
+                        }
+                        syn::GenericArgument::AssocType(assoc) => named_generics
+                            .push((assoc.ident.to_string(), self.rust_type(&assoc.ty, self_ty)?)),
+                        _ => {
+                            return Err(error(
+                                arg.span(),
+                                "Only types are supported as generic arguments",
+                                "Use a type here",
+                            ));
+                        }
+                    }
+                }
+            }
+            PathArguments::Parenthesized(args) => {
+                return Err(error(
+                    args.span(),
+                    "Parenthesized arguments are only supported in `Fn` traits",
+                    "Use `dyn Fn(...)` or `impl Fn(...)`",
+                ));
+            }
+        }
+        Ok(RustPathAndGenerics {
+            path: self.resolve(path.leading_colon.is_some(), &segments, path.span())?,
+            generics,
+            named_generics,
+        })
+    }
+
+    /// The trait and the marker traits of a `dyn` or `impl` type.
+    fn bounds(
+        &self,
+        bounds: &Punctuated<TypeParamBound, Plus>,
+        self_ty: Option<&RustType>,
+        span: proc_macro2::Span,
+    ) -> Result<(RustTrait, Vec<String>), ExportError> {
+        let mut traits = bounds.iter().filter_map(|bound| match bound {
+            TypeParamBound::Trait(bound) => Some(&bound.path),
+            _ => None,
+        });
+        let Some(first) = traits.next() else {
+            return Err(error(span, "Expected a trait", "Add a trait here"));
+        };
+        let last = first.segments.last().expect("paths are not empty");
+        let tr = match &last.arguments {
+            PathArguments::Parenthesized(args)
+                if ["Fn", "FnMut", "FnOnce"].contains(&last.ident.to_string().as_str()) =>
+            {
+                RustTrait::Fn {
+                    name: last.ident.to_string(),
+                    inputs: args
+                        .inputs
+                        .iter()
+                        .map(|ty| self.rust_type(ty, self_ty))
+                        .collect::<Result<_, _>>()?,
+                    output: Box::new(self.return_type(&args.output, self_ty)?),
+                }
+            }
+            _ => RustTrait::Normal(self.path_and_generics(first, self_ty)?),
+        };
+        let markers = traits
+            .map(|path| {
+                path.get_ident().map(ToString::to_string).ok_or_else(|| {
+                    error(
+                        path.span(),
+                        "Only marker traits like `Send` are supported after the first trait",
+                        "Remove this",
+                    )
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok((tr, markers))
+    }
+}
+
+/// A type with nothing but its name, to fill in with the struct update syntax.
+fn empty_type(ty: RustType) -> ZngurType {
+    ZngurType {
+        ty,
+        layout: LayoutPolicy::HeapAllocated,
+        wellknown_traits: vec![],
+        methods: vec![],
+        constructors: vec![],
+        fields: vec![],
+        variants: vec![],
+        cpp_value: None,
+        cpp_ref: None,
+        cpp_stack_owned: None,
+        doc: None,
+        cpp_name: None,
+    }
+}
+
+fn check_no_generics(generics: &Generics) -> Result<(), ExportError> {
+    if generics
+        .params
+        .iter()
+        .any(|param| !matches!(param, GenericParam::Lifetime(_)))
+    {
+        return Err(error(
+            generics.span(),
+            "Generic items can't be exported",
+            "Declare the instances you need in the zng file",
+        ));
+    }
+    Ok(())
+}
+
+fn mutability(is_mut: bool) -> Mutability {
+    if is_mut {
+        Mutability::Mut
+    } else {
+        Mutability::Not
+    }
+}
+
+fn primitive(name: &str) -> Option<PrimitiveRustType> {
+    let sized = |head: char| name.strip_prefix(head)?.parse().ok();
+    Some(match name {
+        "bool" => PrimitiveRustType::Bool,
+        "str" => PrimitiveRustType::Str,
+        "char" => PrimitiveRustType::Char,
+        "usize" => PrimitiveRustType::Usize,
+        _ => {
+            if let Some(bits) = sized('u') {
+                PrimitiveRustType::Uint(bits)
+            } else if let Some(bits) = sized('i') {
+                PrimitiveRustType::Int(bits)
+            } else if let Some(bits) = sized('f') {
+                PrimitiveRustType::Float(bits)
+            } else {
+                return None;
+            }
+        }
+    })
+}
+
+fn prelude(name: &str) -> Option<Vec<String>> {
+    PRELUDE
+        .iter()
+        .find(|(prelude_name, _)| *prelude_name == name)
+        .map(|(_, path)| path.iter().map(|x| (*x).to_owned()).collect())
+}
+
+/// The wellknown traits of a type, from its `#[derive(...)]` attributes.
+fn wellknown_traits(attrs: &[Attribute]) -> syn::Result<Vec<ZngurWellknownTrait>> {
+    let mut traits = vec![];
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("derive")) {
+        attr.parse_nested_meta(|meta| {
+            let name = meta.path.segments.last().map(|x| x.ident.to_string());
+            match name.as_deref() {
+                Some("Copy") => traits.push(ZngurWellknownTrait::Copy),
+                Some("Debug") => traits.push(ZngurWellknownTrait::Debug),
+                _ => {}
+            }
+            Ok(())
+        })?;
+    }
+    // Like in the zng file, the types which aren't `Copy` need to be dropped.
+    if !traits.contains(&ZngurWellknownTrait::Copy) {
+        traits.push(ZngurWellknownTrait::Drop);
+    }
+    Ok(traits)
+}
+
+/// The text of the `///` doc comments, like the doc comments of the zng file.
+fn doc(attrs: &[Attribute]) -> Option<String> {
+    let lines = attrs
+        .iter()
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(meta) if meta.path.is_ident("doc") => match &meta.value {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(line),
+                    ..
+                }) => Some(line.value()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .map(|line| line.strip_prefix(' ').map(str::to_owned).unwrap_or(line))
+        .collect::<Vec<_>>();
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+fn path_attribute(attrs: &[Attribute]) -> Option<String> {
+    attrs.iter().find_map(|attr| match &attr.meta {
+        Meta::NameValue(meta) if meta.path.is_ident("path") => match &meta.value {
+            Expr::Lit(ExprLit {
+                lit: Lit::Str(path),
+                ..
+            }) => Some(path.value()),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+/// The name which an item defines in its module.
+fn item_name(item: &Item) -> Option<String> {
+    let ident = match item {
+        Item::Const(item) => &item.ident,
+        Item::Enum(item) => &item.ident,
+        Item::ExternCrate(item) => {
+            return Some(match &item.rename {
+                Some((_, rename)) => rename.to_string(),
+                None => item.ident.to_string(),
+            });
+        }
+        Item::Fn(item) => &item.sig.ident,
+        Item::Mod(item) => &item.ident,
+        Item::Static(item) => &item.ident,
+        Item::Struct(item) => &item.ident,
+        Item::Trait(item) => &item.ident,
+        Item::TraitAlias(item) => &item.ident,
+        Item::Type(item) => &item.ident,
+        Item::Union(item) => &item.ident,
+        _ => return None,
+    };
+    Some(ident.to_string())
+}
+
+fn item_attrs(item: &Item) -> &[Attribute] {
+    match item {
+        Item::Const(item) => &item.attrs,
+        Item::Enum(item) => &item.attrs,
+        Item::ExternCrate(item) => &item.attrs,
+        Item::Fn(item) => &item.attrs,
+        Item::ForeignMod(item) => &item.attrs,
+        Item::Impl(item) => &item.attrs,
+        Item::Macro(item) => &item.attrs,
+        Item::Mod(item) => &item.attrs,
+        Item::Static(item) => &item.attrs,
+        Item::Struct(item) => &item.attrs,
+        Item::Trait(item) => &item.attrs,
+        Item::TraitAlias(item) => &item.attrs,
+        Item::Type(item) => &item.attrs,
+        Item::Union(item) => &item.attrs,
+        Item::Use(item) => &item.attrs,
+        _ => &[],
+    }
+}
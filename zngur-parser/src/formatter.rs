@@ -0,0 +1,734 @@
+//! Canonical formatting of `.zng` files.
+//!
+//! The formatter reprints the syntax tree of a single file with fixed indentation and spacing.
+//! Comments and single blank lines between items are kept, and items are never reordered. Every
+//! branch of `#if` and `#match` blocks is printed as written, regardless of the active cfg.
+
+use std::fmt::{Display, Formatter, Result};
+
+use itertools::Itertools;
+use zngur_def::{Mutability, RustType, ZngurMethodReceiver, ZngurResultMode, ZngurWellknownTrait};
+
+use crate::{
+    ParsedConstructorArgs, ParsedExternCppItem, ParsedItem, ParsedLayoutPolicy, ParsedMethod,
+    ParsedPath, ParsedPathStart, ParsedRustPathAndGenerics, ParsedRustTrait, ParsedRustType,
+    ParsedTypeItem, ParsedTypeTemplate, Span, Spanned, SyntaxError, Token,
+    cfg::{CfgConditional, CfgPattern, CfgPatternItem, CfgScrutinee},
+    conditional::{BodyItem, Condition, ConditionGuard, NItems},
+    layout_expr::ParsedLayoutExpr,
+    parse_syntax,
+};
+
+const INDENT: &str = "    ";
+
+/// Signatures longer than this are split before their return type.
+const MAX_WIDTH: usize = 100;
+
+/// Reprint the text of a `.zng` file in the canonical style.
+pub fn format_zng(text: &str) -> std::result::Result<String, Vec<SyntaxError>> {
+    let (formatted, errors) = parse_syntax(text, |file, tokens| {
+        let mut printer = Printer::new(text, tokens);
+        for item in &file.0 {
+            printer.item(item);
+        }
+        printer.comments_before(text.len());
+        printer.out
+    });
+    match formatted {
+        Some(formatted) if errors.is_empty() => Ok(formatted),
+        _ => Err(errors),
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Comment<'s> {
+    start: usize,
+    end: usize,
+    text: &'s str,
+}
+
+struct Printer<'s> {
+    text: &'s str,
+    comments: Vec<Comment<'s>>,
+    next_comment: usize,
+    /// Offsets of every `}` token, to find the end of blocks which have no span of their own.
+    close_braces: Vec<usize>,
+    out: String,
+    depth: usize,
+    /// The source offset up to which everything has been printed.
+    cursor: usize,
+    /// Whether nothing has been printed since the last opening brace.
+    block_start: bool,
+}
+
+impl<'s> Printer<'s> {
+    fn new(text: &'s str, tokens: &[(Token<'_>, Span)]) -> Self {
+        // Comments are dropped by the lexer, so they are recovered from the gaps between tokens.
+        let gap_starts = [0].into_iter().chain(tokens.iter().map(|(_, s)| s.end));
+        let gap_ends = tokens.iter().map(|(_, s)| s.start).chain([text.len()]);
+        let mut comments = vec![];
+        for (gap_start, gap_end) in gap_starts.zip(gap_ends) {
+            let mut offset = gap_start;
+            for line in text[gap_start..gap_end].split_inclusive('\n') {
+                if let Some(i) = line.find("//") {
+                    let comment = line[i..].trim_end();
+                    comments.push(Comment {
+                        start: offset + i,
+                        end: offset + i + comment.len(),
+                        text: comment,
+                    });
+                }
+                offset += line.len();
+            }
+        }
+        Printer {
+            text,
+            comments,
+            next_comment: 0,
+            close_braces: tokens
+                .iter()
+                .filter(|(t, _)| *t == Token::BraceClose)
+                .map(|(_, s)| s.start)
+                .collect(),
+            out: String::new(),
+            depth: 0,
+            cursor: 0,
+            block_start: true,
+        }
+    }
+
+    fn line(&mut self, line: &str) {
+        for _ in 0..self.depth {
+            self.out.push_str(INDENT);
+        }
+        self.out.push_str(line);
+        self.out.push('\n');
+        self.block_start = false;
+    }
+
+    /// Keep a blank line of the source before the item starting at `offset`.
+    fn separate(&mut self, offset: usize) {
+        let gap = &self.text[self.cursor.min(offset)..offset];
+        if !self.block_start && gap.matches('\n').count() > 1 {
+            self.out.push('\n');
+        }
+    }
+
+    fn comments_before(&mut self, offset: usize) {
+        while let Some(comment) = self.comments.get(self.next_comment).copied() {
+            if comment.start >= offset {
+                break;
+            }
+            self.separate(comment.start);
+            self.line(comment.text);
+            self.cursor = self.cursor.max(comment.end);
+            self.next_comment += 1;
+        }
+    }
+
+    /// Move a comment on the same source line as `offset` to the end of the last printed line.
+    fn trailing_comment(&mut self, offset: usize) {
+        let Some(comment) = self.comments.get(self.next_comment).copied() else {
+            return;
+        };
+        if comment.start >= offset && !self.text[offset..comment.start].contains('\n') {
+            self.out.pop();
+            self.out.push(' ');
+            self.out.push_str(comment.text);
+            self.out.push('\n');
+            self.cursor = comment.end;
+            self.next_comment += 1;
+        }
+    }
+
+    fn spanned<T>(&mut self, item: &Spanned<T>, print: impl FnOnce(&mut Self, &T, Span)) {
+        self.comments_before(item.span.start);
+        self.separate(item.span.start);
+        print(self, &item.inner, item.span);
+        self.cursor = self.cursor.max(item.span.end);
+        self.trailing_comment(item.span.end);
+    }
+
+    fn open(&mut self, header: &str) {
+        self.line(&format!("{header} {{"));
+        self.depth += 1;
+        self.block_start = true;
+    }
+
+    /// Open a templated type item, putting each instance on its own line if they don't fit on one.
+    fn open_template(
+        &mut self,
+        keyword: &str,
+        ty: &ParsedRustType<'_>,
+        template: &ParsedTypeTemplate<'_>,
+    ) {
+        let param = template.param;
+        let instances = template
+            .instances
+            .iter()
+            .map(|instance| match &instance.layout {
+                Some((_, layout)) => format!("{} => {layout}", instance.ty.inner),
+                None => instance.ty.inner.to_string(),
+            })
+            .collect::<Vec<_>>();
+        let head = format!("{keyword}<{param}> {ty} for {param} in [");
+        let header = format!("{head}{}] {{", instances.join(", "));
+        if self.depth * INDENT.len() + header.len() <= MAX_WIDTH {
+            self.line(&header);
+        } else {
+            self.line(&head);
+            self.depth += 1;
+            for instance in instances {
+                self.line(&format!("{instance},"));
+            }
+            self.depth -= 1;
+            self.line("] {");
+        }
+        self.depth += 1;
+        self.block_start = true;
+    }
+
+    /// Close the block whose `}` is at `end`, followed by `rest` on the same line.
+    fn close(&mut self, end: usize, rest: &str) {
+        self.comments_before(end);
+        self.depth -= 1;
+        if self.block_start {
+            self.out.pop();
+            self.out.push('}');
+            self.out.push_str(rest);
+            self.out.push('\n');
+            self.block_start = false;
+        } else {
+            self.line(&format!("}}{rest}"));
+        }
+    }
+
+    /// The offset of the first `}` after `offset`.
+    fn block_end(&self, offset: usize) -> usize {
+        let i = self.close_braces.partition_point(|&brace| brace < offset);
+        self.close_braces.get(i).copied().unwrap_or(self.text.len())
+    }
+
+    fn item(&mut self, item: &Spanned<ParsedItem<'_>>) {
+        self.spanned(item, |p, item, span| match item {
+            ParsedItem::ConvertPanicToException(_) => p.line("#convert_panic_to_exception"),
+            ParsedItem::CppAdditionalInclude(include) => {
+                p.line(&format!("#cpp_additional_includes \"{include}\""))
+            }
+            ParsedItem::UnstableFeature(feature) => p.line(&format!("#unstable({feature})")),
+            ParsedItem::Mod { path, items } => {
+                p.open(&format!("mod {path}"));
+                for item in items {
+                    p.item(item);
+                }
+                p.close(span.end - 1, "");
+            }
+            ParsedItem::Type {
+                ty,
+                template,
+                items,
+                cpp_name,
+                is_enum,
+                ..
+            } => {
+                p.cpp_name(*cpp_name);
+                let keyword = if *is_enum { "enum" } else { "type" };
+                match template {
+                    Some(template) => p.open_template(keyword, &ty.inner, template),
+                    None => p.open(&format!("{keyword} {}", ty.inner)),
+                }
+                for item in items {
+                    p.type_item(item);
+                }
+                p.close(span.end - 1, "");
+            }
+            ParsedItem::Trait { tr, methods } => {
+                p.open(&format!("trait {}", tr.inner));
+                for method in methods {
+                    p.spanned(method, |p, method, _| p.signature("", method, ""));
+                }
+                p.close(span.end - 1, "");
+            }
+            ParsedItem::Fn(method) => p.signature("", &method.inner, ""),
+            ParsedItem::TraitImpl { tr, ty } => {
+                p.line(&format!("impl {} for {};", tr.inner, ty.inner))
+            }
+            ParsedItem::ExternCpp(items) => {
+                p.open("extern \"C++\"");
+                for item in items {
+                    p.spanned(item, Self::extern_cpp_item);
+                }
+                p.close(span.end - 1, "");
+            }
+            ParsedItem::Alias(alias) => p.line(&format!("use {} as {};", alias.path, alias.name)),
+            ParsedItem::Import(import) => p.line(&format!("merge \"{}\";", import.path.display())),
+            ParsedItem::ModuleImport { path, .. } => {
+                p.line(&format!("import \"{}\";", path.display()))
+            }
+            ParsedItem::MatchOnCfg(condition) => p.conditional(condition, span.end, Self::item),
+            // Malformed items only exist in files with syntax errors, which are never formatted.
+            ParsedItem::Error => {}
+        });
+    }
+
+    fn extern_cpp_item(&mut self, item: &ParsedExternCppItem<'_>, span: Span) {
+        let safety = |is_safe: bool| if is_safe { "safe " } else { "unsafe " };
+        match item {
+            ParsedExternCppItem::Function { is_safe, method } => {
+                self.signature(safety(*is_safe), &method.inner, "")
+            }
+            ParsedExternCppItem::Impl { tr, ty, methods } => {
+                match tr {
+                    Some(tr) => self.open(&format!("impl {tr} for {}", ty.inner)),
+                    None => self.open(&format!("impl {}", ty.inner)),
+                }
+                for method in methods {
+                    self.spanned(method, |p, (is_safe, method), _| {
+                        p.signature(safety(*is_safe), method, "")
+                    });
+                }
+                self.close(span.end - 1, "");
+            }
+            ParsedExternCppItem::Error => {}
+        }
+    }
+
+    fn type_item(&mut self, item: &Spanned<ParsedTypeItem<'_>>) {
+        self.spanned(item, |p, item, span| match item {
+            ParsedTypeItem::Layout(_, policy) => p.line(&format!("{policy};")),
+            ParsedTypeItem::Traits(traits) => p.line(&format!(
+                "wellknown_traits({});",
+                traits
+                    .iter()
+                    .map(|tr| match tr.inner {
+                        ZngurWellknownTrait::Debug => "Debug",
+                        ZngurWellknownTrait::Copy => "Copy",
+                        ZngurWellknownTrait::Drop => "Drop",
+                        ZngurWellknownTrait::Unsized => "?Sized",
+                    })
+                    .join(", ")
+            )),
+            ParsedTypeItem::Constructor {
+                name,
+                args,
+                cpp_name,
+                ..
+            } => {
+                p.cpp_name(*cpp_name);
+                let name = name.map(|name| format!(" {name}")).unwrap_or_default();
+                p.line(&format!("constructor{name}{};", ConstructorArgs(args)));
+            }
+            ParsedTypeItem::Variant { name, args, .. } => {
+                p.line(&format!("{name}{};", ConstructorArgs(args)))
+            }
+            ParsedTypeItem::Field {
+                name,
+                ty,
+                offset,
+                cpp_name,
+                ..
+            } => {
+                p.cpp_name(*cpp_name);
+                let offset = offset.map_or("auto".to_owned(), |offset| offset.to_string());
+                p.line(&format!("field {name} (offset = {offset}, type = {ty});"));
+            }
+            ParsedTypeItem::Method {
+                data,
+                use_path,
+                deref,
+            } => {
+                let mut suffix = String::new();
+                if let Some(use_path) = use_path {
+                    suffix += &format!(" use {use_path}");
+                }
+                if let Some(deref) = deref {
+                    suffix += &format!(" deref {deref}");
+                }
+                p.signature("", data, &suffix);
+            }
+            ParsedTypeItem::TraitImpl { tr, ty } => match ty {
+                Some(ty) => p.line(&format!("impl {tr} for {};", ty.inner)),
+                None => p.line(&format!("impl {tr};")),
+            },
+            ParsedTypeItem::ResultMode(mode) => p.line(&format!("{};", ResultMode(*mode))),
+            ParsedTypeItem::CppValue { field, cpp_type } => {
+                p.line(&format!("#cpp_value \"{field}\" \"{cpp_type}\";"))
+            }
+            ParsedTypeItem::CppRef { cpp_type } => p.line(&format!("#cpp_ref \"{cpp_type}\";")),
+            ParsedTypeItem::CppStackOwned { cpp_type, props } => p.line(&format!(
+                "#cpp_stack_owned \"{cpp_type}\" ({});",
+                Properties(props)
+            )),
+            ParsedTypeItem::MatchOnCfg(condition) => {
+                p.conditional(condition, span.end, Self::type_item)
+            }
+            ParsedTypeItem::Error => {}
+        });
+    }
+
+    /// Print the `#cpp_name` attribute of an item on its own line.
+    fn cpp_name(&mut self, cpp_name: Option<&str>) {
+        if let Some(cpp_name) = cpp_name {
+            self.line(&format!("#cpp_name \"{cpp_name}\""));
+        }
+    }
+
+    /// Print a method followed by a semicolon, moving a long return type to its own line.
+    fn signature(&mut self, prefix: &str, method: &ParsedMethod<'_>, suffix: &str) {
+        self.cpp_name(method.cpp_name);
+        if let Some(mode) = method.result_mode {
+            self.line(&ResultMode(mode).to_string());
+        }
+        let mut output = &method.output;
+        let mut head = prefix.to_owned();
+        // `async fn` is desugared while parsing, with the span of the `async` keyword on the path.
+        if let ParsedRustType::Impl(ParsedRustTrait::Normal(future), bounds) = output {
+            if bounds.is_empty() && &self.text[future.path.span.into_range()] == "async" {
+                head += "async ";
+                output = &future.named_generics[0].1;
+            }
+        }
+        head += &format!("fn {}", method.name);
+        if !method.generics.is_empty() {
+            head += &format!("<{}>", method.generics.iter().join(", "));
+        }
+        let receiver = match method.receiver {
+            ZngurMethodReceiver::Static => None,
+            ZngurMethodReceiver::Ref(Mutability::Not) => Some("&self".to_owned()),
+            ZngurMethodReceiver::Ref(Mutability::Mut) => Some("&mut self".to_owned()),
+            ZngurMethodReceiver::Move => Some("self".to_owned()),
+        };
+        let inputs = receiver
+            .into_iter()
+            .chain(method.inputs.iter().map(|ty| ty.to_string()))
+            .join(", ");
+        head += &format!("({inputs})");
+        if *output == ParsedRustType::Tuple(vec![]) {
+            self.line(&format!("{head}{suffix};"));
+            return;
+        }
+        let line = format!("{head} -> {output}{suffix};");
+        if self.depth * INDENT.len() + line.len() <= MAX_WIDTH {
+            self.line(&line);
+        } else {
+            self.line(&head);
+            self.depth += 1;
+            self.line(&format!("-> {output}{suffix};"));
+            self.depth -= 1;
+        }
+    }
+
+    fn conditional<Item: BodyItem>(
+        &mut self,
+        condition: &Condition<CfgConditional<'_>, Item, NItems>,
+        end: usize,
+        print_item: fn(&mut Self, &Spanned<Item>),
+    ) {
+        match condition {
+            Condition::If(condition) => {
+                let mut blocks = vec![];
+                for (i, arm) in condition.arms.iter().enumerate() {
+                    let keyword = if i == 0 { "#if" } else { "#else if" };
+                    blocks.push((format!("{keyword} {}", arm.guard), &arm.block));
+                }
+                if let Some(fallback) = &condition.fallback {
+                    blocks.push(("#else".to_owned(), fallback));
+                }
+                // The branches have no spans, so their closing braces are searched after the
+                // last item of each branch.
+                let mut search_from = self.cursor;
+                for (i, (header, items)) in blocks.iter().enumerate() {
+                    if i == 0 {
+                        self.open(header);
+                    } else {
+                        self.depth += 1;
+                        self.block_start = true;
+                    }
+                    for item in items.iter() {
+                        print_item(self, item);
+                    }
+                    let last = items.last().map_or(search_from, |item| item.span.end);
+                    match blocks.get(i + 1) {
+                        Some((next, _)) => {
+                            let brace = self.block_end(last);
+                            self.close(brace, &format!(" {next} {{"));
+                            search_from = brace + 1;
+                        }
+                        None => self.close(end - 1, ""),
+                    }
+                }
+            }
+            Condition::Match(condition) => {
+                self.open(&format!("#match {}", condition.scrutinee.inner));
+                for arm in &condition.arms {
+                    self.spanned(arm, |p, arm, span| {
+                        p.open(&format!("{} =>", arm.pattern.inner));
+                        for item in &arm.block {
+                            print_item(p, item);
+                        }
+                        p.close(span.end, "");
+                    });
+                }
+                self.close(end - 1, "");
+            }
+        }
+    }
+}
+
+impl Display for ParsedLayoutPolicy<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            ParsedLayoutPolicy::StackAllocated(props) => {
+                write!(f, "#layout({})", Properties(props))
+            }
+            ParsedLayoutPolicy::Conservative(props) => {
+                write!(f, "#layout_conservative({})", Properties(props))
+            }
+            ParsedLayoutPolicy::HeapAllocated => write!(f, "#heap_allocated"),
+            ParsedLayoutPolicy::OnlyByRef => write!(f, "#only_by_ref"),
+            ParsedLayoutPolicy::Auto => write!(f, "#layout(auto)"),
+        }
+    }
+}
+
+struct Properties<'a, 'b>(&'b [(Spanned<&'a str>, Spanned<ParsedLayoutExpr<'a>>)]);
+
+impl Display for Properties<'_, '_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let props = self
+            .0
+            .iter()
+            .map(|(key, value)| format!("{} = {}", key.inner, value.inner));
+        write!(f, "{}", props.format(", "))
+    }
+}
+
+impl Display for ParsedLayoutExpr<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            ParsedLayoutExpr::Number(n) => write!(f, "{n}"),
+            ParsedLayoutExpr::Type(ty) => write!(f, "{}", ty.inner),
+            ParsedLayoutExpr::SizeOf(ty) => write!(f, "sizeof({})", ty.inner),
+            ParsedLayoutExpr::AlignOf(ty) => write!(f, "alignof({})", ty.inner),
+            ParsedLayoutExpr::Add(lhs, rhs) => write!(f, "{lhs} + {rhs}"),
+            ParsedLayoutExpr::Mul(lhs, rhs) => {
+                let factor = |x: &ParsedLayoutExpr| match x {
+                    ParsedLayoutExpr::Add(..) => format!("({x})"),
+                    _ => x.to_string(),
+                };
+                write!(f, "{} * {}", factor(lhs), factor(rhs))
+            }
+        }
+    }
+}
+
+/// The arguments of a constructor or an enum variant, written after its name.
+struct ConstructorArgs<'a, 'b>(&'b ParsedConstructorArgs<'a>);
+
+impl Display for ConstructorArgs<'_, '_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self.0 {
+            ParsedConstructorArgs::Unit => Ok(()),
+            ParsedConstructorArgs::Tuple(tys) => write!(f, "({})", tys.iter().format(", ")),
+            ParsedConstructorArgs::Named(fields) => write!(
+                f,
+                " {{ {} }}",
+                fields
+                    .iter()
+                    .map(|(name, ty)| format!("{name}: {ty}"))
+                    .format(", ")
+            ),
+        }
+    }
+}
+
+/// The `#result_as_exception` or `#result_as_expected` attribute.
+struct ResultMode(ZngurResultMode);
+
+impl Display for ResultMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self.0 {
+            ZngurResultMode::Exception => write!(f, "#result_as_exception"),
+            ZngurResultMode::Expected => write!(f, "#result_as_expected"),
+        }
+    }
+}
+
+impl Display for ParsedPath<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self.start {
+            ParsedPathStart::Absolute => write!(f, "::")?,
+            ParsedPathStart::Relative => {}
+            ParsedPathStart::Crate if self.segments.is_empty() => return write!(f, "crate"),
+            ParsedPathStart::Crate => write!(f, "crate::")?,
+        }
+        write!(f, "{}", self.segments.iter().format("::"))
+    }
+}
+
+impl Display for ParsedRustPathAndGenerics<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.path)?;
+        if self.generics.is_empty() && self.named_generics.is_empty() {
+            return Ok(());
+        }
+        let generics = self.generics.iter().map(|ty| ty.to_string()).chain(
+            self.named_generics
+                .iter()
+                .map(|(name, ty)| format!("{name} = {ty}")),
+        );
+        write!(f, "<{}>", generics.format(", "))
+    }
+}
+
+impl Display for ParsedRustTrait<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            ParsedRustTrait::Normal(pg) => write!(f, "{pg}"),
+            ParsedRustTrait::Fn {
+                name,
+                inputs,
+                output,
+            } => {
+                write!(f, "{name}({})", inputs.iter().format(", "))?;
+                if **output != ParsedRustType::Tuple(vec![]) {
+                    write!(f, " -> {output}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Display for ParsedRustType<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            ParsedRustType::Primitive(p) => write!(f, "{}", RustType::Primitive(p.clone())),
+            ParsedRustType::Ref(Mutability::Not, ty) => write!(f, "&{ty}"),
+            ParsedRustType::Ref(Mutability::Mut, ty) => write!(f, "&mut {ty}"),
+            ParsedRustType::Raw(Mutability::Not, ty) => write!(f, "*const {ty}"),
+            ParsedRustType::Raw(Mutability::Mut, ty) => write!(f, "*mut {ty}"),
+            ParsedRustType::Boxed(ty) => write!(f, "Box<{ty}>"),
+            ParsedRustType::Slice(ty) => write!(f, "[{ty}]"),
+            ParsedRustType::Array(ty, len) => write!(f, "[{ty}; {len}]"),
+            ParsedRustType::FnPtr {
+                is_unsafe,
+                abi,
+                inputs,
+                output,
+            } => {
+                if *is_unsafe {
+                    write!(f, "unsafe ")?;
+                }
+                if let Some(abi) = abi {
+                    write!(f, "extern \"{abi}\" ")?;
+                }
+                write!(f, "fn({})", inputs.iter().format(", "))?;
+                if **output != ParsedRustType::Tuple(vec![]) {
+                    write!(f, " -> {output}")?;
+                }
+                Ok(())
+            }
+            ParsedRustType::Dyn(tr, bounds) => write!(f, "dyn {}", Bounds(tr, bounds)),
+            ParsedRustType::Impl(tr, bounds) => write!(f, "impl {}", Bounds(tr, bounds)),
+            ParsedRustType::Tuple(tys) if tys.len() == 1 => write!(f, "({},)", tys[0]),
+            ParsedRustType::Tuple(tys) => write!(f, "({})", tys.iter().format(", ")),
+            ParsedRustType::Adt(pg) => write!(f, "{pg}"),
+        }
+    }
+}
+
+struct Bounds<'a, 'b>(&'b ParsedRustTrait<'a>, &'b [&'a str]);
+
+impl Display for Bounds<'_, '_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.0)?;
+        for bound in self.1 {
+            write!(f, " + {bound}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for ConditionGuard<CfgConditional<'_>> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            ConditionGuard::Single {
+                scrutinee, pattern, ..
+            } => match (&scrutinee.inner, &pattern.inner) {
+                (CfgConditional::Single(key), CfgPattern::Single(CfgPatternItem::Some, _)) => {
+                    write!(f, "cfg!({key})")
+                }
+                (CfgConditional::Single(key), pattern) => write!(f, "cfg!({key} = {pattern})"),
+                (scrutinee, pattern) => write!(f, "{scrutinee} = {pattern}"),
+            },
+            ConditionGuard::And(guards, _) => write!(f, "{}", guards.iter().format(" && ")),
+            ConditionGuard::Or(guards, _) => write!(f, "{}", guards.iter().format(" || ")),
+            ConditionGuard::Not(guard, _) => write!(f, "!{guard}"),
+            ConditionGuard::Grouped(guard, _) => write!(f, "({guard})"),
+        }
+    }
+}
+
+impl Display for CfgConditional<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            CfgConditional::Single(key) => write!(f, "cfg!({key})"),
+            CfgConditional::Tuple(keys) => write!(
+                f,
+                "({})",
+                keys.iter().map(|key| format!("cfg!({key})")).join(", ")
+            ),
+        }
+    }
+}
+
+impl Display for CfgScrutinee<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            CfgScrutinee::Key(key) => write!(f, "{}", CfgName(key)),
+            CfgScrutinee::KeyWithItem(key, item) => write!(f, "{}.{}", CfgName(key), CfgName(item)),
+            CfgScrutinee::Feature(feature) => write!(f, "feature.{}", CfgName(feature)),
+            CfgScrutinee::AllFeatures => write!(f, "feature"),
+        }
+    }
+}
+
+/// A cfg key or value, quoted unless it lexes as an identifier.
+struct CfgName<'a>(&'a str);
+
+impl Display for CfgName<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let mut chars = self.0.chars();
+        let is_ident = chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && self.0 != "_"
+            && matches!(Token::ident_or_kw(self.0), Token::Ident(_));
+        if is_ident {
+            write!(f, "{}", self.0)
+        } else {
+            write!(f, "\"{}\"", self.0)
+        }
+    }
+}
+
+impl Display for CfgPattern<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            CfgPattern::Single(item, _) => match item {
+                CfgPatternItem::Empty => write!(f, "_"),
+                CfgPatternItem::Some => write!(f, "Some"),
+                CfgPatternItem::None => write!(f, "None"),
+                CfgPatternItem::Str(s) => write!(f, "\"{s}\""),
+                CfgPatternItem::Number(n) => write!(f, "{n}"),
+            },
+            CfgPattern::And(patterns, _) => write!(f, "{}", patterns.iter().format(" & ")),
+            CfgPattern::Or(patterns, _) => write!(f, "{}", patterns.iter().format(" | ")),
+            CfgPattern::Not(pattern, _) => write!(f, "!{pattern}"),
+            CfgPattern::Grouped(pattern, _) => write!(f, "({pattern})"),
+            CfgPattern::Tuple(patterns, _) => write!(f, "({})", patterns.iter().format(", ")),
+        }
+    }
+}
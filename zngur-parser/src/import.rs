@@ -0,0 +1,280 @@
+use std::{
+    cell::OnceCell,
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::ImportResolver;
+
+/// Where `merge` looks for the files that are not relative to the current file.
+#[derive(Debug, Clone, Default)]
+pub struct ImportOptions {
+    include_dirs: Vec<PathBuf>,
+    packages: HashMap<String, PathBuf>,
+}
+
+impl ImportOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a directory which is searched for paths without a leading `.` or `..`, like
+    /// `merge "std_types.zng";`. Directories are searched in the order they are added.
+    pub fn with_include_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.include_dirs.push(dir.into());
+        self
+    }
+
+    /// Sets the directory of a package, for paths like `merge "@mycrate/foo.zng";`. Packages
+    /// without a directory are looked up with `cargo metadata`.
+    pub fn with_package(mut self, name: impl Into<String>, dir: impl Into<PathBuf>) -> Self {
+        self.packages.insert(name.into(), dir.into());
+        self
+    }
+
+    pub(crate) fn has_include_dirs(&self) -> bool {
+        !self.include_dirs.is_empty()
+    }
+}
+
+/// Finds the files of `merge` paths. A parse uses a single search, so that `cargo metadata` runs
+/// at most once for the packages.
+pub struct ImportSearch {
+    options: ImportOptions,
+    /// The directory `cargo metadata` runs in, which is the directory of the top-level file.
+    root_dir: PathBuf,
+    cargo_packages: OnceCell<Result<HashMap<String, PathBuf>, String>>,
+}
+
+/// A file found for a `merge` path.
+pub struct ResolvedImport {
+    pub path: PathBuf,
+    pub text: String,
+}
+
+impl ImportSearch {
+    /// Creates a search for the paths of `options`. Packages not in `options` are looked up with
+    /// `cargo metadata` in `root_dir`, which is the directory of the top-level file.
+    pub fn new(options: ImportOptions, root_dir: PathBuf) -> Self {
+        Self {
+            options,
+            root_dir,
+            cargo_packages: OnceCell::new(),
+        }
+    }
+
+    pub(crate) fn options(&self) -> &ImportOptions {
+        &self.options
+    }
+
+    /// Finds the files of the `merge` path `path` in a file in the directory `cwd`.
+    pub fn resolve(
+        &self,
+        resolver: &impl ImportResolver,
+        cwd: &Path,
+        path: &Path,
+    ) -> Result<Vec<ResolvedImport>, String> {
+        let (bases, relpath) = self.search_dirs(cwd, path)?;
+        if is_glob(relpath) {
+            for base in &bases {
+                let Ok(matches) = resolver.glob_import(base, relpath) else {
+                    continue;
+                };
+                if matches.is_empty() {
+                    continue;
+                }
+                return matches
+                    .into_iter()
+                    .map(|m| {
+                        let text = resolver.resolve_import(base, &m)?;
+                        Ok(ResolvedImport {
+                            path: base.join(m),
+                            text,
+                        })
+                    })
+                    .collect();
+            }
+            return Err(format!("No files match {}", path.display()));
+        }
+        for base in &bases {
+            if let Ok(text) = resolver.resolve_import(base, relpath) {
+                return Ok(vec![ResolvedImport {
+                    path: base.join(relpath),
+                    text,
+                }]);
+            }
+        }
+        Err(format!("Import path not found: {}", path.display()))
+    }
+
+    /// Returns the directories that `path` may be relative to, in order, and the part of `path`
+    /// which is relative to them.
+    fn search_dirs<'p>(
+        &self,
+        cwd: &Path,
+        path: &'p Path,
+    ) -> Result<(Vec<PathBuf>, &'p Path), String> {
+        let mut components = path.components();
+        match components.next() {
+            Some(Component::CurDir | Component::ParentDir) => Ok((vec![cwd.to_owned()], path)),
+            Some(Component::Normal(first)) => {
+                match first.to_str().and_then(|s| s.strip_prefix('@')) {
+                    Some(package) => Ok((vec![self.package_dir(package)?], components.as_path())),
+                    None => Ok((self.options.include_dirs.clone(), path)),
+                }
+            }
+            _ => Err(format!("Import path not found: {}", path.display())),
+        }
+    }
+
+    fn package_dir(&self, name: &str) -> Result<PathBuf, String> {
+        if let Some(dir) = self.options.packages.get(name) {
+            return Ok(dir.clone());
+        }
+        let packages = self
+            .cargo_packages
+            .get_or_init(|| cargo_packages(&self.root_dir))
+            .as_ref()
+            .map_err(|e| format!("Couldn't find package `{name}`: {e}"))?;
+        packages
+            .get(name)
+            .or_else(|| packages.get(&name.replace('_', "-")))
+            .cloned()
+            .ok_or_else(|| format!("Unknown package `{name}`"))
+    }
+}
+
+/// Lists the directories of the packages in the dependency graph of the cargo workspace
+/// containing `dir`.
+#[cfg(feature = "cargo-metadata")]
+fn cargo_packages(dir: &Path) -> Result<HashMap<String, PathBuf>, String> {
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let output = std::process::Command::new(cargo)
+        .args(["metadata", "--format-version", "1"])
+        .current_dir(dir)
+        .output()
+        .map_err(|e| format!("failed to run `cargo metadata`: {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "`cargo metadata` failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let metadata: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("invalid `cargo metadata` output: {e}"))?;
+    let packages = metadata["packages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|package| {
+            let name = package["name"].as_str()?;
+            let manifest = Path::new(package["manifest_path"].as_str()?);
+            Some((name.to_owned(), manifest.parent()?.to_owned()))
+        })
+        .collect();
+    Ok(packages)
+}
+
+#[cfg(not(feature = "cargo-metadata"))]
+fn cargo_packages(_dir: &Path) -> Result<HashMap<String, PathBuf>, String> {
+    Err(
+        "packages are only looked up with `cargo metadata` with the `cargo-metadata` feature, \
+set the directory of the package instead"
+            .to_owned(),
+    )
+}
+
+fn is_glob(path: &Path) -> bool {
+    path.to_str().is_some_and(|s| s.contains(['*', '?']))
+}
+
+/// Expands a glob like `types/*.zng` relative to `cwd` on the filesystem. The returned paths are
+/// relative to `cwd` and sorted.
+pub fn glob_fs(cwd: &Path, pattern: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut paths = vec![PathBuf::new()];
+    for component in pattern.components() {
+        let component = component.as_os_str();
+        let Some(component_pattern) = component.to_str().filter(|c| c.contains(['*', '?'])) else {
+            for path in &mut paths {
+                path.push(component);
+            }
+            continue;
+        };
+        let mut next = vec![];
+        for path in paths {
+            let Ok(entries) = std::fs::read_dir(cwd.join(&path)) else {
+                continue;
+            };
+            for entry in entries {
+                let entry = entry.map_err(|e| e.to_string())?;
+                let name = entry.file_name();
+                if name
+                    .to_str()
+                    .is_some_and(|n| wildcard_match(component_pattern, n))
+                {
+                    next.push(path.join(name));
+                }
+            }
+        }
+        paths = next;
+    }
+    paths.retain(|path| cwd.join(path).is_file());
+    paths.sort();
+    Ok(paths)
+}
+
+/// Whether `path` matches the glob `pattern`. Wildcards match within a single path component.
+#[cfg(test)]
+pub(crate) fn glob_matches(pattern: &Path, path: &Path) -> bool {
+    let pattern = pattern.components().collect::<Vec<_>>();
+    let path = path.components().collect::<Vec<_>>();
+    pattern.len() == path.len()
+        && pattern.iter().zip(&path).all(|(p, c)| {
+            match (p.as_os_str().to_str(), c.as_os_str().to_str()) {
+                (Some(p), Some(c)) => wildcard_match(p, c),
+                _ => p == c,
+            }
+        })
+}
+
+/// Matches a single path component, where `*` matches any sequence of characters and `?` matches
+/// any single character. Names starting with `.` are only matched by patterns starting with `.`.
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some('?') => {
+                p += 1;
+                n += 1;
+            }
+            Some(c) if *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
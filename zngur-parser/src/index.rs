@@ -0,0 +1,502 @@
+//! A syntactic index of a single `.zng` file, used by editor tooling.
+//!
+//! Building an index never exits the process and does not follow `merge` or `import`
+//! statements. Every branch of `#if` and `#match` blocks is indexed, regardless of which
+//! one the active cfg would select.
+
+use std::{ops::Range, path::PathBuf};
+
+use itertools::Itertools;
+use zngur_def::{RustPathAndGenerics, RustTrait, RustType, ZngurWellknownTrait};
+
+use crate::{
+    ParsedAlias, ParsedExternCppItem, ParsedItem, ParsedMethod, ParsedPath,
+    ParsedRustPathAndGenerics, ParsedRustTrait, ParsedRustType, ParsedTypeItem, Scope, Span,
+    Spanned, SyntaxError, layout_expr::ParsedLayoutExpr, parse_syntax,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefinitionKind {
+    Type,
+    Trait,
+    Fn,
+    Alias,
+    Mod,
+}
+
+/// A declaration in a `.zng` file.
+#[derive(Debug, Clone)]
+pub struct Definition {
+    pub kind: DefinitionKind,
+    /// The fully resolved path of the declared item, e.g. `["std", "vec", "Vec"]`.
+    ///
+    /// For aliases, this is the path the alias expands to.
+    pub path: Vec<String>,
+    /// The declaration as it would be written with every path resolved, e.g. `type ::std::vec::Vec::<i32>`.
+    pub label: String,
+    /// Directives that summarize the item, such as the layout policy of a type.
+    pub detail: Option<String>,
+    pub span: Range<usize>,
+}
+
+/// A path that refers to an item declared somewhere else.
+#[derive(Debug, Clone)]
+pub struct Reference {
+    /// The fully resolved path, after alias expansion.
+    pub path: Vec<String>,
+    pub span: Range<usize>,
+    /// The span of the `use ... as ...;` alias used to resolve this path, if any.
+    pub alias: Option<Range<usize>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkKind {
+    Merge,
+    Import,
+}
+
+/// A `merge` or `import` statement.
+#[derive(Debug, Clone)]
+pub struct FileLink {
+    pub kind: LinkKind,
+    /// The path as written, relative to the directory of the indexed file.
+    pub path: PathBuf,
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FileIndex {
+    pub definitions: Vec<Definition>,
+    pub references: Vec<Reference>,
+    pub links: Vec<FileLink>,
+    /// Lexer and parser errors. The items which could still be parsed are in the other lists.
+    pub errors: Vec<SyntaxError>,
+}
+
+impl FileIndex {
+    pub fn build(text: &str) -> FileIndex {
+        let mut index = FileIndex::default();
+        // Malformed items are skipped while parsing, so the rest of the file is still indexed.
+        let (_, errors) = parse_syntax(text, |ast, _| {
+            let items = ast.0.iter().map(|item| &item.inner);
+            let scope = Scope::new_root(collect_aliases(items.clone().collect()));
+            index.add_items(items, &scope);
+        });
+        index.errors = errors;
+        index
+    }
+
+    /// The innermost reference containing `offset`.
+    pub fn reference_at(&self, offset: usize) -> Option<&Reference> {
+        self.references
+            .iter()
+            .filter(|r| r.span.contains(&offset))
+            .min_by_key(|r| r.span.len())
+    }
+
+    pub fn definition_at(&self, offset: usize) -> Option<&Definition> {
+        self.definitions
+            .iter()
+            .filter(|d| d.span.contains(&offset))
+            .min_by_key(|d| d.span.len())
+    }
+
+    pub fn link_at(&self, offset: usize) -> Option<&FileLink> {
+        self.links.iter().find(|l| l.span.contains(&offset))
+    }
+
+    /// Types, traits and functions declared with exactly this path.
+    pub fn definitions_of(&self, path: &[String]) -> Vec<&Definition> {
+        self.definitions
+            .iter()
+            .filter(|d| {
+                matches!(
+                    d.kind,
+                    DefinitionKind::Type | DefinitionKind::Trait | DefinitionKind::Fn
+                ) && d.path == path
+            })
+            .collect()
+    }
+
+    fn add_items<'a, 'b>(
+        &mut self,
+        items: impl Iterator<Item = &'b ParsedItem<'a>>,
+        scope: &Scope<'a>,
+    ) where
+        'a: 'b,
+    {
+        for item in items {
+            self.add_item(item, scope);
+        }
+    }
+
+    fn add_item<'a>(&mut self, item: &ParsedItem<'a>, scope: &Scope<'a>) {
+        match item {
+            ParsedItem::ConvertPanicToException(_)
+            | ParsedItem::CppAdditionalInclude(_)
+            | ParsedItem::UnstableFeature(_) => {}
+            ParsedItem::Mod { path, items } => {
+                let sub_scope = scope.sub_scope(
+                    &collect_aliases(items.iter().map(|item| &item.inner).collect()),
+                    path.clone(),
+                );
+                self.definitions.push(Definition {
+                    kind: DefinitionKind::Mod,
+                    label: format!("mod {}", display_path(&sub_scope.base)),
+                    path: sub_scope.base.clone(),
+                    detail: None,
+                    span: path.span.into_range(),
+                });
+                self.add_items(items.iter().map(|item| &item.inner), &sub_scope);
+            }
+            ParsedItem::Type {
+                ty,
+                template: None,
+                items,
+                ..
+            } => {
+                self.add_type_definition(&ty.inner, ty.span, type_detail(items), scope);
+                match &ty.inner {
+                    // The path of the declared type is the definition itself.
+                    ParsedRustType::Adt(pg) => self.add_generics(pg, scope),
+                    other => self.add_type(other, scope),
+                }
+                for item in items {
+                    self.add_type_item(&item.inner, scope);
+                }
+            }
+            ParsedItem::Type {
+                ty,
+                template: Some(template),
+                items,
+                ..
+            } => {
+                // Every instance declares its own type, at its place in the list of instances.
+                for instance in &template.instances {
+                    let instance_scope = scope
+                        .with_type_param(template.param, instance.ty.inner.clone().to_zngur(scope));
+                    let detail = match &instance.layout {
+                        Some((_, layout)) => Some(layout.to_string()),
+                        None => type_detail(items),
+                    };
+                    self.add_type_definition(&ty.inner, instance.ty.span, detail, &instance_scope);
+                    self.add_type(&instance.ty.inner, scope);
+                }
+                // The parameter is not a reference to anything, whatever type it stands for.
+                let template_scope = scope.with_type_param(template.param, RustType::Tuple(vec![]));
+                match &ty.inner {
+                    ParsedRustType::Adt(pg) => self.add_generics(pg, &template_scope),
+                    other => self.add_type(other, &template_scope),
+                }
+                for item in items {
+                    self.add_type_item(&item.inner, &template_scope);
+                }
+            }
+            ParsedItem::Trait { tr, methods } => {
+                let resolved = tr.inner.clone().to_zngur(scope);
+                let path = match &resolved {
+                    RustTrait::Normal(pg) => pg.path.clone(),
+                    RustTrait::Fn { .. } => vec![],
+                };
+                self.definitions.push(Definition {
+                    kind: DefinitionKind::Trait,
+                    path,
+                    label: format!("trait {resolved}"),
+                    detail: None,
+                    span: tr.span.into_range(),
+                });
+                match &tr.inner {
+                    ParsedRustTrait::Normal(pg) => self.add_generics(pg, scope),
+                    other => self.add_trait(other, scope),
+                }
+                for method in methods {
+                    self.add_method(&method.inner, scope);
+                }
+            }
+            ParsedItem::Fn(method) => {
+                let path = scope.simple_relative_path(method.inner.name);
+                self.definitions.push(Definition {
+                    kind: DefinitionKind::Fn,
+                    label: format!("fn {}", display_path(&path)),
+                    path,
+                    detail: None,
+                    span: method.span.into_range(),
+                });
+                self.add_method(&method.inner, scope);
+            }
+            ParsedItem::TraitImpl { tr, ty } => {
+                self.add_trait(&tr.inner, scope);
+                self.add_type(&ty.inner, scope);
+            }
+            ParsedItem::ExternCpp(items) => {
+                for item in items {
+                    match &item.inner {
+                        ParsedExternCppItem::Function { method, .. } => {
+                            self.add_method(&method.inner, scope)
+                        }
+                        ParsedExternCppItem::Impl { tr, ty, methods } => {
+                            if let Some(tr) = tr {
+                                self.add_trait(tr, scope);
+                            }
+                            self.add_type(&ty.inner, scope);
+                            for method in methods {
+                                self.add_method(&method.inner.1, scope);
+                            }
+                        }
+                        ParsedExternCppItem::Error => {}
+                    }
+                }
+            }
+            ParsedItem::Alias(alias) => {
+                let path = alias.path.clone().to_zngur(&scope.base);
+                self.definitions.push(Definition {
+                    kind: DefinitionKind::Alias,
+                    label: format!("use {} as {}", display_path(&path), alias.name),
+                    path: path.clone(),
+                    detail: None,
+                    span: alias.span.into_range(),
+                });
+                self.references.push(Reference {
+                    path,
+                    span: alias.path.span.into_range(),
+                    alias: None,
+                });
+            }
+            ParsedItem::Import(import) => self.links.push(FileLink {
+                kind: LinkKind::Merge,
+                path: import.path.clone(),
+                span: import.span.into_range(),
+            }),
+            ParsedItem::ModuleImport { path, span } => self.links.push(FileLink {
+                kind: LinkKind::Import,
+                path: path.clone(),
+                span: span.into_range(),
+            }),
+            ParsedItem::MatchOnCfg(match_) => {
+                for item in match_.all_branch_items() {
+                    self.add_item(&item.inner, scope);
+                }
+            }
+            ParsedItem::Error => {}
+        }
+    }
+
+    fn add_type_item<'a>(&mut self, item: &ParsedTypeItem<'a>, scope: &Scope<'a>) {
+        match item {
+            ParsedTypeItem::Layout(
+                _,
+                crate::ParsedLayoutPolicy::StackAllocated(props)
+                | crate::ParsedLayoutPolicy::Conservative(props),
+            )
+            | ParsedTypeItem::CppStackOwned { props, .. } => {
+                for (_, value) in props {
+                    for ty in value.inner.types() {
+                        self.add_type(ty, scope);
+                    }
+                }
+            }
+            ParsedTypeItem::Layout(..)
+            | ParsedTypeItem::Error
+            | ParsedTypeItem::Traits(_)
+            | ParsedTypeItem::ResultMode(_)
+            | ParsedTypeItem::CppValue { .. }
+            | ParsedTypeItem::CppRef { .. } => {}
+            ParsedTypeItem::Constructor { args, .. } | ParsedTypeItem::Variant { args, .. } => {
+                match args {
+                    crate::ParsedConstructorArgs::Unit => {}
+                    crate::ParsedConstructorArgs::Tuple(tys) => {
+                        for ty in tys {
+                            self.add_type(ty, scope);
+                        }
+                    }
+                    crate::ParsedConstructorArgs::Named(tys) => {
+                        for (_, ty) in tys {
+                            self.add_type(ty, scope);
+                        }
+                    }
+                }
+            }
+            ParsedTypeItem::Field { ty, .. } => self.add_type(ty, scope),
+            ParsedTypeItem::Method {
+                data,
+                use_path,
+                deref,
+            } => {
+                self.add_method(data, scope);
+                if let Some(use_path) = use_path {
+                    self.add_path(use_path, scope);
+                }
+                if let Some(deref) = deref {
+                    self.add_type(deref, scope);
+                }
+            }
+            ParsedTypeItem::TraitImpl { tr, ty } => {
+                self.add_trait(tr, scope);
+                if let Some(ty) = ty {
+                    self.add_type(&ty.inner, scope);
+                }
+            }
+            ParsedTypeItem::MatchOnCfg(match_) => {
+                for item in match_.all_branch_items() {
+                    self.add_type_item(&item.inner, scope);
+                }
+            }
+        }
+    }
+
+    fn add_method<'a>(&mut self, method: &ParsedMethod<'a>, scope: &Scope<'a>) {
+        for ty in method
+            .generics
+            .iter()
+            .chain(&method.inputs)
+            .chain([&method.output])
+        {
+            self.add_type(ty, scope);
+        }
+    }
+
+    fn add_type_definition<'a>(
+        &mut self,
+        ty: &ParsedRustType<'a>,
+        span: Span,
+        detail: Option<String>,
+        scope: &Scope<'a>,
+    ) {
+        let resolved = ty.clone().to_zngur(scope);
+        let path = match &resolved {
+            RustType::Adt(pg) => pg.path.clone(),
+            _ => vec![],
+        };
+        self.definitions.push(Definition {
+            kind: DefinitionKind::Type,
+            path,
+            label: format!("type {resolved}"),
+            detail,
+            span: span.into_range(),
+        });
+    }
+
+    fn add_type<'a>(&mut self, ty: &ParsedRustType<'a>, scope: &Scope<'a>) {
+        match ty {
+            ParsedRustType::Primitive(_) => {}
+            ParsedRustType::Ref(_, ty)
+            | ParsedRustType::Raw(_, ty)
+            | ParsedRustType::Boxed(ty)
+            | ParsedRustType::Slice(ty)
+            | ParsedRustType::Array(ty, _) => self.add_type(ty, scope),
+            ParsedRustType::Dyn(tr, _) | ParsedRustType::Impl(tr, _) => self.add_trait(tr, scope),
+            ParsedRustType::Tuple(tys) => {
+                for ty in tys {
+                    self.add_type(ty, scope);
+                }
+            }
+            ParsedRustType::FnPtr { inputs, output, .. } => {
+                for ty in inputs.iter().chain(Some(&**output)) {
+                    self.add_type(ty, scope);
+                }
+            }
+            ParsedRustType::Adt(pg) if scope.type_param(pg).is_some() => {}
+            ParsedRustType::Adt(pg) => {
+                self.add_path(&pg.path, scope);
+                self.add_generics(pg, scope);
+            }
+        }
+    }
+
+    fn add_trait<'a>(&mut self, tr: &ParsedRustTrait<'a>, scope: &Scope<'a>) {
+        match tr {
+            ParsedRustTrait::Normal(pg) => {
+                self.add_path(&pg.path, scope);
+                self.add_generics(pg, scope);
+            }
+            ParsedRustTrait::Fn { inputs, output, .. } => {
+                for ty in inputs.iter().chain([output.as_ref()]) {
+                    self.add_type(ty, scope);
+                }
+            }
+        }
+    }
+
+    fn add_generics<'a>(&mut self, pg: &ParsedRustPathAndGenerics<'a>, scope: &Scope<'a>) {
+        for ty in pg
+            .generics
+            .iter()
+            .chain(pg.named_generics.iter().map(|(_, ty)| ty))
+        {
+            self.add_type(ty, scope);
+        }
+    }
+
+    fn add_path<'a>(&mut self, path: &ParsedPath<'a>, scope: &Scope<'a>) {
+        self.references.push(Reference {
+            path: scope.resolve_path(path.clone()),
+            span: path.span.into_range(),
+            alias: scope.alias_for(path).map(|alias| alias.span.into_range()),
+        });
+    }
+}
+
+/// Formats a resolved path the way it is written in a `.zng` file.
+pub fn display_path(path: &[String]) -> String {
+    RustPathAndGenerics {
+        path: path.to_vec(),
+        generics: vec![],
+        named_generics: vec![],
+    }
+    .to_string()
+}
+
+/// Aliases declared directly in a list of items, including the ones inside conditional blocks.
+fn collect_aliases<'a>(items: Vec<&ParsedItem<'a>>) -> Vec<ParsedAlias<'a>> {
+    let mut aliases = vec![];
+    for item in items {
+        match item {
+            ParsedItem::Alias(alias) => aliases.push(alias.clone()),
+            ParsedItem::MatchOnCfg(match_) => aliases.extend(collect_aliases(
+                match_
+                    .all_branch_items()
+                    .into_iter()
+                    .map(|item| &item.inner)
+                    .collect(),
+            )),
+            _ => {}
+        }
+    }
+    aliases
+}
+
+fn type_detail(items: &[Spanned<ParsedTypeItem<'_>>]) -> Option<String> {
+    let props = |props: &[(Spanned<&str>, Spanned<ParsedLayoutExpr>)]| {
+        props
+            .iter()
+            .map(|(key, value)| format!("{} = {}", key.inner, value.inner))
+            .join(", ")
+    };
+    let lines = items
+        .iter()
+        .filter_map(|item| match &item.inner {
+            ParsedTypeItem::Layout(_, policy) => Some(policy.to_string()),
+            ParsedTypeItem::Traits(traits) => Some(format!(
+                "wellknown_traits({})",
+                traits
+                    .iter()
+                    .map(|t| match t.inner {
+                        ZngurWellknownTrait::Debug => "Debug",
+                        ZngurWellknownTrait::Copy => "Copy",
+                        ZngurWellknownTrait::Unsized => "?Sized",
+                        ZngurWellknownTrait::Drop => "Drop",
+                    })
+                    .join(", ")
+            )),
+            ParsedTypeItem::CppValue { field, cpp_type } => {
+                Some(format!("#cpp_value \"{field}\" \"{cpp_type}\""))
+            }
+            ParsedTypeItem::CppRef { cpp_type } => Some(format!("#cpp_ref \"{cpp_type}\"")),
+            ParsedTypeItem::CppStackOwned { cpp_type, props: p } => {
+                Some(format!("#cpp_stack_owned \"{cpp_type}\"({})", props(p)))
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
@@ -0,0 +1,421 @@
+//! Arithmetic in the properties of `#layout(...)`, like `#layout(size = 3 * usize, align = usize)`.
+
+use std::path::PathBuf;
+
+use chumsky::prelude::*;
+use zngur_def::{LayoutPolicy, PrimitiveRustType, RustType, ZngurSpec, ZngurWellknownTrait};
+
+use crate::{
+    ParseContext, ParsedRustType, Scope, Span, Spanned, Token, ZngParser, cfg::RustCfgProvider,
+    diagnostics::Diagnostic, rust_type, spanned,
+};
+
+/// The value of a layout property, as written in the zng file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ParsedLayoutExpr<'a> {
+    Number(usize),
+    /// A type on its own, which is its size in `size = ...` and its align in `align = ...`.
+    Type(Spanned<ParsedRustType<'a>>),
+    SizeOf(Spanned<ParsedRustType<'a>>),
+    AlignOf(Spanned<ParsedRustType<'a>>),
+    Add(Box<ParsedLayoutExpr<'a>>, Box<ParsedLayoutExpr<'a>>),
+    Mul(Box<ParsedLayoutExpr<'a>>, Box<ParsedLayoutExpr<'a>>),
+}
+
+pub(crate) fn layout_expr<'a>() -> impl ZngParser<'a, ParsedLayoutExpr<'a>> {
+    recursive(|expr| {
+        let in_parens =
+            |ty| spanned(ty).delimited_by(just(Token::ParenOpen), just(Token::ParenClose));
+        let atom = choice((
+            select! {
+                Token::Number(n) => ParsedLayoutExpr::Number(n),
+            },
+            just(Token::Ident("sizeof"))
+                .ignore_then(in_parens(rust_type()))
+                .map(ParsedLayoutExpr::SizeOf),
+            just(Token::Ident("alignof"))
+                .ignore_then(in_parens(rust_type()))
+                .map(ParsedLayoutExpr::AlignOf),
+            expr.delimited_by(just(Token::ParenOpen), just(Token::ParenClose)),
+            spanned(rust_type()).map(ParsedLayoutExpr::Type),
+        ));
+        let product = atom.clone().foldl(
+            just(Token::Star).ignore_then(atom).repeated(),
+            |lhs, rhs| ParsedLayoutExpr::Mul(Box::new(lhs), Box::new(rhs)),
+        );
+        product.clone().foldl(
+            just(Token::Plus).ignore_then(product).repeated(),
+            |lhs, rhs| ParsedLayoutExpr::Add(Box::new(lhs), Box::new(rhs)),
+        )
+    })
+    .boxed()
+}
+
+impl<'a> ParsedLayoutExpr<'a> {
+    /// The types which this expression refers to.
+    pub(crate) fn types(&self) -> Vec<&ParsedRustType<'a>> {
+        match self {
+            ParsedLayoutExpr::Number(_) => vec![],
+            ParsedLayoutExpr::Type(ty)
+            | ParsedLayoutExpr::SizeOf(ty)
+            | ParsedLayoutExpr::AlignOf(ty) => vec![&ty.inner],
+            ParsedLayoutExpr::Add(lhs, rhs) | ParsedLayoutExpr::Mul(lhs, rhs) => {
+                let mut types = lhs.types();
+                types.extend(rhs.types());
+                types
+            }
+        }
+    }
+
+    /// `property` is the name of the property, which decides the meaning of a type on its own.
+    #[allow(clippy::wrong_self_convention)]
+    pub(crate) fn to_zngur(self, property: &str, scope: &Scope<'_>) -> LayoutExpr {
+        let lower = |x: Box<Self>| Box::new(x.to_zngur(property, scope));
+        match self {
+            ParsedLayoutExpr::Number(n) => LayoutExpr::Number(n),
+            ParsedLayoutExpr::Type(ty) if property == "align" => {
+                LayoutExpr::AlignOf(ty.inner.to_zngur(scope), ty.span)
+            }
+            ParsedLayoutExpr::Type(ty) | ParsedLayoutExpr::SizeOf(ty) => {
+                LayoutExpr::SizeOf(ty.inner.to_zngur(scope), ty.span)
+            }
+            ParsedLayoutExpr::AlignOf(ty) => LayoutExpr::AlignOf(ty.inner.to_zngur(scope), ty.span),
+            ParsedLayoutExpr::Add(lhs, rhs) => LayoutExpr::Add(lower(lhs), lower(rhs)),
+            ParsedLayoutExpr::Mul(lhs, rhs) => LayoutExpr::Mul(lower(lhs), lower(rhs)),
+        }
+    }
+}
+
+/// A layout property with the type paths resolved, ready to be evaluated.
+#[derive(Debug, Clone)]
+pub(crate) enum LayoutExpr {
+    Number(usize),
+    SizeOf(RustType, Span),
+    AlignOf(RustType, Span),
+    Add(Box<LayoutExpr>, Box<LayoutExpr>),
+    Mul(Box<LayoutExpr>, Box<LayoutExpr>),
+}
+
+#[derive(Debug)]
+pub(crate) enum LayoutExprError {
+    /// The layout of the type is not known, at least not yet.
+    Unknown(RustType, Span),
+    /// The layout of the type depends on the pointer width, which is not in the cfg.
+    NeedsPointerWidth(RustType, Span),
+    Overflow,
+}
+
+impl LayoutExpr {
+    fn eval(&self, layouts: &KnownLayouts<'_>) -> Result<usize, LayoutExprError> {
+        let layout_of = |ty: &RustType, span: &Span| {
+            layouts.layout_of(ty).map_err(|e| match e {
+                LayoutError::Unknown => LayoutExprError::Unknown(ty.clone(), *span),
+                LayoutError::NeedsPointerWidth => {
+                    LayoutExprError::NeedsPointerWidth(ty.clone(), *span)
+                }
+            })
+        };
+        match self {
+            LayoutExpr::Number(n) => Ok(*n),
+            LayoutExpr::SizeOf(ty, span) => Ok(layout_of(ty, span)?.0),
+            LayoutExpr::AlignOf(ty, span) => Ok(layout_of(ty, span)?.1),
+            LayoutExpr::Add(lhs, rhs) => lhs
+                .eval(layouts)?
+                .checked_add(rhs.eval(layouts)?)
+                .ok_or(LayoutExprError::Overflow),
+            LayoutExpr::Mul(lhs, rhs) => lhs
+                .eval(layouts)?
+                .checked_mul(rhs.eval(layouts)?)
+                .ok_or(LayoutExprError::Overflow),
+        }
+    }
+}
+
+enum LayoutError {
+    Unknown,
+    NeedsPointerWidth,
+}
+
+/// The layouts which the layout expressions can refer to.
+#[derive(Default)]
+pub(crate) struct KnownLayouts<'a> {
+    /// The cfg with the pointer width. It is only read for the layouts which need it, so the other
+    /// layouts don't depend on the cfg.
+    cfg: Option<&'a dyn RustCfgProvider>,
+    /// The types declared with `#layout(size = X, align = Y)` are known.
+    spec: Option<&'a ZngurSpec>,
+    /// The types of the spec whose layout is not evaluated yet.
+    pending: &'a [PendingLayout],
+}
+
+impl KnownLayouts<'_> {
+    fn layout_of(&self, ty: &RustType) -> Result<(usize, usize), LayoutError> {
+        let pointer = || {
+            let width = self
+                .cfg
+                .and_then(pointer_width)
+                .ok_or(LayoutError::NeedsPointerWidth)?;
+            Ok((width, width))
+        };
+        match ty {
+            RustType::Primitive(
+                PrimitiveRustType::Uint(bits)
+                | PrimitiveRustType::Int(bits)
+                | PrimitiveRustType::Float(bits),
+            ) if *bits <= 32 => Ok((*bits as usize / 8, *bits as usize / 8)),
+            RustType::Primitive(PrimitiveRustType::Bool) => Ok((1, 1)),
+            RustType::Primitive(PrimitiveRustType::Char) => Ok((4, 4)),
+            RustType::Primitive(PrimitiveRustType::Usize) | RustType::FnPtr { .. } => pointer(),
+            RustType::Ref(_, pointee) | RustType::Raw(_, pointee) | RustType::Boxed(pointee) => {
+                let (size, align) = pointer()?;
+                if self.is_unsized(pointee) {
+                    Ok((2 * size, align))
+                } else {
+                    Ok((size, align))
+                }
+            }
+            RustType::Tuple(fields) if fields.is_empty() => Ok((0, 1)),
+            RustType::Array(ty, len) => {
+                let (size, align) = self.layout_of(ty)?;
+                Ok((size.checked_mul(*len).ok_or(LayoutError::Unknown)?, align))
+            }
+            _ => {
+                if self
+                    .pending
+                    .iter()
+                    .any(|pending| pending.ty == *ty && pending.fills_layout())
+                {
+                    return Err(LayoutError::Unknown);
+                }
+                match self.declared(ty).map(|declared| &declared.layout) {
+                    Some(&LayoutPolicy::StackAllocated { size, align }) => Ok((size, align)),
+                    _ => Err(LayoutError::Unknown),
+                }
+            }
+        }
+    }
+
+    fn is_unsized(&self, ty: &RustType) -> bool {
+        match ty {
+            RustType::Primitive(PrimitiveRustType::Str)
+            | RustType::Slice(_)
+            | RustType::Dyn(..) => true,
+            _ => self.declared(ty).is_some_and(|declared| {
+                declared
+                    .wellknown_traits
+                    .contains(&ZngurWellknownTrait::Unsized)
+            }),
+        }
+    }
+
+    fn declared(&self, ty: &RustType) -> Option<&zngur_def::ZngurType> {
+        self.spec?.types.iter().find(|declared| declared.ty == *ty)
+    }
+}
+
+/// The layout of arrays of primitives that have the same layout on every target. The alignment
+/// of 64 and 128 bit integers differs between targets, so arrays of them need an explicit layout.
+pub(crate) fn inferred_array_layout(ty: &RustType) -> Option<LayoutPolicy> {
+    let RustType::Array(..) = ty else {
+        return None;
+    };
+    let (size, align) = KnownLayouts::default().layout_of(ty).ok()?;
+    Some(LayoutPolicy::StackAllocated { size, align })
+}
+
+/// The directive which a layout is written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LayoutTarget {
+    /// `#layout(...)`
+    Layout,
+    /// `#layout_conservative(...)`
+    Conservative,
+    /// `#cpp_stack_owned "..."(...)`
+    CppStackOwned,
+}
+
+/// A layout which refers to types whose layout is not known yet, evaluated after all the files
+/// are parsed.
+#[derive(Debug)]
+pub(crate) struct PendingLayout {
+    ty: RustType,
+    target: LayoutTarget,
+    /// For `#cpp_stack_owned`, whether it is the layout of the type as well, because the type
+    /// doesn't have a `#layout` of its own.
+    is_type_layout: bool,
+    path: PathBuf,
+    size: Spanned<LayoutExpr>,
+    align: Spanned<LayoutExpr>,
+}
+
+/// The size of a pointer in bytes.
+fn pointer_width(cfg: &dyn RustCfgProvider) -> Option<usize> {
+    let width = cfg.get_cfg("target_pointer_width")?;
+    Some(width.first()?.parse::<usize>().ok()? / 8)
+}
+
+impl ParseContext<'_> {
+    /// Evaluates the size and align of `ty`. If they refer to a type whose layout is not known
+    /// yet, this returns `(0, 0)` and they are evaluated in [`Self::resolve_pending_layouts`].
+    pub(crate) fn eval_layout(
+        &mut self,
+        ty: &RustType,
+        target: LayoutTarget,
+        size: Spanned<LayoutExpr>,
+        align: Spanned<LayoutExpr>,
+        spec: &ZngurSpec,
+    ) -> (usize, usize) {
+        let layouts = KnownLayouts {
+            cfg: Some(self.get_config_provider()),
+            spec: Some(spec),
+            pending: &self.pending_layouts,
+        };
+        let pending = PendingLayout {
+            ty: ty.clone(),
+            target,
+            is_type_layout: false,
+            path: self.path.clone(),
+            size,
+            align,
+        };
+        match pending.eval(&layouts) {
+            Ok(layout) => return layout,
+            Err(LayoutExprError::Unknown(..)) => self.pending_layouts.push(pending),
+            Err(e) => self.add_diagnostic(pending.error(e)),
+        }
+        (0, 0)
+    }
+
+    /// Marks the pending `#cpp_stack_owned` layout of `ty` as the layout of the type as well.
+    pub(crate) fn use_pending_cpp_stack_owned_as_layout(&mut self, ty: &RustType) {
+        if let Some(pending) = self
+            .pending_layouts
+            .iter_mut()
+            .rev()
+            .find(|pending| pending.ty == *ty && pending.target == LayoutTarget::CppStackOwned)
+        {
+            pending.is_type_layout = true;
+        }
+    }
+
+    /// Evaluates the layouts which refer to types declared after them, and fills them in the
+    /// spec.
+    pub(crate) fn resolve_pending_layouts(&mut self, spec: &mut ZngurSpec) {
+        let mut pending = std::mem::take(&mut self.pending_layouts);
+        // A layout might refer to another pending one, so this continues until nothing changes.
+        loop {
+            let count = pending.len();
+            let mut i = 0;
+            while i < pending.len() {
+                let layouts = KnownLayouts {
+                    cfg: Some(self.get_config_provider()),
+                    spec: Some(spec),
+                    pending: &pending,
+                };
+                match pending[i].eval(&layouts) {
+                    Ok(layout) => pending.remove(i).fill(spec, layout),
+                    Err(LayoutExprError::Unknown(..)) => i += 1,
+                    Err(e) => {
+                        let error = pending.remove(i).error(e);
+                        self.add_diagnostic(error);
+                    }
+                }
+            }
+            if pending.len() == count {
+                break;
+            }
+        }
+        // The rest refer to undeclared types, or to each other.
+        let layouts = KnownLayouts {
+            cfg: Some(self.get_config_provider()),
+            spec: Some(spec),
+            pending: &pending,
+        };
+        let errors = pending
+            .iter()
+            .filter_map(|layout| Some(layout.error(layout.eval(&layouts).err()?)))
+            .collect::<Vec<_>>();
+        for error in errors {
+            self.add_diagnostic(error);
+        }
+    }
+}
+
+impl PendingLayout {
+    fn fills_layout(&self) -> bool {
+        self.target != LayoutTarget::CppStackOwned || self.is_type_layout
+    }
+
+    fn eval(&self, layouts: &KnownLayouts<'_>) -> Result<(usize, usize), LayoutExprError> {
+        Ok((
+            self.size.inner.eval(layouts)?,
+            self.align.inner.eval(layouts)?,
+        ))
+    }
+
+    fn fill(self, spec: &mut ZngurSpec, (new_size, new_align): (usize, usize)) {
+        let Some(declared) = spec
+            .types
+            .iter_mut()
+            .find(|declared| declared.ty == self.ty)
+        else {
+            return;
+        };
+        match (
+            self.target,
+            &mut declared.layout,
+            &mut declared.cpp_stack_owned,
+        ) {
+            (LayoutTarget::Layout, LayoutPolicy::StackAllocated { size, align }, _)
+            | (LayoutTarget::Conservative, LayoutPolicy::Conservative { size, align }, _) => {
+                (*size, *align) = (new_size, new_align);
+            }
+            (LayoutTarget::CppStackOwned, layout, Some(cpp_stack_owned)) => {
+                (cpp_stack_owned.size, cpp_stack_owned.align) = (new_size, new_align);
+                if let (true, LayoutPolicy::StackAllocated { size, align }) =
+                    (self.is_type_layout, layout)
+                {
+                    (*size, *align) = (new_size, new_align);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn error(&self, error: LayoutExprError) -> Diagnostic {
+        let path = &self.path;
+        match error {
+            LayoutExprError::Unknown(ty, span) => Diagnostic::error(
+                path,
+                span.into_range(),
+                format!("The layout of `{ty}` is not known"),
+            )
+            .with_label(
+                path,
+                span.into_range(),
+                format!(
+                    "Declare `{ty}` with `#layout(size = X, align = Y)` to use its layout here"
+                ),
+            ),
+            LayoutExprError::NeedsPointerWidth(ty, span) => Diagnostic::error(
+                path,
+                span.into_range(),
+                format!("The layout of `{ty}` depends on the target"),
+            )
+            .with_label(
+                path,
+                span.into_range(),
+                "Provide the `target_pointer_width` cfg to use it here",
+            ),
+            LayoutExprError::Overflow => {
+                let span = self.size.span.start.min(self.align.span.start)
+                    ..self.size.span.end.max(self.align.span.end);
+                Diagnostic::error(path, span.clone(), "This layout is too large").with_label(
+                    path,
+                    span,
+                    "This overflows `usize`",
+                )
+            }
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! Rewriting the `#layout(...)` directives of the processed files to match the real layouts.
+
+use std::{collections::HashMap, ops::Range, path::PathBuf};
+
+use itertools::Itertools;
+use zngur_def::RustType;
+
+use crate::{
+    ParseResult,
+    diagnostics::{Diagnostic, Diagnostics},
+};
+
+/// A `#layout(...)` or `#layout_conservative(...)` of a type, in the active branches of the cfg
+/// conditions.
+#[derive(Debug, Clone)]
+pub struct LayoutDirective {
+    pub ty: RustType,
+    /// Whether this is a `#layout_conservative(...)`, whose numbers are upper bounds.
+    pub conservative: bool,
+    /// The file of the directive.
+    pub path: PathBuf,
+    /// Byte range of the size in the file, and its value.
+    pub size: (Range<usize>, usize),
+    /// Byte range of the align in the file, and its value.
+    pub align: (Range<usize>, usize),
+}
+
+/// A directive rewritten by [`ParseResult::fix_layouts`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutFix {
+    /// The type of the directive. For type templates, this is the first instance using it.
+    pub ty: RustType,
+    pub path: PathBuf,
+    /// The size and align before the fix.
+    pub old: (usize, usize),
+    /// The size and align after the fix.
+    pub new: (usize, usize),
+}
+
+/// The result of [`ParseResult::fix_layouts`].
+#[derive(Debug, Default)]
+pub struct FixedLayouts {
+    pub fixes: Vec<LayoutFix>,
+    /// The new text of the changed files.
+    pub files: Vec<(PathBuf, String)>,
+}
+
+impl ParseResult {
+    /// The layout directives of the types, in the active branches of the cfg conditions.
+    pub fn layout_directives(&self) -> &[LayoutDirective] {
+        &self.locations.layouts
+    }
+
+    /// Rewrites the numbers of the layout directives to the real size and align of their types,
+    /// keeping the rest of the files as they are.
+    ///
+    /// Directives of the types missing from `layouts` are kept. A `#layout_conservative(...)` is
+    /// only grown, since its numbers are upper bounds.
+    pub fn fix_layouts(
+        &self,
+        layouts: &HashMap<RustType, (usize, usize)>,
+    ) -> Result<FixedLayouts, Diagnostics> {
+        let mut diagnostics = vec![];
+        let mut fixed = FixedLayouts::default();
+        let mut edits: HashMap<&PathBuf, Vec<(Range<usize>, usize)>> = HashMap::new();
+        let directives = self
+            .locations
+            .layouts
+            .iter()
+            .sorted_by_key(|d| (&d.path, d.size.0.start));
+        // A directive in a type template is shared by all of its instances.
+        for (_, group) in &directives.group_by(|d| (&d.path, d.size.0.start)) {
+            let group = group.collect::<Vec<_>>();
+            let directive = group[0];
+            let probed = group
+                .iter()
+                .filter_map(|d| Some((&d.ty, *layouts.get(&d.ty)?)))
+                .collect::<Vec<_>>();
+            let Some(&(first_ty, new)) = probed.first() else {
+                continue;
+            };
+            if let Some(&(other_ty, other)) = probed.iter().find(|(_, layout)| *layout != new) {
+                let ((size, align), (other_size, other_align)) = (new, other);
+                let span = directive.size.0.start.min(directive.align.0.start)
+                    ..directive.size.0.end.max(directive.align.0.end);
+                diagnostics.push(
+                    Diagnostic::error(
+                        &directive.path,
+                        span.clone(),
+                        "Types with different layouts share this directive",
+                    )
+                    .with_label(
+                        &directive.path,
+                        span,
+                        format!(
+                            "`{first_ty}` has size {size} and align {align}, but `{other_ty}` has size {other_size} and align {other_align}"
+                        ),
+                    ),
+                );
+                continue;
+            }
+            let old = (directive.size.1, directive.align.1);
+            let new = if directive.conservative {
+                (old.0.max(new.0), old.1.max(new.1))
+            } else {
+                new
+            };
+            if new == old {
+                continue;
+            }
+            edits.entry(&directive.path).or_default().extend([
+                (directive.size.0.clone(), new.0),
+                (directive.align.0.clone(), new.1),
+            ]);
+            fixed.fixes.push(LayoutFix {
+                ty: first_ty.clone(),
+                path: directive.path.clone(),
+                old,
+                new,
+            });
+        }
+        if !diagnostics.is_empty() {
+            return Err(Diagnostics::new(diagnostics, self.sources.clone()));
+        }
+        for (path, edits) in edits.into_iter().sorted_by_key(|(path, _)| *path) {
+            let mut text = self.sources[path].clone();
+            // Editing from the end keeps the earlier ranges valid.
+            for (range, value) in edits
+                .into_iter()
+                .sorted_by_key(|(range, _)| range.start)
+                .rev()
+            {
+                text.replace_range(range, &value.to_string());
+            }
+            fixed.files.push((path.clone(), text));
+        }
+        Ok(fixed)
+    }
+}
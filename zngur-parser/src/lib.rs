@@ -1,9 +1,8 @@
-use std::{collections::HashMap, fmt::Display, path::Component};
+use std::{collections::HashMap, fmt::Display, path::Component, rc::Rc};
 
 #[cfg(not(test))]
 use std::process::exit;
 
-use ariadne::{Color, Label, Report, ReportKind, sources};
 use chumsky::prelude::*;
 use itertools::{Either, Itertools};
 
@@ -12,18 +11,119 @@ use zngur_def::{
     LayoutPolicy, Merge, MergeFailure, ModuleImport, Mutability, PrimitiveRustType,
     RustPathAndGenerics, RustTrait, RustType, ZngurConstructor, ZngurExternCppFn,
     ZngurExternCppImpl, ZngurField, ZngurFn, ZngurMethod, ZngurMethodDetails, ZngurMethodReceiver,
-    ZngurSpec, ZngurTrait, ZngurType, ZngurWellknownTrait,
+    ZngurResultMode, ZngurSpec, ZngurTrait, ZngurTraitImpl, ZngurType, ZngurVariant,
+    ZngurWellknownTrait,
 };
 
 pub type Span = SimpleSpan<usize>;
 
 /// Result of parsing a .zng file, containing both the spec and the list of all processed files.
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct ParseResult {
     /// The parsed Zngur specification
     pub spec: ZngurSpec,
     /// All .zng files that were processed (main file + transitive imports)
     pub processed_files: Vec<std::path::PathBuf>,
+    /// Where the items of the spec are declared, for [`Self::validate`].
+    locations: SpecLocations,
+    /// The text of the processed files, for the diagnostics of [`Self::validate`].
+    sources: HashMap<std::path::PathBuf, String>,
+}
+
+impl ParseResult {
+    /// Checks the spec for mistakes which the parser accepts, but which would make the generated
+    /// code fail to compile, like a method using a type which isn't declared.
+    pub fn validate(&self) -> Result<(), Diagnostics> {
+        let diagnostics = validate(&self.spec, &self.locations);
+        if diagnostics.is_empty() {
+            Ok(())
+        } else {
+            Err(Diagnostics::new(diagnostics, self.sources.clone()))
+        }
+    }
+
+    /// An error at the declaration of `ty`, for the problems found after parsing, like a failed
+    /// layout probe.
+    pub fn type_error(
+        &self,
+        ty: &RustType,
+        message: impl Into<String>,
+        label: impl Into<String>,
+    ) -> Diagnostics {
+        let diagnostic = match self.locations.type_location(ty) {
+            Some(location) => location.error(message, label),
+            None => Diagnostic::error(
+                self.processed_files.first().cloned().unwrap_or_default(),
+                0..0,
+                message,
+            ),
+        };
+        Diagnostics::new(vec![diagnostic], self.sources.clone())
+    }
+
+    /// An error about a whole file, for the problems found after parsing, like a failed write.
+    pub fn file_error(&self, path: &std::path::Path, message: impl Into<String>) -> Diagnostics {
+        Diagnostics::new(
+            vec![Diagnostic::error(path, 0..0, message)],
+            self.sources.clone(),
+        )
+    }
+
+    /// The `type` and `enum` items, in the active branches of the cfg conditions.
+    pub fn type_declarations(&self) -> &[TypeDeclaration] {
+        &self.locations.declarations
+    }
+
+    /// The free functions, in the active branches of the cfg conditions.
+    pub fn fn_declarations(&self) -> &[FnDeclaration] {
+        &self.locations.fns
+    }
+
+    /// The text of a processed file.
+    pub fn source(&self, path: &std::path::Path) -> Option<&str> {
+        self.sources.get(path).map(String::as_str)
+    }
+
+    /// Wraps the problems found by other tools in the processed files, so they are rendered
+    /// with the text of the files.
+    pub fn diagnostics(&self, diagnostics: Vec<Diagnostic>) -> Diagnostics {
+        Diagnostics::new(diagnostics, self.sources.clone())
+    }
+}
+
+/// A `type` or `enum` item declaring a type.
+#[derive(Debug, Clone)]
+pub struct TypeDeclaration {
+    pub ty: RustType,
+    pub path: std::path::PathBuf,
+    /// Byte range of the declared type in the item.
+    pub span: std::ops::Range<usize>,
+    /// Byte offset of the end of the last item in the body, where new items can be added.
+    ///
+    /// This is `None` for the instances of type templates, since their body is shared.
+    pub body_end: Option<usize>,
+    /// The names of the declared methods, and the byte ranges of their declarations.
+    pub methods: Vec<(String, std::ops::Range<usize>)>,
+    /// The names of the declared constructors and variants, which is `None` for the constructor
+    /// of a struct, and the byte ranges of their declarations.
+    pub constructors: Vec<(Option<String>, std::ops::Range<usize>)>,
+}
+
+/// A free function declared with `fn`.
+#[derive(Debug, Clone)]
+pub struct FnDeclaration {
+    /// The resolved path of the function, like `["crate", "utils", "parse"]`.
+    pub rust_path: Vec<String>,
+    pub path: std::path::PathBuf,
+    /// Byte range of the declaration.
+    pub span: std::ops::Range<usize>,
+}
+
+/// A lexer or parser error in a single .zng file.
+#[derive(Debug, Clone)]
+pub struct SyntaxError {
+    pub message: String,
+    pub span: std::ops::Range<usize>,
 }
 
 #[cfg(test)]
@@ -31,10 +131,27 @@ mod tests;
 
 pub mod cfg;
 mod conditional;
+pub mod diagnostics;
+#[cfg(feature = "export")]
+pub mod export;
+pub mod formatter;
+pub mod import;
+pub mod index;
+mod layout_expr;
+pub mod layout_fix;
+mod validate;
+
+use import::{ImportOptions, ImportSearch};
+use layout_expr::{
+    LayoutTarget, ParsedLayoutExpr, PendingLayout, inferred_array_layout, layout_expr,
+};
+use layout_fix::LayoutDirective;
 
 use crate::{
     cfg::{CfgConditional, RustCfgProvider},
     conditional::{Condition, ConditionalItem, NItems, conditional_item},
+    diagnostics::{Diagnostic, Diagnostics, Severity},
+    validate::{CppScope, Location, SpecLocations, validate},
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -63,6 +180,35 @@ pub struct UnstableFeatures {
 #[derive(Default)]
 pub struct ZngParserState {
     pub unstable_features: UnstableFeatures,
+    /// The `///` doc comments of the file, keyed by the start offset of the token they document.
+    pub doc_comments: HashMap<usize, String>,
+}
+
+/// Removes the doc comment tokens and returns them, keyed by the start offset of the token
+/// following them.
+fn take_doc_comments(tokens: &mut Vec<(Token<'_>, Span)>) -> HashMap<usize, String> {
+    let mut doc_comments = HashMap::new();
+    let mut lines = vec![];
+    tokens.retain(|(token, span)| {
+        if let Token::DocComment(line) = token {
+            lines.push(line.strip_prefix(' ').unwrap_or(line));
+            return false;
+        }
+        if !lines.is_empty() {
+            doc_comments.insert(span.start, lines.join("\n"));
+            lines.clear();
+        }
+        true
+    });
+    doc_comments
+}
+
+/// The doc comment of the item being parsed, if it has one.
+fn doc_comment<'a, 'b>(
+    extra: &mut chumsky::input::MapExtra<'a, 'b, ParserInput<'a>, ZngParserExtra<'a>>,
+) -> Option<String> {
+    let start = extra.span().start;
+    extra.state().doc_comments.get(&start).cloned()
 }
 
 type ZngParserExtra<'a> =
@@ -81,7 +227,7 @@ impl<'a, T, Item> ZngParser<'a, Item> for T where
 }
 
 #[derive(Debug)]
-pub struct ParsedZngFile<'a>(Vec<ParsedItem<'a>>);
+pub struct ParsedZngFile<'a>(Vec<Spanned<ParsedItem<'a>>>);
 
 #[derive(Debug)]
 pub struct ProcessedZngFile<'a> {
@@ -107,6 +253,8 @@ struct ParsedPath<'a> {
 struct Scope<'a> {
     aliases: Vec<ParsedAlias<'a>>,
     base: Vec<String>,
+    /// Parameters of the type template being instantiated, and the types they stand for.
+    type_params: Vec<(&'a str, RustType)>,
 }
 
 impl<'a> Scope<'a> {
@@ -115,6 +263,7 @@ impl<'a> Scope<'a> {
         Scope {
             aliases,
             base: Vec::new(),
+            type_params: Vec::new(),
         }
     }
 
@@ -132,6 +281,11 @@ impl<'a> Scope<'a> {
         }
     }
 
+    /// Find the alias that `resolve_path` would expand for this path, if any.
+    fn alias_for(&self, path: &ParsedPath<'a>) -> Option<&ParsedAlias<'a>> {
+        self.aliases.iter().find(|alias| path.matches_alias(alias))
+    }
+
     /// Create a fully-qualified path relative to this scope's base path.
     fn simple_relative_path(&self, relative_item_name: &str) -> Vec<String> {
         self.base
@@ -141,7 +295,7 @@ impl<'a> Scope<'a> {
             .collect()
     }
 
-    fn sub_scope(&self, new_aliases: &[ParsedAlias<'a>], nested_path: ParsedPath<'a>) -> Scope<'_> {
+    fn sub_scope(&self, new_aliases: &[ParsedAlias<'a>], nested_path: ParsedPath<'a>) -> Scope<'a> {
         let base = nested_path.to_zngur(&self.base);
         let mut mod_aliases = new_aliases.to_vec();
         mod_aliases.extend_from_slice(&self.aliases);
@@ -149,8 +303,34 @@ impl<'a> Scope<'a> {
         Scope {
             aliases: mod_aliases,
             base,
+            type_params: self.type_params.clone(),
         }
     }
+
+    /// Create a scope in which the type template parameter `name` stands for `ty`.
+    fn with_type_param(&self, name: &'a str, ty: RustType) -> Scope<'a> {
+        let mut scope = self.clone();
+        scope.type_params.push((name, ty));
+        scope
+    }
+
+    /// The type a path stands for, if it is a bare type template parameter.
+    fn type_param(&self, pg: &ParsedRustPathAndGenerics<'_>) -> Option<&RustType> {
+        let [name] = pg.path.segments[..] else {
+            return None;
+        };
+        if pg.path.start != ParsedPathStart::Relative
+            || !pg.generics.is_empty()
+            || !pg.named_generics.is_empty()
+        {
+            return None;
+        }
+        self.type_params
+            .iter()
+            .rev()
+            .find(|(param, _)| *param == name)
+            .map(|(_, ty)| ty)
+    }
 }
 
 impl ParsedPath<'_> {
@@ -237,18 +417,27 @@ enum ParsedItem<'a> {
     UnstableFeature(&'a str),
     Mod {
         path: ParsedPath<'a>,
-        items: Vec<ParsedItem<'a>>,
+        items: Vec<Spanned<ParsedItem<'a>>>,
     },
     Type {
         ty: Spanned<ParsedRustType<'a>>,
+        template: Option<ParsedTypeTemplate<'a>>,
         items: Vec<Spanned<ParsedTypeItem<'a>>>,
+        doc: Option<String>,
+        cpp_name: Option<&'a str>,
+        /// Whether the type is declared with `enum` instead of `type`, so it can have variants.
+        is_enum: bool,
     },
     Trait {
         tr: Spanned<ParsedRustTrait<'a>>,
-        methods: Vec<ParsedMethod<'a>>,
+        methods: Vec<Spanned<ParsedMethod<'a>>>,
     },
     Fn(Spanned<ParsedMethod<'a>>),
-    ExternCpp(Vec<ParsedExternCppItem<'a>>),
+    TraitImpl {
+        tr: Spanned<ParsedRustTrait<'a>>,
+        ty: Spanned<ParsedRustType<'a>>,
+    },
+    ExternCpp(Vec<Spanned<ParsedExternCppItem<'a>>>),
     Alias(ParsedAlias<'a>),
     Import(ParsedImportPath),
     ModuleImport {
@@ -256,6 +445,8 @@ enum ParsedItem<'a> {
         span: Span,
     },
     MatchOnCfg(Condition<CfgConditional<'a>, ParsedItem<'a>, NItems>),
+    /// A malformed item, skipped so that parsing can continue after it.
+    Error,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -269,13 +460,24 @@ enum ProcessedItem<'a> {
     },
     Type {
         ty: Spanned<ParsedRustType<'a>>,
+        template: Option<ParsedTypeTemplate<'a>>,
         items: Vec<Spanned<ParsedTypeItem<'a>>>,
+        doc: Option<String>,
+        cpp_name: Option<&'a str>,
+        /// Whether the type is declared with `enum` instead of `type`, so it can have variants.
+        is_enum: bool,
+        /// Whether this is an instance of a type template, which shares the body of the template.
+        is_instance: bool,
     },
     Trait {
         tr: Spanned<ParsedRustTrait<'a>>,
         methods: Vec<ParsedMethod<'a>>,
     },
     Fn(Spanned<ParsedMethod<'a>>),
+    TraitImpl {
+        tr: Spanned<ParsedRustTrait<'a>>,
+        ty: Spanned<ParsedRustType<'a>>,
+    },
     ExternCpp(Vec<ParsedExternCppItem<'a>>),
     Import(ParsedImportPath),
     ModuleImport {
@@ -284,6 +486,22 @@ enum ProcessedItem<'a> {
     },
 }
 
+/// The parameter and instantiations of a templated type item, like
+/// `type<T> ::std::vec::Vec<T> for T in [i32, crate::Item] { ... }`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ParsedTypeTemplate<'a> {
+    param: &'a str,
+    instances: Vec<ParsedTemplateInstance<'a>>,
+}
+
+/// A type a template is instantiated with, optionally overriding the layout of the template,
+/// like `crate::Item => #layout(size = 16, align = 8)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ParsedTemplateInstance<'a> {
+    ty: Spanned<ParsedRustType<'a>>,
+    layout: Option<(Span, ParsedLayoutPolicy<'a>)>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum ParsedExternCppItem<'a> {
     Function {
@@ -293,8 +511,10 @@ enum ParsedExternCppItem<'a> {
     Impl {
         tr: Option<ParsedRustTrait<'a>>,
         ty: Spanned<ParsedRustType<'a>>,
-        methods: Vec<(bool, ParsedMethod<'a>)>,
+        methods: Vec<Spanned<(bool, ParsedMethod<'a>)>>,
     },
+    /// A malformed item, skipped so that parsing can continue after it.
+    Error,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -304,12 +524,30 @@ enum ParsedConstructorArgs<'a> {
     Named(Vec<(&'a str, ParsedRustType<'a>)>),
 }
 
+impl ParsedConstructorArgs<'_> {
+    fn into_zngur(self, scope: &Scope<'_>) -> Vec<(String, RustType)> {
+        match self {
+            ParsedConstructorArgs::Unit => vec![],
+            ParsedConstructorArgs::Tuple(t) => t
+                .into_iter()
+                .enumerate()
+                .map(|(i, t)| (i.to_string(), t.to_zngur(scope)))
+                .collect(),
+            ParsedConstructorArgs::Named(t) => t
+                .into_iter()
+                .map(|(i, t)| (i.to_owned(), t.to_zngur(scope)))
+                .collect(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum ParsedLayoutPolicy<'a> {
-    StackAllocated(Vec<(Spanned<&'a str>, usize)>),
-    Conservative(Vec<(Spanned<&'a str>, usize)>),
+    StackAllocated(Vec<(Spanned<&'a str>, Spanned<ParsedLayoutExpr<'a>>)>),
+    Conservative(Vec<(Spanned<&'a str>, Spanned<ParsedLayoutExpr<'a>>)>),
     HeapAllocated,
     OnlyByRef,
+    Auto,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -319,17 +557,33 @@ enum ParsedTypeItem<'a> {
     Constructor {
         name: Option<&'a str>,
         args: ParsedConstructorArgs<'a>,
+        doc: Option<String>,
+        cpp_name: Option<&'a str>,
     },
     Field {
         name: String,
         ty: ParsedRustType<'a>,
         offset: Option<usize>,
+        doc: Option<String>,
+        cpp_name: Option<&'a str>,
+    },
+    Variant {
+        name: &'a str,
+        args: ParsedConstructorArgs<'a>,
+        doc: Option<String>,
     },
     Method {
         data: ParsedMethod<'a>,
         use_path: Option<ParsedPath<'a>>,
         deref: Option<ParsedRustType<'a>>,
     },
+    /// `impl Trait;`, optionally repeating the type as `impl Trait for Type;`.
+    TraitImpl {
+        tr: ParsedRustTrait<'a>,
+        ty: Option<Spanned<ParsedRustType<'a>>>,
+    },
+    /// `#result_as_exception;` or `#result_as_expected;`, the default result mode of the methods.
+    ResultMode(ZngurResultMode),
     CppValue {
         field: &'a str,
         cpp_type: &'a str,
@@ -339,9 +593,11 @@ enum ParsedTypeItem<'a> {
     },
     CppStackOwned {
         cpp_type: &'a str,
-        props: Vec<(Spanned<&'a str>, usize)>,
+        props: Vec<(Spanned<&'a str>, Spanned<ParsedLayoutExpr<'a>>)>,
     },
     MatchOnCfg(Condition<CfgConditional<'a>, ParsedTypeItem<'a>, NItems>),
+    /// A malformed item, skipped so that parsing can continue after it.
+    Error,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -351,6 +607,9 @@ struct ParsedMethod<'a> {
     generics: Vec<ParsedRustType<'a>>,
     inputs: Vec<ParsedRustType<'a>>,
     output: ParsedRustType<'a>,
+    doc: Option<String>,
+    cpp_name: Option<&'a str>,
+    result_mode: Option<ZngurResultMode>,
 }
 
 impl ParsedMethod<'_> {
@@ -366,10 +625,24 @@ impl ParsedMethod<'_> {
             inputs: self.inputs.into_iter().map(|x| x.to_zngur(scope)).collect(),
             output: self.output.to_zngur(scope),
             is_safe: true,
+            doc: self.doc,
+            cpp_name: self.cpp_name.map(|x| x.to_owned()),
+            result_mode: self.result_mode,
         }
     }
 }
 
+/// Reports a `#result_as_exception` or `#result_as_expected` on a function which doesn't return
+/// a `Result`.
+fn check_result_mode(method: &ZngurMethod, span: Span, ctx: &mut ParseContext) {
+    if method.result_mode.is_some() && method.output.as_result().is_none() {
+        ctx.add_error_str(
+            "The result mode needs a function returning `::std::result::Result<T, E>`",
+            span,
+        );
+    }
+}
+
 fn checked_merge<T, U>(src: T, dst: &mut U, span: Span, ctx: &mut ParseContext)
 where
     T: Merge<U>,
@@ -404,6 +677,14 @@ impl ProcessedItem<'_> {
                 match path.path.components().next() {
                     Some(Component::CurDir) | Some(Component::ParentDir) => {
                         r.imports.push(Import(path.path));
+                        ctx.merge_spans.push(path.span);
+                    }
+                    Some(Component::Normal(first))
+                        if first.to_string_lossy().starts_with('@')
+                            || ctx.imports.options().has_include_dirs() =>
+                    {
+                        r.imports.push(Import(path.path));
+                        ctx.merge_spans.push(path.span);
                     }
                     _ => ctx.add_error_str(
                         "Module import is not supported. Use a relative path instead.",
@@ -414,7 +695,51 @@ impl ProcessedItem<'_> {
             ProcessedItem::ModuleImport { path, span: _ } => {
                 r.imported_modules.push(ModuleImport { path: path.clone() });
             }
-            ProcessedItem::Type { ty, items } => {
+            ProcessedItem::Type {
+                ty,
+                template: Some(template),
+                items,
+                doc,
+                cpp_name,
+                is_enum,
+                is_instance: _,
+            } => {
+                for instance in template.instances {
+                    let instance_scope =
+                        scope.with_type_param(template.param, instance.ty.inner.to_zngur(scope));
+                    let mut items = items.clone();
+                    if let Some((span, layout)) = instance.layout {
+                        remove_layouts(&mut items);
+                        items.push(Spanned {
+                            inner: ParsedTypeItem::Layout(span, layout),
+                            span,
+                        });
+                    }
+                    // Errors of an instantiation point at its argument in the template.
+                    ProcessedItem::Type {
+                        ty: Spanned {
+                            inner: ty.inner.clone(),
+                            span: instance.ty.span,
+                        },
+                        template: None,
+                        items,
+                        doc: doc.clone(),
+                        cpp_name,
+                        is_enum,
+                        is_instance: true,
+                    }
+                    .add_to_zngur_spec(r, &instance_scope, ctx);
+                }
+            }
+            ProcessedItem::Type {
+                ty,
+                template: None,
+                items,
+                doc,
+                cpp_name,
+                is_enum: _,
+                is_instance,
+            } => {
                 if ty.inner == ParsedRustType::Tuple(vec![]) {
                     // We add unit type implicitly.
                     ctx.add_error_str(
@@ -426,19 +751,37 @@ impl ProcessedItem<'_> {
                 let mut methods = vec![];
                 let mut constructors = vec![];
                 let mut fields = vec![];
+                let mut variants = vec![];
+                let mut trait_impls = vec![];
                 let mut wellknown_traits = vec![];
                 let mut layout = None;
                 let mut layout_span = None;
+                let mut result_mode = None;
                 let mut cpp_value = None;
                 let mut cpp_ref = None;
                 let mut cpp_stack_owned = None;
+                let mut has_malformed_items = false;
+                let self_ty = ty.inner.clone().to_zngur(scope);
+                ctx.locations.add_type(&self_ty, ctx.location(ty.span));
+                let body_end = items
+                    .iter()
+                    .map(|item| item.span.end)
+                    .max()
+                    .filter(|_| !is_instance);
+                let mut method_spans = vec![];
+                let mut method_locations = vec![];
+                let mut constructor_spans = vec![];
                 let mut to_process = items;
                 to_process.reverse(); // create a stack of items to process
-                let check_size_align = |props: Vec<(Spanned<&str>, usize)>| {
+                let check_size_align = |props: Vec<(Spanned<&str>, Spanned<ParsedLayoutExpr>)>| {
                     let mut size = None;
                     let mut align = None;
                     let mut errors = vec![];
                     for (key, value) in props {
+                        let value = Spanned {
+                            inner: value.inner.to_zngur(key.inner, scope),
+                            span: value.span,
+                        };
                         match key.inner {
                             "size" => size = Some(value),
                             "align" => align = Some(value),
@@ -462,10 +805,26 @@ impl ProcessedItem<'_> {
                     let item = item.inner;
                     match item {
                         ParsedTypeItem::Layout(span, p) => {
+                            match &p {
+                                ParsedLayoutPolicy::StackAllocated(props) => {
+                                    ctx.add_layout_directive(&self_ty, props, false)
+                                }
+                                ParsedLayoutPolicy::Conservative(props) => {
+                                    ctx.add_layout_directive(&self_ty, props, true)
+                                }
+                                _ => {}
+                            }
                             layout = Some(match p {
                                 ParsedLayoutPolicy::StackAllocated(p) => {
                                     match check_size_align(p) {
                                         Ok((size, align)) => {
+                                            let (size, align) = ctx.eval_layout(
+                                                &self_ty,
+                                                LayoutTarget::Layout,
+                                                size,
+                                                align,
+                                                r,
+                                            );
                                             LayoutPolicy::StackAllocated { size, align }
                                         }
                                         Err(errs) => {
@@ -477,7 +836,16 @@ impl ProcessedItem<'_> {
                                     }
                                 }
                                 ParsedLayoutPolicy::Conservative(p) => match check_size_align(p) {
-                                    Ok((size, align)) => LayoutPolicy::Conservative { size, align },
+                                    Ok((size, align)) => {
+                                        let (size, align) = ctx.eval_layout(
+                                            &self_ty,
+                                            LayoutTarget::Conservative,
+                                            size,
+                                            align,
+                                            r,
+                                        );
+                                        LayoutPolicy::Conservative { size, align }
+                                    }
                                     Err(errs) => {
                                         for (msg, span) in errs {
                                             ctx.add_error_str(msg, span);
@@ -487,6 +855,7 @@ impl ProcessedItem<'_> {
                                 },
                                 ParsedLayoutPolicy::HeapAllocated => LayoutPolicy::HeapAllocated,
                                 ParsedLayoutPolicy::OnlyByRef => LayoutPolicy::OnlyByRef,
+                                ParsedLayoutPolicy::Auto => LayoutPolicy::Auto,
                             });
                             match layout_span {
                                 Some(_) => {
@@ -498,28 +867,76 @@ impl ProcessedItem<'_> {
                         ParsedTypeItem::Traits(tr) => {
                             wellknown_traits.extend(tr);
                         }
-                        ParsedTypeItem::Constructor { name, args } => {
+                        ParsedTypeItem::Constructor {
+                            name,
+                            args,
+                            doc,
+                            cpp_name,
+                        } => {
+                            if name.is_none() && cpp_name.is_some() {
+                                ctx.add_error_str(
+                                    "`#cpp_name` is only allowed on named constructors",
+                                    item_span,
+                                );
+                            }
+                            let inputs = args.into_zngur(scope);
+                            for (_, input) in &inputs {
+                                ctx.locations
+                                    .add_use(input.clone(), ctx.location(item_span));
+                            }
+                            if let (Some(_), Some(cpp_name)) = (name, cpp_name) {
+                                ctx.locations.add_cpp_name(
+                                    CppScope::Type(self_ty.clone()),
+                                    cpp_name,
+                                    ctx.location(item_span),
+                                );
+                            }
+                            constructor_spans
+                                .push((name.map(|x| x.to_owned()), item_span.into_range()));
                             constructors.push(ZngurConstructor {
+                                doc,
+                                cpp_name: cpp_name.map(|x| x.to_owned()),
                                 name: name.map(|x| x.to_owned()),
-                                inputs: match args {
-                                    ParsedConstructorArgs::Unit => vec![],
-                                    ParsedConstructorArgs::Tuple(t) => t
-                                        .into_iter()
-                                        .enumerate()
-                                        .map(|(i, t)| (i.to_string(), t.to_zngur(scope)))
-                                        .collect(),
-                                    ParsedConstructorArgs::Named(t) => t
-                                        .into_iter()
-                                        .map(|(i, t)| (i.to_owned(), t.to_zngur(scope)))
-                                        .collect(),
-                                },
+                                inputs,
                             })
                         }
-                        ParsedTypeItem::Field { name, ty, offset } => {
+                        ParsedTypeItem::Variant { name, args, doc } => {
+                            let fields = args.into_zngur(scope);
+                            for (_, field) in &fields {
+                                ctx.locations
+                                    .add_use(field.clone(), ctx.location(item_span));
+                            }
+                            constructor_spans.push((Some(name.to_owned()), item_span.into_range()));
+                            variants.push(ZngurVariant {
+                                name: name.to_owned(),
+                                fields,
+                                doc,
+                            });
+                        }
+                        ParsedTypeItem::Field {
+                            name,
+                            ty,
+                            offset,
+                            doc,
+                            cpp_name,
+                        } => {
+                            let ty = ty.to_zngur(scope);
+                            ctx.locations
+                                .add_field(&self_ty, &name, ctx.location(item_span));
+                            ctx.locations.add_use(ty.clone(), ctx.location(item_span));
+                            if let Some(cpp_name) = cpp_name {
+                                ctx.locations.add_cpp_name(
+                                    CppScope::Type(self_ty.clone()),
+                                    cpp_name,
+                                    ctx.location(item_span),
+                                );
+                            }
                             fields.push(ZngurField {
                                 name: name.to_owned(),
-                                ty: ty.to_zngur(scope),
+                                ty,
                                 offset,
+                                doc,
+                                cpp_name: cpp_name.map(|x| x.to_owned()),
                             });
                         }
                         ParsedTypeItem::Method {
@@ -541,12 +958,40 @@ impl ProcessedItem<'_> {
                                 };
                                 Some((deref_type, receiver_mutability))
                             });
+                            let data = data.to_zngur(scope);
+                            check_result_mode(&data, item_span, ctx);
+                            method_locations.push(ctx.location(item_span));
+                            if let Some(cpp_name) = &data.cpp_name {
+                                ctx.locations.add_cpp_name(
+                                    CppScope::Type(self_ty.clone()),
+                                    cpp_name,
+                                    ctx.location(item_span),
+                                );
+                            }
+                            method_spans.push((data.name.clone(), item_span.into_range()));
                             methods.push(ZngurMethodDetails {
-                                data: data.to_zngur(scope),
+                                data,
                                 use_path: use_path.map(|x| scope.resolve_path(x)),
                                 deref,
                             });
                         }
+                        ParsedTypeItem::TraitImpl { tr, ty: impl_ty } => {
+                            if let Some(impl_ty) = impl_ty {
+                                if impl_ty.inner.to_zngur(scope) != self_ty {
+                                    ctx.add_error_str(
+                                        "The trait must be implemented for the enclosing type",
+                                        impl_ty.span,
+                                    );
+                                    continue;
+                                }
+                            }
+                            trait_impls.push((tr.to_zngur(scope), item_span));
+                        }
+                        ParsedTypeItem::ResultMode(mode) => {
+                            if result_mode.replace(mode).is_some() {
+                                ctx.add_error_str("Duplicate result mode found", item_span);
+                            }
+                        }
                         ParsedTypeItem::CppValue { field, cpp_type } => {
                             cpp_value = Some(CppValue(field.to_owned(), cpp_type.to_owned()));
                         }
@@ -565,7 +1010,13 @@ impl ProcessedItem<'_> {
                         }
                         ParsedTypeItem::CppStackOwned { cpp_type, props } => {
                             let (size, align) = match check_size_align(props) {
-                                Ok(x) => x,
+                                Ok((size, align)) => ctx.eval_layout(
+                                    &self_ty,
+                                    LayoutTarget::CppStackOwned,
+                                    size,
+                                    align,
+                                    r,
+                                ),
                                 Err(errs) => {
                                     for (msg, span) in errs {
                                         ctx.add_error_str(msg, span);
@@ -578,7 +1029,6 @@ impl ProcessedItem<'_> {
                                 size,
                                 align,
                             });
-                            layout = Some(LayoutPolicy::StackAllocated { size, align });
                         }
                         ParsedTypeItem::MatchOnCfg(match_) => {
                             let result = match_.eval(ctx);
@@ -586,8 +1036,26 @@ impl ProcessedItem<'_> {
                                 to_process.extend(result);
                             }
                         }
+                        ParsedTypeItem::Error => has_malformed_items = true,
                     }
                 }
+                // The result mode of the type applies to the methods returning a `Result` which
+                // don't have their own, wherever it is in the body.
+                for (method, location) in methods.iter_mut().zip(method_locations) {
+                    let method = &mut method.data;
+                    if method.result_mode.is_none() && method.output.as_result().is_some() {
+                        method.result_mode = result_mode;
+                    }
+                    ctx.locations.add_method(&self_ty, method, location);
+                }
+                ctx.locations.add_declaration(TypeDeclaration {
+                    ty: self_ty.clone(),
+                    path: ctx.path.clone(),
+                    span: ty.span.into_range(),
+                    body_end,
+                    methods: method_spans,
+                    constructors: constructor_spans,
+                });
                 let is_unsized = wellknown_traits
                     .iter()
                     .find(|x| x.inner == ZngurWellknownTrait::Unsized)
@@ -605,51 +1073,77 @@ impl ProcessedItem<'_> {
                 }
                 if let Some(is_unsized) = is_unsized {
                     if let Some(span) = layout_span {
-                        ctx.add_report(
-                            Report::build(
-                                ReportKind::Error,
-                                ctx.filename().to_string(),
-                                span.start,
+                        let path = ctx.path.clone();
+                        ctx.add_diagnostic(
+                            Diagnostic::error(
+                                &path,
+                                span.into_range(),
+                                "Duplicate layout policy found for unsized type.",
                             )
-                            .with_message("Duplicate layout policy found for unsized type.")
                             .with_label(
-                                Label::new((ctx.filename().to_string(), span.start..span.end))
-                                    .with_message(
-                                        "Unsized types have implicit layout policy, remove this.",
-                                    )
-                                    .with_color(Color::Red),
+                                &path,
+                                span.into_range(),
+                                "Unsized types have implicit layout policy, remove this.",
                             )
                             .with_label(
-                                Label::new((
-                                    ctx.filename().to_string(),
-                                    is_unsized.span.start..is_unsized.span.end,
-                                ))
-                                .with_message("Type declared as unsized here.")
-                                .with_color(Color::Blue),
-                            )
-                            .finish(),
+                                &path,
+                                is_unsized.span.into_range(),
+                                "Type declared as unsized here.",
+                            ),
                         )
                     }
                     layout = Some(LayoutPolicy::OnlyByRef);
                 }
+                // `#cpp_stack_owned` is the layout of the type, unless it has a `#layout` as well.
+                if let (None, Some(CppStackOwned { size, align, .. })) = (&layout, &cpp_stack_owned)
+                {
+                    layout = Some(LayoutPolicy::StackAllocated {
+                        size: *size,
+                        align: *align,
+                    });
+                    ctx.use_pending_cpp_stack_owned_as_layout(&self_ty);
+                }
+                let rust_ty = self_ty;
+                if let (Some(cpp_name), RustType::Adt(adt)) = (cpp_name, &rust_ty) {
+                    if let Some((cpp_scope, _)) = CppScope::of_path(&adt.path) {
+                        ctx.locations
+                            .add_cpp_name(cpp_scope, cpp_name, ctx.location(ty.span));
+                    }
+                }
+                if layout.is_none() {
+                    layout = inferred_array_layout(&rust_ty);
+                }
+                for (tr, span) in trait_impls {
+                    let imp = ZngurTraitImpl {
+                        ty: rust_ty.clone(),
+                        tr,
+                    };
+                    ctx.locations
+                        .add_trait_impl(imp.clone(), ctx.location(span));
+                    checked_merge(imp, r, span, ctx);
+                }
                 if let Some(layout) = layout {
                     checked_merge(
                         ZngurType {
-                            ty: ty.inner.to_zngur(scope),
+                            ty: rust_ty,
                             layout,
                             methods,
                             wellknown_traits: wt,
                             constructors,
                             fields,
+                            variants,
                             cpp_value,
                             cpp_ref,
                             cpp_stack_owned,
+                            doc,
+                            cpp_name: cpp_name.map(|x| x.to_owned()),
                         },
                         r,
                         ty.span,
                         ctx,
                     );
-                } else {
+                } else if !has_malformed_items {
+                    // The layout policy might be in the malformed items, which are already reported.
                     ctx.add_error_str(
                         "No layout policy found for this type. \
 Use one of `#layout(size = X, align = Y)`, `#heap_allocated` or `#only_by_ref`.",
@@ -658,10 +1152,17 @@ Use one of `#layout(size = X, align = Y)`, `#heap_allocated` or `#only_by_ref`."
                 };
             }
             ProcessedItem::Trait { tr, methods } => {
+                let methods = methods
+                    .into_iter()
+                    .map(|m| m.to_zngur(scope))
+                    .collect::<Vec<_>>();
+                for method in &methods {
+                    ctx.locations.add_signature(method, ctx.location(tr.span));
+                }
                 checked_merge(
                     ZngurTrait {
                         tr: tr.inner.to_zngur(scope),
-                        methods: methods.into_iter().map(|m| m.to_zngur(scope)).collect(),
+                        methods,
                     },
                     r,
                     tr.span,
@@ -670,6 +1171,20 @@ Use one of `#layout(size = X, align = Y)`, `#heap_allocated` or `#only_by_ref`."
             }
             ProcessedItem::Fn(f) => {
                 let method = f.inner.to_zngur(scope);
+                check_result_mode(&method, f.span, ctx);
+                ctx.locations.add_signature(&method, ctx.location(f.span));
+                let rust_path = scope.simple_relative_path(&method.name);
+                if let (Some(cpp_name), Some((cpp_scope, _))) =
+                    (&method.cpp_name, CppScope::of_path(&rust_path))
+                {
+                    ctx.locations
+                        .add_cpp_name(cpp_scope, cpp_name, ctx.location(f.span));
+                }
+                ctx.locations.add_fn(FnDeclaration {
+                    rust_path: scope.simple_relative_path(&method.name),
+                    path: ctx.path.clone(),
+                    span: f.span.into_range(),
+                });
                 checked_merge(
                     ZngurFn {
                         path: RustPathAndGenerics {
@@ -679,18 +1194,32 @@ Use one of `#layout(size = X, align = Y)`, `#heap_allocated` or `#only_by_ref`."
                         },
                         inputs: method.inputs,
                         output: method.output,
+                        doc: method.doc,
+                        cpp_name: method.cpp_name,
+                        result_mode: method.result_mode,
                     },
                     r,
                     f.span,
                     ctx,
                 );
             }
+            ProcessedItem::TraitImpl { tr, ty } => {
+                let imp = ZngurTraitImpl {
+                    ty: ty.inner.to_zngur(scope),
+                    tr: tr.inner.to_zngur(scope),
+                };
+                let span = Span::from(tr.span.start..ty.span.end);
+                ctx.locations
+                    .add_trait_impl(imp.clone(), ctx.location(span));
+                checked_merge(imp, r, tr.span, ctx);
+            }
             ProcessedItem::ExternCpp(items) => {
                 for item in items {
                     match item {
                         ParsedExternCppItem::Function { is_safe, method } => {
                             let span = method.span;
                             let method = method.inner.to_zngur(scope);
+                            ctx.locations.add_signature(&method, ctx.location(span));
                             checked_merge(
                                 ZngurExternCppFn {
                                     name: method.name.to_string(),
@@ -704,24 +1233,31 @@ Use one of `#layout(size = X, align = Y)`, `#heap_allocated` or `#only_by_ref`."
                             );
                         }
                         ParsedExternCppItem::Impl { tr, ty, methods } => {
+                            let impl_ty = ty.inner.to_zngur(scope);
+                            ctx.locations
+                                .add_use(impl_ty.clone(), ctx.location(ty.span));
+                            let methods = methods
+                                .into_iter()
+                                .map(|method| {
+                                    let (is_safe, x) = method.inner;
+                                    let mut m = x.to_zngur(scope);
+                                    m.is_safe = is_safe;
+                                    ctx.locations.add_signature(&m, ctx.location(method.span));
+                                    m
+                                })
+                                .collect();
                             checked_merge(
                                 ZngurExternCppImpl {
                                     tr: tr.map(|x| x.to_zngur(scope)),
-                                    ty: ty.inner.to_zngur(scope),
-                                    methods: methods
-                                        .into_iter()
-                                        .map(|(is_safe, x)| {
-                                            let mut m = x.to_zngur(scope);
-                                            m.is_safe = is_safe;
-                                            m
-                                        })
-                                        .collect(),
+                                    ty: impl_ty,
+                                    methods,
                                 },
                                 r,
                                 ty.span,
                                 ctx,
                             );
                         }
+                        ParsedExternCppItem::Error => {}
                     }
                 }
             }
@@ -759,6 +1295,13 @@ enum ParsedRustType<'a> {
     Raw(Mutability, Box<ParsedRustType<'a>>),
     Boxed(Box<ParsedRustType<'a>>),
     Slice(Box<ParsedRustType<'a>>),
+    Array(Box<ParsedRustType<'a>>, usize),
+    FnPtr {
+        is_unsafe: bool,
+        abi: Option<&'a str>,
+        inputs: Vec<ParsedRustType<'a>>,
+        output: Box<ParsedRustType<'a>>,
+    },
     Dyn(ParsedRustTrait<'a>, Vec<&'a str>),
     Impl(ParsedRustTrait<'a>, Vec<&'a str>),
     Tuple(Vec<ParsedRustType<'a>>),
@@ -773,6 +1316,18 @@ impl ParsedRustType<'_> {
             ParsedRustType::Raw(m, s) => RustType::Raw(m, Box::new(s.to_zngur(scope))),
             ParsedRustType::Boxed(s) => RustType::Boxed(Box::new(s.to_zngur(scope))),
             ParsedRustType::Slice(s) => RustType::Slice(Box::new(s.to_zngur(scope))),
+            ParsedRustType::Array(s, n) => RustType::Array(Box::new(s.to_zngur(scope)), n),
+            ParsedRustType::FnPtr {
+                is_unsafe,
+                abi,
+                inputs,
+                output,
+            } => RustType::FnPtr {
+                is_unsafe,
+                abi: abi.map(|x| x.to_owned()),
+                inputs: inputs.into_iter().map(|s| s.to_zngur(scope)).collect(),
+                output: Box::new(output.to_zngur(scope)),
+            },
             ParsedRustType::Dyn(tr, bounds) => RustType::Dyn(
                 tr.to_zngur(scope),
                 bounds.into_iter().map(|x| x.to_owned()).collect(),
@@ -784,7 +1339,10 @@ impl ParsedRustType<'_> {
             ParsedRustType::Tuple(v) => {
                 RustType::Tuple(v.into_iter().map(|s| s.to_zngur(scope)).collect())
             }
-            ParsedRustType::Adt(s) => RustType::Adt(s.to_zngur(scope)),
+            ParsedRustType::Adt(s) => match scope.type_param(&s) {
+                Some(ty) => ty.clone(),
+                None => RustType::Adt(s.to_zngur(scope)),
+            },
         }
     }
 }
@@ -841,28 +1399,45 @@ impl ParsedRustPathAndGenerics<'_> {
     }
 }
 
-struct ParseContext<'a, 'b> {
+struct ParseContext<'a> {
     path: std::path::PathBuf,
     text: &'a str,
     depth: usize,
-    reports: Vec<Report<'b, (String, std::ops::Range<usize>)>>,
+    diagnostics: Vec<Diagnostic>,
     source_cache: std::collections::HashMap<std::path::PathBuf, String>,
     /// All .zng files processed during parsing (main file + imports)
     processed_files: Vec<std::path::PathBuf>,
     cfg_provider: Box<dyn RustCfgProvider>,
+    imports: Rc<ImportSearch>,
+    /// Where the items of this file and its merged files are, for the validation errors.
+    locations: SpecLocations,
+    /// Layouts which refer to types declared after them.
+    pending_layouts: Vec<PendingLayout>,
+    /// The spans of the `merge` items of this file, in the order of the imports of the spec.
+    merge_spans: Vec<Span>,
 }
 
-impl<'a, 'b> ParseContext<'a, 'b> {
-    fn new(path: std::path::PathBuf, text: &'a str, cfg: Box<dyn RustCfgProvider>) -> Self {
+impl<'a> ParseContext<'a> {
+    fn new(
+        path: std::path::PathBuf,
+        text: &'a str,
+        cfg: Box<dyn RustCfgProvider>,
+        import_options: ImportOptions,
+    ) -> Self {
+        let root_dir = path.parent().unwrap_or(&path).to_owned();
         let processed_files = vec![path.clone()];
         Self {
             path,
             text,
             depth: 0,
-            reports: Vec::new(),
+            diagnostics: Vec::new(),
             source_cache: HashMap::new(),
             processed_files,
             cfg_provider: cfg,
+            imports: Rc::new(ImportSearch::new(import_options, root_dir)),
+            locations: SpecLocations::default(),
+            pending_layouts: Vec::new(),
+            merge_spans: Vec::new(),
         }
     }
 
@@ -871,42 +1446,41 @@ impl<'a, 'b> ParseContext<'a, 'b> {
         text: &'a str,
         depth: usize,
         cfg: Box<dyn RustCfgProvider>,
+        imports: Rc<ImportSearch>,
     ) -> Self {
         let processed_files = vec![path.clone()];
         Self {
             path,
             text,
             depth,
-            reports: Vec::new(),
+            diagnostics: Vec::new(),
             source_cache: HashMap::new(),
             processed_files,
             cfg_provider: cfg,
+            imports,
+            locations: SpecLocations::default(),
+            pending_layouts: Vec::new(),
+            merge_spans: Vec::new(),
         }
     }
 
-    fn filename(&self) -> &str {
-        self.path.file_name().unwrap().to_str().unwrap()
+    fn add_diagnostic(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
     }
 
-    fn add_report(&mut self, report: Report<'b, (String, std::ops::Range<usize>)>) {
-        self.reports.push(report);
-    }
     fn add_errors<'err_src>(&mut self, errs: impl Iterator<Item = Rich<'err_src, String>>) {
-        let filename = self.filename().to_string();
-        self.reports.extend(errs.map(|e| {
-            Report::build(ReportKind::Error, &filename, e.span().start)
-                .with_message(e.to_string())
-                .with_label(
-                    Label::new((filename.clone(), e.span().into_range()))
-                        .with_message(e.reason().to_string())
-                        .with_color(Color::Red),
-                )
-                .with_labels(e.contexts().map(|(label, span)| {
-                    Label::new((filename.clone(), span.into_range()))
-                        .with_message(format!("while parsing this {}", label))
-                        .with_color(Color::Yellow)
-                }))
-                .finish()
+        let path = self.path.clone();
+        self.diagnostics.extend(errs.map(|e| {
+            let mut diagnostic = Diagnostic::error(&path, e.span().into_range(), e.to_string())
+                .with_label(&path, e.span().into_range(), e.reason().to_string());
+            for (label, span) in e.contexts() {
+                diagnostic = diagnostic.with_label(
+                    &path,
+                    span.into_range(),
+                    format!("while parsing this {}", label),
+                );
+            }
+            diagnostic
         }));
     }
 
@@ -914,72 +1488,68 @@ impl<'a, 'b> ParseContext<'a, 'b> {
         self.add_errors([Rich::custom(span, error)].into_iter());
     }
 
-    fn consume_from(&mut self, mut other: ParseContext<'_, 'b>) {
+    fn consume_from(&mut self, mut other: ParseContext<'_>) {
         // Always merge processed files, regardless of errors
         self.processed_files.append(&mut other.processed_files);
-        if other.has_errors() {
-            self.reports.extend(other.reports);
-            self.source_cache.insert(other.path, other.text.to_string());
-            self.source_cache.extend(other.source_cache);
+        self.locations.extend(other.locations);
+        self.pending_layouts.append(&mut other.pending_layouts);
+        // The sources are kept even without errors, since the validation might point into them.
+        self.diagnostics.extend(other.diagnostics);
+        self.source_cache.insert(other.path, other.text.to_string());
+        self.source_cache.extend(other.source_cache);
+    }
+
+    /// Records where the numbers of a `#layout(...)` are, for [`ParseResult::fix_layouts`].
+    /// Properties computed from an expression are not numbers to rewrite, so they are skipped.
+    fn add_layout_directive(
+        &mut self,
+        ty: &RustType,
+        props: &[(Spanned<&str>, Spanned<ParsedLayoutExpr>)],
+        conservative: bool,
+    ) {
+        let number = |key: &str| {
+            let (_, value) = props.iter().find(|(name, _)| name.inner == key)?;
+            match value.inner {
+                ParsedLayoutExpr::Number(n) => Some((value.span.into_range(), n)),
+                _ => None,
+            }
+        };
+        let (Some(size), Some(align)) = (number("size"), number("align")) else {
+            return;
+        };
+        self.locations.add_layout(LayoutDirective {
+            ty: ty.clone(),
+            conservative,
+            path: self.path.clone(),
+            size,
+            align,
+        });
+    }
+
+    fn location(&self, span: Span) -> Location {
+        Location {
+            path: self.path.clone(),
+            span,
         }
     }
 
     fn has_errors(&self) -> bool {
-        !self.reports.is_empty()
+        !self.diagnostics.is_empty()
     }
 
-    #[cfg(test)]
-    fn emit_ariadne_errors(&self) -> ! {
-        let mut r = Vec::<u8>::new();
-        for err in &self.reports {
-            err.write(
-                sources(
-                    [(self.filename().to_string(), self.text)]
-                        .into_iter()
-                        .chain(
-                            self.source_cache
-                                .iter()
-                                .map(|(path, text)| {
-                                    (
-                                        path.file_name().unwrap().to_str().unwrap().to_string(),
-                                        text.as_str(),
-                                    )
-                                })
-                                .collect::<Vec<_>>()
-                                .into_iter(),
-                        ),
-                ),
-                &mut r,
-            )
-            .unwrap();
-        }
-        std::panic::resume_unwind(Box::new(tests::ErrorText(
-            String::from_utf8(strip_ansi_escapes::strip(r)).unwrap(),
-        )));
+    fn into_diagnostics(mut self) -> Diagnostics {
+        self.source_cache.insert(self.path, self.text.to_string());
+        Diagnostics::new(self.diagnostics, self.source_cache)
     }
 
-    #[cfg(not(test))]
-    fn emit_ariadne_errors(&self) -> ! {
-        for err in &self.reports {
-            err.eprint(sources(
-                [(self.filename().to_string(), self.text)]
-                    .into_iter()
-                    .chain(
-                        self.source_cache
-                            .iter()
-                            .map(|(path, text)| {
-                                (
-                                    path.file_name().unwrap().to_str().unwrap().to_string(),
-                                    text.as_str(),
-                                )
-                            })
-                            .collect::<Vec<_>>()
-                            .into_iter(),
-                    ),
-            ))
-            .unwrap();
+    fn into_parse_result(mut self, spec: ZngurSpec) -> ParseResult {
+        self.source_cache.insert(self.path, self.text.to_string());
+        ParseResult {
+            spec,
+            processed_files: self.processed_files,
+            locations: self.locations,
+            sources: self.source_cache,
         }
-        exit(101);
     }
 
     fn get_config_provider(&self) -> &dyn RustCfgProvider {
@@ -987,6 +1557,21 @@ impl<'a, 'b> ParseContext<'a, 'b> {
     }
 }
 
+#[cfg(test)]
+fn emit_diagnostics(diagnostics: Diagnostics) -> ! {
+    let mut r = Vec::<u8>::new();
+    diagnostics.write(&mut r).unwrap();
+    std::panic::resume_unwind(Box::new(tests::ErrorText(
+        String::from_utf8(strip_ansi_escapes::strip(r)).unwrap(),
+    )));
+}
+
+#[cfg(not(test))]
+fn emit_diagnostics(diagnostics: Diagnostics) -> ! {
+    diagnostics.eprint();
+    exit(101);
+}
+
 /// A trait for types which can resolve filesystem-like paths relative to a given directory.
 pub trait ImportResolver {
     fn resolve_import(
@@ -994,6 +1579,19 @@ pub trait ImportResolver {
         cwd: &std::path::Path,
         relpath: &std::path::Path,
     ) -> Result<String, String>;
+
+    /// Lists the files matching `pattern` relative to the given directory, like `types/*.zng`.
+    /// The returned paths are relative to that directory.
+    fn glob_import(
+        &self,
+        _cwd: &std::path::Path,
+        pattern: &std::path::Path,
+    ) -> Result<Vec<std::path::PathBuf>, String> {
+        Err(format!(
+            "Glob imports are not supported: {}",
+            pattern.display()
+        ))
+    }
 }
 
 /// A default implementation of ImportResolver which uses conventional filesystem paths and semantics.
@@ -1011,114 +1609,159 @@ impl ImportResolver for DefaultImportResolver {
             .map_err(|e| e.to_string())?;
         std::fs::read_to_string(path).map_err(|e| e.to_string())
     }
+
+    fn glob_import(
+        &self,
+        cwd: &std::path::Path,
+        pattern: &std::path::Path,
+    ) -> Result<Vec<std::path::PathBuf>, String> {
+        import::glob_fs(cwd, pattern)
+    }
 }
 
 impl<'a> ParsedZngFile<'a> {
     fn parse_into(zngur: &mut ZngurSpec, ctx: &mut ParseContext, resolver: &impl ImportResolver) {
         let (tokens, errs) = lexer().parse(ctx.text).into_output_errors();
-        let Some(tokens) = tokens else {
+        let Some(mut tokens) = tokens else {
             ctx.add_errors(errs.into_iter().map(|e| e.map_token(|c| c.to_string())));
-            ctx.emit_ariadne_errors();
+            return;
         };
+        let doc_comments = take_doc_comments(&mut tokens);
         let tokens: ParserInput<'_> = tokens.as_slice().map(
             (ctx.text.len()..ctx.text.len()).into(),
             Box::new(|(t, s)| (t, s)),
         );
         let (ast, errs) = file_parser()
             .map_with(|ast, extra| (ast, extra.span()))
-            .parse_with_state(tokens, &mut extra::SimpleState(ZngParserState::default()))
+            .parse_with_state(
+                tokens,
+                &mut extra::SimpleState(ZngParserState {
+                    doc_comments,
+                    ..Default::default()
+                }),
+            )
             .into_output_errors();
+        // Malformed items are skipped by the parser, so the rest of the file is still processed
+        // and its merged files are still parsed to report their errors too.
+        ctx.add_errors(errs.into_iter().map(|e| e.map_token(|c| c.to_string())));
         let Some(ast) = ast else {
-            ctx.add_errors(errs.into_iter().map(|e| e.map_token(|c| c.to_string())));
-            ctx.emit_ariadne_errors();
+            return;
         };
 
         let (aliases, items) = partition_parsed_items(
             ast.0
                 .0
                 .into_iter()
-                .map(|item| process_parsed_item(item, ctx)),
+                .map(|item| process_parsed_item(item.inner, ctx)),
         );
         ProcessedZngFile::new(aliases, items).into_zngur_spec(zngur, ctx);
 
         if let Some(dirname) = ctx.path.to_owned().parent() {
-            for import in std::mem::take(&mut zngur.imports) {
-                match resolver.resolve_import(dirname, &import.0) {
-                    Ok(text) => {
-                        let mut nested_ctx = ParseContext::with_depth(
-                            dirname.join(&import.0),
-                            &text,
-                            ctx.depth + 1,
-                            ctx.get_config_provider().clone_box(),
-                        );
-                        Self::parse_into(zngur, &mut nested_ctx, resolver);
-                        ctx.consume_from(nested_ctx);
-                    }
-                    Err(_) => {
-                        // TODO: emit a better error. How should we get a span here?
-                        // I'd like to avoid putting a ParsedImportPath in ZngurSpec, and
-                        // also not have to pass a filename to add_to_zngur_spec.
-                        ctx.add_report(
-                            Report::build(ReportKind::Error, ctx.filename(), 0)
-                                .with_message(format!(
-                                    "Import path not found: {}",
-                                    import.0.display()
-                                ))
-                                .finish(),
-                        );
+            let merge_spans = std::mem::take(&mut ctx.merge_spans);
+            for (import, span) in std::mem::take(&mut zngur.imports)
+                .into_iter()
+                .zip(merge_spans)
+            {
+                match ctx.imports.resolve(resolver, dirname, &import.0) {
+                    Ok(files) => {
+                        for file in files {
+                            let mut nested_ctx = ParseContext::with_depth(
+                                file.path,
+                                &file.text,
+                                ctx.depth + 1,
+                                ctx.get_config_provider().clone_box(),
+                                ctx.imports.clone(),
+                            );
+                            Self::parse_into(zngur, &mut nested_ctx, resolver);
+                            ctx.consume_from(nested_ctx);
+                        }
                     }
+                    Err(e) => ctx.add_error_str(&e, span),
                 }
             }
         }
     }
 
     /// Parse a .zng file and return both the spec and list of all processed files.
+    ///
+    /// Errors are printed to stderr and terminate the process. Use [`Self::try_parse`] to handle
+    /// them instead.
     pub fn parse(path: std::path::PathBuf, cfg: Box<dyn RustCfgProvider>) -> ParseResult {
+        Self::try_parse(path, cfg).unwrap_or_else(|diagnostics| emit_diagnostics(diagnostics))
+    }
+
+    /// Parse a .zng file and return both the spec and list of all processed files, or the
+    /// diagnostics explaining why it failed.
+    pub fn try_parse(
+        path: std::path::PathBuf,
+        cfg: Box<dyn RustCfgProvider>,
+    ) -> Result<ParseResult, Diagnostics> {
+        Self::try_parse_with_options(path, cfg, ImportOptions::default())
+    }
+
+    /// Like [`Self::try_parse`], but also looks for the merged files in the include directories
+    /// and packages of `import_options`.
+    pub fn try_parse_with_options(
+        path: std::path::PathBuf,
+        cfg: Box<dyn RustCfgProvider>,
+        import_options: ImportOptions,
+    ) -> Result<ParseResult, Diagnostics> {
         let mut zngur = ZngurSpec::default();
         zngur.rust_cfg.extend(cfg.get_cfg_pairs());
-        let text = std::fs::read_to_string(&path).unwrap();
-        let mut ctx = ParseContext::new(path.clone(), &text, cfg.clone_box());
+        let text = std::fs::read_to_string(&path).map_err(|e| {
+            Diagnostics::new(
+                vec![Diagnostic::error(
+                    &path,
+                    0..0,
+                    format!("Couldn't read {}: {e}", path.display()),
+                )],
+                HashMap::new(),
+            )
+        })?;
+        let mut ctx = ParseContext::new(path.clone(), &text, cfg.clone_box(), import_options);
         Self::parse_into(&mut zngur, &mut ctx, &DefaultImportResolver);
+        ctx.resolve_pending_layouts(&mut zngur);
         if ctx.has_errors() {
-            // add report of cfg values used
-            ctx.add_report(
-                Report::build(
-                    ReportKind::Custom("cfg values", ariadne::Color::Green),
-                    path.file_name().unwrap_or_default().to_string_lossy(),
-                    0,
-                )
-                .with_message(
-                    cfg.get_cfg_pairs()
-                        .into_iter()
-                        .map(|(key, value)| match value {
-                            Some(value) => format!("{key}=\"{value}\""),
-                            None => key,
-                        })
-                        .join("\n")
-                        .to_string(),
-                )
-                .finish(),
-            );
-            ctx.emit_ariadne_errors();
-        }
-        ParseResult {
-            spec: zngur,
-            processed_files: ctx.processed_files,
+            let cfg_pairs = cfg.get_cfg_pairs();
+            if !cfg_pairs.is_empty() {
+                // add a note of the cfg values used
+                ctx.add_diagnostic(Diagnostic::new(
+                    Severity::Note,
+                    &path,
+                    0..0,
+                    format!(
+                        "cfg values:\n{}",
+                        cfg_pairs
+                            .into_iter()
+                            .map(|(key, value)| match value {
+                                Some(value) => format!("{key}=\"{value}\""),
+                                None => key,
+                            })
+                            .join("\n")
+                    ),
+                ));
+            }
+            return Err(ctx.into_diagnostics());
         }
+        Ok(ctx.into_parse_result(zngur))
     }
 
     /// Parse a .zng file from a string. Mainly useful for testing.
     pub fn parse_str(text: &str, cfg: impl RustCfgProvider + 'static) -> ParseResult {
-        let mut zngur = ZngurSpec::default();
-        let mut ctx = ParseContext::new(std::path::PathBuf::from("test.zng"), text, Box::new(cfg));
-        Self::parse_into(&mut zngur, &mut ctx, &DefaultImportResolver);
-        if ctx.has_errors() {
-            ctx.emit_ariadne_errors();
-        }
-        ParseResult {
-            spec: zngur,
-            processed_files: ctx.processed_files,
-        }
+        Self::try_parse_str(text, cfg).unwrap_or_else(|diagnostics| emit_diagnostics(diagnostics))
+    }
+
+    /// Parse a .zng file from a string, returning the diagnostics instead of exiting on errors.
+    pub fn try_parse_str(
+        text: &str,
+        cfg: impl RustCfgProvider + 'static,
+    ) -> Result<ParseResult, Diagnostics> {
+        Self::try_parse_str_with_resolver(
+            text,
+            cfg,
+            &DefaultImportResolver,
+            ImportOptions::default(),
+        )
     }
 
     #[cfg(test)]
@@ -1127,19 +1770,87 @@ impl<'a> ParsedZngFile<'a> {
         cfg: impl RustCfgProvider + 'static,
         resolver: &impl ImportResolver,
     ) -> ParseResult {
+        Self::try_parse_str_with_resolver(text, cfg, resolver, ImportOptions::default())
+            .unwrap_or_else(|diagnostics| emit_diagnostics(diagnostics))
+    }
+
+    pub(crate) fn try_parse_str_with_resolver(
+        text: &str,
+        cfg: impl RustCfgProvider + 'static,
+        resolver: &impl ImportResolver,
+        import_options: ImportOptions,
+    ) -> Result<ParseResult, Diagnostics> {
+        Self::try_parse_text_with_resolver(
+            std::path::PathBuf::from("test.zng"),
+            text,
+            Box::new(cfg),
+            resolver,
+            Rc::new(ImportSearch::new(import_options, std::path::PathBuf::new())),
+        )
+    }
+
+    /// Parse the text of the .zng file at `path`, finding the merged files with `imports` and
+    /// reading them with `resolver`. This is for tools like editors, whose files may be unsaved.
+    pub fn try_parse_text_with_resolver(
+        path: std::path::PathBuf,
+        text: &str,
+        cfg: Box<dyn RustCfgProvider>,
+        resolver: &impl ImportResolver,
+        imports: Rc<ImportSearch>,
+    ) -> Result<ParseResult, Diagnostics> {
         let mut zngur = ZngurSpec::default();
-        let mut ctx = ParseContext::new(std::path::PathBuf::from("test.zng"), text, Box::new(cfg));
+        let mut ctx = ParseContext::with_depth(path, text, 0, cfg, imports);
         Self::parse_into(&mut zngur, &mut ctx, resolver);
+        ctx.resolve_pending_layouts(&mut zngur);
         if ctx.has_errors() {
-            ctx.emit_ariadne_errors();
-        }
-        ParseResult {
-            spec: zngur,
-            processed_files: ctx.processed_files,
+            return Err(ctx.into_diagnostics());
         }
+        Ok(ctx.into_parse_result(zngur))
     }
 }
 
+/// Lex and parse a single file without processing it, returning the syntax errors instead of exiting.
+///
+/// The parsed file borrows from the tokens, so it is handed to `f` together with them. It is still
+/// parsed when there are errors, as long as the parser could recover from them.
+fn parse_syntax<R>(
+    text: &str,
+    f: impl for<'a> FnOnce(ParsedZngFile<'a>, &'a [(Token<'a>, Span)]) -> R,
+) -> (Option<R>, Vec<SyntaxError>) {
+    let (tokens, errs) = lexer().parse(text).into_output_errors();
+    let Some(mut tokens) = tokens else {
+        let errors = errs
+            .into_iter()
+            .map(|e| SyntaxError {
+                message: e.to_string(),
+                span: e.span().into_range(),
+            })
+            .collect();
+        return (None, errors);
+    };
+    let doc_comments = take_doc_comments(&mut tokens);
+    let input: ParserInput<'_> = tokens
+        .as_slice()
+        .map((text.len()..text.len()).into(), Box::new(|(t, s)| (t, s)));
+    let (ast, errs) = file_parser()
+        .parse_with_state(
+            input,
+            &mut extra::SimpleState(ZngParserState {
+                doc_comments,
+                ..Default::default()
+            }),
+        )
+        .into_output_errors();
+    let errors = errs
+        .into_iter()
+        .map(|e| SyntaxError {
+            span: e.span().into_range(),
+            message: e.map_token(|c| c.to_string()).to_string(),
+        })
+        .collect();
+    (ast.map(|ast| f(ast, &tokens)), errors)
+}
+
 pub(crate) enum ProcessedItemOrAlias<'a> {
     Ignore,
     Processed(ProcessedItem<'a>),
@@ -1157,7 +1868,7 @@ fn process_parsed_item<'a>(
         ParsedItem::ConvertPanicToException(span) => {
             Ret::Processed(ProcessedItem::ConvertPanicToException(span))
         }
-        ParsedItem::UnstableFeature(_) => {
+        ParsedItem::UnstableFeature(_) | ParsedItem::Error => {
             // ignore
             Ret::Ignore
         }
@@ -1166,7 +1877,9 @@ fn process_parsed_item<'a>(
         }
         ParsedItem::Mod { path, items } => {
             let (aliases, items) = partition_parsed_items(
-                items.into_iter().map(|item| process_parsed_item(item, ctx)),
+                items
+                    .into_iter()
+                    .map(|item| process_parsed_item(item.inner, ctx)),
             );
             Ret::Processed(ProcessedItem::Mod {
                 path,
@@ -1174,10 +1887,72 @@ fn process_parsed_item<'a>(
                 aliases,
             })
         }
-        ParsedItem::Type { ty, items } => Ret::Processed(ProcessedItem::Type { ty, items }),
-        ParsedItem::Trait { tr, methods } => Ret::Processed(ProcessedItem::Trait { tr, methods }),
+        ParsedItem::Type {
+            ty,
+            template,
+            items,
+            doc,
+            cpp_name,
+            is_enum,
+        } => Ret::Processed(ProcessedItem::Type {
+            ty,
+            template,
+            items,
+            doc,
+            cpp_name,
+            is_enum,
+            is_instance: false,
+        }),
+        ParsedItem::Trait { tr, methods } => Ret::Processed(ProcessedItem::Trait {
+            tr,
+            methods: methods
+                .into_iter()
+                .map(|method| {
+                    if method.inner.cpp_name.is_some() {
+                        ctx.add_error_str(
+                            "`#cpp_name` is not supported on trait methods",
+                            method.span,
+                        );
+                    }
+                    if method.inner.result_mode.is_some() {
+                        ctx.add_error_str(
+                            "Result modes are not supported on trait methods",
+                            method.span,
+                        );
+                    }
+                    method.inner
+                })
+                .collect(),
+        }),
         ParsedItem::Fn(method) => Ret::Processed(ProcessedItem::Fn(method)),
-        ParsedItem::ExternCpp(items) => Ret::Processed(ProcessedItem::ExternCpp(items)),
+        ParsedItem::TraitImpl { tr, ty } => Ret::Processed(ProcessedItem::TraitImpl { tr, ty }),
+        ParsedItem::ExternCpp(items) => Ret::Processed(ProcessedItem::ExternCpp(
+            items
+                .into_iter()
+                .map(|item| {
+                    let methods = match &item.inner {
+                        ParsedExternCppItem::Function { method, .. } => vec![&method.inner],
+                        ParsedExternCppItem::Impl { methods, .. } => {
+                            methods.iter().map(|method| &method.inner.1).collect()
+                        }
+                        ParsedExternCppItem::Error => vec![],
+                    };
+                    if methods.iter().any(|method| method.cpp_name.is_some()) {
+                        ctx.add_error_str(
+                            "`#cpp_name` is not supported in `extern \"C++\"` blocks",
+                            item.span,
+                        );
+                    }
+                    if methods.iter().any(|method| method.result_mode.is_some()) {
+                        ctx.add_error_str(
+                            "Result modes are not supported in `extern \"C++\"` blocks",
+                            item.span,
+                        );
+                    }
+                    item.inner
+                })
+                .collect(),
+        )),
         ParsedItem::Import(path) => Ret::Processed(ProcessedItem::Import(path)),
         ParsedItem::ModuleImport { path, span } => {
             Ret::Processed(ProcessedItem::ModuleImport { path, span })
@@ -1261,6 +2036,7 @@ enum Token<'a> {
     KwMod,
     KwCrate,
     KwType,
+    KwEnum,
     KwTrait,
     KwFn,
     KwMut,
@@ -1277,6 +2053,7 @@ enum Token<'a> {
     Ident(&'a str),
     Str(&'a str),
     Number(usize),
+    DocComment(&'a str),
 }
 
 impl<'a> Token<'a> {
@@ -1287,6 +2064,7 @@ impl<'a> Token<'a> {
             "dyn" => Token::KwDyn,
             "mod" => Token::KwMod,
             "type" => Token::KwType,
+            "enum" => Token::KwEnum,
             "trait" => Token::KwTrait,
             "crate" => Token::KwCrate,
             "fn" => Token::KwFn,
@@ -1343,6 +2121,7 @@ impl Display for Token<'_> {
             Token::KwMod => write!(f, "mod"),
             Token::KwCrate => write!(f, "crate"),
             Token::KwType => write!(f, "type"),
+            Token::KwEnum => write!(f, "enum"),
             Token::KwTrait => write!(f, "trait"),
             Token::KwFn => write!(f, "fn"),
             Token::KwMut => write!(f, "mut"),
@@ -1359,6 +2138,7 @@ impl Display for Token<'_> {
             Token::Ident(i) => write!(f, "{i}"),
             Token::Number(n) => write!(f, "{n}"),
             Token::Str(s) => write!(f, r#""{s}""#),
+            Token::DocComment(c) => write!(f, "///{c}"),
         }
     }
 }
@@ -1399,11 +2179,18 @@ fn lexer<'src>()
             .then_ignore(just('"')),
     ));
 
+    let doc_comment = just("///")
+        .and_is(just("////").not())
+        .ignore_then(any().and_is(just('\n').not()).repeated().to_slice())
+        .map(Token::DocComment);
+
     let comment = just("//")
+        .and_is(doc_comment.not())
         .then(any().and_is(just('\n').not()).repeated())
         .padded();
 
-    token
+    doc_comment
+        .or(token)
         .map_with(|tok, extra| (tok, extra.span()))
         .padded_by(comment.repeated())
         .padded()
@@ -1431,7 +2218,10 @@ fn alias<'a>() -> impl Parser<'a, ParserInput<'a>, ParsedItem<'a>, ZngParserExtr
 
 fn file_parser<'a>()
 -> impl Parser<'a, ParserInput<'a>, ParsedZngFile<'a>, ZngParserExtra<'a>> + Clone {
-    item().repeated().collect::<Vec<_>>().map(ParsedZngFile)
+    spanned(item())
+        .repeated()
+        .collect::<Vec<_>>()
+        .map(ParsedZngFile)
 }
 
 fn rust_type<'a>() -> Boxed<'a, 'a, ParserInput<'a>, ParsedRustType<'a>, ZngParserExtra<'a>> {
@@ -1489,7 +2279,17 @@ fn rust_type<'a>() -> Boxed<'a, 'a, ParserInput<'a>, ParsedRustType<'a>, ZngPars
             .map(|xs| ParsedRustType::Tuple(xs));
         let slice = parser
             .clone()
-            .map(|x| ParsedRustType::Slice(Box::new(x)))
+            .then(
+                just(Token::Semicolon)
+                    .ignore_then(select! {
+                        Token::Number(c) => c,
+                    })
+                    .or_not(),
+            )
+            .map(|(x, len)| match len {
+                Some(len) => ParsedRustType::Array(Box::new(x), len),
+                None => ParsedRustType::Slice(Box::new(x)),
+            })
             .delimited_by(just(Token::BracketOpen), just(Token::BracketClose));
         let reference = just(Token::And)
             .ignore_then(
@@ -1505,10 +2305,33 @@ fn rust_type<'a>() -> Boxed<'a, 'a, ParserInput<'a>, ParsedRustType<'a>, ZngPars
                     .to(Mutability::Mut)
                     .or(just(Token::KwConst).to(Mutability::Not)),
             )
-            .then(parser)
+            .then(parser.clone())
             .map(|(m, x)| ParsedRustType::Raw(m, Box::new(x)));
+        let fn_ptr = just(Token::KwUnsafe)
+            .or_not()
+            .then(
+                just(Token::KwExtern)
+                    .ignore_then(
+                        select! {
+                            Token::Str(c) => c,
+                        }
+                        .or_not(),
+                    )
+                    .or_not(),
+            )
+            .then_ignore(just(Token::KwFn))
+            .then(fn_args(parser))
+            .map(
+                |((is_unsafe, abi), (inputs, output))| ParsedRustType::FnPtr {
+                    is_unsafe: is_unsafe.is_some(),
+                    // Like in Rust, `extern fn` is `extern "C" fn`.
+                    abi: abi.map(|abi| abi.unwrap_or("C")),
+                    inputs,
+                    output: Box::new(output),
+                },
+            );
         choice((
-            scalar, boxed, unit, tuple, slice, adt, reference, raw_ptr, dyn_trait,
+            scalar, boxed, unit, tuple, slice, adt, reference, raw_ptr, dyn_trait, fn_ptr,
         ))
     })
     .boxed()
@@ -1599,9 +2422,158 @@ fn rust_trait<'a>(
     rust_trait
 }
 
+/// Removes the `#layout` items, including the ones in `#if` and `#match` branches.
+fn remove_layouts(items: &mut Vec<Spanned<ParsedTypeItem<'_>>>) {
+    items.retain_mut(|item| match &mut item.inner {
+        ParsedTypeItem::Layout(..) => false,
+        ParsedTypeItem::MatchOnCfg(match_) => {
+            for block in match_.all_branch_blocks_mut() {
+                remove_layouts(block);
+            }
+            true
+        }
+        _ => true,
+    });
+}
+
+/// The `#cpp_name "Name"` attribute, which renames the next item in C++.
+fn cpp_name<'a>() -> impl Parser<'a, ParserInput<'a>, &'a str, ZngParserExtra<'a>> + Clone {
+    just([Token::Sharp, Token::Ident("cpp_name")])
+        .ignore_then(select! {
+            Token::Str(c) => c,
+        })
+        .validate(|name: &str, extra, emitter| {
+            let mut chars = name.chars();
+            let is_identifier = chars
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !is_identifier {
+                emitter.emit(Rich::custom(
+                    extra.span(),
+                    format!("`{name}` is not a valid C++ identifier"),
+                ));
+            } else if CPP_KEYWORDS.contains(&name) {
+                emitter.emit(Rich::custom(
+                    extra.span(),
+                    format!("`{name}` is a C++ keyword"),
+                ));
+            }
+            name
+        })
+}
+
+/// The reserved words of C++, which can't be used as names.
+const CPP_KEYWORDS: &[&str] = &[
+    "alignas",
+    "alignof",
+    "and",
+    "and_eq",
+    "asm",
+    "auto",
+    "bitand",
+    "bitor",
+    "bool",
+    "break",
+    "case",
+    "catch",
+    "char",
+    "char8_t",
+    "char16_t",
+    "char32_t",
+    "class",
+    "compl",
+    "concept",
+    "const",
+    "consteval",
+    "constexpr",
+    "constinit",
+    "const_cast",
+    "continue",
+    "co_await",
+    "co_return",
+    "co_yield",
+    "decltype",
+    "default",
+    "delete",
+    "do",
+    "double",
+    "dynamic_cast",
+    "else",
+    "enum",
+    "explicit",
+    "export",
+    "extern",
+    "false",
+    "float",
+    "for",
+    "friend",
+    "goto",
+    "if",
+    "inline",
+    "int",
+    "long",
+    "mutable",
+    "namespace",
+    "new",
+    "noexcept",
+    "not",
+    "not_eq",
+    "nullptr",
+    "operator",
+    "or",
+    "or_eq",
+    "private",
+    "protected",
+    "public",
+    "register",
+    "reinterpret_cast",
+    "requires",
+    "return",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "static_assert",
+    "static_cast",
+    "struct",
+    "switch",
+    "template",
+    "this",
+    "thread_local",
+    "throw",
+    "true",
+    "try",
+    "typedef",
+    "typeid",
+    "typename",
+    "union",
+    "unsigned",
+    "using",
+    "virtual",
+    "void",
+    "volatile",
+    "wchar_t",
+    "while",
+    "xor",
+    "xor_eq",
+];
+
+/// The `#result_as_exception` and `#result_as_expected` attributes, which unwrap the `Result`
+/// returned by a function in C++.
+fn result_mode<'a>() -> impl Parser<'a, ParserInput<'a>, ZngurResultMode, ZngParserExtra<'a>> + Clone
+{
+    just(Token::Sharp).ignore_then(select! {
+        Token::Ident("result_as_exception") => ZngurResultMode::Exception,
+        Token::Ident("result_as_expected") => ZngurResultMode::Expected,
+    })
+}
+
 fn method<'a>() -> impl Parser<'a, ParserInput<'a>, ParsedMethod<'a>, ZngParserExtra<'a>> + Clone {
-    spanned(just(Token::KwAsync))
+    cpp_name()
         .or_not()
+        .then(result_mode().or_not())
+        .then(spanned(just(Token::KwAsync)).or_not())
         .then_ignore(just(Token::KwFn))
         .then(select! {
             Token::Ident(c) => c,
@@ -1614,79 +2586,58 @@ fn method<'a>() -> impl Parser<'a, ParserInput<'a>, ParsedMethod<'a>, ZngParserE
                 .or(empty().to(vec![])),
         )
         .then(fn_args(rust_type()))
-        .map(|(((opt_async, name), generics), args)| {
-            let is_self = |c: &ParsedRustType<'_>| {
-                if let ParsedRustType::Adt(c) = c {
-                    c.path.start == ParsedPathStart::Relative
-                        && &c.path.segments == &["self"]
-                        && c.generics.is_empty()
-                } else {
-                    false
+        .map_with(
+            |(((((cpp_name, result_mode), opt_async), name), generics), args), extra| {
+                let is_self = |c: &ParsedRustType<'_>| {
+                    if let ParsedRustType::Adt(c) = c {
+                        c.path.start == ParsedPathStart::Relative
+                            && &c.path.segments == &["self"]
+                            && c.generics.is_empty()
+                    } else {
+                        false
+                    }
+                };
+                let (inputs, receiver) = match args.0.get(0) {
+                    Some(x) if is_self(&x) => (args.0[1..].to_vec(), ZngurMethodReceiver::Move),
+                    Some(ParsedRustType::Ref(m, x)) if is_self(&x) => {
+                        (args.0[1..].to_vec(), ZngurMethodReceiver::Ref(*m))
+                    }
+                    _ => (args.0, ZngurMethodReceiver::Static),
+                };
+                let mut output = args.1;
+                if let Some(async_kw) = opt_async {
+                    output = ParsedRustType::Impl(
+                        ParsedRustTrait::Normal(ParsedRustPathAndGenerics {
+                            path: ParsedPath {
+                                start: ParsedPathStart::Absolute,
+                                segments: vec!["std", "future", "Future"],
+                                span: async_kw.span,
+                            },
+                            generics: vec![],
+                            named_generics: vec![("Output", output)],
+                        }),
+                        vec![],
+                    )
                 }
-            };
-            let (inputs, receiver) = match args.0.get(0) {
-                Some(x) if is_self(&x) => (args.0[1..].to_vec(), ZngurMethodReceiver::Move),
-                Some(ParsedRustType::Ref(m, x)) if is_self(&x) => {
-                    (args.0[1..].to_vec(), ZngurMethodReceiver::Ref(*m))
+                ParsedMethod {
+                    name,
+                    receiver,
+                    generics,
+                    inputs,
+                    output,
+                    doc: doc_comment(extra),
+                    cpp_name,
+                    result_mode,
                 }
-                _ => (args.0, ZngurMethodReceiver::Static),
-            };
-            let mut output = args.1;
-            if let Some(async_kw) = opt_async {
-                output = ParsedRustType::Impl(
-                    ParsedRustTrait::Normal(ParsedRustPathAndGenerics {
-                        path: ParsedPath {
-                            start: ParsedPathStart::Absolute,
-                            segments: vec!["std", "future", "Future"],
-                            span: async_kw.span,
-                        },
-                        generics: vec![],
-                        named_generics: vec![("Output", output)],
-                    }),
-                    vec![],
-                )
-            }
-            ParsedMethod {
-                name,
-                receiver,
-                generics,
-                inputs,
-                output,
-            }
-        })
+            },
+        )
 }
 
-fn inner_type_item<'a>()
--> impl Parser<'a, ParserInput<'a>, ParsedTypeItem<'a>, ZngParserExtra<'a>> + Clone {
-    let property_item = (spanned(select! {
-        Token::Ident(c) => c,
-    }))
-    .then_ignore(just(Token::Eq))
-    .then(select! {
-        Token::Number(c) => c,
-    });
-    let layout = just([Token::Sharp, Token::Ident("layout")])
-        .ignore_then(
-            property_item
-                .clone()
-                .separated_by(just(Token::Comma))
-                .collect::<Vec<_>>()
-                .delimited_by(just(Token::ParenOpen), just(Token::ParenClose)),
-        )
-        .map(ParsedLayoutPolicy::StackAllocated)
-        .or(just([Token::Sharp, Token::Ident("layout_conservative")])
-            .ignore_then(
-                property_item
-                    .clone()
-                    .separated_by(just(Token::Comma))
-                    .collect::<Vec<_>>()
-                    .delimited_by(just(Token::ParenOpen), just(Token::ParenClose)),
-            )
-            .map(ParsedLayoutPolicy::Conservative))
-        .or(just([Token::Sharp, Token::Ident("only_by_ref")]).to(ParsedLayoutPolicy::OnlyByRef))
-        .or(just([Token::Sharp, Token::Ident("heap_allocated")])
-            .to(ParsedLayoutPolicy::HeapAllocated))
-        .map_with(|x, extra| ParsedTypeItem::Layout(extra.span(), x))
+fn inner_type_item<'a>(
+    is_enum: bool,
+) -> impl Parser<'a, ParserInput<'a>, ParsedTypeItem<'a>, ZngParserExtra<'a>> + Clone {
+    let layout = layout_policy()
+        .map(|(span, policy)| ParsedTypeItem::Layout(span, policy))
         .boxed();
     let trait_item = select! {
         Token::Ident("Debug") => ZngurWellknownTrait::Debug,
@@ -1721,36 +2672,74 @@ fn inner_type_item<'a>()
         .map(ParsedConstructorArgs::Named))
         .or(empty().to(ParsedConstructorArgs::Unit))
         .boxed();
-    let constructor = just(Token::Ident("constructor")).ignore_then(
-        (select! {
-            Token::Ident(c) => Some(c),
-        })
-        .or(empty().to(None))
-        .then(constructor_args)
-        .map(|(name, args)| ParsedTypeItem::Constructor { name, args }),
-    );
-    let field = just(Token::Ident("field")).ignore_then(
-        (select! {
-            Token::Ident(c) => c.to_owned(),
-            Token::Number(c) => c.to_string(),
-        })
+    let constructor = cpp_name()
+        .or_not()
+        .then_ignore(just(Token::Ident("constructor")))
         .then(
-            just(Token::Ident("offset"))
-                .then(just(Token::Eq))
-                .ignore_then(select! {
-                    Token::Number(c) => Some(c),
-                    Token::Ident("auto") => None,
-                })
-                .then(
-                    just(Token::Comma)
-                        .then(just(Token::KwType))
-                        .then(just(Token::Eq))
-                        .ignore_then(rust_type()),
-                )
-                .delimited_by(just(Token::ParenOpen), just(Token::ParenClose)),
+            (select! {
+                Token::Ident(c) => Some(c),
+            })
+            .or(empty().to(None))
+            .then(constructor_args.clone()),
         )
-        .map(|(name, (offset, ty))| ParsedTypeItem::Field { name, ty, offset }),
-    );
+        .map_with(
+            |(cpp_name, (name, args)), extra| ParsedTypeItem::Constructor {
+                name,
+                args,
+                doc: doc_comment(extra),
+                cpp_name,
+            },
+        );
+    let variant = (select! {
+        Token::Ident(c) => c,
+    })
+    .then(constructor_args)
+    .map_with(|(name, args), extra| ParsedTypeItem::Variant {
+        name,
+        args,
+        doc: doc_comment(extra),
+    });
+    let field = cpp_name()
+        .or_not()
+        .then_ignore(just(Token::Ident("field")))
+        .then(
+            (select! {
+                Token::Ident(c) => c.to_owned(),
+                Token::Number(c) => c.to_string(),
+            })
+            .then(
+                just(Token::Ident("offset"))
+                    .then(just(Token::Eq))
+                    .ignore_then(select! {
+                        Token::Number(c) => Some(c),
+                        Token::Ident("auto") => None,
+                    })
+                    .then(
+                        just(Token::Comma)
+                            .then(just(Token::KwType))
+                            .then(just(Token::Eq))
+                            .ignore_then(rust_type()),
+                    )
+                    .delimited_by(just(Token::ParenOpen), just(Token::ParenClose)),
+            ),
+        )
+        .map_with(
+            |(cpp_name, (name, (offset, ty))), extra| ParsedTypeItem::Field {
+                name,
+                ty,
+                offset,
+                doc: doc_comment(extra),
+                cpp_name,
+            },
+        );
+    let trait_impl = impl_keyword()
+        .ignore_then(rust_trait(rust_type()))
+        .then(
+            just(Token::KwFor)
+                .ignore_then(spanned(rust_type()))
+                .or_not(),
+        )
+        .map(|(tr, ty)| ParsedTypeItem::TraitImpl { tr, ty });
     let cpp_value = just(Token::Sharp)
         .then(just(Token::Ident("cpp_value")))
         .ignore_then(select! {
@@ -1774,13 +2763,7 @@ fn inner_type_item<'a>()
         .ignore_then(select! {
             Token::Str(c) => c,
         })
-        .then(
-            property_item
-                .clone()
-                .separated_by(just(Token::Comma))
-                .collect::<Vec<_>>()
-                .delimited_by(just(Token::ParenOpen), just(Token::ParenClose)),
-        )
+        .then(layout_properties())
         .map(|(cpp_type, props)| ParsedTypeItem::CppStackOwned { cpp_type, props });
     recursive(|item| {
         let inner_item = choice((
@@ -1788,6 +2771,7 @@ fn inner_type_item<'a>()
             traits,
             constructor,
             field,
+            trait_impl,
             cpp_value,
             cpp_ref,
             cpp_stack_owned,
@@ -1809,25 +2793,163 @@ fn inner_type_item<'a>()
                     use_path,
                     data,
                 }),
+            // After the methods, which can start with a result mode attribute.
+            result_mode().map(ParsedTypeItem::ResultMode),
         ));
+        // Anything else starting with a name is an enum variant.
+        let inner_item = if is_enum {
+            inner_item.or(variant.clone()).boxed()
+        } else {
+            inner_item.boxed()
+        };
 
         let match_stmt =
             conditional_item::<_, CfgConditional<'a>, NItems>(item).map(ParsedTypeItem::MatchOnCfg);
 
         choice((match_stmt, inner_item.then_ignore(just(Token::Semicolon))))
+            .recover_with(via_parser(skip_malformed_item().to(ParsedTypeItem::Error)))
     })
 }
 
-fn type_item<'a>() -> impl Parser<'a, ParserInput<'a>, ParsedItem<'a>, ZngParserExtra<'a>> + Clone {
-    just(Token::KwType)
-        .ignore_then(spanned(rust_type()))
+fn layout_properties<'a>() -> impl Parser<
+    'a,
+    ParserInput<'a>,
+    Vec<(Spanned<&'a str>, Spanned<ParsedLayoutExpr<'a>>)>,
+    ZngParserExtra<'a>,
+> + Clone {
+    let property_item = (spanned(select! {
+        Token::Ident(c) => c,
+    }))
+    .then_ignore(just(Token::Eq))
+    .then(spanned(layout_expr()));
+    property_item
+        .separated_by(just(Token::Comma))
+        .collect::<Vec<_>>()
+        .delimited_by(just(Token::ParenOpen), just(Token::ParenClose))
+        .boxed()
+}
+
+fn layout_policy<'a>()
+-> impl Parser<'a, ParserInput<'a>, (Span, ParsedLayoutPolicy<'a>), ZngParserExtra<'a>> + Clone {
+    just([Token::Sharp, Token::Ident("layout")])
+        .ignore_then(
+            just(Token::Ident("auto"))
+                .delimited_by(just(Token::ParenOpen), just(Token::ParenClose))
+                .to(ParsedLayoutPolicy::Auto)
+                .or(layout_properties().map(ParsedLayoutPolicy::StackAllocated)),
+        )
+        .or(just([Token::Sharp, Token::Ident("layout_conservative")])
+            .ignore_then(layout_properties())
+            .map(ParsedLayoutPolicy::Conservative))
+        .or(just([Token::Sharp, Token::Ident("only_by_ref")]).to(ParsedLayoutPolicy::OnlyByRef))
+        .or(just([Token::Sharp, Token::Ident("heap_allocated")])
+            .to(ParsedLayoutPolicy::HeapAllocated))
+        .map_with(|x, extra| (extra.span(), x))
+        .boxed()
+}
+
+/// The `for T in [A, B => #layout(...)]` part of a templated type item.
+fn type_template_instances<'a>() -> impl Parser<
+    'a,
+    ParserInput<'a>,
+    (Spanned<&'a str>, Vec<ParsedTemplateInstance<'a>>),
+    ZngParserExtra<'a>,
+> + Clone {
+    let instance = spanned(rust_type())
+        .then(just(Token::ArrowArm).ignore_then(layout_policy()).or_not())
+        .map(|(ty, layout)| ParsedTemplateInstance { ty, layout });
+    just(Token::KwFor)
+        .ignore_then(spanned(select! {
+            Token::Ident(c) => c,
+        }))
+        .then_ignore(just(Token::Ident("in")))
         .then(
-            spanned(inner_type_item())
-                .repeated()
+            instance
+                .separated_by(just(Token::Comma))
+                .allow_trailing()
                 .collect::<Vec<_>>()
-                .delimited_by(just(Token::BraceOpen), just(Token::BraceClose)),
+                .delimited_by(just(Token::BracketOpen), just(Token::BracketClose)),
+        )
+        .boxed()
+}
+
+fn type_item<'a>() -> impl Parser<'a, ParserInput<'a>, ParsedItem<'a>, ZngParserExtra<'a>> + Clone {
+    let template_param = select! {
+        Token::Ident(c) => c,
+    }
+    .delimited_by(just(Token::AngleOpen), just(Token::AngleClose));
+    // Variants are only parsed in `enum` items, so that a typo in a `type` item reports the
+    // expected items instead.
+    let type_or_enum = |keyword, is_enum| {
+        just(keyword)
+            .to(is_enum)
+            .then(spanned(template_param.clone()).or_not())
+            .then(spanned(rust_type()))
+            .then(type_template_instances().or_not())
+            .then(
+                spanned(inner_type_item(is_enum))
+                    .repeated()
+                    .collect::<Vec<_>>()
+                    .delimited_by(just(Token::BraceOpen), just(Token::BraceClose)),
+            )
+            .boxed()
+    };
+    cpp_name()
+        .or_not()
+        .then(choice((
+            type_or_enum(Token::KwType, false),
+            type_or_enum(Token::KwEnum, true),
+        )))
+        .validate(
+            |(cpp_name, ((((is_enum, param), ty), instances), items)), _, emitter| {
+                let template = match (param, instances) {
+                    (None, None) => None,
+                    (Some(param), Some((for_param, instances))) => {
+                        if for_param.inner != param.inner {
+                            emitter.emit(Rich::custom(
+                                for_param.span,
+                                format!("Expected the template parameter `{}`", param.inner),
+                            ));
+                        }
+                        Some(ParsedTypeTemplate {
+                            param: param.inner,
+                            instances,
+                        })
+                    }
+                    (Some(param), None) => {
+                        emitter.emit(Rich::custom(
+                            param.span,
+                            format!(
+                                "Templated types need a list of instances, like `for {} in [...]`",
+                                param.inner
+                            ),
+                        ));
+                        None
+                    }
+                    (None, Some((for_param, _))) => {
+                        emitter.emit(Rich::custom(
+                            for_param.span,
+                            format!(
+                                "Instances are only allowed for templated types, like `type<{}>`",
+                                for_param.inner
+                            ),
+                        ));
+                        None
+                    }
+                };
+                (cpp_name, is_enum, ty, template, items)
+            },
+        )
+        .map_with(
+            |(cpp_name, is_enum, ty, template, items), extra| ParsedItem::Type {
+                ty,
+                template,
+                items,
+                doc: doc_comment(extra),
+                cpp_name,
+                is_enum,
+            },
         )
-        .map(|(ty, items)| ParsedItem::Type { ty, items })
         .boxed()
 }
 
@@ -1836,8 +2958,7 @@ fn trait_item<'a>() -> impl Parser<'a, ParserInput<'a>, ParsedItem<'a>, ZngParse
     just(Token::KwTrait)
         .ignore_then(spanned(rust_trait(rust_type())))
         .then(
-            method()
-                .then_ignore(just(Token::Semicolon))
+            spanned(method().then_ignore(just(Token::Semicolon)))
                 .repeated()
                 .collect::<Vec<_>>()
                 .delimited_by(just(Token::BraceOpen), just(Token::BraceClose)),
@@ -1846,6 +2967,46 @@ fn trait_item<'a>() -> impl Parser<'a, ParserInput<'a>, ParsedItem<'a>, ZngParse
         .boxed()
 }
 
+/// The `impl` keyword of a trait impl, rejecting the generics of `impl<T: Bound> Trait for Type<T>`,
+/// which can't be exposed to C++ without a concrete type.
+fn impl_keyword<'a>() -> impl Parser<'a, ParserInput<'a>, (), ZngParserExtra<'a>> + Clone {
+    let bounds = rust_trait(rust_type())
+        .separated_by(just(Token::Plus))
+        .at_least(1);
+    let generic = select! {
+        Token::Ident(c) => c,
+    }
+    .then(just(Token::Colon).ignore_then(bounds).or_not());
+    just(Token::KwImpl)
+        .ignore_then(
+            generic
+                .separated_by(just(Token::Comma))
+                .allow_trailing()
+                .delimited_by(just(Token::AngleOpen), just(Token::AngleClose))
+                .map_with(|_, extra| extra.span())
+                .or_not(),
+        )
+        .validate(|generics: Option<Span>, _, emitter| {
+            if let Some(span) = generics {
+                emitter.emit(Rich::custom(
+                    span,
+                    "Generic trait impls are not supported, implement the trait for each type instead",
+                ));
+            }
+        })
+}
+
+fn trait_impl_item<'a>()
+-> impl Parser<'a, ParserInput<'a>, ParsedItem<'a>, ZngParserExtra<'a>> + Clone {
+    impl_keyword()
+        .ignore_then(spanned(rust_trait(rust_type())))
+        .then_ignore(just(Token::KwFor))
+        .then(spanned(rust_type()))
+        .then_ignore(just(Token::Semicolon))
+        .map(|(tr, ty)| ParsedItem::TraitImpl { tr, ty })
+        .boxed()
+}
+
 fn fn_item<'a>() -> impl Parser<'a, ParserInput<'a>, ParsedItem<'a>, ZngParserExtra<'a>> + Clone {
     spanned(method())
         .then_ignore(just(Token::Semicolon))
@@ -1885,19 +3046,19 @@ fn extern_cpp_item<'a>()
                 .then(spanned(rust_type())),
         )
         .then(
-            safety
-                .then(method())
-                .then_ignore(just(Token::Semicolon))
+            spanned(safety.then(method()).then_ignore(just(Token::Semicolon)))
                 .repeated()
                 .collect::<Vec<_>>()
                 .delimited_by(just(Token::BraceOpen), just(Token::BraceClose)),
         )
         .map(|((tr, ty), methods)| ParsedExternCppItem::Impl { tr, ty, methods });
+    let item = function.or(impl_block).recover_with(via_parser(
+        skip_malformed_item().to(ParsedExternCppItem::Error),
+    ));
     just(Token::KwExtern)
         .then(just(Token::Str("C++")))
         .ignore_then(
-            function
-                .or(impl_block)
+            spanned(item)
                 .repeated()
                 .collect::<Vec<_>>()
                 .delimited_by(just(Token::BraceOpen), just(Token::BraceClose))
@@ -1938,7 +3099,7 @@ fn item<'a>() -> impl Parser<'a, ParserInput<'a>, ParsedItem<'a>, ZngParserExtra
             just(Token::KwMod)
                 .ignore_then(path())
                 .then(
-                    item.clone()
+                    spanned(item.clone())
                         .repeated()
                         .collect::<Vec<_>>()
                         .delimited_by(just(Token::BraceOpen), just(Token::BraceClose)),
@@ -1946,6 +3107,7 @@ fn item<'a>() -> impl Parser<'a, ParserInput<'a>, ParsedItem<'a>, ZngParserExtra
                 .map(|(path, items)| ParsedItem::Mod { path, items }),
             type_item(),
             trait_item(),
+            trait_impl_item(),
             extern_cpp_item(),
             fn_item(),
             additional_include_item(),
@@ -1954,10 +3116,29 @@ fn item<'a>() -> impl Parser<'a, ParserInput<'a>, ParsedItem<'a>, ZngParserExtra
             alias(),
             conditional_item::<_, CfgConditional<'a>, NItems>(item).map(ParsedItem::MatchOnCfg),
         ))
+        .recover_with(via_parser(skip_malformed_item().to(ParsedItem::Error)))
     })
     .boxed()
 }
 
+/// Skips the tokens of a malformed item, up to and including the next `;` or the next balanced
+/// `{ ... }` block, whichever comes first.
+///
+/// This never consumes the `}` closing the enclosing block, so the block itself can still be parsed.
+fn skip_malformed_item<'a>() -> impl Parser<'a, ParserInput<'a>, (), ZngParserExtra<'a>> + Clone {
+    let block = recursive(|block| {
+        block
+            .or(none_of([Token::BraceOpen, Token::BraceClose]).ignored())
+            .repeated()
+            .delimited_by(just(Token::BraceOpen), just(Token::BraceClose))
+    });
+    none_of([Token::Semicolon, Token::BraceOpen, Token::BraceClose])
+        .repeated()
+        .then(just(Token::Semicolon).ignored().or(block))
+        .ignored()
+        .boxed()
+}
+
 fn import_item<'a>() -> impl Parser<'a, ParserInput<'a>, ParsedItem<'a>, ZngParserExtra<'a>> + Clone
 {
     just(Token::KwMerge)
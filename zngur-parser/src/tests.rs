@@ -4,8 +4,9 @@ use expect_test::{Expect, expect};
 use zngur_def::{LayoutPolicy, RustPathAndGenerics, RustType, ZngurSpec};
 
 use crate::{
-    ImportResolver, ParsedZngFile,
+    ImportResolver, ParseResult, ParsedZngFile,
     cfg::{InMemoryRustCfgProvider, NullCfg, RustCfgProvider},
+    import::{ImportOptions, glob_matches},
 };
 
 fn check_success(zng: &str) {
@@ -70,10 +71,7 @@ fn catch_parse_fail(
         Err(e) => match e.downcast::<ErrorText>() {
             Ok(t) => {
                 eprintln!("{}", &t.0);
-                crate::ParseResult {
-                    spec: ZngurSpec::default(),
-                    processed_files: Vec::new(),
-                }
+                crate::ParseResult::default()
             }
             Err(e) => std::panic::resume_unwind(e),
         },
@@ -122,12 +120,19 @@ type () {
 }
     "#,
         expect![[r#"
-            Error: found 'welcome_traits' expected '#', 'wellknown_traits', 'constructor', 'field', 'async', 'fn', or '}'
+            Error: found 'welcome_traits' expected '#', 'wellknown_traits', 'constructor', 'field', 'impl', 'async', or 'fn'
                ╭─[test.zng:4:5]
                │
              4 │     welcome_traits(Copy);
                │     ───────┬──────  
-               │            ╰──────── found 'welcome_traits' expected '#', 'wellknown_traits', 'constructor', 'field', 'async', 'fn', or '}'
+               │            ╰──────── found 'welcome_traits' expected '#', 'wellknown_traits', 'constructor', 'field', 'impl', 'async', or 'fn'
+            ───╯
+            Error: Unit type is declared implicitly. Remove this entirely.
+               ╭─[test.zng:2:6]
+               │
+             2 │ type () {
+               │      ─┬  
+               │       ╰── Unit type is declared implicitly. Remove this entirely.
             ───╯
         "#]],
     );
@@ -260,6 +265,31 @@ impl ImportResolver for MockFilesystem {
             .cloned()
             .ok_or_else(|| format!("File not found: {}", path.display()))
     }
+
+    fn glob_import(
+        &self,
+        cwd: &std::path::Path,
+        pattern: &std::path::Path,
+    ) -> Result<Vec<std::path::PathBuf>, String> {
+        let mut matches = self
+            .files
+            .keys()
+            .filter_map(|path| path.strip_prefix(cwd).ok())
+            .filter(|path| glob_matches(pattern, path))
+            .map(|path| path.to_owned())
+            .collect::<Vec<_>>();
+        matches.sort();
+        Ok(matches)
+    }
+}
+
+fn parse_with_import_options(
+    zng: &str,
+    resolver: &MockFilesystem,
+    import_options: ImportOptions,
+) -> ParseResult {
+    ParsedZngFile::try_parse_str_with_resolver(zng, NullCfg, resolver, import_options)
+        .unwrap_or_else(|diagnostics| crate::emit_diagnostics(diagnostics))
 }
 
 #[test]
@@ -344,6 +374,12 @@ fn import_not_found() {
     "#,
         expect![[r#"
             Error: Import path not found: ./a.zng
+               ╭─[test.zng:2:5]
+               │
+             2 │     merge "./a.zng";
+               │     ────────┬───────  
+               │             ╰───────── Import path not found: ./a.zng
+            ───╯
         "#]],
         &resolver,
     );
@@ -541,6 +577,134 @@ type Main {
     assert!(file_names.contains(&"c.zng"));
 }
 
+#[test]
+fn glob_import() {
+    let resolver = MockFilesystem::new(vec![
+        ("./types/b.zng", "type B { #layout(size = 1, align = 1); }"),
+        ("./types/a.zng", "type A { #layout(size = 1, align = 1); }"),
+        (
+            "./types/nested/c.zng",
+            "type C { #layout(size = 1, align = 1); }",
+        ),
+        (
+            "./other.zng",
+            "type Other { #layout(size = 1, align = 1); }",
+        ),
+    ]);
+
+    let parsed = ParsedZngFile::parse_str_with_resolver(
+        r#"
+merge "./types/*.zng";
+        "#,
+        NullCfg,
+        &resolver,
+    );
+    expect![[r#"
+        [
+            "test.zng",
+            "./types/a.zng",
+            "./types/b.zng",
+        ]
+    "#]]
+    .assert_debug_eq(&parsed.processed_files);
+}
+
+#[test]
+fn glob_import_without_matches() {
+    let resolver = MockFilesystem::new(vec![(
+        "./types/a.zng",
+        "type A { #layout(size = 1, align = 1); }",
+    )]);
+    check_import_fail(
+        r#"
+    merge "./types/*.zngur";
+    "#,
+        expect![[r#"
+            Error: No files match ./types/*.zngur
+               ╭─[test.zng:2:5]
+               │
+             2 │     merge "./types/*.zngur";
+               │     ────────────┬───────────  
+               │                 ╰───────────── No files match ./types/*.zngur
+            ───╯
+        "#]],
+        &resolver,
+    );
+}
+
+#[test]
+fn include_dir_import() {
+    let resolver = MockFilesystem::new(vec![
+        (
+            "shared/std_types.zng",
+            r#"merge "./option.zng"; type ::std::string::String { #layout(size = 24, align = 8); }"#,
+        ),
+        (
+            "shared/option.zng",
+            "type ::std::option::Option<i32> { #layout(size = 8, align = 4); }",
+        ),
+        (
+            "vendor/std_types.zng",
+            "type Shadowed { #layout(size = 1, align = 1); }",
+        ),
+        (
+            "vendor/extra.zng",
+            "type Extra { #layout(size = 1, align = 1); }",
+        ),
+    ]);
+
+    let parsed = parse_with_import_options(
+        r#"
+merge "std_types.zng";
+merge "extra.zng";
+        "#,
+        &resolver,
+        ImportOptions::new()
+            .with_include_dir("shared")
+            .with_include_dir("vendor"),
+    );
+    expect![[r#"
+        [
+            "test.zng",
+            "shared/std_types.zng",
+            "shared/./option.zng",
+            "vendor/extra.zng",
+        ]
+    "#]]
+    .assert_debug_eq(&parsed.processed_files);
+}
+
+#[test]
+fn package_import() {
+    let resolver = MockFilesystem::new(vec![
+        (
+            "crates/mycrate/zng/foo.zng",
+            "type ::mycrate::Foo { #layout(size = 1, align = 1); }",
+        ),
+        (
+            "crates/mycrate/zng/bar.zng",
+            "type ::mycrate::Bar { #layout(size = 1, align = 1); }",
+        ),
+    ]);
+
+    let parsed = parse_with_import_options(
+        r#"
+merge "@mycrate/zng/foo.zng";
+merge "@mycrate/zng/b*.zng";
+        "#,
+        &resolver,
+        ImportOptions::new().with_package("mycrate", "crates/mycrate"),
+    );
+    expect![[r#"
+        [
+            "test.zng",
+            "crates/mycrate/zng/foo.zng",
+            "crates/mycrate/zng/bar.zng",
+        ]
+    "#]]
+    .assert_debug_eq(&parsed.processed_files);
+}
+
 fn assert_layout(wanted_size: usize, wanted_align: usize, layout: &LayoutPolicy) {
     if !matches!(layout, LayoutPolicy::StackAllocated { size, align } if *size == wanted_size && *align == wanted_align)
     {
@@ -937,12 +1101,12 @@ extern "C++" {
     check_fail(
         source,
         expect![[r#"
-            Error: found 'fn' expected 'safe', 'unsafe', 'impl', or '}'
+            Error: found 'fn' expected 'safe', 'unsafe', or 'impl'
                ╭─[test.zng:3:5]
                │
              3 │     fn foo();
                │     ─┬  
-               │      ╰── found 'fn' expected 'safe', 'unsafe', 'impl', or '}'
+               │      ╰── found 'fn' expected 'safe', 'unsafe', or 'impl'
             ───╯
         "#]],
     );
@@ -966,3 +1130,2425 @@ extern "C++" {
         "#]],
     );
 }
+
+#[test]
+fn syntax_errors_recovered_in_merged_files() {
+    let resolver = MockFilesystem::new(vec![(
+        "./a.zng",
+        r#"
+type A {
+    #layout(size = 1 align = 1);
+    fn foo(&self) -> i32;
+}
+extern "C++" {
+    safe fn bar() -> ;
+}
+"#,
+    )]);
+
+    check_import_fail(
+        r#"
+merge "./a.zng";
+type B {
+    #layout(size = 1, align = 1);
+    fn foo(&self) i32;
+}
+fn baz(;
+trait C {
+    fn qux(&self);
+}
+"#,
+        expect![[r#"
+            Error: found 'i32' expected '->', 'use', 'deref', or ';'
+               ╭─[test.zng:5:19]
+               │
+             5 │     fn foo(&self) i32;
+               │                   ─┬─  
+               │                    ╰─── found 'i32' expected '->', 'use', 'deref', or ';'
+            ───╯
+            Error: found ';' expected something else, 'Box', '(', '[', '::', 'crate', '&', '*', 'dyn', 'impl', 'unsafe', 'extern', 'fn', or ')'
+               ╭─[test.zng:7:8]
+               │
+             7 │ fn baz(;
+               │        ┬  
+               │        ╰── found ';' expected something else, 'Box', '(', '[', '::', 'crate', '&', '*', 'dyn', 'impl', 'unsafe', 'extern', 'fn', or ')'
+            ───╯
+            Error: found 'align' expected '*', '+', ',', or ')'
+               ╭─[a.zng:3:22]
+               │
+             3 │     #layout(size = 1 align = 1);
+               │                      ──┬──  
+               │                        ╰──── found 'align' expected '*', '+', ',', or ')'
+            ───╯
+            Error: found ';' expected something else, 'Box', '(', '[', '::', 'crate', '&', '*', 'dyn', 'impl', 'unsafe', 'extern', or 'fn'
+               ╭─[a.zng:7:22]
+               │
+             7 │     safe fn bar() -> ;
+               │                      ┬  
+               │                      ╰── found ';' expected something else, 'Box', '(', '[', '::', 'crate', '&', '*', 'dyn', 'impl', 'unsafe', 'extern', or 'fn'
+            ───╯
+        "#]],
+        &resolver,
+    );
+}
+
+#[test]
+fn type_template_is_expanded_per_instance() {
+    let parsed = ParsedZngFile::parse_str(
+        r#"
+type crate::Item {
+    #layout(size = 12, align = 4);
+}
+
+type<T> ::std::option::Option<T> for T in [i32, crate::Item => #layout(size = 16, align = 4)] {
+    #layout(size = 8, align = 4);
+    wellknown_traits(Debug);
+    fn unwrap(self) -> T;
+    fn as_ref(&self) -> ::std::option::Option<&T>;
+}
+"#,
+        NullCfg,
+    );
+    expect![[r#"
+        [
+            (
+                "crate::Item",
+                StackAllocated {
+                    size: 12,
+                    align: 4,
+                },
+                [],
+            ),
+            (
+                "::std::option::Option::<i32>",
+                StackAllocated {
+                    size: 8,
+                    align: 4,
+                },
+                [
+                    "i32",
+                    "::std::option::Option::<&i32>",
+                ],
+            ),
+            (
+                "::std::option::Option::<crate::Item>",
+                StackAllocated {
+                    size: 16,
+                    align: 4,
+                },
+                [
+                    "crate::Item",
+                    "::std::option::Option::<&crate::Item>",
+                ],
+            ),
+        ]
+    "#]]
+    .assert_debug_eq(
+        &parsed
+            .spec
+            .types
+            .iter()
+            .map(|ty| {
+                (
+                    ty.ty.to_string(),
+                    &ty.layout,
+                    ty.methods
+                        .iter()
+                        .map(|m| m.data.output.to_string())
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>(),
+    );
+}
+
+#[test]
+fn type_template_instance_layout_overrides_cfg_layouts() {
+    let parsed = ParsedZngFile::try_parse_str(
+        r#"
+#unstable(cfg_if)
+#unstable(cfg_match)
+
+type crate::Item {
+    #layout(size = 12, align = 4);
+}
+
+type<T> ::std::option::Option<T> for T in [i32, crate::Item => #layout(size = 16, align = 4)] {
+    #if cfg!(target_pointer_width = "64") {
+        #layout(size = 8, align = 4);
+    } #else {
+        #match cfg!(target_os) {
+            "linux" => #layout(size = 8, align = 4);
+            _ => #heap_allocated;
+        }
+    }
+    fn unwrap(self) -> T;
+}
+"#,
+        InMemoryRustCfgProvider::default().with_values([("target_pointer_width", &["32"])]),
+    )
+    .unwrap_or_else(|e| panic!("{e}"));
+    expect![[r#"
+        [
+            (
+                "crate::Item",
+                StackAllocated {
+                    size: 12,
+                    align: 4,
+                },
+            ),
+            (
+                "::std::option::Option::<i32>",
+                HeapAllocated,
+            ),
+            (
+                "::std::option::Option::<crate::Item>",
+                StackAllocated {
+                    size: 16,
+                    align: 4,
+                },
+            ),
+        ]
+    "#]]
+    .assert_debug_eq(
+        &parsed
+            .spec
+            .types
+            .iter()
+            .map(|ty| (ty.ty.to_string(), &ty.layout))
+            .collect::<Vec<_>>(),
+    );
+}
+
+#[test]
+fn type_template_parameter_must_match() {
+    check_fail(
+        r#"
+type<T> ::std::option::Option<T> for U in [i32] {
+    #layout(size = 8, align = 4);
+}
+type<T> ::std::vec::Vec<T> {
+    #layout(size = 24, align = 8);
+}
+"#,
+        expect![[r#"
+            Error: Expected the template parameter `T`
+               ╭─[test.zng:2:38]
+               │
+             2 │ type<T> ::std::option::Option<T> for U in [i32] {
+               │                                      ┬  
+               │                                      ╰── Expected the template parameter `T`
+            ───╯
+            Error: Templated types need a list of instances, like `for T in [...]`
+               ╭─[test.zng:5:5]
+               │
+             5 │ type<T> ::std::vec::Vec<T> {
+               │     ─┬─  
+               │      ╰─── Templated types need a list of instances, like `for T in [...]`
+            ───╯
+        "#]],
+    );
+}
+
+#[test]
+fn index_resolves_aliases_and_modules() {
+    let source = r#"
+use ::std::vec::Vec as MyVec;
+merge "./types.zng";
+
+mod crate {
+    type Item {
+        #layout(size = 8, align = 8);
+    }
+
+    fn make() -> MyVec<Item>;
+}
+"#;
+    let index = crate::index::FileIndex::build(source);
+    assert!(index.errors.is_empty());
+
+    let item_def = index.definitions_of(&["crate".to_owned(), "Item".to_owned()])[0];
+    assert_eq!(item_def.label, "type crate::Item");
+    assert_eq!(
+        item_def.detail.as_deref(),
+        Some("#layout(size = 8, align = 8)")
+    );
+
+    let vec_ref = index
+        .reference_at(source.find("MyVec<Item>").unwrap())
+        .unwrap();
+    assert_eq!(vec_ref.path, ["std", "vec", "Vec"]);
+    assert_eq!(
+        vec_ref.alias.clone().map(|span| &source[span]),
+        Some("use ::std::vec::Vec as MyVec;")
+    );
+
+    let item_ref = index.reference_at(source.find("Item>;").unwrap()).unwrap();
+    assert_eq!(item_ref.path, ["crate", "Item"]);
+
+    let link = index.link_at(source.find("types.zng").unwrap()).unwrap();
+    assert_eq!(link.path.to_str(), Some("./types.zng"));
+}
+
+#[test]
+fn index_reports_syntax_errors() {
+    let index = crate::index::FileIndex::build("type crate::Foo { #layout(size = 8 align = 8); }");
+    assert_eq!(index.errors.len(), 1);
+    // The malformed layout is skipped, and the type around it is still indexed.
+    assert_eq!(index.definitions[0].label, "type crate::Foo");
+}
+
+fn check_format(zng: &str, formatted: Expect) {
+    let result = crate::formatter::format_zng(zng).unwrap();
+    formatted.assert_eq(&result);
+    assert_eq!(crate::formatter::format_zng(&result).unwrap(), result);
+}
+
+#[test]
+fn format_every_item_kind() {
+    check_format(
+        r#"
+#unstable(cfg_if) #unstable(cfg_match)
+#convert_panic_to_exception
+#cpp_additional_includes "
+#include <memory>
+"
+use ::std::vec::Vec as MyVec ;
+merge "./types.zng";  import "./module.zng";
+
+
+// A type with every kind of item
+type   crate::Foo{
+#layout(size=16,align=8); wellknown_traits(Debug,Copy);
+    constructor Bar(i32,( i32 ,)) ;
+    constructor { x: i32, y: &mut [u8] };
+    field x (offset = auto, type = i32 ); // trailing comment
+    fn new() -> crate::Foo;
+    async fn wait(&self) -> i32;
+    fn get(&self,usize) -> ::std::option::Option<&i32> deref [i32];
+    fn call(&self) -> Box<dyn Fn(i32) -> i32 + Send> use crate::Foo::method;
+    #if cfg!(feature."float-values") && !cfg!(target_os = "linux" | "macos") { fn float(&self) -> f64; }
+    #else if cfg!(debug_assertions) {} #else { fn int(&self) -> i32; }
+}
+type str { wellknown_traits(?Sized); #cpp_ref "::std::string_view"; }
+mod ::std { mod vec {
+    type Vec<i32> { #heap_allocated;
+        #match cfg!(target_pointer_width) { 32 => fn push(&mut self, i32); _ => {} }
+    }
+} }
+trait ::std::iter::Iterator::<Item = i32> {
+    fn next(&mut self) -> ::std::option::Option<i32>; // the only method
+}
+extern "C++" {
+    safe fn callback(*const u8, *mut u8);
+    impl crate::Foo {
+        // comment inside an impl
+        unsafe fn raw(&self) -> *mut i8;
+    }
+}
+fn free<i32>(impl Fn(i32)) -> ();
+#match (cfg!(target_os), cfg!(feature)) {
+    ("linux", "a" | "b") => type crate::Linux { #only_by_ref; }
+    _ => {
+        // nothing
+    }
+}
+// last comment
+"#,
+        expect![[r##"
+            #unstable(cfg_if)
+            #unstable(cfg_match)
+            #convert_panic_to_exception
+            #cpp_additional_includes "
+            #include <memory>
+            "
+            use ::std::vec::Vec as MyVec;
+            merge "./types.zng";
+            import "./module.zng";
+
+            // A type with every kind of item
+            type crate::Foo {
+                #layout(size = 16, align = 8);
+                wellknown_traits(Debug, Copy);
+                constructor Bar(i32, (i32,));
+                constructor { x: i32, y: &mut [u8] };
+                field x (offset = auto, type = i32); // trailing comment
+                fn new() -> crate::Foo;
+                async fn wait(&self) -> i32;
+                fn get(&self, usize) -> ::std::option::Option<&i32> deref [i32];
+                fn call(&self) -> Box<dyn Fn(i32) -> i32 + Send> use crate::Foo::method;
+                #if cfg!(feature."float-values") && !cfg!(target_os = "linux" | "macos") {
+                    fn float(&self) -> f64;
+                } #else if cfg!(debug_assertions) {} #else {
+                    fn int(&self) -> i32;
+                }
+            }
+            type str {
+                wellknown_traits(?Sized);
+                #cpp_ref "::std::string_view";
+            }
+            mod ::std {
+                mod vec {
+                    type Vec<i32> {
+                        #heap_allocated;
+                        #match cfg!(target_pointer_width) {
+                            32 => {
+                                fn push(&mut self, i32);
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            trait ::std::iter::Iterator<Item = i32> {
+                fn next(&mut self) -> ::std::option::Option<i32>; // the only method
+            }
+            extern "C++" {
+                safe fn callback(*const u8, *mut u8);
+                impl crate::Foo {
+                    // comment inside an impl
+                    unsafe fn raw(&self) -> *mut i8;
+                }
+            }
+            fn free<i32>(impl Fn(i32));
+            #match (cfg!(target_os), cfg!(feature)) {
+                ("linux", "a" | "b") => {
+                    type crate::Linux {
+                        #only_by_ref;
+                    }
+                }
+                _ => {
+                    // nothing
+                }
+            }
+            // last comment
+        "##]],
+    );
+}
+
+#[test]
+fn format_reports_syntax_errors() {
+    let errors = crate::formatter::format_zng("type crate::Foo { fn foo() }").unwrap_err();
+    assert_eq!(errors.len(), 1);
+}
+
+#[test]
+fn try_parse_returns_diagnostics() {
+    let diagnostics = ParsedZngFile::try_parse_str(
+        r#"
+type str {
+    wellknown_traits(?Sized);
+    #layout(size = 1, align = 1);
+}
+"#,
+        NullCfg,
+    )
+    .unwrap_err();
+    let text = diagnostics
+        .source(std::path::Path::new("test.zng"))
+        .unwrap();
+    expect![[r##"
+        [
+            (
+                Error,
+                "test.zng",
+                "#layout(size = 1, align = 1)",
+                "Duplicate layout policy found for unsized type.",
+                [
+                    (
+                        "#layout(size = 1, align = 1)",
+                        "Unsized types have implicit layout policy, remove this.",
+                    ),
+                    (
+                        "?Sized",
+                        "Type declared as unsized here.",
+                    ),
+                ],
+            ),
+        ]
+    "##]]
+    .assert_debug_eq(
+        &diagnostics
+            .iter()
+            .map(|d| {
+                (
+                    d.severity,
+                    d.path.display().to_string(),
+                    &text[d.span.clone()],
+                    &d.message,
+                    d.labels
+                        .iter()
+                        .map(|l| (&text[l.span.clone()], &l.message))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>(),
+    );
+}
+
+#[test]
+fn format_type_templates() {
+    check_format(
+        r#"
+type<T>::std::option::Option<T> for T in [i32,crate::Item=>#layout(size=16,align=8),] {
+#layout(size = 8, align = 4);
+}
+type<T> ::std::vec::Vec<T> for T in [i32, crate::Item, crate::SomeVeryLongTypeName, ::std::string::String, u8] {
+    #layout(size = 24, align = 8);
+}
+"#,
+        expect![[r#"
+            type<T> ::std::option::Option<T> for T in [i32, crate::Item => #layout(size = 16, align = 8)] {
+                #layout(size = 8, align = 4);
+            }
+            type<T> ::std::vec::Vec<T> for T in [
+                i32,
+                crate::Item,
+                crate::SomeVeryLongTypeName,
+                ::std::string::String,
+                u8,
+            ] {
+                #layout(size = 24, align = 8);
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn layout_auto() {
+    let parsed = ParsedZngFile::parse_str(
+        r#"
+type ::std::string::String {
+    #layout(auto);
+}
+
+type<T> ::std::vec::Vec<T> for T in [i32, crate::Point => #layout(auto)] {
+    #layout(size = 24, align = 8);
+}
+"#,
+        NullCfg,
+    );
+    expect![[r#"
+        [
+            (
+                "::std::string::String",
+                Auto,
+            ),
+            (
+                "::std::vec::Vec::<i32>",
+                StackAllocated {
+                    size: 24,
+                    align: 8,
+                },
+            ),
+            (
+                "::std::vec::Vec::<crate::Point>",
+                Auto,
+            ),
+        ]
+    "#]]
+    .assert_debug_eq(
+        &parsed
+            .spec
+            .types
+            .iter()
+            .map(|ty| (ty.ty.to_string(), &ty.layout))
+            .collect::<Vec<_>>(),
+    );
+    check_format(
+        r#"
+type ::std::string::String { #layout(auto); }
+"#,
+        expect![[r#"
+            type ::std::string::String {
+                #layout(auto);
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn array_layout_is_inferred() {
+    let parsed = ParsedZngFile::parse_str(
+        r#"
+type [u8; 32] {
+    wellknown_traits(Copy);
+}
+
+type [[f32; 4]; 4] {}
+
+type [u64; 2] {
+    #layout(size = 16, align = 8);
+}
+
+mod crate {
+    fn hash(&str) -> [u8; 32];
+}
+"#,
+        NullCfg,
+    );
+    expect![[r#"
+        [
+            (
+                "[u8; 32]",
+                StackAllocated {
+                    size: 32,
+                    align: 1,
+                },
+            ),
+            (
+                "[[f32; 4]; 4]",
+                StackAllocated {
+                    size: 64,
+                    align: 4,
+                },
+            ),
+            (
+                "[u64; 2]",
+                StackAllocated {
+                    size: 16,
+                    align: 8,
+                },
+            ),
+        ]
+    "#]]
+    .assert_debug_eq(
+        &parsed
+            .spec
+            .types
+            .iter()
+            .map(|ty| (ty.ty.to_string(), &ty.layout))
+            .collect::<Vec<_>>(),
+    );
+}
+
+#[test]
+fn array_of_target_dependent_layout_needs_layout() {
+    check_fail(
+        r#"
+type [u64; 2] {
+    wellknown_traits(Copy);
+}
+"#,
+        expect![[r#"
+            Error: No layout policy found for this type. Use one of `#layout(size = X, align = Y)`, `#heap_allocated` or `#only_by_ref`.
+               ╭─[test.zng:2:6]
+               │
+             2 │ type [u64; 2] {
+               │      ────┬───  
+               │          ╰───── No layout policy found for this type. Use one of `#layout(size = X, align = Y)`, `#heap_allocated` or `#only_by_ref`.
+            ───╯
+        "#]],
+    );
+}
+
+#[test]
+fn fn_pointer_types() {
+    let parsed = ParsedZngFile::parse_str(
+        r#"
+mod crate {
+    fn register(extern "C" fn(*mut u8, usize), *mut u8);
+    fn callback() -> unsafe extern fn(i32) -> i32;
+    fn map(fn(&str) -> bool, unsafe fn()) -> extern "C-unwind" fn();
+}
+"#,
+        NullCfg,
+    );
+    expect![[r#"
+        [
+            [
+                "extern \"C\" fn(*mut u8, usize)",
+                "*mut u8",
+                "()",
+            ],
+            [
+                "unsafe extern \"C\" fn(i32) -> i32",
+            ],
+            [
+                "fn(&str) -> bool",
+                "unsafe fn()",
+                "extern \"C-unwind\" fn()",
+            ],
+        ]
+    "#]]
+    .assert_debug_eq(
+        &parsed
+            .spec
+            .funcs
+            .iter()
+            .map(|f| {
+                f.inputs
+                    .iter()
+                    .chain(Some(&f.output))
+                    .map(|ty| ty.to_string())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>(),
+    );
+}
+
+#[test]
+fn doc_comments_are_kept() {
+    let parsed = ParsedZngFile::parse_str(
+        r#"
+/// A point in the plane.
+///
+/// Both coordinates are in pixels.
+type crate::Point {
+    #layout(size = 8, align = 4);
+
+    /// Creates a point.
+    constructor { x: i32, y: i32 };
+    /// The horizontal coordinate.
+    field x (offset = 0, type = i32);
+    // Not a doc comment.
+    field y (offset = 4, type = i32);
+
+    //// Not a doc comment either.
+    /// Distance from the origin.
+    fn norm(&self) -> i32;
+}
+
+mod crate {
+    /// The origin.
+    fn origin() -> Point;
+}
+"#,
+        NullCfg,
+    );
+    let ty = &parsed.spec.types[0];
+    expect![[r#"
+        (
+            Some(
+                "A point in the plane.\n\nBoth coordinates are in pixels.",
+            ),
+            [
+                Some(
+                    "Creates a point.",
+                ),
+            ],
+            [
+                Some(
+                    "The horizontal coordinate.",
+                ),
+                None,
+            ],
+            [
+                Some(
+                    "Distance from the origin.",
+                ),
+            ],
+            [
+                Some(
+                    "The origin.",
+                ),
+            ],
+        )
+    "#]]
+    .assert_debug_eq(&(
+        &ty.doc,
+        ty.constructors.iter().map(|c| &c.doc).collect::<Vec<_>>(),
+        ty.fields.iter().map(|f| &f.doc).collect::<Vec<_>>(),
+        ty.methods.iter().map(|m| &m.data.doc).collect::<Vec<_>>(),
+        parsed.spec.funcs.iter().map(|f| &f.doc).collect::<Vec<_>>(),
+    ));
+}
+
+#[test]
+fn merge_keeps_first_doc_comment() {
+    let parsed = ParsedZngFile::parse_str(
+        r#"
+/// The first declaration.
+type crate::Point {
+    #layout(size = 8, align = 4);
+    fn norm(&self) -> i32;
+}
+
+/// The second declaration.
+type crate::Point {
+    #layout(size = 8, align = 4);
+    /// Distance from the origin.
+    fn norm(&self) -> i32;
+}
+"#,
+        NullCfg,
+    );
+    let ty = &parsed.spec.types[0];
+    expect![[r#"
+        (
+            Some(
+                "The first declaration.",
+            ),
+            [
+                Some(
+                    "Distance from the origin.",
+                ),
+            ],
+        )
+    "#]]
+    .assert_debug_eq(&(
+        &ty.doc,
+        ty.methods.iter().map(|m| &m.data.doc).collect::<Vec<_>>(),
+    ));
+}
+
+#[test]
+fn format_keeps_doc_comments() {
+    check_format(
+        r#"
+/// A point.
+type crate::Point {
+    #layout(size = 8, align = 4);
+        /// Distance from the origin.
+    fn norm(&self) -> i32;
+}
+"#,
+        expect![[r#"
+            /// A point.
+            type crate::Point {
+                #layout(size = 8, align = 4);
+                /// Distance from the origin.
+                fn norm(&self) -> i32;
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn enum_variants() {
+    let parsed = ParsedZngFile::parse_str(
+        r#"
+enum crate::Shape {
+    #layout(size = 24, align = 8);
+    Circle(f64);
+    Rect { w: f64, h: f64 };
+    Empty;
+    fn area(&self) -> f64;
+}
+"#,
+        NullCfg,
+    );
+    let ty = &parsed.spec.types[0];
+    expect![[r#"
+        [
+            (
+                "Circle",
+                [
+                    "0: f64",
+                ],
+            ),
+            (
+                "Rect",
+                [
+                    "w: f64",
+                    "h: f64",
+                ],
+            ),
+            (
+                "Empty",
+                [],
+            ),
+        ]
+    "#]]
+    .assert_debug_eq(
+        &ty.variants
+            .iter()
+            .map(|v| {
+                let fields = v.fields.iter().map(|(name, ty)| format!("{name}: {ty}"));
+                (&v.name, fields.collect::<Vec<_>>())
+            })
+            .collect::<Vec<_>>(),
+    );
+}
+
+#[test]
+fn variants_need_enum_item() {
+    check_fail(
+        r#"
+type crate::Shape {
+    #layout(size = 24, align = 8);
+    Circle(f64);
+}
+"#,
+        expect![[r#"
+            Error: found 'Circle' expected '#', 'wellknown_traits', 'constructor', 'field', 'impl', 'async', or 'fn'
+               ╭─[test.zng:4:5]
+               │
+             4 │     Circle(f64);
+               │     ───┬──  
+               │        ╰──── found 'Circle' expected '#', 'wellknown_traits', 'constructor', 'field', 'impl', 'async', or 'fn'
+            ───╯
+        "#]],
+    );
+}
+
+#[test]
+fn format_enum() {
+    check_format(
+        r#"
+enum crate::Shape{#layout(size=24,align=8);Circle(f64);Rect{w:f64,h:f64};Empty;}
+enum<T> ::std::option::Option<T> for T in [i32] { #layout(size = 8, align = 4); None; Some(T); }
+"#,
+        expect![[r#"
+            enum crate::Shape {
+                #layout(size = 24, align = 8);
+                Circle(f64);
+                Rect { w: f64, h: f64 };
+                Empty;
+            }
+            enum<T> ::std::option::Option<T> for T in [i32] {
+                #layout(size = 8, align = 4);
+                None;
+                Some(T);
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn cpp_names() {
+    let parsed = ParsedZngFile::parse_str(
+        r#"
+#cpp_name "Point2D"
+type crate::Point {
+    #layout(size = 8, align = 4);
+
+    #cpp_name "fromPolar"
+    constructor polar { r: f32, theta: f32 };
+    #cpp_name "xCoord"
+    field x (offset = 0, type = f32);
+    field y (offset = 4, type = f32);
+
+    /// Consumes the point.
+    #cpp_name "intoArray"
+    fn into_array(self) -> [f32; 2];
+}
+
+mod crate {
+    #cpp_name "makeOrigin"
+    fn origin() -> Point;
+}
+"#,
+        NullCfg,
+    );
+    let ty = &parsed.spec.types[0];
+    expect![[r#"
+        (
+            Some(
+                "Point2D",
+            ),
+            [
+                Some(
+                    "fromPolar",
+                ),
+            ],
+            [
+                Some(
+                    "xCoord",
+                ),
+                None,
+            ],
+            [
+                (
+                    Some(
+                        "intoArray",
+                    ),
+                    Some(
+                        "Consumes the point.",
+                    ),
+                ),
+            ],
+            [
+                Some(
+                    "makeOrigin",
+                ),
+            ],
+        )
+    "#]]
+    .assert_debug_eq(&(
+        &ty.cpp_name,
+        ty.constructors
+            .iter()
+            .map(|c| &c.cpp_name)
+            .collect::<Vec<_>>(),
+        ty.fields.iter().map(|f| &f.cpp_name).collect::<Vec<_>>(),
+        ty.methods
+            .iter()
+            .map(|m| (&m.data.cpp_name, &m.data.doc))
+            .collect::<Vec<_>>(),
+        parsed
+            .spec
+            .funcs
+            .iter()
+            .map(|f| &f.cpp_name)
+            .collect::<Vec<_>>(),
+    ));
+}
+
+#[test]
+fn cpp_name_on_unnamed_constructor() {
+    check_fail(
+        r#"
+type crate::Point {
+    #layout(size = 8, align = 4);
+    #cpp_name "make"
+    constructor { x: f32, y: f32 };
+}
+"#,
+        expect![[r#"
+            Error: `#cpp_name` is only allowed on named constructors
+               ╭─[test.zng:4:5]
+               │
+             4 │ ╭─▶     #cpp_name "make"
+             5 │ ├─▶     constructor { x: f32, y: f32 };
+               │ │                                         
+               │ ╰───────────────────────────────────────── `#cpp_name` is only allowed on named constructors
+            ───╯
+        "#]],
+    );
+}
+
+#[test]
+fn invalid_cpp_names() {
+    check_fail(
+        r#"
+type crate::Point {
+    #layout(size = 8, align = 4);
+    #cpp_name "do it"
+    fn do_it(&self);
+    #cpp_name "delete"
+    fn delete(self);
+}
+"#,
+        expect![[r#"
+            Error: `do it` is not a valid C++ identifier
+               ╭─[test.zng:4:5]
+               │
+             4 │     #cpp_name "do it"
+               │     ────────┬────────  
+               │             ╰────────── `do it` is not a valid C++ identifier
+            ───╯
+            Error: `delete` is a C++ keyword
+               ╭─[test.zng:6:5]
+               │
+             6 │     #cpp_name "delete"
+               │     ─────────┬────────  
+               │              ╰────────── `delete` is a C++ keyword
+            ───╯
+        "#]],
+    );
+}
+
+#[test]
+fn format_cpp_names() {
+    check_format(
+        r#"
+#cpp_name "Point2D" type crate::Point {
+    #layout(size = 8, align = 4);
+    #cpp_name "xCoord" field x (offset = 0, type = f32);
+    #cpp_name "intoArray" fn into_array(self) -> [f32; 2];
+}
+"#,
+        expect![[r##"
+            #cpp_name "Point2D"
+            type crate::Point {
+                #layout(size = 8, align = 4);
+                #cpp_name "xCoord"
+                field x (offset = 0, type = f32);
+                #cpp_name "intoArray"
+                fn into_array(self) -> [f32; 2];
+            }
+        "##]],
+    );
+}
+
+#[test]
+fn result_modes() {
+    let parsed = ParsedZngFile::parse_str(
+        r#"
+type crate::Parser {
+    #layout(size = 0, align = 1);
+    #result_as_exception;
+
+    fn parse(&str) -> ::std::result::Result<i32, crate::Error>;
+    #result_as_expected
+    fn parse_expected(&str) -> ::std::result::Result<i32, crate::Error>;
+    fn len(&self) -> usize;
+}
+
+mod crate {
+    #result_as_expected
+    fn parse_all(&str) -> ::std::result::Result<i32, Error>;
+}
+"#,
+        NullCfg,
+    );
+    expect![[r#"
+        (
+            [
+                Some(
+                    Exception,
+                ),
+                Some(
+                    Expected,
+                ),
+                None,
+            ],
+            [
+                Some(
+                    Expected,
+                ),
+            ],
+        )
+    "#]]
+    .assert_debug_eq(&(
+        parsed.spec.types[0]
+            .methods
+            .iter()
+            .map(|m| m.data.result_mode)
+            .collect::<Vec<_>>(),
+        parsed
+            .spec
+            .funcs
+            .iter()
+            .map(|f| f.result_mode)
+            .collect::<Vec<_>>(),
+    ));
+}
+
+#[test]
+fn result_mode_needs_result() {
+    check_fail(
+        r#"
+type crate::Parser {
+    #layout(size = 0, align = 1);
+    #result_as_exception
+    fn len(&self) -> usize;
+}
+"#,
+        expect![[r#"
+            Error: The result mode needs a function returning `::std::result::Result<T, E>`
+               ╭─[test.zng:4:5]
+               │
+             4 │ ╭─▶     #result_as_exception
+             5 │ ├─▶     fn len(&self) -> usize;
+               │ │                                 
+               │ ╰───────────────────────────────── The result mode needs a function returning `::std::result::Result<T, E>`
+            ───╯
+        "#]],
+    );
+}
+
+#[test]
+fn duplicate_result_mode() {
+    check_fail(
+        r#"
+type crate::Parser {
+    #layout(size = 0, align = 1);
+    #result_as_exception;
+    #result_as_expected;
+}
+"#,
+        expect![[r#"
+            Error: Duplicate result mode found
+               ╭─[test.zng:5:5]
+               │
+             5 │     #result_as_expected;
+               │     ──────────┬─────────  
+               │               ╰─────────── Duplicate result mode found
+            ───╯
+        "#]],
+    );
+}
+
+#[test]
+fn result_mode_on_trait_method() {
+    check_fail(
+        r#"
+trait crate::Parse {
+    #result_as_exception
+    fn parse(&self) -> ::std::result::Result<i32, crate::Error>;
+}
+"#,
+        expect![[r#"
+            Error: Result modes are not supported on trait methods
+               ╭─[test.zng:3:5]
+               │
+             3 │ ╭─▶     #result_as_exception
+             4 │ ├─▶     fn parse(&self) -> ::std::result::Result<i32, crate::Error>;
+               │ │                                                                      
+               │ ╰────────────────────────────────────────────────────────────────────── Result modes are not supported on trait methods
+            ───╯
+        "#]],
+    );
+}
+
+#[test]
+fn format_result_modes() {
+    check_format(
+        r#"
+type crate::Parser {
+    #layout(size = 0, align = 1);
+    #result_as_exception;
+    #cpp_name "parseExpected" #result_as_expected fn parse_expected(&str) -> ::std::result::Result<i32, crate::Error>;
+}
+"#,
+        expect![[r#"
+            type crate::Parser {
+                #layout(size = 0, align = 1);
+                #result_as_exception;
+                #cpp_name "parseExpected"
+                #result_as_expected
+                fn parse_expected(&str) -> ::std::result::Result<i32, crate::Error>;
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn trait_impls() {
+    let parsed = ParsedZngFile::parse_str(
+        r#"
+type crate::Counter {
+    #layout(size = 4, align = 4);
+    impl ::std::iter::Iterator<Item = i32>;
+}
+
+mod crate {
+    type Shape {
+        #layout(size = 24, align = 8);
+        impl Describe for Shape;
+    }
+
+    impl ::std::fmt::Display for Counter;
+}
+"#,
+        NullCfg,
+    );
+    expect![[r#"
+        [
+            ZngurTraitImpl {
+                ty: Adt(
+                    RustPathAndGenerics {
+                        path: [
+                            "crate",
+                            "Counter",
+                        ],
+                        generics: [],
+                        named_generics: [],
+                    },
+                ),
+                tr: Normal(
+                    RustPathAndGenerics {
+                        path: [
+                            "std",
+                            "iter",
+                            "Iterator",
+                        ],
+                        generics: [],
+                        named_generics: [
+                            (
+                                "Item",
+                                Primitive(
+                                    Int(
+                                        32,
+                                    ),
+                                ),
+                            ),
+                        ],
+                    },
+                ),
+            },
+            ZngurTraitImpl {
+                ty: Adt(
+                    RustPathAndGenerics {
+                        path: [
+                            "crate",
+                            "Shape",
+                        ],
+                        generics: [],
+                        named_generics: [],
+                    },
+                ),
+                tr: Normal(
+                    RustPathAndGenerics {
+                        path: [
+                            "crate",
+                            "Describe",
+                        ],
+                        generics: [],
+                        named_generics: [],
+                    },
+                ),
+            },
+            ZngurTraitImpl {
+                ty: Adt(
+                    RustPathAndGenerics {
+                        path: [
+                            "crate",
+                            "Counter",
+                        ],
+                        generics: [],
+                        named_generics: [],
+                    },
+                ),
+                tr: Normal(
+                    RustPathAndGenerics {
+                        path: [
+                            "std",
+                            "fmt",
+                            "Display",
+                        ],
+                        generics: [],
+                        named_generics: [],
+                    },
+                ),
+            },
+        ]
+    "#]]
+    .assert_debug_eq(&parsed.spec.trait_impls);
+}
+
+#[test]
+fn trait_impl_for_another_type() {
+    check_fail(
+        r#"
+type crate::Counter {
+    #layout(size = 4, align = 4);
+    impl ::std::iter::Iterator<Item = i32> for crate::Shape;
+}
+"#,
+        expect![[r#"
+            Error: The trait must be implemented for the enclosing type
+               ╭─[test.zng:4:48]
+               │
+             4 │     impl ::std::iter::Iterator<Item = i32> for crate::Shape;
+               │                                                ──────┬─────  
+               │                                                      ╰─────── The trait must be implemented for the enclosing type
+            ───╯
+        "#]],
+    );
+}
+
+#[test]
+fn validate_trait_impls() {
+    let parsed = ParsedZngFile::try_parse_str(
+        r#"
+trait crate::Describe {
+    fn describe(&self) -> i32;
+}
+
+type crate::Counter {
+    #layout(size = 4, align = 4);
+    impl crate::Missing;
+}
+
+impl crate::Describe for crate::Shape;
+impl Fn(i32) -> i32 for crate::Counter;
+"#,
+        NullCfg,
+    )
+    .unwrap_or_else(|e| panic!("{e}"));
+    expect![[r#"
+        Error: Trait `crate::Missing` is not declared
+           ╭─[test.zng:8:5]
+           │
+         8 │     impl crate::Missing;
+           │     ──────────┬─────────  
+           │               ╰─────────── Add a `trait crate::Missing { ... }` item with the methods to add to `crate::Counter`
+        ───╯
+        Error: Type `crate::Shape` is not declared
+            ╭─[test.zng:11:6]
+            │
+         11 │ impl crate::Describe for crate::Shape;
+            │      ────────────────┬───────────────  
+            │                      ╰───────────────── Add a `type crate::Shape { ... }` item to implement `crate::Describe` for it
+        ────╯
+        Error: `impl Fn(i32) -> i32 for crate::Counter` is not supported for Fn traits
+            ╭─[test.zng:12:6]
+            │
+         12 │ impl Fn(i32) -> i32 for crate::Counter;
+            │      ────────────────┬────────────────  
+            │                      ╰────────────────── Only traits declared with a `trait` item can be implemented
+        ────╯
+    "#]].assert_eq(&parsed.validate().unwrap_err().to_string());
+}
+
+#[test]
+fn generic_trait_impls() {
+    check_fail(
+        r#"
+impl<T: crate::Bound> crate::Describe for crate::Wrapper<T>;
+
+type crate::Counter {
+    #layout(size = 4, align = 4);
+    impl<T> crate::Describe;
+}
+"#,
+        expect![[r#"
+            Error: Generic trait impls are not supported, implement the trait for each type instead
+               ╭─[test.zng:2:5]
+               │
+             2 │ impl<T: crate::Bound> crate::Describe for crate::Wrapper<T>;
+               │     ────────┬────────  
+               │             ╰────────── Generic trait impls are not supported, implement the trait for each type instead
+            ───╯
+            Error: Generic trait impls are not supported, implement the trait for each type instead
+               ╭─[test.zng:6:9]
+               │
+             6 │     impl<T> crate::Describe;
+               │         ─┬─  
+               │          ╰─── Generic trait impls are not supported, implement the trait for each type instead
+            ───╯
+        "#]],
+    );
+}
+
+#[test]
+fn format_trait_impls() {
+    check_format(
+        r#"
+type crate::Counter { #layout(size = 4, align = 4); impl ::std::iter::Iterator<Item=i32>; }
+impl   crate::Describe for crate::Shape ;
+"#,
+        expect![[r#"
+            type crate::Counter {
+                #layout(size = 4, align = 4);
+                impl ::std::iter::Iterator<Item = i32>;
+            }
+            impl crate::Describe for crate::Shape;
+        "#]],
+    );
+}
+
+#[test]
+fn spec_json_roundtrip() {
+    let parsed = ParsedZngFile::parse_str(
+        r#"
+trait ::std::iter::Iterator::<Item = i32> {
+    fn next(&mut self) -> ::std::option::Option<i32>;
+}
+
+type ::std::option::Option<i32> {
+    #layout(size = 8, align = 4);
+    wellknown_traits(Copy, Debug);
+
+    constructor None;
+    constructor Some(i32);
+}
+
+type Box<dyn Fn(i32) -> i32> {
+    #layout(size = 16, align = 8);
+}
+
+mod crate {
+    type Point {
+        #layout(size = 8, align = 4);
+        field x (offset = 0, type = i32);
+        fn scale(&mut self, [f32; 2]);
+    }
+
+    fn origin() -> Point;
+}
+"#,
+        NullCfg,
+    );
+    let json = serde_json::to_string(&parsed.spec).unwrap();
+    let spec: ZngurSpec = serde_json::from_str(&json).unwrap();
+    assert_eq!(format!("{spec:?}"), format!("{:?}", parsed.spec));
+    expect![[r#"
+        [
+          {
+            "tr": {
+              "Normal": {
+                "path": [
+                  "std",
+                  "iter",
+                  "Iterator"
+                ],
+                "generics": [],
+                "named_generics": [
+                  [
+                    "Item",
+                    {
+                      "Primitive": {
+                        "Int": 32
+                      }
+                    }
+                  ]
+                ]
+              }
+            },
+            "methods": [
+              {
+                "name": "next",
+                "generics": [],
+                "receiver": {
+                  "Ref": "Mut"
+                },
+                "inputs": [],
+                "output": {
+                  "Adt": {
+                    "path": [
+                      "std",
+                      "option",
+                      "Option"
+                    ],
+                    "generics": [
+                      {
+                        "Primitive": {
+                          "Int": 32
+                        }
+                      }
+                    ],
+                    "named_generics": []
+                  }
+                },
+                "is_safe": true,
+                "doc": null,
+                "cpp_name": null,
+                "result_mode": null
+              }
+            ]
+          }
+        ]"#]]
+    .assert_eq(&serde_json::to_string_pretty(&spec.traits.values().collect::<Vec<_>>()).unwrap());
+}
+
+#[test]
+fn print_spec_roundtrip() {
+    let parsed = ParsedZngFile::parse_str(
+        r#"
+#cpp_additional_includes "
+#include <string>
+"
+#convert_panic_to_exception
+
+trait ::std::iter::Iterator::<Item = i32> {
+    /// Advances the iterator.
+    fn next(&mut self) -> ::std::option::Option<i32>;
+}
+
+type str {
+    wellknown_traits(?Sized, Debug);
+    fn len(&self) -> usize;
+}
+
+/// An optional number.
+enum ::std::option::Option<i32> {
+    #layout(size = 8, align = 4);
+    wellknown_traits(Copy);
+
+    None;
+    /// Some number.
+    Some(i32);
+
+    #cpp_name "some"
+    constructor Some(i32);
+    fn unwrap(self) -> i32;
+}
+
+type Box<dyn Fn(i32) -> i32 + Send> {
+    #heap_allocated;
+}
+
+type ::std::vec::Vec<[u8; 4]> {
+    #layout_conservative(size = 24, align = 8);
+    fn as_ptr(&self) -> *const [u8; 4];
+    fn get_mut(&mut self, usize) -> ::std::option::Option<&mut [u8; 4]> use ::std::vec::Vec deref [[u8; 4]];
+    fn iter(&self) -> ::std::slice::Iter<[u8; 4]> deref [[u8; 4]];
+}
+
+type crate::CppBox {
+    #only_by_ref;
+    #cpp_value "0" "::std::string";
+}
+
+type crate::Handle {
+    #cpp_ref "::Handle";
+}
+
+type crate::Owned {
+    #cpp_stack_owned "::Owned"(size = 16, align = 8);
+}
+
+mod crate {
+    #cpp_name "Pt"
+    type Point {
+        #layout(size = 8, align = 4);
+
+        constructor { x: i32, y: i32 };
+        #cpp_name "horizontal"
+        field x (offset = 0, type = i32);
+        field y (offset = auto, type = i32);
+        async fn norm(&self) -> f32;
+        fn map<i32>(self, fn(i32) -> i32, unsafe extern "C" fn(*mut i32)) -> Point;
+        impl ::std::iter::Iterator<Item = i32>;
+    }
+
+    /// The origin.
+    fn origin() -> Point;
+    fn ignore<Point>(Point);
+
+    mod inner {
+        #cpp_name "makePoint"
+        fn make(i32, (i32,), (i32, f64)) -> crate::Point;
+    }
+}
+
+fn top_level(&str);
+
+impl ::std::fmt::Debug for crate::Point;
+
+extern "C++" {
+    safe fn log(&str);
+    unsafe fn raw(*mut u8) -> bool;
+    impl crate::Handle {
+        safe fn close(&mut self);
+    }
+    impl ::std::ops::Drop for crate::Point {
+        unsafe fn drop(&mut self);
+    }
+}
+"#,
+        NullCfg,
+    );
+    let printed = parsed.spec.to_zng_string();
+    let reparsed = ParsedZngFile::parse_str(&printed, NullCfg);
+    assert_eq!(
+        format!("{:#?}", reparsed.spec),
+        format!("{:#?}", parsed.spec)
+    );
+    expect![[r##"
+        #cpp_additional_includes "
+        #include <string>
+        "
+
+        #convert_panic_to_exception
+
+        trait ::std::iter::Iterator::<Item = i32> {
+            /// Advances the iterator.
+            fn next(&mut self) -> ::std::option::Option::<i32>;
+        }
+
+        type str {
+            wellknown_traits(?Sized, Debug);
+            fn len(&self) -> usize;
+        }
+
+        /// An optional number.
+        enum ::std::option::Option::<i32> {
+            #layout(size = 8, align = 4);
+            wellknown_traits(Copy);
+            None;
+            /// Some number.
+            Some(i32);
+            #cpp_name "some"
+            constructor Some(i32);
+            fn unwrap(self) -> i32;
+        }
+
+        type Box<dyn Fn(i32) -> i32+ Send> {
+            #heap_allocated;
+        }
+
+        type ::std::vec::Vec::<[u8; 4]> {
+            #layout_conservative(size = 24, align = 8);
+            fn as_ptr(&self) -> *const [u8; 4];
+            fn get_mut(&mut self, usize) -> ::std::option::Option::<&mut [u8; 4]> use ::std::vec::Vec deref [[u8; 4]];
+            fn iter(&self) -> ::std::slice::Iter::<[u8; 4]> deref [[u8; 4]];
+        }
+
+        type crate::CppBox {
+            #only_by_ref;
+            #cpp_value "0" "::std::string";
+        }
+
+        type crate::Handle {
+            #cpp_ref "::Handle";
+        }
+
+        type crate::Owned {
+            #cpp_stack_owned "::Owned"(size = 16, align = 8);
+        }
+
+        #cpp_name "Pt"
+        type crate::Point {
+            #layout(size = 8, align = 4);
+            constructor { x: i32, y: i32 };
+            #cpp_name "horizontal"
+            field x (offset = 0, type = i32);
+            field y (offset = auto, type = i32);
+            fn norm(&self) -> impl ::std::future::Future::<Output = f32>;
+            fn map<i32>(self, fn(i32) -> i32, unsafe extern "C" fn(*mut i32)) -> crate::Point;
+        }
+
+        impl ::std::iter::Iterator::<Item = i32> for crate::Point;
+        impl ::std::fmt::Debug for crate::Point;
+
+        mod crate {
+            /// The origin.
+            fn origin() -> crate::Point;
+            fn ignore<crate::Point>(crate::Point);
+        }
+
+        mod crate::inner {
+            #cpp_name "makePoint"
+            fn make(i32, (i32), (i32, f64)) -> crate::Point;
+        }
+
+        fn top_level(&str);
+
+        extern "C++" {
+            safe fn log(&str);
+            unsafe fn raw(*mut u8) -> bool;
+            impl crate::Handle {
+                safe fn close(&mut self);
+            }
+            impl ::std::ops::Drop for crate::Point {
+                unsafe fn drop(&mut self);
+            }
+        }
+    "##]].assert_eq(&printed);
+}
+
+fn check_invalid(zng: &str, error: Expect) {
+    let parsed = ParsedZngFile::parse_str(zng, NullCfg);
+    match parsed.validate() {
+        Ok(()) => panic!("Validation succeeded but we expected fail"),
+        Err(diagnostics) => error.assert_eq(&diagnostics.to_string()),
+    }
+}
+
+#[test]
+fn validate_accepts_declared_types() {
+    let parsed = ParsedZngFile::parse_str(
+        r#"
+type bool {
+    #layout(size = 1, align = 1);
+    wellknown_traits(Copy);
+}
+
+type str {
+    wellknown_traits(?Sized);
+}
+
+type Box<dyn Fn(i32) -> bool> {
+    #layout(size = 16, align = 8);
+}
+
+type crate::Point {
+    #heap_allocated;
+    wellknown_traits(Copy);
+    constructor { x: i32, y: i32 };
+    fn is_zero(&self) -> bool;
+    fn name(&self) -> &str;
+    fn filter(&self) -> impl Fn(i32) -> bool;
+    fn raw(*const crate::Point) -> *mut u8;
+}
+
+mod crate {
+    fn call(fn(i32) -> usize, f64) -> ();
+}
+"#,
+        NullCfg,
+    );
+    parsed.validate().unwrap();
+}
+
+#[test]
+fn validate_undeclared_type() {
+    check_invalid(
+        r#"
+type crate::Point {
+    #layout(size = 8, align = 4);
+    fn name(&self) -> &str;
+    fn is_zero(&self) -> bool;
+}
+
+mod crate {
+    fn make_name() -> &mut str;
+}
+"#,
+        expect![[r#"
+            Error: Type `str` is not declared
+               ╭─[test.zng:4:5]
+               │
+             4 │     fn name(&self) -> &str;
+               │     ───────────┬───────────  
+               │                ╰───────────── Add a `type str { ... }` item to use it here
+            ───╯
+            Error: Type `bool` is not declared
+               ╭─[test.zng:5:5]
+               │
+             5 │     fn is_zero(&self) -> bool;
+               │     ─────────────┬────────────  
+               │                  ╰────────────── Add a `type bool { ... }` item to use it here
+            ───╯
+        "#]],
+    );
+}
+
+#[test]
+fn validate_undeclared_impl_trait_box() {
+    check_invalid(
+        r#"
+type crate::Point {
+    #layout(size = 8, align = 4);
+    fn xs(&self) -> impl Iterator<Item = i32>;
+}
+"#,
+        expect![[r#"
+            Error: Type `Box<dyn ::Iterator::<Item = i32>>` is not declared
+               ╭─[test.zng:4:5]
+               │
+             4 │     fn xs(&self) -> impl Iterator<Item = i32>;
+               │     ─────────────────────┬────────────────────  
+               │                          ╰────────────────────── Add a `type Box<dyn ::Iterator::<Item = i32>> { ... }` item to use it here
+            ───╯
+        "#]],
+    );
+}
+
+#[test]
+fn validate_copy_only_by_ref() {
+    check_invalid(
+        r#"
+type crate::Point {
+    #only_by_ref;
+    wellknown_traits(Copy);
+}
+"#,
+        expect![[r#"
+            Error: Copy type `crate::Point` can't be `#only_by_ref`
+               ╭─[test.zng:2:6]
+               │
+             2 │ type crate::Point {
+               │      ──────┬─────  
+               │            ╰─────── Copy types are passed by value, so they need a layout
+            ───╯
+        "#]],
+    );
+}
+
+#[test]
+fn validate_field_of_only_by_ref() {
+    check_invalid(
+        r#"
+type crate::Point {
+    #only_by_ref;
+    field x (offset = 0, type = i32);
+}
+"#,
+        expect![[r#"
+            Error: Field `x` of a type without a known layout
+               ╭─[test.zng:4:5]
+               │
+             4 │     field x (offset = 0, type = i32);
+               │     ────────────────┬────────────────  
+               │                     ╰────────────────── `crate::Point` is only used by reference
+            ───╯
+        "#]],
+    );
+}
+
+#[test]
+fn validate_duplicate_method() {
+    check_invalid(
+        r#"
+type crate::Point {
+    #layout(size = 8, align = 4);
+    fn norm(&self) -> f32;
+    fn scale(&mut self, f32);
+    fn norm(&self) -> f32;
+    fn scale(&mut self, f64);
+}
+"#,
+        expect![[r#"
+            Error: Duplicate method `norm`
+               ╭─[test.zng:6:5]
+               │
+             6 │     fn norm(&self) -> f32;
+               │     ───────────┬──────────  
+               │                ╰──────────── This method is already declared with the same signature
+            ───╯
+            Error: Conflicting signatures for method `scale`
+               ╭─[test.zng:7:5]
+               │
+             7 │     fn scale(&mut self, f64);
+               │     ────────────┬────────────  
+               │                 ╰────────────── This method is already declared with a different signature
+            ───╯
+        "#]],
+    );
+}
+
+#[test]
+fn validate_duplicate_cpp_names() {
+    check_invalid(
+        r#"
+type crate::Point {
+    #layout(size = 8, align = 4);
+    fn b(&self);
+    #cpp_name "b"
+    fn c(&self);
+}
+
+mod crate {
+    fn make_point() -> crate::Point;
+    #cpp_name "make_point"
+    fn new_point() -> crate::Point;
+}
+"#,
+        expect![[r#"
+            Error: Duplicate C++ name `b`
+               ╭─[test.zng:5:5]
+               │
+             5 │ ╭─▶     #cpp_name "b"
+             6 │ ├─▶     fn c(&self);
+               │ │                      
+               │ ╰────────────────────── This name is used by method `b` and method `c` in C++
+            ───╯
+            Error: Duplicate C++ name `make_point`
+                ╭─[test.zng:11:5]
+                │
+             11 │ ╭─▶     #cpp_name "make_point"
+             12 │ ├─▶     fn new_point() -> crate::Point;
+                │ │                                         
+                │ ╰───────────────────────────────────────── This name is used by function `crate::make_point` and function `crate::new_point` in C++
+            ────╯
+        "#]],
+    );
+}
+
+#[test]
+fn validate_errors_in_merged_files() {
+    let resolver = MockFilesystem::new(vec![(
+        "./point.zng",
+        r#"
+type crate::Point {
+    #layout(size = 8, align = 4);
+    fn name(&self) -> &str;
+}
+"#,
+    )]);
+    let parsed = ParsedZngFile::parse_str_with_resolver(
+        r#"
+merge "./point.zng";
+"#,
+        NullCfg,
+        &resolver,
+    );
+    expect![[r#"
+        Error: Type `str` is not declared
+           ╭─[point.zng:4:5]
+           │
+         4 │     fn name(&self) -> &str;
+           │     ───────────┬───────────  
+           │                ╰───────────── Add a `type str { ... }` item to use it here
+        ───╯
+    "#]]
+    .assert_eq(&parsed.validate().unwrap_err().to_string());
+}
+
+fn check_fix_layouts(parsed: &ParseResult, layouts: &[(&str, (usize, usize))], files: Expect) {
+    let layouts = layouts
+        .iter()
+        .map(|(ty, layout)| (parsed_type(ty), *layout))
+        .collect();
+    let fixed = match parsed.fix_layouts(&layouts) {
+        Ok(fixed) => fixed
+            .files
+            .iter()
+            .map(|(path, text)| format!("// {}\n{text}", path.display()))
+            .collect::<String>(),
+        Err(e) => e.to_string(),
+    };
+    files.assert_eq(&fixed);
+}
+
+fn parsed_type(ty: &str) -> RustType {
+    ParsedZngFile::parse_str(&format!("type {ty} {{ #heap_allocated; }}"), NullCfg)
+        .spec
+        .types
+        .remove(0)
+        .ty
+}
+
+#[test]
+fn fix_layouts_in_active_cfg_branch() {
+    let parsed = ParsedZngFile::parse_str(
+        r#"
+#unstable(cfg_if)
+
+type ::std::string::String {
+    #if cfg!(target_pointer_width = "64") {
+        #layout(size = 12,   align = 4); // keeps the formatting
+    } #else {
+        #layout(size = 12, align = 4);
+    }
+}
+
+type crate::Point {
+    #layout_conservative(size = 16, align = 2);
+}
+
+type crate::Unchanged {
+    #layout(size = 8, align = 8);
+}
+"#,
+        InMemoryRustCfgProvider::default().with_values([("target_pointer_width", &["64"])]),
+    );
+    check_fix_layouts(
+        &parsed,
+        &[
+            ("::std::string::String", (24, 8)),
+            ("crate::Point", (8, 4)),
+            ("crate::Unchanged", (8, 8)),
+        ],
+        expect![[r#"
+            // test.zng
+
+            #unstable(cfg_if)
+
+            type ::std::string::String {
+                #if cfg!(target_pointer_width = "64") {
+                    #layout(size = 24,   align = 8); // keeps the formatting
+                } #else {
+                    #layout(size = 12, align = 4);
+                }
+            }
+
+            type crate::Point {
+                #layout_conservative(size = 16, align = 4);
+            }
+
+            type crate::Unchanged {
+                #layout(size = 8, align = 8);
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn fix_layouts_of_type_templates() {
+    let parsed = ParsedZngFile::parse_str(
+        r#"
+type<T> ::std::vec::Vec<T> for T in [i32, u8 => #layout(size = 1, align = 1)] {
+    #layout(size = 100, align = 8);
+}
+"#,
+        NullCfg,
+    );
+    check_fix_layouts(
+        &parsed,
+        &[
+            ("::std::vec::Vec<i32>", (24, 8)),
+            ("::std::vec::Vec<u8>", (24, 8)),
+        ],
+        expect![[r#"
+            // test.zng
+
+            type<T> ::std::vec::Vec<T> for T in [i32, u8 => #layout(size = 24, align = 8)] {
+                #layout(size = 24, align = 8);
+            }
+        "#]],
+    );
+    let parsed = ParsedZngFile::parse_str(
+        r#"
+type<T> ::std::option::Option<T> for T in [i32, u8] {
+    #layout(size = 8, align = 4);
+}
+"#,
+        NullCfg,
+    );
+    check_fix_layouts(
+        &parsed,
+        &[
+            ("::std::option::Option<i32>", (8, 4)),
+            ("::std::option::Option<u8>", (2, 1)),
+        ],
+        expect![[r#"
+            Error: Types with different layouts share this directive
+               ╭─[test.zng:3:20]
+               │
+             3 │     #layout(size = 8, align = 4);
+               │                    ──────┬─────  
+               │                          ╰─────── `::std::option::Option::<i32>` has size 8 and align 4, but `::std::option::Option::<u8>` has size 2 and align 1
+            ───╯
+        "#]],
+    );
+}
+
+#[test]
+fn fix_layouts_in_merged_files() {
+    let resolver = MockFilesystem::new(vec![(
+        "./point.zng",
+        r#"
+type crate::Point {
+    #layout(size = 4, align = 4);
+}
+"#,
+    )]);
+    let parsed = ParsedZngFile::parse_str_with_resolver(
+        r#"
+merge "./point.zng";
+"#,
+        NullCfg,
+        &resolver,
+    );
+    check_fix_layouts(
+        &parsed,
+        &[("crate::Point", (8, 4))],
+        expect![[r#"
+        // ./point.zng
+
+        type crate::Point {
+            #layout(size = 8, align = 4);
+        }
+    "#]],
+    );
+}
+
+#[test]
+fn layout_expressions() {
+    let source = r#"
+type ::std::string::String {
+    #layout(size = 3 * usize, align = usize);
+}
+
+type crate::Named {
+    #layout(size = sizeof(::std::string::String) + 8, align = alignof(::std::string::String));
+}
+
+type crate::Later {
+    #layout(size = 2 * (crate::Point + 4), align = crate::Point);
+}
+
+type crate::Point {
+    #layout(size = sizeof([u32; 2]), align = u32);
+}
+
+type crate::Wide {
+    #layout_conservative(size = sizeof(&str) + sizeof(Box<dyn crate::Tr>), align = usize);
+}
+"#;
+    let layouts = |pointer_width: &str| {
+        ParsedZngFile::parse_str(
+            source,
+            InMemoryRustCfgProvider::default()
+                .with_values([("target_pointer_width", &[pointer_width])]),
+        )
+        .spec
+        .types
+        .iter()
+        .map(|ty| format!("{}: {:?}", ty.ty, ty.layout))
+        .collect::<Vec<_>>()
+        .join("\n")
+    };
+    expect![[r#"
+        ::std::string::String: StackAllocated { size: 24, align: 8 }
+        crate::Named: StackAllocated { size: 32, align: 8 }
+        crate::Later: StackAllocated { size: 24, align: 4 }
+        crate::Point: StackAllocated { size: 8, align: 4 }
+        crate::Wide: Conservative { size: 32, align: 8 }"#]]
+    .assert_eq(&layouts("64"));
+    expect![[r#"
+        ::std::string::String: StackAllocated { size: 12, align: 4 }
+        crate::Named: StackAllocated { size: 20, align: 4 }
+        crate::Later: StackAllocated { size: 24, align: 4 }
+        crate::Point: StackAllocated { size: 8, align: 4 }
+        crate::Wide: Conservative { size: 16, align: 4 }"#]]
+    .assert_eq(&layouts("32"));
+}
+
+#[test]
+fn pending_layout_fills_its_own_directive() {
+    let parsed = ParsedZngFile::parse_str(
+        r#"
+type crate::Owned {
+    #layout(size = 8, align = 8);
+    #cpp_stack_owned "::Owned"(size = sizeof(crate::Later), align = crate::Later);
+}
+
+type crate::Later {
+    #layout(size = 16, align = 4);
+}
+
+type crate::Reverse {
+    #layout(size = sizeof(crate::Later), align = crate::Later);
+    #cpp_stack_owned "::Reverse"(size = 8, align = 8);
+}
+"#,
+        NullCfg,
+    );
+    expect![[r#"
+        [
+            (
+                StackAllocated {
+                    size: 8,
+                    align: 8,
+                },
+                Some(
+                    CppStackOwned {
+                        cpp_type: "::Owned",
+                        size: 16,
+                        align: 4,
+                    },
+                ),
+            ),
+            (
+                StackAllocated {
+                    size: 16,
+                    align: 4,
+                },
+                Some(
+                    CppStackOwned {
+                        cpp_type: "::Reverse",
+                        size: 8,
+                        align: 8,
+                    },
+                ),
+            ),
+        ]
+    "#]]
+    .assert_debug_eq(&[0, 2].map(|i| {
+        let ty = &parsed.spec.types[i];
+        (&ty.layout, &ty.cpp_stack_owned)
+    }));
+}
+
+#[test]
+fn layout_expressions_need_pointer_width() {
+    check_fail(
+        r#"
+type ::std::string::String {
+    #layout(size = 3 * usize, align = usize);
+}
+"#,
+        expect![[r#"
+            Error: The layout of `usize` depends on the target
+               ╭─[test.zng:3:24]
+               │
+             3 │     #layout(size = 3 * usize, align = usize);
+               │                        ──┬──  
+               │                          ╰──── Provide the `target_pointer_width` cfg to use it here
+            ───╯
+        "#]],
+    );
+}
+
+#[test]
+fn layout_expressions_of_unknown_types() {
+    check_fail(
+        r#"
+type crate::A {
+    #layout(size = sizeof(crate::B), align = 8);
+}
+
+type crate::B {
+    #layout(size = sizeof(crate::A), align = 8);
+}
+
+type crate::C {
+    #heap_allocated;
+}
+
+type crate::D {
+    #layout(size = 8, align = alignof(crate::C));
+}
+"#,
+        expect![[r#"
+            Error: The layout of `crate::B` is not known
+               ╭─[test.zng:3:27]
+               │
+             3 │     #layout(size = sizeof(crate::B), align = 8);
+               │                           ────┬───  
+               │                               ╰───── Declare `crate::B` with `#layout(size = X, align = Y)` to use its layout here
+            ───╯
+            Error: The layout of `crate::A` is not known
+               ╭─[test.zng:7:27]
+               │
+             7 │     #layout(size = sizeof(crate::A), align = 8);
+               │                           ────┬───  
+               │                               ╰───── Declare `crate::A` with `#layout(size = X, align = Y)` to use its layout here
+            ───╯
+            Error: The layout of `crate::C` is not known
+                ╭─[test.zng:15:39]
+                │
+             15 │     #layout(size = 8, align = alignof(crate::C));
+                │                                       ────┬───  
+                │                                           ╰───── Declare `crate::C` with `#layout(size = X, align = Y)` to use its layout here
+            ────╯
+        "#]],
+    );
+}
+
+#[test]
+fn format_layout_expressions() {
+    check_format(
+        r#"
+type crate::Point {
+    #layout(size=2*(usize+4)+sizeof(  [u8;3]), align=alignof(u64));
+}
+"#,
+        expect![[r#"
+            type crate::Point {
+                #layout(size = 2 * (usize + 4) + sizeof([u8; 3]), align = alignof(u64));
+            }
+        "#]],
+    );
+}
+
+#[cfg(feature = "export")]
+fn check_exports(zng: &str, files: &[(&str, &str)], spec: Expect) {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static NEXT_DIR: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "zngur-exports-{}-{}",
+        std::process::id(),
+        NEXT_DIR.fetch_add(1, Ordering::Relaxed)
+    ));
+    for (path, text) in files {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, text).unwrap();
+    }
+    let mut parsed = ParsedZngFile::parse_str(zng, NullCfg);
+    let result = match parsed.merge_exports(dir.join(files[0].0)) {
+        Ok(()) => parsed.spec.to_zng_string(),
+        Err(e) => e.to_string().replace(&format!("{}/", dir.display()), ""),
+    };
+    std::fs::remove_dir_all(&dir).unwrap();
+    spec.assert_eq(&result);
+}
+
+#[test]
+#[cfg(feature = "export")]
+fn export_struct_impl_and_fn() {
+    check_exports(
+        r#"
+type bool {
+    #layout(size = 1, align = 1);
+}
+"#,
+        &[
+            (
+                "src/lib.rs",
+                r#"
+mod geometry;
+
+#[zngur::export]
+pub fn is_origin(p: &geometry::Point) -> bool {
+    p.x == 0.0 && p.y == 0.0
+}
+"#,
+            ),
+            (
+                "src/geometry.rs",
+                r#"
+/// A point in the plane.
+#[zngur::export(layout(size = 16, align = 8))]
+#[derive(Clone, Copy)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+#[zngur::export]
+impl Point {
+    pub fn new(x: f64, y: f64) -> Self {
+        Point { x, y }
+    }
+
+    pub fn norm(&self) -> f64 {
+        (self.x * self.x + self.y * self.y).sqrt()
+    }
+
+    fn private(&self) {}
+}
+"#,
+            ),
+        ],
+        expect![[r#"
+            type bool {
+                #layout(size = 1, align = 1);
+            }
+
+            /// A point in the plane.
+            type crate::geometry::Point {
+                #layout(size = 16, align = 8);
+                wellknown_traits(Copy);
+                constructor { x: f64, y: f64 };
+                fn new(f64, f64) -> crate::geometry::Point;
+                fn norm(&self) -> f64;
+            }
+
+            mod crate {
+                fn is_origin(&crate::geometry::Point) -> bool;
+            }
+        "#]],
+    );
+}
+
+#[test]
+#[cfg(feature = "export")]
+fn export_conflicts_with_zng_file() {
+    check_exports(
+        r#"
+type crate::Point {
+    #layout(size = 8, align = 4);
+}
+"#,
+        &[(
+            "src/lib.rs",
+            r#"
+#[zngur::export(layout(size = 16, align = 8))]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+"#,
+        )],
+        expect![[r#"
+            Error: Duplicate layout policy found
+               ╭─[lib.rs:3:12]
+               │
+             3 │ pub struct Point {
+               │            ──┬──  
+               │              ╰──── `crate::Point` is declared differently in the zng file
+            ───╯
+        "#]],
+    );
+}
+
+#[test]
+#[cfg(feature = "export")]
+fn export_impl_of_undeclared_type() {
+    check_exports(
+        "",
+        &[(
+            "src/lib.rs",
+            r#"
+pub struct Hidden;
+
+#[zngur::export]
+impl Hidden {
+    pub fn get(&self) -> i32 {
+        4
+    }
+}
+"#,
+        )],
+        expect![[r#"
+            Error: Type `crate::Hidden` is not declared
+               ╭─[lib.rs:5:6]
+               │
+             5 │ impl Hidden {
+               │      ───┬──  
+               │         ╰──── Export it with `#[zngur::export]`, or declare it in the zng file
+            ───╯
+        "#]],
+    );
+}
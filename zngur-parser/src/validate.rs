@@ -0,0 +1,394 @@
+//! Semantic checks of the resolved spec.
+//!
+//! These catch mistakes that the parser accepts, but which would otherwise only show up as
+//! confusing errors when compiling the generated Rust or C++ code. They run on the complete spec,
+//! after all the merged files are processed, and before the code is generated.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::PathBuf,
+};
+
+use zngur_def::{
+    LayoutPolicy, PrimitiveRustType, RustTrait, RustType, ZngurMethod, ZngurSpec, ZngurTraitImpl,
+    ZngurWellknownTrait,
+};
+
+use crate::{
+    FnDeclaration, Span, TypeDeclaration, diagnostics::Diagnostic, layout_fix::LayoutDirective,
+};
+
+/// A span in one of the processed files.
+#[derive(Debug, Clone)]
+pub(crate) struct Location {
+    pub(crate) path: PathBuf,
+    pub(crate) span: Span,
+}
+
+impl Location {
+    pub(crate) fn error(&self, message: impl Into<String>, label: impl Into<String>) -> Diagnostic {
+        Diagnostic::error(&self.path, self.span.into_range(), message).with_label(
+            &self.path,
+            self.span.into_range(),
+            label,
+        )
+    }
+}
+
+/// The C++ scope of an item, in which its name must be unique.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum CppScope {
+    /// The methods, constructors and fields of a type.
+    Type(RustType),
+    /// The types and functions of the namespace of a Rust module.
+    Namespace(Vec<String>),
+}
+
+impl CppScope {
+    /// The namespace of the item with this Rust path, and its name in it.
+    pub(crate) fn of_path(path: &[String]) -> Option<(Self, &str)> {
+        let (name, namespace) = path.split_last()?;
+        Some((CppScope::Namespace(namespace.to_vec()), name))
+    }
+}
+
+/// Where the items of the spec are declared, which the spec itself doesn't keep.
+#[derive(Debug, Default)]
+pub(crate) struct SpecLocations {
+    /// The first declaration of each type.
+    types: HashMap<RustType, Location>,
+    /// The declarations of the methods of each type, by name.
+    methods: HashMap<(RustType, String), Vec<Location>>,
+    fields: HashMap<(RustType, String), Location>,
+    /// The types mentioned in the signatures of methods, functions, fields and constructors.
+    uses: Vec<(RustType, Location)>,
+    trait_impls: Vec<(ZngurTraitImpl, Location)>,
+    pub(crate) layouts: Vec<LayoutDirective>,
+    pub(crate) declarations: Vec<TypeDeclaration>,
+    pub(crate) fns: Vec<FnDeclaration>,
+    /// The names set with `#cpp_name`.
+    cpp_names: Vec<(CppScope, String, Location)>,
+}
+
+impl SpecLocations {
+    pub(crate) fn type_location(&self, ty: &RustType) -> Option<&Location> {
+        self.types.get(ty)
+    }
+
+    pub(crate) fn add_type(&mut self, ty: &RustType, location: Location) {
+        self.types.entry(ty.clone()).or_insert(location);
+    }
+
+    pub(crate) fn add_method(&mut self, ty: &RustType, method: &ZngurMethod, location: Location) {
+        self.add_signature(method, location.clone());
+        self.methods
+            .entry((ty.clone(), method.name.clone()))
+            .or_default()
+            .push(location);
+    }
+
+    pub(crate) fn add_field(&mut self, ty: &RustType, name: &str, location: Location) {
+        self.fields
+            .entry((ty.clone(), name.to_owned()))
+            .or_insert(location);
+    }
+
+    pub(crate) fn add_signature(&mut self, method: &ZngurMethod, location: Location) {
+        // A `Result` with a result mode is split into its `Ok` and `Err` types in C++.
+        let outputs = match method.result_mode.and(method.output.as_result()) {
+            Some((ok, err)) => vec![ok, err],
+            None => vec![&method.output],
+        };
+        for ty in method.inputs.iter().chain(outputs) {
+            self.add_use(ty.clone(), location.clone());
+        }
+    }
+
+    pub(crate) fn add_use(&mut self, ty: RustType, location: Location) {
+        self.uses.push((ty, location));
+    }
+
+    pub(crate) fn add_trait_impl(&mut self, imp: ZngurTraitImpl, location: Location) {
+        self.trait_impls.push((imp, location));
+    }
+
+    pub(crate) fn add_layout(&mut self, layout: LayoutDirective) {
+        self.layouts.push(layout);
+    }
+
+    pub(crate) fn add_declaration(&mut self, declaration: TypeDeclaration) {
+        self.declarations.push(declaration);
+    }
+
+    pub(crate) fn add_fn(&mut self, declaration: FnDeclaration) {
+        self.fns.push(declaration);
+    }
+
+    pub(crate) fn add_cpp_name(&mut self, scope: CppScope, name: &str, location: Location) {
+        self.cpp_names.push((scope, name.to_owned(), location));
+    }
+
+    pub(crate) fn extend(&mut self, other: SpecLocations) {
+        for (ty, location) in other.types {
+            self.types.entry(ty).or_insert(location);
+        }
+        for (key, locations) in other.methods {
+            self.methods.entry(key).or_default().extend(locations);
+        }
+        for (key, location) in other.fields {
+            self.fields.entry(key).or_insert(location);
+        }
+        self.uses.extend(other.uses);
+        self.trait_impls.extend(other.trait_impls);
+        self.layouts.extend(other.layouts);
+        self.declarations.extend(other.declarations);
+        self.fns.extend(other.fns);
+        self.cpp_names.extend(other.cpp_names);
+    }
+}
+
+/// Checks the spec for mistakes which the generated code can't handle.
+pub(crate) fn validate(spec: &ZngurSpec, locations: &SpecLocations) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    // Types of imported modules are declared in their own spec, so we can't check their uses.
+    if spec.imported_modules.is_empty() {
+        check_declared_types(spec, locations, &mut diagnostics);
+    }
+    check_trait_impls(spec, locations, &mut diagnostics);
+    check_cpp_names(spec, locations, &mut diagnostics);
+    for ty in &spec.types {
+        let Some(location) = locations.types.get(&ty.ty) else {
+            continue;
+        };
+        let is_copy = ty.wellknown_traits.contains(&ZngurWellknownTrait::Copy);
+        match ty.layout {
+            // `#heap_allocated` types can be `Copy` too, since C++ still owns them by value.
+            LayoutPolicy::OnlyByRef if is_copy => diagnostics.push(location.error(
+                format!("Copy type `{}` can't be `#only_by_ref`", ty.ty),
+                "Copy types are passed by value, so they need a layout",
+            )),
+            LayoutPolicy::OnlyByRef => {
+                for field in &ty.fields {
+                    let Some(location) = locations.fields.get(&(ty.ty.clone(), field.name.clone()))
+                    else {
+                        continue;
+                    };
+                    diagnostics.push(location.error(
+                        format!("Field `{}` of a type without a known layout", field.name),
+                        format!("`{}` is only used by reference", ty.ty),
+                    ));
+                }
+            }
+            _ => {}
+        }
+        for (i, method) in ty.methods.iter().enumerate() {
+            let Some(previous) = ty.methods[..i].iter().find(|m| {
+                m.data.name == method.data.name && m.data.generics == method.data.generics
+            }) else {
+                continue;
+            };
+            // Methods are recorded in the order they are added to the type.
+            let index = ty.methods[..i]
+                .iter()
+                .filter(|m| m.data.name == method.data.name)
+                .count();
+            let Some(location) = locations
+                .methods
+                .get(&(ty.ty.clone(), method.data.name.clone()))
+                .and_then(|locations| locations.get(index))
+            else {
+                continue;
+            };
+            let same_signature = previous.data.receiver == method.data.receiver
+                && previous.data.inputs == method.data.inputs
+                && previous.data.output == method.data.output;
+            diagnostics.push(if same_signature {
+                location.error(
+                    format!("Duplicate method `{}`", method.data.name),
+                    "This method is already declared with the same signature",
+                )
+            } else {
+                location.error(
+                    format!("Conflicting signatures for method `{}`", method.data.name),
+                    "This method is already declared with a different signature",
+                )
+            });
+        }
+    }
+    diagnostics
+}
+
+fn check_declared_types(
+    spec: &ZngurSpec,
+    locations: &SpecLocations,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let declared = |ty: &RustType| spec.types.iter().any(|t| t.ty == *ty);
+    let mut reported = vec![];
+    for (ty, location) in &locations.uses {
+        let Some(undeclared) = undeclared_type(ty, &declared) else {
+            continue;
+        };
+        // Report each type once, at its first use.
+        if reported.contains(&undeclared) {
+            continue;
+        }
+        diagnostics.push(location.error(
+            format!("Type `{undeclared}` is not declared"),
+            format!("Add a `type {undeclared} {{ ... }}` item to use it here"),
+        ));
+        reported.push(undeclared);
+    }
+}
+
+/// Checks that the methods of `impl Trait for Type;` items can be added to their types.
+fn check_trait_impls(
+    spec: &ZngurSpec,
+    locations: &SpecLocations,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mut reported = vec![];
+    for (imp, location) in &locations.trait_impls {
+        // The same impl may be merged from more than one file.
+        if reported.contains(imp) {
+            continue;
+        }
+        reported.push(imp.clone());
+        let ZngurTraitImpl { ty, tr } = imp;
+        if let RustTrait::Fn { .. } = tr {
+            diagnostics.push(location.error(
+                format!("`impl {tr} for {ty}` is not supported for Fn traits"),
+                "Only traits declared with a `trait` item can be implemented",
+            ));
+        } else if !spec.traits.contains_key(tr) {
+            diagnostics.push(location.error(
+                format!("Trait `{tr}` is not declared"),
+                format!("Add a `trait {tr} {{ ... }}` item with the methods to add to `{ty}`"),
+            ));
+        }
+        // Types of imported modules are declared in their own spec.
+        if spec.imported_modules.is_empty() && !spec.types.iter().any(|t| t.ty == *ty) {
+            diagnostics.push(location.error(
+                format!("Type `{ty}` is not declared"),
+                format!("Add a `type {ty} {{ ... }}` item to implement `{tr}` for it"),
+            ));
+        }
+    }
+}
+
+/// Checks that the names set with `#cpp_name` don't clash with the C++ name of another item.
+fn check_cpp_names(spec: &ZngurSpec, locations: &SpecLocations, diagnostics: &mut Vec<Diagnostic>) {
+    // The Rust items with each C++ name. Generic instances of an item share its name.
+    let mut scopes: HashMap<CppScope, HashMap<String, BTreeSet<String>>> = HashMap::new();
+    let mut add = |scope: CppScope, cpp_name: &str, item: String| {
+        scopes
+            .entry(scope)
+            .or_default()
+            .entry(cpp_name.to_owned())
+            .or_default()
+            .insert(item);
+    };
+    for ty in &spec.types {
+        let scope = CppScope::Type(ty.ty.clone());
+        for method in &ty.methods {
+            let name = &method.data.name;
+            let cpp_name = method.data.cpp_name.as_deref();
+            add(
+                scope.clone(),
+                cpp_name.unwrap_or(cpp_keyword_name(name)),
+                format!("method `{name}`"),
+            );
+        }
+        for constructor in &ty.constructors {
+            if let Some(name) = &constructor.name {
+                let cpp_name = constructor.cpp_name.as_deref().unwrap_or(name);
+                add(scope.clone(), cpp_name, format!("constructor `{name}`"));
+            }
+        }
+        for variant in &ty.variants {
+            let name = &variant.name;
+            add(scope.clone(), name, format!("variant `{name}`"));
+        }
+        for field in &ty.fields {
+            let name = &field.name;
+            let cpp_name = match &field.cpp_name {
+                Some(cpp_name) => cpp_name.clone(),
+                None if name.parse::<u32>().is_ok() => format!("f{name}"),
+                None => cpp_keyword_name(name).to_owned(),
+            };
+            add(scope.clone(), &cpp_name, format!("field `{name}`"));
+        }
+        let RustType::Adt(adt) = &ty.ty else {
+            continue;
+        };
+        let Some((scope, name)) = CppScope::of_path(&adt.path) else {
+            continue;
+        };
+        add(
+            scope.clone(),
+            name,
+            format!("type `{}`", adt.path.join("::")),
+        );
+        if let Some(cpp_name) = &ty.cpp_name {
+            add(scope, cpp_name, format!("the alias of `{}`", ty.ty));
+        }
+    }
+    for func in &spec.funcs {
+        let Some((scope, name)) = CppScope::of_path(&func.path.path) else {
+            continue;
+        };
+        let cpp_name = func.cpp_name.as_deref().unwrap_or(name);
+        add(
+            scope,
+            cpp_name,
+            format!("function `{}`", func.path.path.join("::")),
+        );
+    }
+    for (scope, name, location) in &locations.cpp_names {
+        let Some(items) = scopes.get(scope).and_then(|names| names.get(name)) else {
+            continue;
+        };
+        if items.len() > 1 {
+            let items = items.iter().map(String::as_str).collect::<Vec<_>>();
+            diagnostics.push(location.error(
+                format!("Duplicate C++ name `{name}`"),
+                format!("This name is used by {} in C++", items.join(" and ")),
+            ));
+        }
+    }
+}
+
+/// The C++ name of a method or field without `#cpp_name`, which avoids the C++ keywords.
+fn cpp_keyword_name(name: &str) -> &str {
+    match name {
+        "new" => "new_",
+        "default" => "default_",
+        x => x,
+    }
+}
+
+/// Finds a type in `ty` which C++ needs a declaration for, but which isn't declared.
+fn undeclared_type(ty: &RustType, declared: &impl Fn(&RustType) -> bool) -> Option<RustType> {
+    match ty {
+        // These are built into the generated header.
+        RustType::Primitive(
+            PrimitiveRustType::Uint(_)
+            | PrimitiveRustType::Int(_)
+            | PrimitiveRustType::Float(_)
+            | PrimitiveRustType::Usize,
+        ) => None,
+        RustType::Tuple(fields) if fields.is_empty() => None,
+        // Returning `impl Trait` returns a box of the trait object.
+        RustType::Impl(tr, bounds) => undeclared_type(
+            &RustType::Boxed(Box::new(RustType::Dyn(tr.clone(), bounds.clone()))),
+            declared,
+        ),
+        RustType::Ref(_, inner) | RustType::Raw(_, inner) => undeclared_type(inner, declared),
+        RustType::FnPtr { inputs, output, .. } => inputs
+            .iter()
+            .chain([&**output])
+            .find_map(|ty| undeclared_type(ty, declared)),
+        _ if declared(ty) => None,
+        _ => Some(ty.clone()),
+    }
+}
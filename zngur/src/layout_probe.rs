@@ -0,0 +1,265 @@
+//! Computing the layout of types with a probe build, for `#layout(auto)`.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use zngur_generator::{LayoutPolicy, ParseResult, RustType};
+
+use crate::Diagnostics;
+
+/// Computes the size and align of Rust types, by compiling and running a small program which
+/// prints them.
+///
+/// The program is compiled with the local `rustc`, so the layouts are the ones of this compiler
+/// and target. Types of other crates need the rlib of their crate, which are passed with
+/// [`Self::with_crate`] for the types under `crate::` and [`Self::with_extern`] for the others.
+/// Types of the standard library need nothing.
+///
+/// Usage:
+/// ```ignore
+/// Zngur::from_zng_file(crate_dir.join("main.zng"))
+///     .with_layout_probe(LayoutProbe::from_cargo_env())
+///     // ...
+///     .generate();
+/// ```
+#[must_use]
+#[derive(Debug, Clone, Default)]
+pub struct LayoutProbe {
+    rustc: Option<PathBuf>,
+    target: Option<String>,
+    out_dir: Option<PathBuf>,
+    /// The crate which the `crate::` paths refer to.
+    krate: Option<(String, PathBuf)>,
+    externs: Vec<(String, PathBuf)>,
+    library_dirs: Vec<PathBuf>,
+}
+
+impl LayoutProbe {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A probe using the `rustc`, target and output directory of the current build script.
+    pub fn from_cargo_env() -> Self {
+        let mut probe = Self::new();
+        probe.rustc = std::env::var_os("RUSTC").map(PathBuf::from);
+        probe.target = std::env::var("TARGET").ok();
+        probe.out_dir = std::env::var_os("OUT_DIR").map(|dir| PathBuf::from(dir).join("zngur"));
+        probe
+    }
+
+    /// Set the `rustc` which compiles the probe. Default is the `rustc` in `PATH`.
+    pub fn with_rustc(mut self, rustc: impl AsRef<Path>) -> Self {
+        self.rustc = Some(rustc.as_ref().to_owned());
+        self
+    }
+
+    /// Set the target to compile the probe for. The probe runs after compiling, so the target
+    /// must be runnable on this machine.
+    pub fn with_target(mut self, target: &str) -> Self {
+        self.target = Some(target.to_owned());
+        self
+    }
+
+    /// Set the directory to build the probe in. Default is a new directory in the system's
+    /// temporary directory.
+    pub fn with_out_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.out_dir = Some(dir.as_ref().to_owned());
+        self
+    }
+
+    /// Set the crate which the `crate::` paths refer to, and its rlib.
+    pub fn with_crate(mut self, name: &str, rlib: impl AsRef<Path>) -> Self {
+        self.krate = Some((name.replace('-', "_"), rlib.as_ref().to_owned()));
+        self
+    }
+
+    /// Add another crate whose types are used in the zng file, like `::serde_json::Value`.
+    pub fn with_extern(mut self, name: &str, rlib: impl AsRef<Path>) -> Self {
+        self.externs
+            .push((name.replace('-', "_"), rlib.as_ref().to_owned()));
+        self
+    }
+
+    /// Add a directory to search for the dependencies of the crates, like `target/debug/deps`.
+    pub fn with_library_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.library_dirs.push(dir.as_ref().to_owned());
+        self
+    }
+
+    /// Computes the size and align of each of the types, in order.
+    pub fn probe(&self, types: &[RustType]) -> Result<Vec<(usize, usize)>, String> {
+        // Each probe of this process gets its own directory, since they may run at the same time.
+        static NEXT_DIR: AtomicUsize = AtomicUsize::new(0);
+        let out_dir = self.out_dir.clone().unwrap_or_else(|| {
+            std::env::temp_dir().join(format!(
+                "zngur-layout-probe-{}-{}",
+                std::process::id(),
+                NEXT_DIR.fetch_add(1, Ordering::Relaxed)
+            ))
+        });
+        std::fs::create_dir_all(&out_dir)
+            .map_err(|e| format!("Couldn't create {}: {e}", out_dir.display()))?;
+        // Removes the temporary directory on every return, including the failed ones.
+        let _cleanup = self
+            .out_dir
+            .is_none()
+            .then(|| RemoveDirOnDrop(out_dir.clone()));
+        let source = out_dir.join("zngur_layout_probe.rs");
+        let binary = out_dir.join(format!(
+            "zngur_layout_probe{}",
+            std::env::consts::EXE_SUFFIX
+        ));
+        std::fs::write(&source, self.source(types))
+            .map_err(|e| format!("Couldn't write {}: {e}", source.display()))?;
+
+        let mut rustc = Command::new(self.rustc.as_deref().unwrap_or(Path::new("rustc")));
+        rustc
+            .arg("--edition=2021")
+            .arg("--crate-type=bin")
+            .arg("-o")
+            .arg(&binary)
+            .arg(&source);
+        if let Some(target) = &self.target {
+            rustc.arg("--target").arg(target);
+        }
+        for (name, rlib) in self.krate.iter().chain(&self.externs) {
+            rustc
+                .arg("--extern")
+                .arg(format!("{name}={}", rlib.display()));
+        }
+        for dir in &self.library_dirs {
+            rustc.arg("-L").arg(format!("dependency={}", dir.display()));
+        }
+        let output = rustc
+            .output()
+            .map_err(|e| format!("Couldn't run rustc: {e}"))?;
+        if !output.status.success() {
+            return Err(format!(
+                "Failed to compile the layout probe:\n{}",
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+
+        let output = Command::new(&binary)
+            .output()
+            .map_err(|e| format!("Couldn't run the layout probe: {e}"))?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if !output.status.success() {
+            return Err(format!(
+                "The layout probe failed:\n{}",
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        stdout
+            .lines()
+            .map(|line| {
+                let (size, align) = line.split_once(' ')?;
+                Some((size.parse().ok()?, align.parse().ok()?))
+            })
+            .collect::<Option<Vec<_>>>()
+            .filter(|layouts| layouts.len() == types.len())
+            .ok_or_else(|| format!("Unexpected output of the layout probe:\n{stdout}"))
+    }
+
+    fn source(&self, types: &[RustType]) -> String {
+        let mut source = String::from("// Generated by zngur to compute the layout of types.\n");
+        source += "#![allow(warnings)]\n";
+        if let Some((name, _)) = &self.krate {
+            // Makes the `crate::` paths point into the probed crate.
+            source += &format!("pub use ::{name}::*;\n");
+        }
+        source += "fn main() {\n";
+        for ty in types {
+            source += &format!(
+                "    println!(\"{{}} {{}}\", ::std::mem::size_of::<{ty}>(), ::std::mem::align_of::<{ty}>());\n"
+            );
+        }
+        source += "}\n";
+        source
+    }
+
+    /// Like [`Self::probe`], but reports the errors at the first of the types in the zng file.
+    pub(crate) fn probe_spec_types(
+        &self,
+        parse_result: &ParseResult,
+        types: &[RustType],
+    ) -> Result<Vec<(usize, usize)>, Diagnostics> {
+        self.probe(types).map_err(|e| {
+            parse_result.type_error(&types[0], e, "This is the first of the probed types")
+        })
+    }
+
+    /// Replaces the `#layout(auto)` of the types in the spec with their probed layout.
+    pub(crate) fn resolve(&self, parse_result: &mut ParseResult) -> Result<(), Diagnostics> {
+        let types = auto_layout_types(parse_result);
+        if types.is_empty() {
+            return Ok(());
+        }
+        let layouts = self.probe_spec_types(parse_result, &types)?;
+        let auto_types = parse_result
+            .spec
+            .types
+            .iter_mut()
+            .filter(|ty| ty.layout == LayoutPolicy::Auto);
+        for (ty, (size, align)) in auto_types.zip(layouts) {
+            ty.layout = LayoutPolicy::StackAllocated { size, align };
+        }
+        Ok(())
+    }
+}
+
+struct RemoveDirOnDrop(PathBuf);
+
+impl Drop for RemoveDirOnDrop {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// The types with `#layout(auto)`, which need a probe to generate the code.
+pub(crate) fn auto_layout_types(parse_result: &ParseResult) -> Vec<RustType> {
+    parse_result
+        .spec
+        .types
+        .iter()
+        .filter(|ty| ty.layout == LayoutPolicy::Auto)
+        .map(|ty| ty.ty.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use zngur_generator::{PrimitiveRustType, RustPathAndGenerics, RustType};
+
+    use super::LayoutProbe;
+
+    #[test]
+    fn probe_std_types() {
+        let types = [
+            RustType::Adt(RustPathAndGenerics {
+                path: vec!["std".to_owned(), "string".to_owned(), "String".to_owned()],
+                generics: vec![],
+                named_generics: vec![],
+            }),
+            RustType::Array(
+                Box::new(RustType::Primitive(PrimitiveRustType::Uint(16))),
+                3,
+            ),
+        ];
+        let expected = vec![
+            (size_of::<String>(), align_of::<String>()),
+            (size_of::<[u16; 3]>(), align_of::<[u16; 3]>()),
+        ];
+        // The probes of one process don't share their temporary directory.
+        std::thread::scope(|scope| {
+            let probes = [(); 2].map(|_| scope.spawn(|| LayoutProbe::new().probe(&types)));
+            for probe in probes {
+                assert_eq!(probe.join().unwrap(), Ok(expected.clone()));
+            }
+        });
+    }
+}
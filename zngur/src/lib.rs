@@ -1,17 +1,24 @@
 //! This crate contains an API for using the Zngur code generator inside build scripts. For more information
 //! about the Zngur itself, see [the documentation](https://hkalbasi.github.io/zngur).
 
-use std::{
-    fs::File,
-    io::Write,
-    path::{Path, PathBuf},
-};
+use std::path::{Path, PathBuf};
 
 use zngur_generator::{
     ParsedZngFile, ZngHeaderGenerator, ZngurGenerator,
     cfg::{InMemoryRustCfgProvider, NullCfg, RustCfgProvider},
+    import::ImportOptions,
 };
 
+pub use zngur_generator::diagnostics::{Diagnostic, DiagnosticLabel, Diagnostics, Severity};
+pub use zngur_generator::{ParseResult, ZngurSpec};
+
+mod layout_probe;
+
+pub use layout_probe::LayoutProbe;
+pub use zngur_generator::layout_fix::LayoutFix;
+#[cfg(feature = "macros")]
+pub use zngur_macros::{export, include_zng};
+
 #[must_use]
 /// Builder for the Zngur generator.
 ///
@@ -38,6 +45,10 @@ pub struct Zngur {
     zng_header_in_place: bool,
     zng_h_file_path: Option<PathBuf>,
     crate_name: Option<String>,
+    import_options: ImportOptions,
+    layout_probe: Option<LayoutProbe>,
+    #[cfg(feature = "export")]
+    crate_root: Option<PathBuf>,
 }
 
 impl Zngur {
@@ -54,6 +65,10 @@ impl Zngur {
             zng_header_in_place: false,
             zng_h_file_path: None,
             crate_name: None,
+            import_options: ImportOptions::default(),
+            layout_probe: None,
+            #[cfg(feature = "export")]
+            crate_root: None,
         }
     }
 
@@ -72,6 +87,8 @@ impl Zngur {
         self
     }
 
+    /// Set the path of the generated Rust file. It can be left out when the crate uses
+    /// `include_zng!` to generate the Rust code.
     pub fn with_rs_file(mut self, path: impl AsRef<Path>) -> Self {
         self.rs_file_path = Some(path.as_ref().to_owned());
         self
@@ -79,7 +96,8 @@ impl Zngur {
 
     /// Set the path for the dependency file (.d file) output.
     ///
-    /// The dependency file lists all .zng files that were processed (main file + imports).
+    /// The dependency file lists all .zng files that were processed (main file + imports), and the
+    /// Rust files scanned for `#[zngur::export]` items.
     /// This can be used by build systems to detect when regeneration is needed.
     pub fn with_depfile(mut self, path: impl AsRef<Path>) -> Self {
         self.depfile_path = Some(path.as_ref().to_owned());
@@ -101,6 +119,37 @@ impl Zngur {
         self
     }
 
+    /// Add a directory to search for merged files whose path doesn't start with `.` or `..`, like
+    /// `merge "std_types.zng";`. Directories are searched in the order they are added.
+    pub fn with_include_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.import_options = self.import_options.with_include_dir(dir.as_ref());
+        self
+    }
+
+    /// Set the directory of a package, for merged files like `merge "@mycrate/foo.zng";`.
+    ///
+    /// Packages without a directory are looked up in the dependencies of the cargo workspace
+    /// that contains the zng file.
+    pub fn with_package_dir(mut self, name: &str, dir: impl AsRef<Path>) -> Self {
+        self.import_options = self.import_options.with_package(name, dir.as_ref());
+        self
+    }
+
+    /// Compute the layout of the `#layout(auto)` types with a probe build before generating the
+    /// code. The probe is only built if there are such types.
+    pub fn with_layout_probe(mut self, probe: LayoutProbe) -> Self {
+        self.layout_probe = Some(probe);
+        self
+    }
+
+    /// Merge the items marked with `#[zngur::export]` into the spec. `crate_root` is the root file of the
+    /// crate, like `src/lib.rs`, and the files of its modules are found from there.
+    #[cfg(feature = "export")]
+    pub fn with_exports_from(mut self, crate_root: impl AsRef<Path>) -> Self {
+        self.crate_root = Some(crate_root.as_ref().to_owned());
+        self
+    }
+
     pub fn with_rust_cargo_cfg(mut self) -> Self {
         self.rust_cfg = Some(Box::new(
             InMemoryRustCfgProvider::default().load_from_cargo_env(),
@@ -133,23 +182,115 @@ impl Zngur {
         self
     }
 
+    /// Parses the zng file and returns the resolved spec, after processing the merges, aliases and
+    /// cfg conditions. The output file paths are ignored.
+    pub fn try_parse_spec(self) -> Result<ZngurSpec, Diagnostics> {
+        Ok(self.try_parse()?.spec)
+    }
+
+    /// Like [`Self::try_parse_spec`], but also keeps where the items are declared and the text of
+    /// the processed files, for the tools which edit them.
+    pub fn try_parse(mut self) -> Result<ParseResult, Diagnostics> {
+        self.parse()
+    }
+
+    /// Parses the zng file and merges the exports of the crate, leaving the output options of
+    /// `self` for the code generation.
+    fn parse(&mut self) -> Result<ParseResult, Diagnostics> {
+        let rust_cfg = self.rust_cfg.take().unwrap_or_else(|| Box::new(NullCfg));
+        #[cfg_attr(not(feature = "export"), allow(unused_mut))]
+        let mut parse_result = ParsedZngFile::try_parse_with_options(
+            self.zng_file.clone(),
+            rust_cfg,
+            std::mem::take(&mut self.import_options),
+        )?;
+        #[cfg(feature = "export")]
+        if let Some(crate_root) = &self.crate_root {
+            parse_result.merge_exports(crate_root)?;
+        }
+        Ok(parse_result)
+    }
+
+    /// Probes the layout of the types with `#layout(...)` or `#layout_conservative(...)`, and
+    /// rewrites the numbers which are out of date in the zng files, using the layout probe of
+    /// [`Self::with_layout_probe`]. The output file paths are ignored.
+    ///
+    /// Only the directives in the active `#if` and `#match` branches are checked, so this should
+    /// run once with the cfg of each target.
+    pub fn try_fix_layouts(mut self) -> Result<Vec<LayoutFix>, Diagnostics> {
+        let parse_result = self.parse()?;
+        let mut types = vec![];
+        for directive in parse_result.layout_directives() {
+            if !types.contains(&directive.ty) {
+                types.push(directive.ty.clone());
+            }
+        }
+        if types.is_empty() {
+            return Ok(vec![]);
+        }
+        let layouts = self
+            .layout_probe
+            .unwrap_or_default()
+            .probe_spec_types(&parse_result, &types)?;
+        let fixed = parse_result.fix_layouts(&types.into_iter().zip(layouts).collect())?;
+        for (path, text) in &fixed.files {
+            std::fs::write(path, text).map_err(|e| {
+                parse_result.file_error(path, format!("Failed to write the fixed layouts: {e}"))
+            })?;
+        }
+        Ok(fixed.fixes)
+    }
+
+    /// Generates the output files, printing the errors in the zng file and exiting the process if
+    /// there are any.
     pub fn generate(self) {
-        let rust_cfg = self.rust_cfg.unwrap_or_else(|| Box::new(NullCfg));
-        let parse_result = ParsedZngFile::parse(self.zng_file, rust_cfg);
+        if let Err(diagnostics) = self.try_generate() {
+            diagnostics.eprint();
+            std::process::exit(101);
+        }
+    }
+
+    /// Generates the output files, returning the errors in the zng file instead of exiting.
+    pub fn try_generate(mut self) -> Result<(), Diagnostics> {
+        let mut parse_result = self.parse()?;
+        parse_result.validate()?;
+        match &self.layout_probe {
+            Some(probe) => probe.resolve(&mut parse_result)?,
+            None => {
+                if let Some(ty) = layout_probe::auto_layout_types(&parse_result).first() {
+                    return Err(parse_result.type_error(
+                        ty,
+                        "`#layout(auto)` needs a layout probe",
+                        "Use `Zngur::with_layout_probe` to compute the layout of this type",
+                    ));
+                }
+            }
+        }
         let crate_name = self
             .crate_name
             .or_else(|| std::env::var("CARGO_PKG_NAME").ok())
             .unwrap_or_else(|| "crate".to_owned());
-        // We will pass crate_name to ZngurGenerator instead of mutating spec.
+        // We will pass crate_name to ZngurGenerator instead of mutating spec. The spec is taken
+        // out of the parse result, which still reports the errors of writing the files.
         let panic_to_exception = parse_result.spec.convert_panic_to_exception.0;
-        let mut file = ZngurGenerator::build_from_zng(parse_result.spec, crate_name);
-
-        let rs_file_path = self.rs_file_path.expect("No rs file path provided");
-        let h_file_path = self.h_file_path.expect("No h file path provided");
+        let spec = std::mem::take(&mut parse_result.spec);
+        let mut file = ZngurGenerator::build_from_zng(spec, crate_name);
+
+        let Some(h_file_path) = self.h_file_path else {
+            return Err(parse_result.file_error(
+                &self.zng_file,
+                "No h file path provided, set it with `Zngur::with_h_file`",
+            ));
+        };
+        let write = |path: &Path, contents: &str| {
+            std::fs::write(path, contents).map_err(|e| {
+                parse_result.file_error(path, format!("Failed to write {}: {e}", path.display()))
+            })
+        };
 
         file.0.cpp_include_header_name = h_file_path
             .file_name()
-            .unwrap()
+            .unwrap_or_default()
             .to_string_lossy()
             .into_owned();
 
@@ -164,31 +305,26 @@ impl Zngur {
         }
         let (rust, h, cpp) = file.render(self.zng_header_in_place);
 
-        File::create(&rs_file_path)
-            .unwrap()
-            .write_all(rust.as_bytes())
-            .unwrap();
-        File::create(&h_file_path)
-            .unwrap()
-            .write_all(h.as_bytes())
-            .unwrap();
+        if let Some(rs_file_path) = &self.rs_file_path {
+            write(rs_file_path, &rust)?;
+        }
+        write(&h_file_path, &h)?;
         if let Some(cpp) = &cpp {
-            let cpp_file_path = self
-                .cpp_file_path
-                .as_ref()
-                .expect("No cpp file path provided");
-            File::create(cpp_file_path)
-                .unwrap()
-                .write_all(cpp.as_bytes())
-                .unwrap();
+            let Some(cpp_file_path) = &self.cpp_file_path else {
+                return Err(parse_result.file_error(
+                    &self.zng_file,
+                    "No cpp file path provided, set it with `Zngur::with_cpp_file`",
+                ));
+            };
+            write(cpp_file_path, cpp)?;
         }
 
         // Write dependency file if requested
         if let Some(depfile_path) = self.depfile_path {
-            let mut targets = vec![
-                h_file_path.display().to_string(),
-                rs_file_path.display().to_string(),
-            ];
+            let mut targets = vec![h_file_path.display().to_string()];
+            if let Some(rs_file_path) = &self.rs_file_path {
+                targets.push(rs_file_path.display().to_string());
+            }
             if let Some(cpp_path) = self.cpp_file_path {
                 if cpp.is_some() {
                     targets.push(cpp_path.display().to_string());
@@ -196,28 +332,37 @@ impl Zngur {
             }
 
             // Format: "target1 target2: dep1 dep2 dep3"
+            // The generated Rust file might be among the scanned files of the crate, but it
+            // can't depend on itself.
             let deps: Vec<String> = parse_result
                 .processed_files
                 .iter()
+                .filter(|p| !is_same_file(p, self.rs_file_path.as_deref()))
                 .map(|p| p.display().to_string())
                 .collect();
 
             let depfile_content = format!("{}: {}\n", targets.join(" "), deps.join(" "));
 
-            File::create(depfile_path)
-                .unwrap()
-                .write_all(depfile_content.as_bytes())
-                .unwrap();
+            write(&depfile_path, &depfile_content)?;
         }
-        if let Some(zng_h) = self.zng_h_file_path {
-            let mut zng = ZngurHdr::new()
-                .with_panic_to_exception_as(panic_to_exception)
-                .with_zng_header(zng_h);
+        if let Some(zng_h) = &self.zng_h_file_path {
+            let mut zng = ZngurHdr::new().with_panic_to_exception_as(panic_to_exception);
             if let Some(cpp_namespace) = &self.cpp_namespace {
-                zng = zng.with_cpp_namespace(&cpp_namespace);
+                zng = zng.with_cpp_namespace(cpp_namespace);
             }
-            zng.generate();
+            write(zng_h, &zng.render())?;
         }
+        Ok(())
+    }
+}
+
+fn is_same_file(path: &Path, other: Option<&Path>) -> bool {
+    let Some(other) = other else {
+        return false;
+    };
+    match (path.canonicalize(), other.canonicalize()) {
+        (Ok(path), Ok(other)) => path == other,
+        _ => path == other,
     }
 }
 
@@ -260,17 +405,43 @@ impl ZngurHdr {
         self
     }
 
-    pub fn generate(self) {
-        let generator = ZngHeaderGenerator {
+    /// The contents of the header. The output file is ignored.
+    pub fn render(&self) -> String {
+        ZngHeaderGenerator {
             panic_to_exception: self.panic_to_exception,
-            cpp_namespace: self.cpp_namespace.unwrap_or_else(|| "rust".to_owned()),
-        };
+            cpp_namespace: self.cpp_namespace.as_deref().unwrap_or("rust").to_owned(),
+        }
+        .render()
+    }
 
+    pub fn generate(self) {
+        let rendered = self.render();
         let out_h = self
             .zng_header_file
             .expect("Missing zng header output file");
-        let rendered = generator.render();
         std::fs::write(&out_h, rendered)
             .unwrap_or_else(|_| panic!("Couldn't write contents to {}", out_h.display()));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Zngur;
+
+    #[test]
+    fn write_errors_are_diagnostics() {
+        let dir = std::env::temp_dir().join(format!("zngur-generate-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let zng = dir.join("main.zng");
+        std::fs::write(&zng, "type bool {\n    #layout(size = 1, align = 1);\n}\n").unwrap();
+        let result = Zngur::from_zng_file(&zng)
+            .with_h_file(dir.join("generated.h"))
+            .with_rs_file(dir.join("src/generated.rs"))
+            .try_generate();
+        let _ = std::fs::remove_dir_all(&dir);
+        let diagnostics = result.unwrap_err();
+        let message = &diagnostics.iter().next().unwrap().message;
+        assert!(message.starts_with("Failed to write "), "{message}");
+        assert!(message.contains("generated.rs"), "{message}");
+    }
+}